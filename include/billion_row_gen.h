@@ -0,0 +1,37 @@
+/* The C API of billion-row-gen, built with
+ *   cargo rustc --release --lib --features ffi --crate-type cdylib
+ * Every function returns 0 on success, or -1 with the reason available
+ * from brg_last_error(). Configs are JSON objects of command line options
+ * named as their long flags, such as
+ *   {"rows": 1000000, "seed": 1, "order": "by-station", "force": true}
+ */
+#ifndef BILLION_ROW_GEN_H
+#define BILLION_ROW_GEN_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Called with each row, without its newline; returning anything but 0
+ * stops generation. */
+typedef int (*brg_row_fn)(const uint8_t *row, size_t len, void *user_data);
+
+/* Generates the dataset config_json describes into out_path. */
+int brg_generate(const char *config_json, const char *out_path);
+
+/* Generates the rows config_json describes, handing each to callback with
+ * user_data on the calling thread instead of writing them anywhere. */
+int brg_generate_rows(const char *config_json, brg_row_fn callback, void *user_data);
+
+/* Why the last call on this thread failed, valid until the next call, or
+ * NULL if it didn't. */
+const char *brg_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,81 @@
+use std::thread;
+#[cfg(target_os = "linux")]
+use std::{collections::HashSet, fs, io, mem};
+
+use eyre::{eyre, Result};
+
+/// The CPUs this process may run on, in ascending order
+#[cfg(target_os = "linux")]
+pub fn allowed_cpus() -> Result<Vec<usize>> {
+    // SAFETY: an all zero cpu_set_t is a valid, empty set
+    let mut set: libc::cpu_set_t = unsafe { mem::zeroed() };
+    // SAFETY: `set` is a cpu_set_t of the size passed
+    if unsafe { libc::sched_getaffinity(0, mem::size_of::<libc::cpu_set_t>(), &mut set) } != 0 {
+        return Err(eyre!(
+            "sched_getaffinity failed: {}",
+            io::Error::last_os_error()
+        ));
+    }
+    Ok((0..libc::CPU_SETSIZE as usize)
+        // SAFETY: `cpu` is below CPU_SETSIZE
+        .filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, &set) })
+        .collect())
+}
+
+/// The physical cores among the CPUs this process may use, counting
+/// hyperthreads of one core once, capped by the CPUs a cgroup quota allows
+/// it. Without topology to go by, every CPU counts as a core.
+#[cfg(target_os = "linux")]
+pub fn physical_cores() -> usize {
+    let available = thread::available_parallelism().map_or(1, |n| n.get());
+    let Ok(cpus) = allowed_cpus() else {
+        return available;
+    };
+    // Hyperthreads of a core share its list of siblings
+    let mut cores = HashSet::new();
+    for cpu in &cpus {
+        let topology = format!("/sys/devices/system/cpu/cpu{}/topology", cpu);
+        let siblings = fs::read_to_string(format!("{}/core_cpus_list", topology))
+            .or_else(|_| fs::read_to_string(format!("{}/thread_siblings_list", topology)));
+        match siblings {
+            Ok(siblings) => cores.insert(siblings.trim().to_string()),
+            Err(_) => return available,
+        };
+    }
+    cores.len().clamp(1, available)
+}
+
+/// Pins the calling thread to `cpu`. Memory the thread touches afterwards
+/// is then allocated on that CPU's NUMA node under the default first touch
+/// policy.
+#[cfg(target_os = "linux")]
+pub fn pin_current_thread(cpu: usize) -> Result<()> {
+    // SAFETY: an all zero cpu_set_t is a valid, empty set
+    let mut set: libc::cpu_set_t = unsafe { mem::zeroed() };
+    // SAFETY: callers pass CPUs from `allowed_cpus`, below CPU_SETSIZE
+    unsafe { libc::CPU_SET(cpu, &mut set) };
+    // SAFETY: `set` is a cpu_set_t of the size passed
+    if unsafe { libc::sched_setaffinity(0, mem::size_of::<libc::cpu_set_t>(), &set) } != 0 {
+        return Err(eyre!(
+            "Could not pin a thread to CPU {}: {}",
+            cpu,
+            io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn allowed_cpus() -> Result<Vec<usize>> {
+    Err(eyre!("--pin-threads is only supported on Linux"))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn physical_cores() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pin_current_thread(_cpu: usize) -> Result<()> {
+    Err(eyre!("--pin-threads is only supported on Linux"))
+}
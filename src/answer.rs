@@ -0,0 +1,129 @@
+use std::{
+    cmp::Ordering,
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use clap::ValueEnum;
+use eyre::Result;
+
+use crate::{census::Stats, template};
+
+/// How the answer key, each station's min, mean, and max, is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// `{Abha=-23.0/18.0/59.2, Abidjan=...}` in `<name>.out`, as the
+    /// challenge's reference implementation prints it, to diff against a
+    /// solver's stdout
+    Official,
+    /// `station;min;mean;max;count` lines in `<name>.answer.csv`, for tools
+    Csv,
+}
+
+/// How a mean is rounded to one decimal where it falls halfway
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Rounding {
+    /// Towards positive infinity, as the reference implementation's
+    /// `Math.round` does and the challenge specifies
+    HalfUp,
+    /// To the even tenth, as IEEE floating-point formatting does
+    HalfEven,
+}
+
+/// Writes the answer key for `stations` as `format` beside `output`,
+/// returning its path
+pub fn write(
+    output: &str,
+    stations: &[(Vec<u8>, Stats)],
+    format: Format,
+    rounding: Rounding,
+) -> Result<String> {
+    let output = template::without_shard(output);
+    let path = match format {
+        Format::Official => named(&output, "out"),
+        Format::Csv => named(&output, "answer.csv"),
+    };
+    let mut writer = BufWriter::new(File::create(&path)?);
+    render(&mut writer, stations, format, rounding)?;
+    writer.flush()?;
+    Ok(path)
+}
+
+/// Writes each of `stations` with its min, mean, and max to `writer` as
+/// `format`
+pub fn render(
+    writer: &mut impl Write,
+    stations: &[(Vec<u8>, Stats)],
+    format: Format,
+    rounding: Rounding,
+) -> Result<()> {
+    match format {
+        Format::Official => {
+            writer.write_all(b"{")?;
+            for (i, (station, stats)) in stations.iter().enumerate() {
+                if i > 0 {
+                    writer.write_all(b", ")?;
+                }
+                writer.write_all(station)?;
+                write!(
+                    writer,
+                    "={}/{}/{}",
+                    decimal(stats.min),
+                    decimal(mean(stats, rounding)),
+                    decimal(stats.max)
+                )?;
+            }
+            writer.write_all(b"}\n")?;
+        }
+        Format::Csv => {
+            writeln!(writer, "station;min;mean;max;count")?;
+            for (station, stats) in stations {
+                writer.write_all(station)?;
+                writeln!(
+                    writer,
+                    ";{};{};{};{}",
+                    decimal(stats.min),
+                    decimal(mean(stats, rounding)),
+                    decimal(stats.max),
+                    stats.count
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `output` with its extension replaced by `extension`, unless that names
+/// the output itself
+fn named(output: &str, extension: &str) -> String {
+    let path = Path::new(output)
+        .with_extension(extension)
+        .to_string_lossy()
+        .into_owned();
+    match path == output {
+        true => format!("{}.{}", output, extension),
+        false => path,
+    }
+}
+
+/// The mean in tenths, rounded by `rounding`, worked out exactly
+pub fn mean(stats: &Stats, rounding: Rounding) -> i64 {
+    let (sum, count) = (stats.sum as i128, stats.count as i128);
+    let (quotient, remainder) = (sum.div_euclid(count), sum.rem_euclid(count));
+    let up = match (2 * remainder).cmp(&count) {
+        Ordering::Less => false,
+        Ordering::Greater => true,
+        Ordering::Equal => match rounding {
+            Rounding::HalfUp => true,
+            Rounding::HalfEven => quotient % 2 != 0,
+        },
+    };
+    (quotient + i128::from(up)) as i64
+}
+
+/// Tenths of a degree as a number with one decimal
+fn decimal(tenths: i64) -> String {
+    let sign = if tenths < 0 { "-" } else { "" };
+    format!("{}{}.{}", sign, tenths.abs() / 10, tenths.abs() % 10)
+}
@@ -0,0 +1,389 @@
+#[cfg(feature = "compression")]
+use std::io::{BufReader, Seek, SeekFrom};
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Read, Write},
+    path::Path,
+    time::UNIX_EPOCH,
+};
+
+use clap::ValueEnum;
+use eyre::{eyre, Result};
+#[cfg(feature = "compression")]
+use miniz_oxide::deflate::core::{
+    compress, create_comp_flags_from_zip_params, CompressorOxide, TDEFLFlush, TDEFLStatus,
+};
+
+#[cfg(feature = "compression")]
+use crate::{compress::crc32_update, schema::civil_from_days};
+use crate::{output, template};
+
+/// Formats a finished dataset can be packaged in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// An uncompressed tar archive
+    Tar,
+    /// A tar archive compressed as a single gzip stream
+    #[value(name = "tar.gz")]
+    TarGz,
+    /// A zip archive, each file deflated, with Zip64 records for large files
+    Zip,
+}
+
+/// Deflate level used inside archives, the same default as gzip and zip
+#[cfg(feature = "compression")]
+const LEVEL: i32 = 6;
+
+/// Where the archive of `output` is written: named after the output, or
+/// what its parts' names share when it has a `{shard}` placeholder
+pub fn path(output: &str, format: Format) -> String {
+    let extension = format
+        .to_possible_value()
+        .expect("Formats have no skipped values");
+    format!(
+        "{}.{}",
+        template::without_shard(output),
+        extension.get_name()
+    )
+}
+
+/// Packages `files` into an archive at `path`, each under its file name,
+/// then removes them. The archive is written under a temporary name until
+/// it is complete, so a failure leaves the files where they were.
+pub fn create(path: &str, format: Format, files: &[String]) -> Result<()> {
+    let _span = tracing::debug_span!("archive", files = files.len()).entered();
+    let partial = output::partial_path(path);
+    let mut archive = BufWriter::new(File::create(&partial)?);
+    match format {
+        Format::Tar => write_tar(&mut archive, files)?,
+        #[cfg(feature = "compression")]
+        Format::TarGz => {
+            // Magic, deflate, no flags, no modification time, no extra
+            // flags, and an unknown operating system
+            archive.write_all(&[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff])?;
+            let mut gzip = Deflater::new(&mut archive);
+            write_tar(&mut gzip, files)?;
+            let (archive, crc, len, _) = gzip.finish()?;
+            archive.write_all(&crc.to_le_bytes())?;
+            archive.write_all(&(len as u32).to_le_bytes())?;
+        }
+        #[cfg(feature = "compression")]
+        Format::Zip => write_zip(&mut archive, files)?,
+        #[cfg(not(feature = "compression"))]
+        Format::TarGz | Format::Zip => unreachable!("Compressed archives are refused up front"),
+    }
+    archive
+        .into_inner()
+        .map_err(|e| e.into_error())?
+        .sync_all()?;
+    fs::rename(&partial, path)?;
+    for file in files {
+        fs::remove_file(file)?;
+    }
+    Ok(())
+}
+
+fn name(path: &str) -> Result<String> {
+    Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| eyre!("{} has no file name to archive it under", path))
+}
+
+/// Seconds since the Unix epoch at which `metadata` was last modified
+fn mtime(metadata: &fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Appends `files` to a ustar archive, with sizes too large for its octal
+/// field in the base-256 form GNU tar and bsdtar read
+fn write_tar<W: Write>(archive: &mut W, files: &[String]) -> Result<()> {
+    for file in files {
+        let name = name(file)?;
+        if name.len() > 100 {
+            return Err(eyre!("{} is too long a name for a tar archive", name));
+        }
+        let metadata = fs::metadata(file)?;
+        let len = metadata.len();
+        let mut header = [0u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[100..108].copy_from_slice(b"0000644\0");
+        header[108..116].copy_from_slice(b"0000000\0");
+        header[116..124].copy_from_slice(b"0000000\0");
+        if len < 1 << 33 {
+            header[124..136].copy_from_slice(format!("{:011o}\0", len).as_bytes());
+        } else {
+            header[124] = 0x80;
+            header[128..136].copy_from_slice(&len.to_be_bytes());
+        }
+        header[136..148].copy_from_slice(format!("{:011o}\0", mtime(&metadata)).as_bytes());
+        header[148..156].fill(b' ');
+        header[156] = b'0';
+        header[257..265].copy_from_slice(b"ustar\x0000");
+        let checksum: u32 = header.iter().map(|&b| u32::from(b)).sum();
+        header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+        archive.write_all(&header)?;
+        let copied = io::copy(&mut File::open(file)?.take(len), archive)?;
+        if copied != len {
+            return Err(eyre!("{} changed while it was archived", file));
+        }
+        archive.write_all(&[0; 512][..(512 - len % 512) as usize % 512])?;
+    }
+    // Two empty blocks end the archive
+    archive.write_all(&[0; 1024])?;
+    Ok(())
+}
+
+/// A zip entry, as the central directory records it
+#[cfg(feature = "compression")]
+struct ZipEntry {
+    name: String,
+    crc: u32,
+    len: u64,
+    compressed: u64,
+    offset: u64,
+    time: u16,
+    date: u16,
+}
+
+/// Where a 32-bit zip field is replaced by its Zip64 extra field
+#[cfg(feature = "compression")]
+const ZIP64: u32 = u32::MAX;
+
+/// Writes `files` as a zip archive, deflating each. Local headers are
+/// patched with the CRC and sizes once each file is written, and Zip64
+/// records are only used where a size or offset needs them.
+#[cfg(feature = "compression")]
+fn write_zip(archive: &mut BufWriter<File>, files: &[String]) -> Result<()> {
+    let mut entries = Vec::new();
+    for file in files {
+        let name = name(file)?;
+        let metadata = fs::metadata(file)?;
+        let len = metadata.len();
+        let (time, date) = dos_time(mtime(&metadata));
+        let offset = archive.stream_position()?;
+        // Deflate can grow data slightly, so leave room for that
+        let zip64 = len + len / 1_000 + (1 << 16) >= u64::from(ZIP64);
+        let mut header = Vec::new();
+        header.extend(0x0403_4b50u32.to_le_bytes());
+        header.extend(version_needed(zip64).to_le_bytes());
+        // Names are UTF-8
+        header.extend(0x0800u16.to_le_bytes());
+        header.extend(8u16.to_le_bytes());
+        header.extend(time.to_le_bytes());
+        header.extend(date.to_le_bytes());
+        // The CRC and sizes are filled in once known
+        header.extend([0; 12]);
+        header.extend((name.len() as u16).to_le_bytes());
+        header.extend(if zip64 { 20u16 } else { 0 }.to_le_bytes());
+        header.extend(name.as_bytes());
+        if zip64 {
+            header.extend(1u16.to_le_bytes());
+            header.extend(16u16.to_le_bytes());
+            header.extend([0; 16]);
+        }
+        archive.write_all(&header)?;
+
+        let mut deflater = Deflater::new(&mut *archive);
+        io::copy(
+            &mut BufReader::with_capacity(1 << 20, File::open(file)?),
+            &mut deflater,
+        )?;
+        let (_, crc, read, compressed) = deflater.finish()?;
+        if read != len {
+            return Err(eyre!("{} changed while it was archived", file));
+        }
+        if !zip64 && compressed >= u64::from(ZIP64) {
+            return Err(eyre!("{} grew past 4 GiB when deflated", file));
+        }
+
+        let end = archive.stream_position()?;
+        archive.seek(SeekFrom::Start(offset + 14))?;
+        archive.write_all(&crc.to_le_bytes())?;
+        if zip64 {
+            archive.write_all(&[0xff; 8])?;
+            archive.seek(SeekFrom::Start(offset + 30 + name.len() as u64 + 4))?;
+            archive.write_all(&len.to_le_bytes())?;
+            archive.write_all(&compressed.to_le_bytes())?;
+        } else {
+            archive.write_all(&(compressed as u32).to_le_bytes())?;
+            archive.write_all(&(len as u32).to_le_bytes())?;
+        }
+        archive.seek(SeekFrom::Start(end))?;
+        entries.push(ZipEntry {
+            name,
+            crc,
+            len,
+            compressed,
+            offset,
+            time,
+            date,
+        });
+    }
+
+    let directory_offset = archive.stream_position()?;
+    for entry in &entries {
+        // Only the fields too large for 32 bits go in the Zip64 extra field,
+        // in this order
+        let large: Vec<u64> = [entry.len, entry.compressed, entry.offset]
+            .into_iter()
+            .filter(|&value| value >= u64::from(ZIP64))
+            .collect();
+        let field = |value: u64| u32::try_from(value).unwrap_or(ZIP64);
+        let mut header = Vec::new();
+        header.extend(0x0201_4b50u32.to_le_bytes());
+        // Made by Unix, to the version Zip64 needs
+        header.extend(((3u16 << 8) | 45).to_le_bytes());
+        header.extend(version_needed(!large.is_empty()).to_le_bytes());
+        header.extend(0x0800u16.to_le_bytes());
+        header.extend(8u16.to_le_bytes());
+        header.extend(entry.time.to_le_bytes());
+        header.extend(entry.date.to_le_bytes());
+        header.extend(entry.crc.to_le_bytes());
+        header.extend(field(entry.compressed).to_le_bytes());
+        header.extend(field(entry.len).to_le_bytes());
+        header.extend((entry.name.len() as u16).to_le_bytes());
+        let extra_len = if large.is_empty() {
+            0
+        } else {
+            4 + 8 * large.len()
+        };
+        header.extend((extra_len as u16).to_le_bytes());
+        // No comment, on the first disk, with no internal attributes
+        header.extend([0; 6]);
+        // A regular file, readable by all and writable by its owner
+        header.extend((0o100_644u32 << 16).to_le_bytes());
+        header.extend(field(entry.offset).to_le_bytes());
+        header.extend(entry.name.as_bytes());
+        if !large.is_empty() {
+            header.extend(1u16.to_le_bytes());
+            header.extend(((8 * large.len()) as u16).to_le_bytes());
+            for value in large {
+                header.extend(value.to_le_bytes());
+            }
+        }
+        archive.write_all(&header)?;
+    }
+    let directory_end = archive.stream_position()?;
+    let directory_len = directory_end - directory_offset;
+
+    let count = entries.len() as u64;
+    if directory_offset >= u64::from(ZIP64) || count >= 0xffff {
+        let mut record = Vec::new();
+        record.extend(0x0606_4b50u32.to_le_bytes());
+        record.extend(44u64.to_le_bytes());
+        record.extend(((3u16 << 8) | 45).to_le_bytes());
+        record.extend(45u16.to_le_bytes());
+        record.extend([0; 8]);
+        record.extend(count.to_le_bytes());
+        record.extend(count.to_le_bytes());
+        record.extend(directory_len.to_le_bytes());
+        record.extend(directory_offset.to_le_bytes());
+        // The locator, pointing back at the record
+        record.extend(0x0706_4b50u32.to_le_bytes());
+        record.extend(0u32.to_le_bytes());
+        record.extend(directory_end.to_le_bytes());
+        record.extend(1u32.to_le_bytes());
+        archive.write_all(&record)?;
+    }
+    let mut end = Vec::new();
+    end.extend(0x0605_4b50u32.to_le_bytes());
+    end.extend([0; 4]);
+    let short_count = count.min(0xffff) as u16;
+    end.extend(short_count.to_le_bytes());
+    end.extend(short_count.to_le_bytes());
+    end.extend((directory_len.min(u64::from(ZIP64)) as u32).to_le_bytes());
+    end.extend((directory_offset.min(u64::from(ZIP64)) as u32).to_le_bytes());
+    end.extend(0u16.to_le_bytes());
+    archive.write_all(&end)?;
+    Ok(())
+}
+
+#[cfg(feature = "compression")]
+fn version_needed(zip64: bool) -> u16 {
+    if zip64 {
+        45
+    } else {
+        20
+    }
+}
+
+/// An MS-DOS time and date, which zip stores, for `seconds` since the Unix
+/// epoch in UTC. DOS dates start in 1980.
+#[cfg(feature = "compression")]
+fn dos_time(seconds: u64) -> (u16, u16) {
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let secs = seconds % 86_400;
+    let time = ((secs / 3_600) << 11) | ((secs % 3_600 / 60) << 5) | (secs % 60 / 2);
+    let date = (((year.clamp(1980, 2107) - 1980) << 9) | (month << 5) | day) as u16;
+    (time as u16, date)
+}
+
+/// A raw deflate stream over `inner`, counting and summing what goes in
+#[cfg(feature = "compression")]
+struct Deflater<W: Write> {
+    inner: W,
+    compressor: Box<CompressorOxide>,
+    buf: Vec<u8>,
+    crc: u32,
+    len: u64,
+    compressed: u64,
+}
+
+#[cfg(feature = "compression")]
+impl<W: Write> Deflater<W> {
+    fn new(inner: W) -> Self {
+        // Negative window bits ask for raw deflate, without a zlib header
+        let flags = create_comp_flags_from_zip_params(LEVEL, -15, 0);
+        Self {
+            inner,
+            compressor: Box::new(CompressorOxide::new(flags)),
+            buf: vec![0; 1 << 16],
+            crc: !0,
+            len: 0,
+            compressed: 0,
+        }
+    }
+
+    fn deflate(&mut self, mut data: &[u8], flush: TDEFLFlush) -> io::Result<()> {
+        loop {
+            let (status, read, wrote) = compress(&mut self.compressor, data, &mut self.buf, flush);
+            self.inner.write_all(&self.buf[..wrote])?;
+            self.compressed += wrote as u64;
+            data = &data[read..];
+            match status {
+                TDEFLStatus::Done => return Ok(()),
+                TDEFLStatus::Okay if data.is_empty() && flush == TDEFLFlush::None => return Ok(()),
+                TDEFLStatus::Okay => {}
+                TDEFLStatus::BadParam | TDEFLStatus::PutBufFailed => {
+                    return Err(io::Error::other("Deflate failed"))
+                }
+            }
+        }
+    }
+
+    /// Ends the stream, returning the inner writer, the CRC-32 and length
+    /// of what went in, and the length of what came out
+    fn finish(mut self) -> io::Result<(W, u32, u64, u64)> {
+        self.deflate(&[], TDEFLFlush::Finish)?;
+        Ok((self.inner, !self.crc, self.len, self.compressed))
+    }
+}
+
+#[cfg(feature = "compression")]
+impl<W: Write> Write for Deflater<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.crc = crc32_update(self.crc, data);
+        self.len += data.len() as u64;
+        self.deflate(data, TDEFLFlush::None)?;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
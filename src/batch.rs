@@ -0,0 +1,255 @@
+use std::{collections::HashMap, env, ffi::OsString, fs, iter, path::Path, sync::Arc};
+
+use clap::{parser::ValueSource, CommandFactory, Parser};
+use eyre::{eyre, Result, WrapErr};
+
+use crate::{
+    cache,
+    cli::{list_options, with_stations, Args},
+    errors, interrupt,
+    progress::{Batch, ProgressMode},
+    station::{load_weather_stations, Stations},
+    summary::Summary,
+    toml::{self, Table, Value},
+};
+
+/// One dataset of a batch
+struct Dataset {
+    args: Args,
+    /// The `[matrix]` values it was given, as `option=value` pairs
+    described: String,
+}
+
+/// Generates every dataset the TOML config at `path` describes, in turn,
+/// each with `generate`. Its top-level options apply to all of them, along
+/// with those given before `batch`; `[matrix]` gives values for options to
+/// take, with a dataset for each combination, and `{option}` in the output path
+/// stands for the value a dataset was given. Station lists are loaded once
+/// for every dataset reading them the same way.
+pub fn batch<G>(args: &Args, path: &str, generate: G) -> Result<()>
+where
+    G: Fn(Args) -> Result<Summary>,
+{
+    let config =
+        fs::read_to_string(path).wrap_err_with(|| format!("Could not open file {}", path))?;
+    let datasets = toml::parse(&config)
+        .and_then(|tables| datasets(&tables))
+        .wrap_err_with(|| format!("Invalid batch config {}", path))?;
+    let mut outputs: HashMap<&str, &str> = HashMap::new();
+    for dataset in &datasets {
+        if let Some(other) = outputs.insert(&dataset.args.output, &dataset.described) {
+            let option = dataset.described.split('=').next().unwrap_or_default();
+            return Err(eyre!(
+                "The datasets {} and {} would both be written to {}; name the \
+                 [matrix] options in `output`, such as `{{{}}}`",
+                other,
+                dataset.described,
+                dataset.args.output,
+                option
+            ));
+        }
+    }
+
+    let mode = match args.tui {
+        true => ProgressMode::Tui,
+        false => args.progress.unwrap_or_else(ProgressMode::detect),
+    };
+    let progress = Batch::new(datasets.len() as u64, args.quiet, mode);
+    let mut lists: HashMap<(String, u64, bool), Arc<Stations>> = HashMap::new();
+    let count = datasets.len();
+    let mut remaining = datasets.into_iter();
+    while let Some(Dataset { args, described }) = remaining.next() {
+        // Ctrl-C between datasets; one cut short reports itself
+        if interrupt::requested() {
+            let skipped: Vec<_> = iter::once(Dataset { args, described })
+                .chain(remaining)
+                .map(|dataset| match dataset.described.is_empty() {
+                    true => dataset.args.output,
+                    false => dataset.described,
+                })
+                .collect();
+            return Err(eyre!(
+                "Interrupted after {} of {} datasets, skipping {}",
+                count - skipped.len(),
+                count,
+                skipped.join(", ")
+            ));
+        }
+        let mut args = with_stations(&args)?;
+        if args.schema.is_none() {
+            let options = list_options(&args);
+            let key = (
+                args.weather_stations.clone(),
+                options.fingerprint(),
+                args.cache_stations,
+            );
+            let stations = match lists.get(&key) {
+                Some(stations) => stations.clone(),
+                None => {
+                    let _span = tracing::debug_span!("load_stations").entered();
+                    let stations = match args.cache_stations {
+                        true => cache::load(&args.weather_stations, options)?,
+                        false => load_weather_stations(args.weather_stations.clone(), options)?,
+                    };
+                    lists.entry(key).or_insert(Arc::new(stations)).clone()
+                }
+            };
+            args.loaded_stations = Some(stations);
+        }
+        tracing::debug!(dataset = described, output = args.output, "Generating");
+        progress.start(&args.output);
+        args.batch = Some(progress.clone());
+        generate(args).wrap_err_with(|| format!("Could not generate the dataset {}", described))?;
+        progress.done();
+    }
+    progress.finish();
+    Ok(())
+}
+
+/// The options given before `batch` followed by the config's, once for each
+/// combination of its matrix's values, the first option in the matrix
+/// changing slowest
+fn datasets(tables: &[Table]) -> Result<Vec<Dataset>> {
+    let mut options = Vec::new();
+    let mut matrix = Vec::new();
+    for table in tables {
+        if table.array {
+            return Err(eyre!(
+                "Unknown array of tables [[{}]]; options go at the top or under [matrix]",
+                table.name
+            ));
+        }
+        match table.name.as_str() {
+            "" => options.extend(table.pairs.iter().cloned()),
+            "matrix" => {
+                for (name, values) in &table.pairs {
+                    match values {
+                        Value::Array(values) if !values.is_empty() => {
+                            matrix.push((name.clone(), values.clone()))
+                        }
+                        _ => {
+                            return Err(eyre!(
+                                "`{}` in [matrix] must be an array of at least one value",
+                                name
+                            ))
+                        }
+                    }
+                }
+            }
+            name => {
+                return Err(eyre!(
+                    "Unknown table [{}]; options go at the top or under [matrix]",
+                    name
+                ))
+            }
+        }
+    }
+    if let Some((name, _)) = matrix
+        .iter()
+        .find(|(name, _)| options.iter().any(|(option, _)| option == name))
+    {
+        return Err(eyre!("`{}` is given both at the top and in [matrix]", name));
+    }
+    let shared = Args::command()
+        .try_get_matches_from(shared_words())
+        .map_err(|e| eyre!("{}", errors::problem(&e)))?;
+    let names = options
+        .iter()
+        .map(|(name, _)| name)
+        .chain(matrix.iter().map(|(name, _)| name));
+    for name in names {
+        let id = name.replace('-', "_");
+        let known = Args::command()
+            .get_arguments()
+            .any(|arg| arg.get_id() == id.as_str());
+        if known && shared.value_source(&id) == Some(ValueSource::CommandLine) {
+            return Err(eyre!(
+                "--{} is given both before `batch` and in the config",
+                name.replace('_', "-")
+            ));
+        }
+    }
+
+    let combinations = matrix.iter().map(|(_, values)| values.len()).product();
+    let mut datasets = Vec::with_capacity(combinations);
+    for combination in 0..combinations {
+        // Mixed radix, the last option's value changing fastest
+        let mut rest = combination;
+        let mut picked = Vec::with_capacity(matrix.len());
+        for (name, values) in matrix.iter().rev() {
+            picked.push((name.clone(), values[rest % values.len()].clone()));
+            rest /= values.len();
+        }
+        picked.reverse();
+
+        let mut words = shared_words();
+        for (name, value) in options.iter().chain(&picked) {
+            words.extend(flag(name, value)?.map(OsString::from));
+        }
+        let mut dataset =
+            Args::try_parse_from(words).map_err(|e| eyre!("{}", errors::problem(&e)))?;
+        for (name, value) in &picked {
+            let placeholder = format!("{{{}}}", name.replace('_', "-"));
+            dataset.output = dataset.output.replace(&placeholder, &path_part(value)?);
+        }
+        let described = picked
+            .iter()
+            .map(|(name, value)| Ok(format!("{}={}", name, text(name, value)?)))
+            .collect::<Result<Vec<_>>>()?
+            .join(" ");
+        datasets.push(Dataset {
+            args: dataset,
+            described,
+        });
+    }
+    Ok(datasets)
+}
+
+/// The process's arguments up to the `batch` subcommand: the first `batch`
+/// that isn't the value of an option before it
+fn shared_words() -> Vec<OsString> {
+    let words: Vec<_> = env::args_os().collect();
+    let at = (1..words.len())
+        .find(|&at| words[at] == "batch" && Args::try_parse_from(&words[..at]).is_ok())
+        .unwrap_or(words.len());
+    words[..at].to_vec()
+}
+
+/// The command line words setting the long option `name` to `value`:
+/// `true` passes the flag, `false` leaves it out, and an array gives
+/// comma-separated values
+fn flag(name: &str, value: &Value) -> Result<Option<String>> {
+    let flag = format!("--{}", name.replace('_', "-"));
+    Ok(match value {
+        Value::Bool(true) => Some(flag),
+        Value::Bool(false) => None,
+        value => Some(format!("{}={}", flag, text(name, value)?)),
+    })
+}
+
+fn text(name: &str, value: &Value) -> Result<String> {
+    match value {
+        Value::String(string) | Value::Number(string) => Ok(string.clone()),
+        Value::Bool(b) => Ok(b.to_string()),
+        Value::Array(items) => Ok(items
+            .iter()
+            .map(|item| match item {
+                Value::Array(_) => Err(eyre!("The option {} can't take nested arrays", name)),
+                item => text(name, item),
+            })
+            .collect::<Result<Vec<_>>>()?
+            .join(",")),
+    }
+}
+
+/// `value` as it names a dataset's output: a path's file name without its
+/// extension, and anything else as written
+fn path_part(value: &Value) -> Result<String> {
+    let text = text("", value)?;
+    Ok(match text.contains('/') {
+        true => Path::new(&text)
+            .file_stem()
+            .map_or(text.clone(), |stem| stem.to_string_lossy().into_owned()),
+        false => text,
+    })
+}
@@ -0,0 +1,415 @@
+use std::{
+    io::{BufRead, BufReader, BufWriter, Read, Write},
+    net::TcpStream,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::Duration,
+};
+
+use eyre::{eyre, Result, WrapErr};
+
+use crate::json;
+
+/// How long the broker has to confirm it has every message once the last
+/// is sent
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// NATS's own limit on a message when the server doesn't state one
+const NATS_MAX_PAYLOAD: usize = 1 << 20;
+
+/// The largest remaining length an MQTT packet can declare
+const MQTT_MAX_PACKET: usize = 268_435_455;
+
+/// The message brokers `--output` can name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Protocol {
+    Nats,
+    Mqtt,
+}
+
+/// Whether `output` names a message broker rather than a file
+pub fn is_url(output: &str) -> bool {
+    output.starts_with("nats://") || output.starts_with("mqtt://")
+}
+
+/// A `nats://[user:password@]host[:port]/subject` or
+/// `mqtt://[user:password@]host[:port]/topic` URL, taken apart
+struct Target {
+    protocol: Protocol,
+    address: String,
+    credentials: Option<(String, String)>,
+    subject: String,
+}
+
+impl Target {
+    fn parse(url: &str) -> Result<Self> {
+        let (protocol, rest, port) = match url.split_once("://") {
+            Some(("nats", rest)) => (Protocol::Nats, rest, 4222),
+            Some(("mqtt", rest)) => (Protocol::Mqtt, rest, 1883),
+            _ => return Err(eyre!("`{}` is not a nats:// or mqtt:// URL", url)),
+        };
+        let (authority, subject) = rest.split_once('/').unwrap_or((rest, ""));
+        let (credentials, host) = match authority.rsplit_once('@') {
+            Some((user, host)) => {
+                let (user, password) = user.split_once(':').unwrap_or((user, ""));
+                (Some((user.to_string(), password.to_string())), host)
+            }
+            None => (None, authority),
+        };
+        if host.is_empty() {
+            return Err(eyre!("`{}` names no host", url));
+        }
+        let what = match protocol {
+            Protocol::Nats => "subject",
+            Protocol::Mqtt => "topic",
+        };
+        if subject.is_empty() {
+            return Err(eyre!("`{}` names no {} after the host", url, what));
+        }
+        if protocol == Protocol::Nats && subject.contains(char::is_whitespace) {
+            return Err(eyre!("The NATS subject `{}` can't hold spaces", subject));
+        }
+        // A port is only missing when nothing follows the last `:`, or it
+        // is an IPv6 address's
+        let address = match host.rsplit_once(':') {
+            Some((_, port)) if !port.is_empty() && !port.contains(']') => host.to_string(),
+            _ => format!("{}:{}", host, port),
+        };
+        Ok(Self {
+            protocol,
+            address,
+            credentials,
+            subject: subject.to_string(),
+        })
+    }
+}
+
+/// What the broker sent back, as the thread reading its replies reports it
+enum Reply {
+    Ping,
+    Pong,
+    Error(String),
+    Closed,
+}
+
+/// Publishes rows to the subject or topic of a message broker, `batch` rows
+/// to a message, in place of an output file. Messages are sent without
+/// waiting for the broker, which is only asked to confirm it has them all
+/// once the last is sent.
+pub struct Publisher {
+    protocol: Protocol,
+    subject: String,
+    writer: BufWriter<TcpStream>,
+    replies: Receiver<Reply>,
+    batch: usize,
+    /// The rows of the message being gathered, separated by newlines
+    message: Vec<u8>,
+    rows: usize,
+    max_payload: usize,
+}
+
+impl Publisher {
+    /// Connects to the broker `url` names, for messages of `batch` rows
+    pub fn connect(url: &str, batch: usize) -> Result<Self> {
+        let target = Target::parse(url)?;
+        let stream = TcpStream::connect(&target.address)
+            .wrap_err_with(|| format!("Could not connect to {}", target.address))?;
+        stream.set_nodelay(true)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = BufWriter::with_capacity(1 << 20, stream);
+        let max_payload = match target.protocol {
+            Protocol::Nats => nats_handshake(&mut reader, &mut writer, &target)?,
+            Protocol::Mqtt => {
+                mqtt_handshake(&mut reader, &mut writer, &target)?;
+                MQTT_MAX_PACKET - 2 - target.subject.len()
+            }
+        };
+        let (sender, replies) = mpsc::channel();
+        let protocol = target.protocol;
+        thread::spawn(move || match protocol {
+            Protocol::Nats => read_nats(reader, sender),
+            Protocol::Mqtt => read_mqtt(reader, sender),
+        });
+        tracing::debug!(url, max_payload, "Connected to the broker");
+        Ok(Self {
+            protocol,
+            subject: target.subject,
+            writer,
+            replies,
+            batch,
+            message: Vec::new(),
+            rows: 0,
+            max_payload,
+        })
+    }
+
+    /// Publishes the rows of `chunk`, each ending in a newline, holding back
+    /// any that don't fill a message
+    pub fn write(&mut self, chunk: &[u8]) -> Result<()> {
+        self.answer()?;
+        let Some(rows) = chunk.strip_suffix(b"\n") else {
+            return Ok(());
+        };
+        for row in rows.split(|&b| b == b'\n') {
+            if self.rows > 0 {
+                self.message.push(b'\n');
+            }
+            self.message.extend_from_slice(row);
+            self.rows += 1;
+            if self.rows == self.batch {
+                self.publish()?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.answer()?;
+        Ok(self.writer.flush()?)
+    }
+
+    /// Publishes the rows held back, then waits for the broker to confirm
+    /// it has every message before disconnecting
+    pub fn finish(mut self) -> Result<()> {
+        if self.rows > 0 {
+            self.publish()?;
+        }
+        match self.protocol {
+            Protocol::Nats => self.writer.write_all(b"PING\r\n")?,
+            Protocol::Mqtt => self.writer.write_all(&[0xc0, 0])?,
+        }
+        self.writer.flush()?;
+        loop {
+            match self.replies.recv_timeout(CONFIRM_TIMEOUT) {
+                Ok(Reply::Pong) => break,
+                Ok(reply) => self.handle(reply)?,
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(eyre!(
+                        "The broker didn't confirm it had every message within {} seconds",
+                        CONFIRM_TIMEOUT.as_secs()
+                    ))
+                }
+                Err(RecvTimeoutError::Disconnected) => self.handle(Reply::Closed)?,
+            }
+        }
+        if self.protocol == Protocol::Mqtt {
+            self.writer.write_all(&[0xe0, 0])?;
+            self.writer.flush()?;
+        }
+        Ok(())
+    }
+
+    fn publish(&mut self) -> Result<()> {
+        if self.message.len() > self.max_payload {
+            return Err(eyre!(
+                "A message of {} bytes is over the {} bytes the broker accepts; lower --publish-batch",
+                self.message.len(),
+                self.max_payload
+            ));
+        }
+        match self.protocol {
+            Protocol::Nats => {
+                write!(
+                    self.writer,
+                    "PUB {} {}\r\n",
+                    self.subject,
+                    self.message.len()
+                )?;
+                self.writer.write_all(&self.message)?;
+                self.writer.write_all(b"\r\n")?;
+            }
+            Protocol::Mqtt => {
+                // QoS 0, so the broker sends nothing back for each message
+                let mut header = vec![0x30];
+                push_length(&mut header, 2 + self.subject.len() + self.message.len());
+                push_string(&mut header, &self.subject);
+                self.writer.write_all(&header)?;
+                self.writer.write_all(&self.message)?;
+            }
+        }
+        self.message.clear();
+        self.rows = 0;
+        Ok(())
+    }
+
+    /// Handles what the broker has sent since last asked, without waiting
+    fn answer(&mut self) -> Result<()> {
+        while let Ok(reply) = self.replies.try_recv() {
+            self.handle(reply)?;
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, reply: Reply) -> Result<()> {
+        match reply {
+            // The server drops clients that leave its pings unanswered
+            Reply::Ping => Ok(self.writer.write_all(b"PONG\r\n")?),
+            Reply::Pong => Ok(()),
+            Reply::Error(e) => Err(eyre!("The broker refused the messages: {}", e)),
+            Reply::Closed => Err(eyre!("The broker closed the connection")),
+        }
+    }
+}
+
+impl std::fmt::Debug for Publisher {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Publisher")
+            .field("protocol", &self.protocol)
+            .field("subject", &self.subject)
+            .finish()
+    }
+}
+
+/// Reads the server's `INFO`, sends `CONNECT`, and returns the largest
+/// message the server accepts
+fn nats_handshake(
+    reader: &mut impl BufRead,
+    writer: &mut impl Write,
+    target: &Target,
+) -> Result<usize> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let Some(info) = line.trim_end().strip_prefix("INFO ") else {
+        return Err(eyre!("{} isn't a NATS server", target.address));
+    };
+    let max_payload = json::parse(info)
+        .ok()
+        .and_then(|info| match info.get("max_payload") {
+            Some(json::Value::Number(max)) => Some(*max as usize),
+            _ => None,
+        })
+        .unwrap_or(NATS_MAX_PAYLOAD);
+    let mut connect =
+        String::from("{\"verbose\":false,\"pedantic\":false,\"name\":\"billion-row-gen\"");
+    if let Some((user, password)) = &target.credentials {
+        connect.push_str(&format!(
+            ",\"user\":{},\"pass\":{}",
+            crate::summary::json_string(user),
+            crate::summary::json_string(password)
+        ));
+    }
+    write!(writer, "CONNECT {}}}\r\n", connect)?;
+    writer.flush()?;
+    Ok(max_payload)
+}
+
+/// Sends `CONNECT` for a clean MQTT 3.1.1 session and checks the `CONNACK`
+fn mqtt_handshake(reader: &mut impl Read, writer: &mut impl Write, target: &Target) -> Result<()> {
+    let mut body = Vec::new();
+    push_string(&mut body, "MQTT");
+    body.push(4);
+    // A clean session, and the user name and password when given
+    let flags = match target.credentials {
+        Some(_) => 0xc2,
+        None => 0x02,
+    };
+    body.push(flags);
+    // No keep alive, since a stalled run may send nothing for a while
+    body.extend_from_slice(&[0, 0]);
+    push_string(
+        &mut body,
+        &format!("billion-row-gen-{}", std::process::id()),
+    );
+    if let Some((user, password)) = &target.credentials {
+        push_string(&mut body, user);
+        push_string(&mut body, password);
+    }
+    let mut packet = vec![0x10];
+    push_length(&mut packet, body.len());
+    packet.extend_from_slice(&body);
+    writer.write_all(&packet)?;
+    writer.flush()?;
+
+    let mut connack = [0; 4];
+    reader
+        .read_exact(&mut connack)
+        .wrap_err_with(|| format!("{} isn't an MQTT broker", target.address))?;
+    match connack {
+        [0x20, 2, _, 0] => Ok(()),
+        [0x20, 2, _, 4 | 5] => Err(eyre!(
+            "{} refused the user name or password",
+            target.address
+        )),
+        [0x20, 2, _, code] => Err(eyre!(
+            "{} refused the connection with code {}",
+            target.address,
+            code
+        )),
+        _ => Err(eyre!("{} isn't an MQTT broker", target.address)),
+    }
+}
+
+/// Passes on the server's pings, pongs, and errors until it disconnects
+fn read_nats(mut reader: impl BufRead, replies: Sender<Reply>) {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let reply = match reader.read_line(&mut line) {
+            Ok(0) | Err(_) => Reply::Closed,
+            Ok(_) => match line.trim_end() {
+                "PING" => Reply::Ping,
+                "PONG" => Reply::Pong,
+                reply => match reply.strip_prefix("-ERR ") {
+                    Some(e) => Reply::Error(e.trim_matches('\'').to_string()),
+                    None => continue,
+                },
+            },
+        };
+        let closed = matches!(reply, Reply::Closed);
+        if replies.send(reply).is_err() || closed {
+            return;
+        }
+    }
+}
+
+/// Passes on the broker's `PINGRESP` until it disconnects, skipping any
+/// other packet
+fn read_mqtt(mut reader: impl Read, replies: Sender<Reply>) {
+    loop {
+        let reply = match read_packet(&mut reader) {
+            Ok(0xd0) => Reply::Pong,
+            Ok(_) => continue,
+            Err(_) => Reply::Closed,
+        };
+        let closed = matches!(reply, Reply::Closed);
+        if replies.send(reply).is_err() || closed {
+            return;
+        }
+    }
+}
+
+/// Reads one MQTT packet, returning its type and flags
+fn read_packet(reader: &mut impl Read) -> std::io::Result<u8> {
+    let mut byte = [0; 1];
+    reader.read_exact(&mut byte)?;
+    let kind = byte[0];
+    let (mut length, mut shift) = (0usize, 0);
+    loop {
+        reader.read_exact(&mut byte)?;
+        length |= usize::from(byte[0] & 0x7f) << shift;
+        shift += 7;
+        if byte[0] & 0x80 == 0 || shift > 21 {
+            break;
+        }
+    }
+    std::io::copy(&mut reader.take(length as u64), &mut std::io::sink())?;
+    Ok(kind)
+}
+
+/// Appends an MQTT remaining length, seven bits to a byte
+fn push_length(packet: &mut Vec<u8>, mut length: usize) {
+    loop {
+        let byte = (length % 128) as u8;
+        length /= 128;
+        match length {
+            0 => return packet.push(byte),
+            _ => packet.push(byte | 0x80),
+        }
+    }
+}
+
+/// Appends an MQTT string, prefixed by its length
+fn push_string(packet: &mut Vec<u8>, string: &str) {
+    packet.extend_from_slice(&(string.len() as u16).to_be_bytes());
+    packet.extend_from_slice(string.as_bytes());
+}
@@ -0,0 +1,399 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+/// Symbols coded with one table before the next selector
+const GROUP_SIZE: usize = 50;
+
+/// The longest code the encoder assigns, as the bzip2 tool limits them
+const MAX_CODE_LENGTH: u8 = 17;
+
+/// Rounds of choosing a table for each group and fitting the tables to the
+/// groups that chose them
+const ITERATIONS: usize = 4;
+
+/// `data` as a complete bzip2 stream of blocks of `level` times 100k, from
+/// 1 to 9, which concatenated with others reads back as one stream with the
+/// bzip2 tool
+pub fn stream(data: &[u8], level: usize) -> Vec<u8> {
+    let mut bits = BitWriter::default();
+    bits.write(32, u32::from_be_bytes(*b"BZh0") + level as u32);
+    // Bytes a block holds once runs are shortened, as the bzip2 tool limits
+    // them
+    let block_size = level * 100_000 - 19;
+    let (mut rest, mut combined) = (data, 0u32);
+    while !rest.is_empty() {
+        let (block, taken) = shorten_runs(rest, block_size);
+        let crc = crc32(&rest[..taken]);
+        combined = combined.rotate_left(1) ^ crc;
+        write_block(&mut bits, &block, crc);
+        rest = &rest[taken..];
+    }
+    bits.write(24, 0x177245);
+    bits.write(24, 0x385090);
+    bits.write(32, combined);
+    bits.finish()
+}
+
+/// As much of `data` as fits a block of `block_size` with each run of 4 to
+/// 255 equal bytes written as 4 of them and a count of the rest, and how
+/// much that was
+fn shorten_runs(data: &[u8], block_size: usize) -> (Vec<u8>, usize) {
+    let mut block = Vec::with_capacity(block_size.min(data.len()));
+    let mut taken = 0;
+    while let Some(&byte) = data.get(taken) {
+        let run = data[taken..]
+            .iter()
+            .take(255)
+            .take_while(|&&b| b == byte)
+            .count();
+        if block.len() + run.min(5) > block_size {
+            break;
+        }
+        match run {
+            4.. => {
+                block.extend_from_slice(&[byte; 4]);
+                block.push((run - 4) as u8);
+            }
+            _ => block.extend(std::iter::repeat_n(byte, run)),
+        }
+        taken += run;
+    }
+    (block, taken)
+}
+
+/// Appends `block` to `bits` sorted by the Burrows-Wheeler transform, moved
+/// to front, and Huffman coded, under `crc`, the CRC of the data it came from
+fn write_block(bits: &mut BitWriter, block: &[u8], crc: u32) {
+    let order = sort_rotations(block);
+    let n = block.len();
+    let origin = order
+        .iter()
+        .position(|&i| i == 0)
+        .expect("Every rotation is sorted");
+    let last = order.iter().map(|&i| block[(i as usize + n - 1) % n]);
+
+    let mut in_use = [false; 256];
+    for &b in block {
+        in_use[usize::from(b)] = true;
+    }
+    let mut index = [0u16; 256];
+    let mut used = 0;
+    for (b, _) in in_use.iter().enumerate().filter(|(_, &used)| used) {
+        index[b] = used;
+        used += 1;
+    }
+    let end_of_block = used + 1;
+    let alphabet = usize::from(used) + 2;
+
+    // Positions in the move-to-front list, with runs of the front symbol as
+    // RUNA and RUNB digits and every other position one higher
+    let mut front: Vec<u16> = (0..used).collect();
+    let mut symbols = Vec::with_capacity(n + 1);
+    let mut zeros = 0;
+    for b in last {
+        let symbol = index[usize::from(b)];
+        let at = front
+            .iter()
+            .position(|&s| s == symbol)
+            .expect("Every symbol is in the list");
+        if at == 0 {
+            zeros += 1;
+            continue;
+        }
+        push_run(&mut symbols, zeros);
+        zeros = 0;
+        front[..=at].rotate_right(1);
+        symbols.push(at as u16 + 1);
+    }
+    push_run(&mut symbols, zeros);
+    symbols.push(end_of_block);
+
+    let (lengths, selectors) = fit_tables(&symbols, alphabet);
+    let codes: Vec<Vec<u32>> = lengths
+        .iter()
+        .map(|lengths| canonical_codes(lengths))
+        .collect();
+
+    bits.write(24, 0x314159);
+    bits.write(24, 0x265359);
+    bits.write(32, crc);
+    // Not randomised
+    bits.write(1, 0);
+    bits.write(24, origin as u32);
+    let ranges: Vec<&[bool]> = in_use.chunks(16).collect();
+    let used_ranges = ranges.iter().map(|range| range.contains(&true));
+    bits.write(16, flags(used_ranges));
+    for range in ranges.iter().filter(|range| range.contains(&true)) {
+        bits.write(16, flags(range.iter().copied()));
+    }
+
+    bits.write(3, lengths.len() as u32);
+    bits.write(15, selectors.len() as u32);
+    let mut tables: Vec<u8> = (0..lengths.len() as u8).collect();
+    for &selector in &selectors {
+        let at = tables
+            .iter()
+            .position(|&t| t == selector)
+            .expect("Every table is in the list");
+        tables[..=at].rotate_right(1);
+        bits.write(at as u32 + 1, (1 << (at + 1)) - 2);
+    }
+    for lengths in &lengths {
+        let mut current = lengths[0];
+        bits.write(5, u32::from(current));
+        for &length in lengths {
+            while current < length {
+                bits.write(2, 0b10);
+                current += 1;
+            }
+            while current > length {
+                bits.write(2, 0b11);
+                current -= 1;
+            }
+            bits.write(1, 0);
+        }
+    }
+
+    for (group, &table) in symbols.chunks(GROUP_SIZE).zip(&selectors) {
+        let (lengths, codes) = (&lengths[usize::from(table)], &codes[usize::from(table)]);
+        for &symbol in group {
+            let symbol = usize::from(symbol);
+            bits.write(u32::from(lengths[symbol]), codes[symbol]);
+        }
+    }
+}
+
+/// The start of each rotation of `block` in sorted order, found by sorting
+/// on twice as many leading bytes each round, only among the rotations
+/// still tied
+fn sort_rotations(block: &[u8]) -> Vec<u32> {
+    let n = block.len();
+    let mut order: Vec<u32> = (0..n as u32).collect();
+    order.sort_unstable_by_key(|&i| block[i as usize]);
+    // Each rotation's rank is where the rotations it ties with start in
+    // `order`
+    let mut rank = vec![0u32; n];
+    for at in 1..n {
+        let (previous, i) = (order[at - 1] as usize, order[at] as usize);
+        rank[i] = match block[i] == block[previous] {
+            true => rank[previous],
+            false => at as u32,
+        };
+    }
+    let mut next = rank.clone();
+    let mut length = 1;
+    while length < n {
+        let mut tied = false;
+        let mut start = 0;
+        while start < n {
+            let group = rank[order[start] as usize];
+            let end = start
+                + order[start..]
+                    .iter()
+                    .take_while(|&&i| rank[i as usize] == group)
+                    .count();
+            if end - start > 1 {
+                let key = |i: u32| rank[(i as usize + length) % n];
+                order[start..end].sort_unstable_by_key(|&i| key(i));
+                for at in start + 1..end {
+                    let (previous, i) = (order[at - 1], order[at]);
+                    next[i as usize] = match key(i) == key(previous) {
+                        true => {
+                            tied = true;
+                            next[previous as usize]
+                        }
+                        false => at as u32,
+                    };
+                }
+            }
+            start = end;
+        }
+        rank.copy_from_slice(&next);
+        if !tied {
+            break;
+        }
+        length *= 2;
+    }
+    order
+}
+
+/// Appends a run of `zeros` as RUNA (0) and RUNB (1) digits, least
+/// significant first, worth 1 and 2 times their place
+fn push_run(symbols: &mut Vec<u16>, mut zeros: usize) {
+    while zeros > 0 {
+        zeros -= 1;
+        symbols.push((zeros & 1) as u16);
+        zeros >>= 1;
+    }
+}
+
+/// Code lengths for 2 to 6 tables, more for more symbols, and which table
+/// codes each group of symbols. Tables start out favouring a band of
+/// symbols each, then each group picks the table coding it shortest and the
+/// tables are rebuilt from the groups that picked them.
+fn fit_tables(symbols: &[u16], alphabet: usize) -> (Vec<Vec<u8>>, Vec<u8>) {
+    let tables = match symbols.len() {
+        0..200 => 2,
+        200..600 => 3,
+        600..1200 => 4,
+        1200..2400 => 5,
+        _ => 6,
+    };
+    let mut frequencies = vec![0usize; alphabet];
+    for &symbol in symbols {
+        frequencies[usize::from(symbol)] += 1;
+    }
+    let mut lengths = Vec::with_capacity(tables);
+    let (mut remaining, mut start) = (symbols.len(), 0);
+    for left in (1..=tables).rev() {
+        let (target, mut end, mut taken) = (remaining / left, start, 0);
+        while taken < target && end < alphabet {
+            taken += frequencies[end];
+            end += 1;
+        }
+        let band = start..end;
+        lengths.push(
+            (0..alphabet)
+                .map(|symbol| if band.contains(&symbol) { 0 } else { 15 })
+                .collect::<Vec<u8>>(),
+        );
+        (remaining, start) = (remaining - taken, end);
+    }
+
+    let mut selectors = Vec::with_capacity(symbols.len().div_ceil(GROUP_SIZE));
+    for _ in 0..ITERATIONS {
+        let mut frequencies = vec![vec![0usize; alphabet]; tables];
+        selectors.clear();
+        for group in symbols.chunks(GROUP_SIZE) {
+            let cost = |t: usize| -> usize {
+                group
+                    .iter()
+                    .map(|&s| usize::from(lengths[t][usize::from(s)]))
+                    .sum()
+            };
+            let best = (0..tables)
+                .min_by_key(|&t| cost(t))
+                .expect("2 or more tables");
+            selectors.push(best as u8);
+            for &symbol in group {
+                frequencies[best][usize::from(symbol)] += 1;
+            }
+        }
+        lengths = frequencies.iter().map(|f| code_lengths(f)).collect();
+    }
+    (lengths, selectors)
+}
+
+/// Huffman code lengths of at most [`MAX_CODE_LENGTH`] for symbols seen
+/// `frequencies` times, giving unseen ones a code too since every symbol
+/// needs one. Frequencies are flattened until the longest code fits.
+fn code_lengths(frequencies: &[usize]) -> Vec<u8> {
+    let mut weights: Vec<usize> = frequencies.iter().map(|&f| f.max(1)).collect();
+    loop {
+        let n = weights.len();
+        let mut parents = vec![0; 2 * n];
+        let mut heap: BinaryHeap<_> = weights
+            .iter()
+            .enumerate()
+            .map(|(node, &weight)| Reverse((weight, node)))
+            .collect();
+        let mut node = n;
+        while let (Some(Reverse((a, left))), Some(Reverse((b, right)))) = (heap.pop(), heap.pop()) {
+            parents[left] = node;
+            parents[right] = node;
+            heap.push(Reverse((a + b, node)));
+            node += 1;
+        }
+        let root = node - 1;
+        let lengths: Vec<u8> = (0..n)
+            .map(|mut at| {
+                let mut depth = 0;
+                while at != root {
+                    at = parents[at];
+                    depth += 1;
+                }
+                depth
+            })
+            .collect();
+        if lengths.iter().all(|&length| length <= MAX_CODE_LENGTH) {
+            return lengths;
+        }
+        for weight in &mut weights {
+            *weight = 1 + *weight / 2;
+        }
+    }
+}
+
+/// Codes for `lengths` counting up through each length in symbol order, as
+/// bzip2 assigns them
+fn canonical_codes(lengths: &[u8]) -> Vec<u32> {
+    let mut codes = vec![0; lengths.len()];
+    let mut code = 0;
+    for length in 1..=MAX_CODE_LENGTH {
+        for (symbol, _) in lengths.iter().enumerate().filter(|(_, &l)| l == length) {
+            codes[symbol] = code;
+            code += 1;
+        }
+        code <<= 1;
+    }
+    codes
+}
+
+/// Up to 16 flags as bits, the first the most significant
+fn flags(flags: impl Iterator<Item = bool>) -> u32 {
+    flags.fold(0, |bits, flag| bits << 1 | u32::from(flag))
+}
+
+/// The CRC-32 bzip2 uses, with the 0x04c11db7 polynomial taken most
+/// significant bit first
+fn crc32(data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = (i as u32) << 24;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 0x8000_0000 != 0 {
+                    (crc << 1) ^ 0x04c1_1db7
+                } else {
+                    crc << 1
+                };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+    !data.iter().fold(!0u32, |crc, &byte| {
+        TABLE[((crc >> 24) ^ u32::from(byte)) as usize] ^ (crc << 8)
+    })
+}
+
+/// Bits packed most significant first
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    pending: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    /// Appends the low `count` bits of `value`, at most 32
+    fn write(&mut self, count: u32, value: u32) {
+        self.pending = self.pending << count | u64::from(value) & ((1 << count) - 1);
+        self.count += count;
+        while self.count >= 8 {
+            self.count -= 8;
+            self.bytes.push((self.pending >> self.count) as u8);
+        }
+    }
+
+    /// The bytes written, the last padded with zero bits
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push((self.pending << (8 - self.count)) as u8);
+        }
+        self.bytes
+    }
+}
@@ -0,0 +1,141 @@
+use std::{
+    fs,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use eyre::{Result, WrapErr};
+
+use crate::station::{load_weather_stations, ListOptions, Stations};
+
+/// Starts every cache, naming its format so an older one is never misread
+const MAGIC: &[u8; 8] = b"BRGSTN2\n";
+
+const HAS_MEAN: u8 = 1;
+const HAS_STDDEV: u8 = 2;
+const HAS_WEIGHT: u8 = 4;
+
+/// Loads the stations from `path` through `<path>.cache`, a pre-parsed
+/// binary copy reused while the list's size and modification time, and the
+/// `options` reading it, are those it was made with, and rewritten from the
+/// list otherwise. A cache that can't be written only costs the next run the
+/// parsing again.
+pub fn load(path: &str, options: ListOptions) -> Result<Stations> {
+    let _span = tracing::debug_span!("station_cache").entered();
+    let cache = format!("{}.cache", path);
+    let source = source_stamp(path, options)?;
+    if let Some(stations) = fs::read(&cache)
+        .ok()
+        .and_then(|bytes| decode(&bytes, source))
+    {
+        tracing::debug!(cache, "Reused the station cache");
+        return Ok(stations);
+    }
+    let stations = load_weather_stations(path.to_string(), options)?;
+    let partial = format!("{}.partial", cache);
+    let written =
+        fs::write(&partial, encode(&stations, source)).and_then(|()| fs::rename(&partial, &cache));
+    match written {
+        Ok(()) => tracing::debug!(cache, "Wrote the station cache"),
+        Err(e) => {
+            let _ = fs::remove_file(&partial);
+            tracing::warn!("Could not write the station cache {}: {}", cache, e);
+        }
+    }
+    Ok(stations)
+}
+
+/// The size and modification time in nanoseconds of the station list, and
+/// the fingerprint of the options reading it
+fn source_stamp(path: &str, options: ListOptions) -> Result<[u64; 3]> {
+    let metadata = fs::metadata(path).wrap_err_with(|| format!("Could not open file {}", path))?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+        });
+    Ok([
+        metadata.len(),
+        modified.as_nanos() as u64,
+        options.fingerprint(),
+    ])
+}
+
+/// The magic, the source's stamp, and the station count, then each station
+/// as its name's length and bytes, a byte flagging which parameters follow,
+/// and those parameters, all little-endian
+fn encode(stations: &Stations, source: [u64; 3]) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    for value in source.into_iter().chain([stations.len() as u64]) {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    for station in stations.iter() {
+        let name = stations.name(station);
+        bytes.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(name.as_bytes());
+        let parameters = [
+            (HAS_MEAN, station.mean),
+            (HAS_STDDEV, station.stddev),
+            (HAS_WEIGHT, station.weight),
+        ];
+        let flags = parameters
+            .iter()
+            .filter(|(_, value)| value.is_some())
+            .fold(0, |flags, (flag, _)| flags | flag);
+        bytes.push(flags);
+        for value in parameters.iter().filter_map(|(_, value)| *value) {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    bytes
+}
+
+/// The stations in a cache made from a list stamped `source`, or `None` if
+/// it was made from another version of it or is damaged
+fn decode(bytes: &[u8], source: [u64; 3]) -> Option<Stations> {
+    let mut reader = Reader { bytes };
+    if reader.take(MAGIC.len())? != MAGIC || [reader.u64()?, reader.u64()?, reader.u64()?] != source
+    {
+        return None;
+    }
+    let count = reader.u64()?;
+    let mut stations = Stations::default();
+    for _ in 0..count {
+        let length = u32::from_le_bytes(reader.take(4)?.try_into().ok()?) as usize;
+        let id = std::str::from_utf8(reader.take(length)?).ok()?;
+        let flags = reader.take(1)?[0];
+        let mut parameter = |flag: u8| match flags & flag {
+            0 => Some(None),
+            _ => reader.u64().map(|bits| Some(f64::from_bits(bits))),
+        };
+        let (mean, stddev, weight) = (
+            parameter(HAS_MEAN)?,
+            parameter(HAS_STDDEV)?,
+            parameter(HAS_WEIGHT)?,
+        );
+        stations.push(id, mean, stddev, weight);
+    }
+    reader.bytes.is_empty().then_some(stations)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.bytes.len() < len {
+            return None;
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Some(taken)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+}
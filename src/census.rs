@@ -0,0 +1,200 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    thread,
+};
+
+use eyre::{eyre, Result};
+
+use crate::{template, verify};
+
+/// What the rows of one station add up to, in tenths of a degree
+#[derive(Debug, Clone, Copy)]
+pub struct Stats {
+    pub count: u64,
+    pub min: i64,
+    pub max: i64,
+    pub sum: i64,
+}
+
+impl Stats {
+    pub fn new(measurement: i64) -> Self {
+        Self {
+            count: 1,
+            min: measurement,
+            max: measurement,
+            sum: measurement,
+        }
+    }
+
+    pub fn add(&mut self, measurement: i64) {
+        self.count += 1;
+        self.min = self.min.min(measurement);
+        self.max = self.max.max(measurement);
+        self.sum += measurement;
+    }
+
+    pub fn merge(&mut self, other: Stats) {
+        self.count += other.count;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.sum += other.sum;
+    }
+}
+
+/// Every station in the complete output from `paths`, its parts in order,
+/// with what its rows add up to, sorted by name. Every file is read back in
+/// parallel, so the figures are exact.
+pub fn tally(paths: &[String]) -> Result<Vec<(Vec<u8>, Stats)>> {
+    let _span = tracing::debug_span!("census").entered();
+    let mut stations: HashMap<Vec<u8>, Stats> = HashMap::new();
+    for path in paths {
+        let size = fs::metadata(path)?.len();
+        let tallies = thread::scope(|scope| {
+            let handles: Vec<_> = verify::ranges(size)
+                .into_iter()
+                .map(|range| scope.spawn(move || tally_range(path, range)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("A station counter panicked"))
+                .collect::<Result<Vec<_>>>()
+        })?;
+        for tally in tallies {
+            for (station, stats) in tally {
+                match stations.get_mut(&station) {
+                    Some(total) => total.merge(stats),
+                    None => {
+                        stations.insert(station, stats);
+                    }
+                }
+            }
+        }
+    }
+    let mut stations: Vec<_> = stations.into_iter().collect();
+    stations.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(stations)
+}
+
+fn tally_range(path: &str, range: std::ops::Range<u64>) -> Result<HashMap<Vec<u8>, Stats>> {
+    let mut stations: HashMap<Vec<u8>, Stats> = HashMap::new();
+    let mut malformed = None;
+    verify::read_lines(path, range, |offset, line| {
+        let line = line.strip_suffix(b"\n").unwrap_or(line);
+        let parsed = line
+            .iter()
+            .rposition(|&b| b == b';')
+            .and_then(|end| Some((&line[..end], tenths(&line[end + 1..])?)));
+        let Some((station, measurement)) = parsed else {
+            malformed.get_or_insert(offset);
+            return;
+        };
+        match stations.get_mut(station) {
+            Some(stats) => stats.add(measurement),
+            None => {
+                stations.insert(station.to_vec(), Stats::new(measurement));
+            }
+        }
+    })?;
+    match malformed {
+        Some(offset) => Err(eyre!(
+            "{} holds a line that isn't a measurement at byte {}",
+            path,
+            offset
+        )),
+        None => Ok(stations),
+    }
+}
+
+/// A measurement such as `-12.3` in tenths, or `None` if it isn't one
+pub fn tenths(measurement: &[u8]) -> Option<i64> {
+    let (sign, digits) = match measurement.strip_prefix(b"-") {
+        Some(digits) => (-1, digits),
+        None => (1, measurement),
+    };
+    let [whole @ .., b'.', tenth] = digits else {
+        return None;
+    };
+    if whole.is_empty() || !whole.iter().chain([tenth]).all(u8::is_ascii_digit) {
+        return None;
+    }
+    let whole = whole
+        .iter()
+        .fold(0, |value, digit| value * 10 + i64::from(digit - b'0'));
+    Some(sign * (whole * 10 + i64::from(tenth - b'0')))
+}
+
+/// Writes each station's row count to `<output>.stations` as `name;count`
+/// lines sorted by name, returning its path
+pub fn write_counts(output: &str, stations: &[(Vec<u8>, Stats)]) -> Result<String> {
+    let path = format!("{}.stations", template::without_shard(output));
+    let mut writer = BufWriter::new(File::create(&path)?);
+    writeln!(writer, "# {} distinct stations", stations.len())?;
+    for (station, stats) in stations {
+        writer.write_all(station)?;
+        writeln!(writer, ";{}", stats.count)?;
+    }
+    writer.flush()?;
+    Ok(path)
+}
+
+/// Writes the name of each station to `path`, one a line sorted by name
+pub fn write_keyset(path: &str, stations: &[(Vec<u8>, Stats)]) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for (station, _) in stations {
+        writer.write_all(station)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes each station's row count and share of all rows to `path`, or to
+/// stdout for `-`, as `station;rows;share` lines from the most rows to the
+/// fewest, after a comment summing up the spread
+pub fn write_frequencies(path: &str, stations: &[(Vec<u8>, Stats)]) -> Result<()> {
+    let mut counts: Vec<_> = stations
+        .iter()
+        .map(|(station, stats)| (station, stats.count))
+        .collect();
+    counts.sort_by(|(a, a_rows), (b, b_rows)| b_rows.cmp(a_rows).then(a.cmp(b)));
+    let rows: u64 = counts.iter().map(|(_, count)| count).sum();
+    let (Some((_, most)), Some((_, fewest))) = (counts.first(), counts.last()) else {
+        return Err(eyre!("No stations to report the frequency of"));
+    };
+    let median = counts[counts.len() / 2].1;
+    tracing::info!(
+        stations = counts.len(),
+        most,
+        median,
+        fewest,
+        "Rows per station"
+    );
+
+    let mut writer: Box<dyn Write> = match path {
+        "-" => Box::new(BufWriter::new(io::stdout().lock())),
+        path => Box::new(BufWriter::new(File::create(path)?)),
+    };
+    writeln!(
+        writer,
+        "# {} rows over {} stations: most {}, median {}, fewest {}",
+        rows,
+        counts.len(),
+        most,
+        median,
+        fewest
+    )?;
+    writeln!(writer, "station;rows;share")?;
+    for (station, count) in &counts {
+        writer.write_all(station)?;
+        writeln!(
+            writer,
+            ";{};{:.4}%",
+            count,
+            *count as f64 * 100.0 / rows as f64
+        )?;
+    }
+    writer.flush()?;
+    Ok(())
+}
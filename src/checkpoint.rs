@@ -0,0 +1,194 @@
+use std::{
+    fs::{self, File},
+    io::{BufReader, Read},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use eyre::{eyre, Result};
+
+use crate::{
+    checksum::Checksum,
+    cli::{Args, Written},
+    summary,
+};
+
+/// How far a run got: enough to continue it exactly where it stopped. Rows
+/// are regenerated from the seed, so only their count and the bytes they
+/// took are recorded, alongside a hash of the options that shaped them.
+#[derive(Debug, Clone, Copy)]
+pub struct Checkpoint {
+    pub seed: u64,
+    pub config_hash: u64,
+    pub written: Written,
+}
+
+impl Checkpoint {
+    /// The start of a fresh run
+    pub fn new(args: &Args, seed: u64) -> Self {
+        Self {
+            seed,
+            config_hash: summary::config_hash(args, seed),
+            written: Written { rows: 0, bytes: 0 },
+        }
+    }
+
+    /// The start of a run appending to the complete lines already in `path`
+    pub fn existing(args: &Args, seed: u64, path: &str) -> Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let (mut rows, mut bytes, mut last) = (0, 0, b'\n');
+        let mut buf = vec![0; 64 << 10];
+        loop {
+            let read = reader.read(&mut buf)?;
+            if read == 0 {
+                break;
+            }
+            rows += buf[..read].iter().filter(|&&b| b == b'\n').count() as u64;
+            bytes += read as u64;
+            last = buf[read - 1];
+        }
+        if last != b'\n' {
+            return Err(eyre!("{} doesn't end on a whole line", path));
+        }
+        if rows > args.row_count() {
+            return Err(eyre!(
+                "{} already holds {} rows, more than --rows",
+                path,
+                rows
+            ));
+        }
+        Ok(Self {
+            written: Written { rows, bytes },
+            ..Self::new(args, seed)
+        })
+    }
+
+    pub fn path(output: &str) -> String {
+        format!("{}.checkpoint", output)
+    }
+
+    /// Reads the checkpoint an interrupted run left next to `args.output`,
+    /// rejecting it if `args` would generate different rows. The seed is
+    /// taken from the checkpoint unless `--seed` gives the same one.
+    pub fn load(args: &Args) -> Result<Self> {
+        let path = Self::path(&args.output);
+        let text = fs::read_to_string(&path)
+            .map_err(|_| eyre!("Could not read {}, is there a run to resume?", path))?;
+        let field = |key: &str| -> Result<u64> {
+            let value = text
+                .lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix(" = "))
+                .ok_or_else(|| eyre!("{} has no {}", path, key))?;
+            let parsed = match key {
+                "config_hash" => u64::from_str_radix(value, 16),
+                _ => value.parse(),
+            };
+            parsed.map_err(|_| eyre!("Invalid {} in {}", key, path))
+        };
+        let checkpoint = Self {
+            seed: field("seed")?,
+            config_hash: field("config_hash")?,
+            written: Written {
+                rows: field("rows")?,
+                bytes: field("bytes")?,
+            },
+        };
+        if args.seed.is_some_and(|seed| seed != checkpoint.seed) {
+            return Err(eyre!(
+                "--seed differs from the interrupted run's seed, {}",
+                checkpoint.seed
+            ));
+        }
+        if summary::config_hash(args, checkpoint.seed) != checkpoint.config_hash {
+            return Err(eyre!(
+                "The options shaping the rows differ from the interrupted run's"
+            ));
+        }
+        Ok(checkpoint)
+    }
+
+    /// Replaces the checkpoint next to `output`, through a temporary file so
+    /// that a crash while saving leaves the previous one intact
+    fn save(&self, output: &str) -> Result<()> {
+        let path = Self::path(output);
+        let temp = format!("{}.tmp", path);
+        let text = format!(
+            "seed = {}\nconfig_hash = {:016x}\nrows = {}\nbytes = {}\n",
+            self.seed, self.config_hash, self.written.rows, self.written.bytes
+        );
+        fs::write(&temp, text)?;
+        File::open(&temp)?.sync_all()?;
+        fs::rename(&temp, &path)?;
+        Ok(())
+    }
+}
+
+/// Saves a checkpoint from the writer thread every `interval`, once what it
+/// records has been written and synced to storage
+pub struct Checkpointer {
+    output: String,
+    interval: Duration,
+    last: Instant,
+    /// Where this run began
+    start: Checkpoint,
+    chunk_rows: u64,
+    rows: u64,
+}
+
+impl Checkpointer {
+    /// Checkpoints for a run of `rows` rows in all, in chunks of
+    /// `chunk_rows`, starting from `start`
+    pub fn new(
+        output: &str,
+        interval: Duration,
+        start: Checkpoint,
+        chunk_rows: u64,
+        rows: u64,
+    ) -> Self {
+        Self {
+            output: output.to_string(),
+            interval,
+            last: Instant::now(),
+            start,
+            chunk_rows,
+            rows,
+        }
+    }
+
+    pub fn due(&self) -> bool {
+        self.last.elapsed() >= self.interval
+    }
+
+    /// Records that `chunks` chunks holding `bytes` bytes have been written
+    /// since the run began; the caller has synced them first
+    pub fn save(&mut self, chunks: u64, bytes: u64) -> Result<()> {
+        let written = Written {
+            rows: (self.start.written.rows + chunks * self.chunk_rows).min(self.rows),
+            bytes: self.start.written.bytes + bytes,
+        };
+        Checkpoint {
+            written,
+            ..self.start
+        }
+        .save(&self.output)?;
+        tracing::debug!(rows = written.rows, bytes = written.bytes, "Checkpoint");
+        self.last = Instant::now();
+        Ok(())
+    }
+}
+
+/// Feeds the first `bytes` bytes of `path`, which a resumed run keeps, to
+/// `checksum`
+pub fn sum_kept(path: &str, bytes: u64, checksum: &Mutex<Checksum>) -> Result<()> {
+    let mut reader = BufReader::new(File::open(path)?).take(bytes);
+    let mut checksum = checksum.lock().expect("A writer panicked");
+    let mut buf = vec![0; 64 << 10];
+    loop {
+        let read = reader.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        checksum.update(&buf[..read]);
+    }
+    Ok(())
+}
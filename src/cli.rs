@@ -0,0 +1,2489 @@
+use std::{
+    collections::HashSet,
+    fs,
+    io::{self, IsTerminal},
+    iter,
+    ops::Range,
+    path::Path,
+    process,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use eyre::Result;
+use rand::{seq::SliceRandom, Rng};
+
+#[cfg(unix)]
+use crate::parallel;
+use crate::{
+    affinity, answer, archive, batch, broker, cache, census,
+    checkpoint::{self, Checkpoint, Checkpointer},
+    checksum::{self, Algorithm, Checksum},
+    completions,
+    compress::{self, Codec, Compression},
+    distribution::Distribution,
+    drift, dups,
+    encode::LineEncoder,
+    entropy::Entropy,
+    errors::{self, ErrorFormat},
+    expr::{Program, Value},
+    extract, filter, fixtures, fuzz, huge_pages, index, init, interrupt,
+    length_mix::LengthMix,
+    logging::{self, LogLevel},
+    manifest, memory,
+    model::{DistinctTemps, Model, ModelSpec},
+    noaa, normalize, notify, orc,
+    order::{Order, StationPicker},
+    output::{self, FlushPolicy, Format, IoBackend, OnError, OnStall, Output, Sink},
+    passthrough::Passthrough,
+    pattern::Pattern,
+    preset,
+    progress::{self, Progress, ProgressMode},
+    protobuf, regen, repro, results,
+    rng::{BatchRng, GenRng, RngKind},
+    rolling::{self, RollingFile},
+    schema::Schema,
+    shuffle, solve, sort,
+    station::{
+        self, apply_station_params, load_weather_stations, sample_weather_stations, ListOptions,
+        NamePolicy, Quote, Stations, MAX_NAME_BYTES, MAX_STATIONS,
+    },
+    summary::{self, Summary},
+    template, transform, tune, verify, watch, MAX_TEMP, MIN_TEMP,
+};
+
+/// Generates a large number of rows for the one billion row challenge
+#[derive(Parser, Debug, Clone)]
+#[command(version, about, long_about = None)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Number of rows to generate, such as `1B`, `500M`, `10_000_000`, or
+    /// `1e9`
+    #[arg(short, long, default_value_t = 1_000_000_000, value_parser = parse_count)]
+    pub rows: u64,
+
+    /// First row of the stream of `--rows` rows to generate, counting from
+    /// zero, so the output holds only part of the dataset
+    #[arg(long, default_value_t = 0, value_parser = parse_count)]
+    pub start_row: u64,
+
+    /// Row of the stream to stop before; the end of the stream by default
+    #[arg(long, value_parser = parse_count)]
+    pub end_row: Option<u64>,
+
+    /// Path to the weather station examples, as `name;mean` lines or, if it
+    /// ends in `.json`, an array of `{"name": ..., "mean": ...}` objects; an
+    /// `http://` or `https://` URL is downloaded at startup
+    #[arg(short, long, default_value_t = String::from("./data/weather_stations.csv"))]
+    pub weather_stations: String,
+
+    /// SHA-256 the station list must have, in hex, refusing to run with any
+    /// other; pins a downloaded list to the expected version
+    #[arg(long, value_parser = station::parse_sha256)]
+    pub stations_sha256: Option<String>,
+
+    /// Keep a pre-parsed binary copy of the station list in `<list>.cache`,
+    /// reused while the list is unchanged, to skip parsing huge lists
+    #[arg(long)]
+    pub cache_stations: bool,
+
+    /// Skip malformed station list entries with a warning instead of failing
+    #[arg(long)]
+    pub lenient: bool,
+
+    /// Never take the station list's first line for a header, even if it
+    /// looks like one
+    #[arg(long)]
+    pub no_header: bool,
+
+    /// What separates the station list's columns, such as `,` or `tab`;
+    /// sniffed from its first lines among `;`, tab, and `,` by default
+    #[arg(long, value_parser = station::parse_delimiter)]
+    pub stations_delimiter: Option<char>,
+
+    /// Use a uniform random sample of at most this many stations from the
+    /// list, streamed through a reservoir so huge lists never sit in memory
+    #[arg(long, value_parser = parse_count)]
+    pub max_stations: Option<u64>,
+
+    /// Put station names in this Unicode normalization form as they're
+    /// loaded, so names differing only in how their accents are encoded
+    /// become the same station
+    #[arg(long, value_enum, default_value_t = normalize::Form::None, conflicts_with = "schema")]
+    pub normalize: normalize::Form,
+
+    /// What to do with station names holding `;`, a line break, or another
+    /// control character, which would otherwise corrupt the output
+    #[arg(long, value_enum, default_value_t = NamePolicy::Reject, conflicts_with = "schema")]
+    pub bad_name_policy: NamePolicy,
+
+    /// Which station names to write as RFC 4180 quoted fields, so that names
+    /// holding `;`, `"`, or line breaks reach CSV readers intact
+    #[arg(long, value_enum, default_value_t = Quote::Never, conflicts_with = "schema")]
+    pub quote: Quote,
+
+    /// Path to the file to generate, which may name it with `{seed}`, the
+    /// `{date}`, and with `--max-file-size`, each part's `{shard:04}`
+    #[arg(short, long, default_value_t = String::from("./data/measurements.txt"))]
+    pub output: String,
+
+    /// Split the output into parts of at most this size, such as `1GiB`,
+    /// numbered before the extension as `measurements.0001.txt` and so on
+    #[arg(long, value_parser = parse_size)]
+    pub max_file_size: Option<usize>,
+
+    /// Once complete, package the output, its parts, and its manifest into
+    /// one archive named after it, removing the files it holds
+    #[arg(long, value_enum)]
+    pub archive: Option<archive::Format>,
+
+    /// Replace the output file if it already exists
+    #[arg(long, global = true)]
+    pub force: bool,
+
+    /// Write to the first free `name.N.ext` beside the output file if it
+    /// already exists, rather than failing
+    #[arg(long, conflicts_with_all = ["force", "append", "resume"])]
+    pub append_suffix: bool,
+
+    /// What happens to the partly written output when generation fails
+    #[arg(long, value_enum, default_value_t = OnError::RenamePartial)]
+    pub on_error: OnError,
+
+    /// Fail when the output's directory doesn't exist, rather than creating
+    /// it and any missing parents
+    #[arg(long)]
+    pub no_mkpath: bool,
+
+    /// Add rows to the end of an existing output until it holds `--rows`,
+    /// continuing the stream after the rows it already has
+    #[arg(long, conflicts_with_all = ["force", "resume"])]
+    pub append: bool,
+
+    /// Path to a `name;mean;stddev;weight` file overriding the parameters of
+    /// individual stations; empty fields keep the station's own values
+    #[arg(long)]
+    pub station_params: Option<String>,
+
+    /// Path to a schema file describing the columns to generate, used instead
+    /// of the weather station format
+    #[arg(long)]
+    pub schema: Option<String>,
+
+    /// Path to a script computing each measurement from the `station` name
+    /// and `row` index, e.g. `hash(station) * 60 - 20 + normal(0, 5)`
+    #[arg(long)]
+    pub script: Option<String>,
+
+    /// How each station's successive measurements are related: `iid` or
+    /// `ar1:<phi>` for an AR(1) series around the station's mean temperature
+    #[arg(long, default_value = "iid", conflicts_with = "script")]
+    pub model: ModelSpec,
+
+    /// Distribution of independent measurements, around each station's mean
+    /// temperature and standard deviation for all but `uniform`
+    #[arg(long, value_enum, default_value_t = Distribution::Uniform, conflicts_with = "script")]
+    pub distribution: Distribution,
+
+    /// Limit measurements to this many distinct, evenly spaced values
+    #[arg(long)]
+    pub distinct_temps: Option<u32>,
+
+    /// Where stations and measurements come from; anything other than
+    /// `random` replaces the options shaping random generation
+    #[arg(long, value_enum, default_value_t = Pattern::Random)]
+    pub pattern: Pattern,
+
+    /// How compressible the output is: lower entropy draws from fewer
+    /// stations and repeats stations and values in runs
+    #[arg(long, value_enum, default_value_t = Entropy::High)]
+    pub entropy: Entropy,
+
+    /// Fraction of rows, between 0 and 1, whose measurement is replaced by
+    /// `-0.0`
+    #[arg(long, value_parser = parse_rate)]
+    pub negative_zero_rate: Option<f64>,
+
+    /// Write station names at these lengths in bytes, each in a share of
+    /// the rows set by its weight, such as `1:10,50:60,100:30`; every name
+    /// is cut or repeated to fit
+    #[arg(long, conflicts_with = "schema")]
+    pub name_length_mix: Option<LengthMix>,
+
+    /// Carry these columns of the station list, found by the names in its
+    /// header, into each row between the station and its measurement, such
+    /// as `lat,lon,country`
+    #[arg(long, value_delimiter = ',', conflicts_with_all = ["schema", "no_header"])]
+    pub passthrough_columns: Vec<String>,
+
+    /// Refuse to generate unless the output keeps to the official
+    /// challenge's limits: at most 10,000 distinct stations, names of at
+    /// most 100 bytes, and measurements within -99.9 to 99.9
+    #[arg(long, conflicts_with = "schema")]
+    pub enforce_official_limits: bool,
+
+    /// How rows are arranged in the output
+    #[arg(long, value_enum, default_value_t = Order::Random)]
+    pub order: Order,
+
+    /// Random number generator, trading statistical quality for speed
+    #[arg(long, value_enum, default_value_t = RngKind::Std)]
+    pub rng: RngKind,
+
+    /// Rows generated into memory before each write; the best value depends
+    /// on the storage being written to
+    #[arg(long, default_value_t = 10_000, value_parser = clap::value_parser!(u64).range(1..))]
+    pub chunk_rows: u64,
+
+    /// Capacity of the output write buffer, e.g. `8MiB`; chunks are gathered
+    /// into it so the file sees fewer, larger writes
+    #[arg(long, default_value = "8MiB", value_parser = parse_size)]
+    pub write_buffer: usize,
+
+    /// Number of threads generating chunks in parallel, each writing its
+    /// chunks straight to their place in the file; requires `--rng philox`
+    /// and options that keep rows independent of each other [default: one
+    /// per physical core when those hold, otherwise 1]
+    #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub threads: Option<usize>,
+
+    /// Pin each `--threads` worker to its own CPU and allocate its buffers
+    /// on that CPU's NUMA node; Linux only
+    #[arg(long)]
+    pub pin_threads: bool,
+
+    /// Back the chunk buffers with 2 MiB transparent huge pages, so filling
+    /// them takes fewer TLB misses; Linux only
+    #[arg(long)]
+    pub huge_pages: bool,
+
+    /// Compress the output, on a pool of threads running alongside
+    /// generation: gzip, bzip2, or snappy, with a level after a colon for
+    /// gzip (0-9) and bzip2 (1-9), such as `gzip:9`
+    #[arg(long, value_name = "CODEC[:LEVEL]")]
+    pub compress: Option<Compression>,
+
+    /// Threads compressing the output [default: one per CPU]
+    #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub compress_threads: Option<usize>,
+
+    /// How the rows are laid out in the output file
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+
+    /// Column data gathered in memory before `--format orc` writes it out
+    /// as a stripe
+    #[arg(long, default_value = "64MiB", value_parser = parse_size)]
+    pub stripe_size: usize,
+
+    /// How chunks are written to the output file
+    #[arg(long, value_enum, default_value_t = IoBackend::Write)]
+    pub io: IoBackend,
+
+    /// Evict the output from the page cache as it is written, so generating
+    /// a large file doesn't push everything else out of memory; Linux only
+    #[arg(long)]
+    pub drop_cache: bool,
+
+    /// Have each `--io direct` write reach the device before it completes,
+    /// with O_DSYNC on Unix or FILE_FLAG_WRITE_THROUGH on Windows
+    #[arg(long)]
+    pub write_through: bool,
+
+    /// When buffered output is written to the file
+    #[arg(long, value_enum, default_value_t = FlushPolicy::Buffer)]
+    pub flush: FlushPolicy,
+
+    /// Don't reserve storage for the estimated size of the output before
+    /// generating; Linux and macOS reserve it otherwise
+    #[arg(long)]
+    pub no_preallocate: bool,
+
+    /// Wait for the output, its manifest, and the directory naming them to
+    /// reach stable storage before exiting
+    #[arg(long)]
+    pub fsync: bool,
+
+    /// Only warn when the estimated size of the output exceeds the free
+    /// space on its filesystem, instead of refusing to start
+    #[arg(long)]
+    pub no_space_check: bool,
+
+    /// Seed for the random number generator, making the output reproducible;
+    /// a random seed is used when omitted
+    #[arg(long, global = true)]
+    pub seed: Option<u64>,
+
+    /// Derive the seed from a label such as `team-a/run-42`, easier to
+    /// coordinate runs by than a number; the manifest records both
+    #[arg(long, conflicts_with = "seed")]
+    pub seed_label: Option<String>,
+
+    /// Where generated bytes go; `null` discards them, still counting them,
+    /// to measure generation speed apart from the disk
+    #[arg(long, value_enum, default_value_t = Sink::File)]
+    pub sink: Sink,
+
+    /// Rows published in each message when `--output` names a broker, as
+    /// `nats://[user:password@]host[:port]/subject` or
+    /// `mqtt://[user:password@]host[:port]/topic`
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    pub publish_batch: u64,
+
+    /// Keep the buffers held while generating within this many bytes,
+    /// shrinking buffer counts, sizes, and parallelism to fit; for `dups`,
+    /// `shuffle`, and `sort`, such as 2GiB, the memory they may use, which
+    /// bounds the size of each spill file
+    #[arg(long, global = true, value_parser = parse_size)]
+    pub memory_limit: Option<usize>,
+
+    /// Chunk buffers queued between generating and writing; generation
+    /// pauses while all of them wait on a slow output, so this bounds the
+    /// memory it holds. The default suits `--io`.
+    #[arg(long)]
+    pub queue_depth: Option<usize>,
+
+    /// How long generation may pause for a slow output, such as `30s`,
+    /// before `--on-stall` decides what happens; it waits for as long as
+    /// it takes when omitted
+    #[arg(long, value_parser = parse_interval)]
+    pub stall_timeout: Option<Duration>,
+
+    /// What a pause longer than `--stall-timeout` does
+    #[arg(long, value_enum, default_value_t = OnStall::Warn, requires = "stall_timeout")]
+    pub on_stall: OnStall,
+
+    /// Spend a few seconds benchmarking thread counts, chunk sizes, and
+    /// write buffer sizes against the output path, then generate with the
+    /// fastest combination
+    #[arg(long)]
+    pub auto_tune: bool,
+
+    /// Print no progress while generating, and log only warnings and errors
+    /// unless `--log-level` says otherwise
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+
+    /// How progress is shown; a bar when stderr is a terminal, and plain
+    /// status lines when it isn't
+    #[arg(long, value_enum)]
+    pub progress: Option<ProgressMode>,
+
+    /// Show a live dashboard instead of the progress bar: throughput over
+    /// time, how busy each thread is, bytes written, ETA, and the options
+    /// generating; short for `--progress tui`
+    #[arg(long, conflicts_with_all = ["progress", "quiet"])]
+    pub tui: bool,
+
+    /// Time between plain and JSON progress lines, such as `10s`, `500ms`,
+    /// or `1m`
+    #[arg(long, default_value = "10s", value_parser = parse_interval)]
+    pub progress_interval: Duration,
+
+    /// Append a timestamped line with the rows and bytes written and the
+    /// rate since the last line to this file every `--progress-interval`,
+    /// however progress is shown, leaving a record of an unattended run
+    #[arg(long)]
+    pub progress_log: Option<String>,
+
+    /// Don't write `<output>.manifest.json`, which records the version,
+    /// seed, options, and input file hashes that regenerate the output, and
+    /// the machine and filesystem it was generated on
+    #[arg(long)]
+    pub no_manifest: bool,
+
+    /// Generate for at most this long, such as `30s` or `10m`, then finish
+    /// cleanly with however many rows were written; `--rows` still caps them
+    #[arg(long, value_parser = parse_interval)]
+    pub duration: Option<Duration>,
+
+    /// Record progress in `<output>.checkpoint` this often, such as `30s` or
+    /// `5m`, so that `--resume` can continue the run if it is interrupted
+    #[arg(long, value_parser = parse_interval)]
+    pub checkpoint_interval: Option<Duration>,
+
+    /// Continue an interrupted run from its `<output>.checkpoint`, given the
+    /// same options; its seed is used unless `--seed` gives the same one
+    #[arg(long)]
+    pub resume: bool,
+
+    /// Generate again whenever the station list, `--station-params`,
+    /// `--schema`, or `--script` file changes, keeping the seed, until
+    /// Ctrl-C; for iterating on inputs against a small dataset
+    #[arg(long, conflicts_with_all = ["resume", "append", "duration", "checkpoint_interval"])]
+    pub watch: bool,
+
+    /// The most detailed messages logged to stderr, `info` unless `--quiet`
+    /// makes it `warn`; `debug` also reports the time spent in each phase
+    #[arg(long, value_enum)]
+    pub log_level: Option<LogLevel>,
+
+    /// How a failure is reported on stderr: `json` prints one line with a
+    /// stable error code and whether running again may succeed, for
+    /// orchestration
+    #[arg(long, value_enum, default_value_t = ErrorFormat::Pretty)]
+    pub errors: ErrorFormat,
+
+    /// POST the run's summary as JSON to this `http://` or `https://` URL
+    /// once it finishes, or a description of the error if it fails
+    #[arg(long, value_parser = notify::parse_url)]
+    pub notify_url: Option<String>,
+
+    /// Sum the output as it is written, printing the checksum once done
+    #[arg(long, value_enum)]
+    pub checksum: Option<Algorithm>,
+
+    /// Once written, re-read the whole output on every core, checking that
+    /// each line is well formed and in range and that no rows are missing
+    #[arg(long)]
+    pub verify_after: bool,
+
+    /// Once written, check the lines found at this many random offsets into
+    /// the output, for a quick check where reading it all back is too slow
+    #[arg(long, conflicts_with = "verify_after")]
+    pub spot_check: Option<u64>,
+
+    /// Write `<output>.idx` listing the byte offset of every this many rows,
+    /// such as `1M`, so readers can seek to a row without scanning for it
+    #[arg(long, value_parser = parse_count)]
+    pub index: Option<u64>,
+
+    /// Write `<output>.stations` listing each station present in the output
+    /// with its exact row count, so checkers know the real set of stations
+    #[arg(long, conflicts_with = "schema")]
+    pub station_counts: bool,
+
+    /// Write the distinct stations present in the output, one name a line
+    /// sorted by byte order, to this path, the exact set of keys a solver's
+    /// result must hold
+    #[arg(long, conflicts_with = "schema")]
+    pub emit_keyset: Option<String>,
+
+    /// Write every station present in the output with its row count and
+    /// share of the rows, most frequent first, to this path, or to stdout for
+    /// `-`, to check the skew the station weights were meant to produce
+    #[arg(long, conflicts_with = "schema")]
+    pub frequency_report: Option<String>,
+
+    /// Write each station's min, mean, and max, as the challenge expects
+    /// solvers to print them (`official`, to `<name>.out`) and as
+    /// `station;min;mean;max;count` lines (`csv`); either or both
+    #[arg(long, value_enum, value_delimiter = ',', conflicts_with = "schema")]
+    pub answer_key: Vec<answer::Format>,
+
+    /// How the answer key rounds each mean to one decimal: half up towards
+    /// positive as the challenge specifies, or half to even as IEEE
+    /// floating-point formatting does, to match a solver that does
+    #[arg(long, value_enum, default_value_t = answer::Rounding::HalfUp, requires = "answer_key")]
+    pub rounding: answer::Rounding,
+
+    /// Write a JSON summary of the finished run, with its throughput, seed,
+    /// a hash of the options shaping the rows, and a checksum of the output,
+    /// to this path, or to stdout for `-`
+    #[arg(long)]
+    pub summary: Option<String>,
+
+    /// Takes the rows in place of the output, for the C API and generating
+    /// into memory
+    #[arg(skip)]
+    pub row_callback: Option<output::RowCallback>,
+
+    /// The station list `--weather-stations` names, already loaded by a
+    /// batch that generates several datasets from it
+    #[arg(skip)]
+    pub loaded_stations: Option<Arc<Stations>>,
+
+    /// The URL `--weather-stations` named, once the list has been
+    /// downloaded and `--weather-stations` holds where it was saved
+    #[arg(skip)]
+    pub stations_url: Option<String>,
+
+    /// The batch this dataset is one of, whose progress its own is shown in
+    #[arg(skip)]
+    pub batch: Option<progress::Batch>,
+
+    /// When this run's `--duration` runs out, set once generation starts
+    #[arg(skip)]
+    pub deadline: Option<Instant>,
+}
+
+impl Args {
+    /// The rows of the stream to generate, `--start-row` up to `--end-row`
+    pub fn row_range(&self) -> Range<u64> {
+        self.start_row..self.end_row.unwrap_or(self.rows)
+    }
+
+    /// How many rows the output should hold
+    pub fn row_count(&self) -> u64 {
+        let range = self.row_range();
+        range.end - range.start
+    }
+
+    /// Threads generating chunks, one unless `--threads` or its default
+    /// says otherwise
+    pub fn threads(&self) -> usize {
+        self.threads.unwrap_or(1)
+    }
+
+    /// The most detailed messages logged, `--log-level` or its default
+    pub fn log_level(&self) -> LogLevel {
+        match (self.log_level, self.quiet) {
+            (Some(level), _) => level,
+            (None, true) => LogLevel::Warn,
+            (None, false) => LogLevel::Info,
+        }
+    }
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Tools for preparing weather station lists
+    #[command(subcommand)]
+    Stations(StationsCommand),
+    /// Small canonical datasets built into the binary, with their expected
+    /// results, for test suites in any language to share
+    #[command(subcommand)]
+    Fixtures(FixturesCommand),
+    /// Measures generation throughput for each thread count and I/O backend
+    /// on this machine, generating a short run of rows per configuration
+    /// into a scratch file next to `--output` that is removed afterwards.
+    /// Always uses `--rng philox`, so every configuration is comparable
+    SelfBench {
+        /// Rows generated for each configuration
+        #[arg(long, default_value_t = 5_000_000, value_parser = parse_count)]
+        rows: u64,
+    },
+    /// Estimates the size of the output the other options describe, how
+    /// long generating it takes, and the memory its buffers need, from a
+    /// short sample generated without writing anything
+    Estimate,
+    /// Generates the rows the other options describe twice from the same
+    /// seed, `--seed` or a random one, without writing them, and compares
+    /// their hashes, to confirm this build and platform generate
+    /// deterministically. `--threads` gives the second run a different
+    /// thread count.
+    ReproCheck {
+        /// Rows generated by each run
+        #[arg(long, default_value_t = 1_000_000, value_parser = parse_count)]
+        rows: u64,
+
+        /// Threads for the second run, which uses the first's when omitted
+        #[arg(long)]
+        threads: Option<usize>,
+    },
+    /// Regenerates one part of output split with `--max-file-size` from its
+    /// manifest, to replace a lost or damaged part without writing the
+    /// rest; `--force` replaces the part if it still exists
+    RegenShard {
+        /// The manifest of the split run
+        #[arg(long)]
+        manifest: String,
+
+        /// The part to regenerate, numbered from 1
+        #[arg(long)]
+        shard: u32,
+    },
+    /// Generates every dataset a TOML config describes, one after another:
+    /// its top-level options apply to all of them, and `[matrix]` lists
+    /// values for options to take, with a dataset for every combination.
+    /// Each station list is loaded once for all the datasets using it.
+    Batch {
+        /// Path to the config
+        #[arg(long)]
+        config: String,
+    },
+    /// Asks for the row count, station list, output format, and output
+    /// path, offering those of the other options, writes them to a config
+    /// for `batch`, and generates the dataset if asked to
+    Init {
+        /// Path to write the config to
+        #[arg(long, default_value = "billion-row-gen.toml")]
+        config: String,
+    },
+    /// Works out each station's min, mean, and max in a measurements file,
+    /// reading it in parallel with exact integer arithmetic, and prints them
+    /// as the challenge expects, a trusted baseline for checking and timing
+    /// solvers against
+    Solve {
+        /// Measurements file to solve
+        file: String,
+
+        /// Read the file one line at a time on one thread into a plain
+        /// `HashMap` instead, a deliberately slow but obviously correct
+        /// reference for teaching
+        #[arg(long)]
+        naive: bool,
+
+        /// How the results are printed
+        #[arg(long, value_enum, default_value_t = answer::Format::Official)]
+        format: answer::Format,
+
+        /// How each mean is rounded to one decimal where it falls halfway
+        #[arg(long, value_enum, default_value_t = answer::Rounding::HalfUp)]
+        rounding: answer::Rounding,
+    },
+    /// Reports how many rows of a file exactly duplicate another and which
+    /// lines repeat most, in bounded memory, to check injected duplicates or
+    /// characterize a dataset from elsewhere
+    Dups {
+        /// File to read
+        file: String,
+
+        /// Most repeated lines to list
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+    /// Writes the rows of a measurements file for the named stations to
+    /// stdout, in the order they are in the file, for debugging how a
+    /// solver handles a few keys without searching the whole file by hand
+    Extract {
+        /// Measurements file to read
+        file: String,
+
+        /// Name of a station whose rows are written, which may be given
+        /// more than once
+        #[arg(long)]
+        station: Vec<String>,
+
+        /// Station list naming more stations whose rows are written, one
+        /// per line or in any format `--weather-stations` reads
+        #[arg(long)]
+        stations_file: Option<String>,
+    },
+    /// Writes the rows of a measurements file that a predicate accepts to a
+    /// new file, in the order they are in the file, for building fixtures
+    /// of only the edge cases out of an existing dataset
+    Filter {
+        /// Measurements file to read
+        file: String,
+
+        /// Expression over `station` and `temp`, in the language of
+        /// `--script`, that is true for the rows to keep, such as
+        /// `temp > 50 || temp < -50`; `rand()` draws from `--seed`
+        #[arg(long = "where", value_name = "EXPR")]
+        predicate: String,
+
+        /// File to write the matching rows to, which `--force` lets replace
+        output: String,
+    },
+    /// Rewrites a measurements file with its temperatures in another unit or
+    /// precision, or another delimiter, in one streaming pass
+    Transform {
+        /// Measurements file to read
+        file: String,
+
+        /// File to write the rewritten rows to, which `--force` lets replace
+        output: String,
+
+        /// Unit the file's temperatures are in
+        #[arg(long, value_enum, default_value_t = transform::Unit::Celsius)]
+        from_unit: transform::Unit,
+
+        /// Unit to write temperatures in
+        #[arg(long, value_enum, default_value_t = transform::Unit::Celsius)]
+        to_unit: transform::Unit,
+
+        /// Character separating the station from the temperature
+        #[arg(long, default_value_t = ';', value_parser = station::parse_delimiter)]
+        delimiter: char,
+
+        /// Decimals to round temperatures to, instead of as many as each is
+        /// written with
+        #[arg(long)]
+        decimals: Option<usize>,
+    },
+    /// Shuffles the rows of an existing file, such as one written sorted,
+    /// into a new file with a two pass external shuffle through spill files
+    /// beside it, drawn from `--seed`
+    Shuffle {
+        /// File to shuffle
+        file: String,
+
+        /// File to write the shuffled rows to, which `--force` lets replace
+        #[arg(short, long)]
+        output: String,
+    },
+    /// Sorts the rows of an existing file into a new file with an external
+    /// merge sort through spill files beside it, for clustered datasets to
+    /// measure best-case aggregation against
+    Sort {
+        /// File to sort
+        file: String,
+
+        /// What to order the rows by
+        #[arg(long, value_enum)]
+        by: sort::SortKey,
+
+        /// File to write the sorted rows to, which `--force` lets replace
+        #[arg(short, long)]
+        output: String,
+    },
+    /// Runs two solvers on many small random datasets, varying the seed,
+    /// row count, stations, Unicode in their names, distribution, and edge
+    /// values, until their results differ, then shrinks the dataset they
+    /// disagree on and reports the options reproducing it. The dataset is
+    /// generated next to `--output`.
+    Fuzz {
+        /// First solver's command, split on whitespace, run with the
+        /// dataset's path after its own arguments
+        #[arg(long)]
+        solver_a: String,
+
+        /// Second solver's command, such as `billion-row-gen solve` for the
+        /// reference
+        #[arg(long)]
+        solver_b: String,
+
+        /// Datasets to try, each from the seed after the last
+        #[arg(long, default_value_t = 50, value_parser = parse_count)]
+        iterations: u64,
+    },
+    /// Prints a solver's results in the official format, sorted by station,
+    /// whatever their order, spacing, and line breaks, so that comparing
+    /// them with the expected results only finds real differences
+    Normalize {
+        /// File holding the solver's output, or `-` for stdin
+        #[arg(default_value = "-")]
+        file: String,
+    },
+    /// Checks a solver's results against the reference's for the
+    /// measurements file it solved, reporting missing and extra stations and
+    /// values outside the tolerances. The mean may stray by the tolerances;
+    /// min and max, which need no rounding, must match exactly.
+    VerifySolution {
+        /// Measurements file the solver was run on, left out with
+        /// `--expected`
+        file: Option<String>,
+
+        /// File holding the solver's output, or `-` for stdin, the default
+        solution: Option<String>,
+
+        /// Results already worked out for the measurements, such as an
+        /// answer key or the output of `solve`, to check against instead of
+        /// the measurements file, or `-` for stdin
+        #[arg(long)]
+        expected: Option<String>,
+
+        /// Absolute difference allowed from an expected value
+        #[arg(long, default_value_t = 0.0)]
+        abs_tol: f64,
+
+        /// Difference allowed from an expected value, as a fraction of it
+        #[arg(long, default_value_t = 0.0)]
+        rel_tol: f64,
+
+        /// Let min and max stray by the tolerances too
+        #[arg(long)]
+        tolerate_min_max: bool,
+
+        /// Also write any mismatches to this file as an HTML page, with
+        /// expected and solved values side by side
+        #[arg(long, value_name = "PATH")]
+        report: Option<String>,
+    },
+    /// Compares two measurements files: their rows, distinct stations, the
+    /// stations only one holds, how rows are shared between stations, the
+    /// spread of temperatures, and each station's mean. Fails if the shares
+    /// or temperatures differ by more than chance explains, confirming that
+    /// a regenerated dataset is statistically equivalent to the original.
+    CompareData {
+        /// First measurements file, such as the original
+        a: String,
+
+        /// Second measurements file, such as the regenerated one
+        b: String,
+    },
+    /// Prints a completion script for `shell` to stdout, to be sourced from
+    /// its startup file or saved where it looks for completions
+    Completions {
+        #[arg(value_enum)]
+        shell: completions::Shell,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum FixturesCommand {
+    /// Writes a fixture's measurements and the results the challenge expects
+    /// for them to a directory, named after the fixture and its version
+    Write {
+        /// Which fixture to write
+        #[arg(long, value_enum)]
+        name: fixtures::Fixture,
+
+        /// Directory to write the fixture to, created if need be
+        #[arg(short, long)]
+        output: String,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum StationsCommand {
+    /// Converts NOAA GSOD daily summaries or climate normals CSV files into a
+    /// station list with mean, standard deviation, and weight columns
+    ImportNoaa {
+        /// NOAA CSV files to import
+        #[arg(required = true)]
+        files: Vec<String>,
+
+        /// Path to write the station list to, instead of stdout
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// The input temperatures are already in Celsius, not Fahrenheit
+        #[arg(long)]
+        celsius: bool,
+    },
+    /// Writes a synthetic station list built to stress solvers, drawn from
+    /// `--seed`
+    Generate {
+        /// What the station names are made of
+        #[arg(long, value_enum)]
+        preset: preset::Preset,
+
+        /// Number of distinct stations
+        #[arg(long, default_value_t = 10_000, value_parser = parse_count)]
+        count: u64,
+
+        /// Path to write the station list to, instead of stdout
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+}
+
+/// Runs the command line on the process's arguments
+pub fn run() -> Result<()> {
+    color_eyre::install()?;
+    let args = match Args::try_parse() {
+        Ok(args) => args,
+        // Help and the version are printed as usual
+        Err(e) if e.use_stderr() && errors::json_requested() => {
+            eprintln!("{}", errors::arguments_json(&e));
+            process::exit(2);
+        }
+        Err(e) => e.exit(),
+    };
+    logging::init(args.log_level());
+    let format = args.errors;
+    match run_args(args) {
+        Err(e) if format == ErrorFormat::Json => {
+            eprintln!("{}", errors::json(&e));
+            process::exit(1);
+        }
+        result => result,
+    }
+}
+
+fn run_args(mut args: Args) -> Result<()> {
+    resolve_seed_label(&mut args);
+
+    if let Some(command) = &args.command {
+        return match command {
+            Command::Stations(StationsCommand::ImportNoaa {
+                files,
+                output,
+                celsius,
+            }) => noaa::import_noaa(files, output.as_deref(), *celsius),
+            Command::Stations(StationsCommand::Generate {
+                preset,
+                count,
+                output,
+            }) => {
+                let seed = args.seed.unwrap_or_else(rand::random);
+                preset::generate(*preset, *count as usize, seed, output.as_deref())
+            }
+            Command::Fixtures(FixturesCommand::Write { name, output }) => {
+                fixtures::write(*name, output, args.force)
+            }
+            Command::SelfBench { rows } => {
+                let seed = args.seed.unwrap_or_else(rand::random);
+                let args = with_stations(&args)?;
+                interrupt::install()?;
+                tune::self_bench(&args, *rows, |trial, path| {
+                    generate(trial, path, seed, None, None).map(|written| written.bytes)
+                })
+            }
+            Command::Estimate => {
+                let seed = args.seed.unwrap_or_else(rand::random);
+                interrupt::install()?;
+                // On the threads the run itself would default to
+                let args = Args {
+                    threads: Some(args.threads.unwrap_or_else(|| default_threads(&args))),
+                    ..with_stations(&args)?
+                };
+                tune::estimate(&args, |trial, path| {
+                    generate(trial, path, seed, None, None).map(|written| written.bytes)
+                })
+            }
+            Command::ReproCheck { rows, threads } => {
+                let seed = args.seed.unwrap_or_else(rand::random);
+                let args = with_stations(&args)?;
+                interrupt::install()?;
+                repro::repro_check(&args, *rows, seed, *threads, |run, seed, checksum| {
+                    generate(run, &run.output, seed, Some(checksum), None)
+                        .map(|written| written.bytes)
+                })
+            }
+            Command::RegenShard { manifest, shard } => {
+                interrupt::install()?;
+                regen::regen_shard(manifest, *shard, args.force, args.quiet)
+            }
+            Command::Batch { config } => batch::batch(&args, config, generate_and_notify),
+            Command::Init { config } => init::init(&args, config, generate_and_notify),
+            Command::Solve {
+                file,
+                naive,
+                format,
+                rounding,
+            } => solve::solve(file, *naive, *format, *rounding),
+            Command::Normalize { file } => results::normalize(file),
+            Command::VerifySolution {
+                file,
+                solution,
+                expected,
+                abs_tol,
+                rel_tol,
+                tolerate_min_max,
+                report,
+            } => {
+                // With the expected results given, the only file named is
+                // the solution
+                let (reference, solution) = match (expected, file, solution) {
+                    (Some(expected), solution, None) => {
+                        (results::Reference::Results(expected), solution.as_deref())
+                    }
+                    (Some(_), _, Some(_)) => {
+                        return Err(eyre::eyre!(
+                            "--expected replaces the measurements file, so give only the solution"
+                        ))
+                    }
+                    (None, Some(file), solution) => {
+                        (results::Reference::Measurements(file), solution.as_deref())
+                    }
+                    (None, None, _) => {
+                        return Err(eyre::eyre!(
+                            "verify-solution needs the measurements file the solver was run on, \
+                             or the results expected from it with --expected"
+                        ))
+                    }
+                };
+                results::verify(
+                    reference,
+                    solution.unwrap_or("-"),
+                    results::Tolerance {
+                        absolute: *abs_tol,
+                        relative: *rel_tol,
+                        min_max: *tolerate_min_max,
+                    },
+                    report.as_deref(),
+                )
+            }
+            Command::CompareData { a, b } => drift::compare(a, b),
+            Command::Dups { file, top } => dups::dups(file, *top, args.memory_limit, args.quiet),
+            Command::Extract {
+                file,
+                station,
+                stations_file,
+            } => extract::extract(file, station, stations_file.as_deref()),
+            Command::Shuffle { file, output } => {
+                let seed = args.seed.unwrap_or_else(rand::random);
+                shuffle::shuffle_existing(
+                    file,
+                    output,
+                    args.memory_limit,
+                    args.quiet,
+                    args.force,
+                    &mut GenRng::seed_from_u64(args.rng, seed),
+                )
+            }
+            Command::Sort { file, by, output } => {
+                sort::sort_file(file, output, *by, args.memory_limit, args.quiet, args.force)
+            }
+            Command::Transform {
+                file,
+                output,
+                from_unit,
+                to_unit,
+                delimiter,
+                decimals,
+            } => transform::transform(
+                file,
+                output,
+                transform::Rewrite {
+                    from_unit: *from_unit,
+                    to_unit: *to_unit,
+                    delimiter: *delimiter,
+                    decimals: *decimals,
+                },
+                args.force,
+            ),
+            Command::Filter {
+                file,
+                predicate,
+                output,
+            } => filter::filter(
+                file,
+                predicate,
+                output,
+                args.seed.unwrap_or_else(rand::random),
+                args.force,
+            ),
+            Command::Fuzz {
+                solver_a,
+                solver_b,
+                iterations,
+            } => {
+                let args = with_stations(&args)?;
+                interrupt::install()?;
+                fuzz::fuzz(
+                    &args,
+                    solver_a,
+                    solver_b,
+                    *iterations,
+                    |case, path, seed| {
+                        generate(case, path, seed, None, None).map(|written| written.bytes)
+                    },
+                )
+            }
+            Command::Completions { shell } => {
+                print!("{}", completions::generate(*shell, &mut Args::command()));
+                Ok(())
+            }
+        };
+    }
+
+    let args = with_stations(&args)?;
+    if args.watch {
+        return watch::run(args, generate_and_notify);
+    }
+    generate_and_notify(args).map(drop)
+}
+
+/// `args` with its station list downloaded if it's a URL and checked
+/// against `--stations-sha256`, for the commands that generate from it
+pub fn with_stations(args: &Args) -> Result<Args> {
+    let path = station::resolve(&args.weather_stations, args.stations_sha256.as_deref())?;
+    Ok(Args {
+        stations_url: notify::parse_url(&args.weather_stations).ok(),
+        weather_stations: path,
+        ..args.clone()
+    })
+}
+
+/// Generates the dataset, then notifies `--notify-url` of how the run ended
+fn generate_and_notify(args: Args) -> Result<Summary> {
+    interrupt::install()?;
+    let (notify_url, output) = (args.notify_url.clone(), args.output.clone());
+    let result = generate_dataset(args);
+    if let Some(url) = &notify_url {
+        notify::send(url, &output, &result);
+    }
+    result
+}
+
+/// `output`, or the first `name.N.ext` beside it whose file, or first part
+/// when `rolling`, isn't already a regular file
+fn free_output(output: &str, rolling: bool) -> String {
+    let taken = |path: &str| {
+        let first = match rolling {
+            true => rolling::part_path(path, 1),
+            false => path.to_string(),
+        };
+        fs::metadata(first).is_ok_and(|metadata| metadata.is_file())
+    };
+    std::iter::once(output.to_string())
+        .chain((1..).map(|n| output::suffixed_path(output, n)))
+        .find(|path| !taken(path))
+        .expect("some suffix is free")
+}
+
+/// Generates the dataset `args` describe, with its manifest and any
+/// checksums, summary, or archive asked for, returning its summary
+pub fn generate_dataset(mut args: Args) -> Result<Summary> {
+    resolve_seed_label(&mut args);
+    if args.output.contains('{') {
+        // Naming the output after its seed fixes the seed up front; a
+        // resumed run's seed is only known from its checkpoint, beside it
+        if template::uses(&args.output, "seed") && args.seed.is_none() {
+            if args.resume {
+                return Err(eyre::eyre!(
+                    "--resume needs --seed to name an output with `{{seed}}`"
+                ));
+            }
+            args.seed = Some(rand::random());
+        }
+        let seed = args.seed.unwrap_or_default();
+        args.output = template::expand(&args.output, seed, args.max_file_size.is_some())?;
+    }
+    if broker::is_url(&args.output) {
+        if args.sink == Sink::Null {
+            return Err(eyre::eyre!(
+                "--sink null can't be combined with --output naming a broker"
+            ));
+        }
+        args.sink = Sink::Broker;
+    }
+    let range = args.row_range();
+    if range.start > range.end || range.end > args.rows {
+        return Err(eyre::eyre!(
+            "--start-row and --end-row must be in order, and within --rows"
+        ));
+    }
+    if args.threads.is_none() {
+        args.threads = Some(default_threads(&args));
+    }
+    check_options(&args)?;
+    if args.append_suffix && args.sink == Sink::File {
+        args.output = free_output(&args.output, args.max_file_size.is_some());
+    }
+
+    // Before anything is loaded, so a missing directory doesn't fail late
+    if let Some(dir) = Path::new(&args.output)
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty() && !dir.exists())
+        .filter(|_| args.sink == Sink::File)
+    {
+        if args.no_mkpath {
+            return Err(eyre::eyre!(
+                "The output directory {} doesn't exist",
+                dir.display()
+            ));
+        }
+        fs::create_dir_all(dir)?;
+    }
+    // Written under a temporary name and renamed once complete, so that an
+    // interrupted run never leaves a file that looks finished. Pipes and
+    // other special files are written in place.
+    let rolling = args.max_file_size.is_some();
+    let first_path = match rolling {
+        true => rolling::part_path(&args.output, 1),
+        false => args.output.clone(),
+    };
+    let existing = fs::metadata(&first_path).ok();
+    let in_place = existing
+        .as_ref()
+        .is_some_and(|metadata| !metadata.is_file());
+    if args.append && in_place {
+        return Err(eyre::eyre!(
+            "--append requires {} to be a regular file",
+            args.output
+        ));
+    }
+    if let Some(format) = args.archive {
+        if format != archive::Format::Tar && !cfg!(feature = "compression") {
+            return Err(eyre::eyre!(
+                "--archive {} needs the `compression` feature, which this build leaves out",
+                manifest::value_name(format)
+            ));
+        }
+        if in_place || args.sink != Sink::File {
+            return Err(eyre::eyre!(
+                "--archive requires the output to be a regular file"
+            ));
+        }
+        let path = archive::path(&args.output, format);
+        if Path::new(&path).exists() && !args.force {
+            return Err(eyre::eyre!(
+                "{} already exists, pass --force to replace it",
+                path
+            ));
+        }
+    }
+    if existing.is_some() && !in_place && !args.force && !args.append && args.sink == Sink::File {
+        return Err(eyre::eyre!(
+            "{} already exists, pass --force to replace it",
+            first_path
+        ));
+    }
+    // Parts are given temporary names of their own
+    let written_path = if in_place || rolling || args.sink != Sink::File {
+        args.output.clone()
+    } else {
+        output::partial_path(&args.output)
+    };
+    // A global shuffle needs the complete output, so generate it aside first
+    let shuffled = args.order == Order::Shuffled;
+    let generated_path = if shuffled {
+        format!("{}.unshuffled", args.output)
+    } else {
+        written_path.clone()
+    };
+    let resume = args.resume.then(|| Checkpoint::load(&args)).transpose()?;
+    let seed = match resume {
+        Some(checkpoint) => checkpoint.seed,
+        None => args.seed.unwrap_or_else(rand::random),
+    };
+    // Appending continues after the rows already there, as resuming does,
+    // with the output under its temporary name until it is complete again
+    let resume = match resume {
+        None if args.append && existing.is_some() => {
+            let start = Checkpoint::existing(&args, seed, &args.output)?;
+            fs::rename(&args.output, &written_path)?;
+            Some(start)
+        }
+        resume => resume,
+    };
+    let args = if args.auto_tune {
+        tune::auto_tune(&args, |trial, path| {
+            generate(trial, path, seed, None, None).map(|written| written.bytes)
+        })?
+    } else {
+        args
+    };
+    let start = Instant::now();
+    let args = Args {
+        deadline: args.duration.map(|duration| start + duration),
+        ..args
+    };
+    // Only the final output is summed, so after the shuffle if there is one
+    let algorithm = args.checksum.unwrap_or(Algorithm::Sha256);
+    // Split output is summed part by part once complete instead
+    let checksum = ((args.checksum.is_some() && !rolling) || args.summary.is_some())
+        .then(|| Mutex::new(Checksum::new(algorithm)));
+    let generated_checksum = if shuffled { None } else { checksum.as_ref() };
+    if let (Some(checkpoint), Some(checksum)) = (resume, generated_checksum) {
+        checkpoint::sum_kept(&generated_path, checkpoint.written.bytes, checksum)?;
+    }
+    let generated = (|| -> Result<Written> {
+        let written = generate(&args, &generated_path, seed, generated_checksum, resume)?;
+        if complete(&args, written) && (args.resume || args.checkpoint_interval.is_some()) {
+            fs::remove_file(Checkpoint::path(&args.output))?;
+        }
+        if shuffled {
+            // A different seed, so the shuffle doesn't replay generation's draws
+            let mut rng = GenRng::seed_from_u64(args.rng, !seed);
+            shuffle::shuffle_file(
+                &generated_path,
+                &written_path,
+                args.memory_limit,
+                args.quiet,
+                checksum.as_ref(),
+                &mut rng,
+            )?;
+            fs::remove_file(&generated_path)?;
+        }
+        Ok(written)
+    })();
+    let written = match generated {
+        Ok(written) => written,
+        Err(error) => {
+            if rolling {
+                let parts = rolling::partial_parts(&args.output);
+                match args.on_error {
+                    OnError::Keep => drop(rolling::complete(&args.output)?),
+                    policy => policy.apply(
+                        &args.output,
+                        &parts.iter().map(String::as_str).collect::<Vec<_>>(),
+                    )?,
+                }
+            } else if written_path != args.output {
+                args.on_error
+                    .apply(&args.output, &[&written_path, &generated_path])?;
+            }
+            return Err(error);
+        }
+    };
+    // An interrupted run's output keeps its temporary name
+    let output_paths = match (rolling, complete(&args, written)) {
+        (true, true) => rolling::complete(&args.output)?,
+        (true, false) => rolling::partial_parts(&args.output),
+        (false, true) => {
+            if written_path != args.output {
+                fs::rename(&written_path, &args.output)?;
+            }
+            vec![args.output.clone()]
+        }
+        (false, false) => vec![written_path.clone()],
+    };
+    // Parts are recorded individually, under the name they are numbered from
+    let (output_path, parts) = match rolling {
+        true => (&args.output, &output_paths[..]),
+        false => (&output_paths[0], &[][..]),
+    };
+    let elapsed = start.elapsed();
+    let checksum =
+        checksum.map(|checksum| checksum.into_inner().expect("A writer panicked").finish());
+    if let (Some(checksum), Some(_), false) = (&checksum, args.checksum, rolling) {
+        // As `sha256sum --check` reads it, kept out of output going to stdout
+        match output::is_stdout(&args.output) {
+            true => eprintln!("{}  {}", checksum, args.output),
+            false => println!("{}  {}", checksum, args.output),
+        }
+    }
+    if (args.verify_after || args.spot_check.is_some()) && complete(&args, written) {
+        let format = match &args.schema {
+            Some(path) => verify::Format::Schema(Schema::load(path)?),
+            None => verify::Format::Measurements,
+        };
+        match args.spot_check {
+            Some(samples) => verify::spot_check(&output_paths, &format, samples)?,
+            None => verify::verify(&output_paths, &format, written.rows, args.quiet)?,
+        }
+    }
+    let sums = match rolling && complete(&args, written) {
+        true => Some(rolling::write_sums(&args.output, &output_paths, algorithm)?),
+        false => None,
+    };
+    if let (Some((_, sums)), Some(_)) = (&sums, args.checksum) {
+        print!("{}", sums);
+    }
+    // Named after the algorithm wherever it's recorded
+    let checksum = checksum.map(|checksum| format!("{}:{}", algorithm.name(), checksum));
+    let summary = Summary {
+        output: args.output.clone(),
+        rows: written.rows,
+        bytes: written.bytes,
+        elapsed,
+        seed,
+        config_hash: summary::config_hash(&args, seed),
+        checksum: checksum.clone(),
+    };
+    if let Some(path) = &args.summary {
+        summary.write(path)?;
+    }
+    let write_manifest = !args.no_manifest && args.sink == Sink::File;
+    if write_manifest {
+        manifest::write(
+            &args,
+            seed,
+            written,
+            output_path,
+            parts,
+            checksum.as_deref(),
+        )?;
+    }
+    let indexes = match args.index.filter(|_| complete(&args, written)) {
+        Some(interval) => index::write(&output_paths, interval)?,
+        None => Vec::new(),
+    };
+    // The stations present are tallied once for every file listing them
+    let mut tallies = Vec::new();
+    let tallied = args.station_counts
+        || args.emit_keyset.is_some()
+        || args.frequency_report.is_some()
+        || !args.answer_key.is_empty();
+    if tallied && complete(&args, written) {
+        let stations = census::tally(&output_paths)?;
+        if args.station_counts {
+            tallies.push(census::write_counts(&args.output, &stations)?);
+        }
+        if let Some(path) = &args.emit_keyset {
+            census::write_keyset(path, &stations)?;
+            tallies.push(path.clone());
+        }
+        if let Some(path) = &args.frequency_report {
+            census::write_frequencies(path, &stations)?;
+            if path != "-" {
+                tallies.push(path.clone());
+            }
+        }
+        for &format in &args.answer_key {
+            tallies.push(answer::write(
+                &args.output,
+                &stations,
+                format,
+                args.rounding,
+            )?);
+        }
+    }
+    let mut finished = output_paths;
+    finished.extend(indexes);
+    finished.extend(tallies);
+    finished.extend(sums.map(|(path, _)| path));
+    if write_manifest {
+        finished.push(manifest::path(&args.output));
+    }
+    // Only a complete dataset is worth distributing
+    if let Some(format) = args.archive.filter(|_| complete(&args, written)) {
+        let path = archive::path(&args.output, format);
+        tracing::info!("Archiving into {}", path);
+        archive::create(&path, format, &finished)?;
+        finished = vec![path];
+    }
+    if args.fsync && args.sink == Sink::File {
+        for path in &finished {
+            output::sync_to_storage(path)?;
+        }
+    }
+    if !complete(&args, written) {
+        return Err(eyre::eyre!(
+            "Interrupted after {} of {} rows",
+            written.rows,
+            args.row_count()
+        ));
+    }
+
+    Ok(summary)
+}
+
+/// What generation wrote: all the rows asked for, unless interrupted
+#[derive(Debug, Clone, Copy)]
+pub struct Written {
+    pub rows: u64,
+    pub bytes: u64,
+}
+
+/// Whether a run that wrote `written` finished, by reaching `--rows` or
+/// running out its `--duration`, rather than being interrupted
+pub fn complete(args: &Args, written: Written) -> bool {
+    written.rows == args.row_count() || interrupt::out_of_time(args.deadline)
+}
+
+/// Generates the rows `args` describe into `output`, returning what was
+/// written, and feeding it to `checksum` if given. When resuming, `output`
+/// keeps what the checkpoint records and generation continues after it.
+fn generate(
+    args: &Args,
+    output: &str,
+    seed: u64,
+    checksum: Option<&Mutex<Checksum>>,
+    resume: Option<Checkpoint>,
+) -> Result<Written> {
+    let start = resume.unwrap_or_else(|| Checkpoint::new(args, seed));
+    match &args.schema {
+        Some(schema_path) => generate_from_schema(args, schema_path, output, checksum, &start),
+        None => generate_measurements(args, output, checksum, &start),
+    }
+}
+
+fn generate_from_schema(
+    args: &Args,
+    schema_path: &str,
+    output: &str,
+    checksum: Option<&Mutex<Checksum>>,
+    start: &Checkpoint,
+) -> Result<Written> {
+    let mut schema = tracing::debug_span!("load_schema").in_scope(|| Schema::load(schema_path))?;
+    match args.order {
+        Order::Random | Order::Shuffled => {}
+        Order::ByTime => schema.order_by_time(args.rows)?,
+        Order::ByStation => {
+            return Err(eyre::eyre!(
+                "--order by-station is not supported with --schema"
+            ))
+        }
+    }
+    generate_lines(
+        args,
+        output,
+        checksum,
+        start,
+        schema.line_length_estimate(),
+        GenRng::seed_from_u64(args.rng, start.seed),
+        |row, rng, out_buf| schema.generate_line(row, rng, out_buf),
+    )
+}
+
+/// How `--weather-stations` is read
+pub fn list_options(args: &Args) -> ListOptions {
+    ListOptions {
+        lenient: args.lenient,
+        no_header: args.no_header,
+        delimiter: args.stations_delimiter,
+        param_columns: None,
+    }
+}
+
+fn generate_measurements(
+    args: &Args,
+    output: &str,
+    checksum: Option<&Mutex<Checksum>>,
+    start: &Checkpoint,
+) -> Result<Written> {
+    if args.order == Order::ByTime {
+        return Err(eyre::eyre!(
+            "--order by-time requires a --schema with a timestamp column"
+        ));
+    }
+    if args.pattern != Pattern::Random {
+        check_pattern_options(args)?;
+    }
+    if !args.passthrough_columns.is_empty() {
+        // The carried columns are written where a shorter name or another
+        // format's station field would take them as part of the name
+        let conflicts = [
+            ("--name-length-mix", args.name_length_mix.is_some()),
+            (args.format.option(), args.format != Format::Text),
+        ];
+        if let Some((option, _)) = conflicts.iter().find(|(_, set)| *set) {
+            return Err(eyre::eyre!(
+                "{} can't be combined with --passthrough-columns",
+                option
+            ));
+        }
+    }
+    if args.name_length_mix.is_some() {
+        // Cutting a quoted name would leave its quotes unbalanced, and
+        // rows of one station written at several lengths aren't clustered
+        let conflicts = [
+            ("--quote", args.quote != Quote::Never),
+            ("--order by-station", args.order == Order::ByStation),
+        ];
+        if let Some((option, _)) = conflicts.iter().find(|(_, set)| *set) {
+            return Err(eyre::eyre!(
+                "{} can't be combined with --name-length-mix",
+                option
+            ));
+        }
+    }
+    let script = args
+        .script
+        .as_ref()
+        .map(|path| Program::load(path, &["station", "row"]))
+        .transpose()?;
+    let mut rng = GenRng::seed_from_u64(args.rng, start.seed);
+    let loading = tracing::debug_span!("load_stations").entered();
+    let passthrough = match args.passthrough_columns.as_slice() {
+        [] => None,
+        columns => Some(Passthrough::load(
+            &args.weather_stations,
+            columns,
+            list_options(args),
+            args.normalize,
+        )?),
+    };
+    // Carried columns hold no parameters, wherever they are
+    let options = ListOptions {
+        param_columns: passthrough.as_ref().map(Passthrough::param_columns),
+        ..list_options(args)
+    };
+    // Sampling a loaded list draws the same stations as sampling while
+    // reading it
+    let loaded = args.cache_stations || args.loaded_stations.is_some();
+    let mut stations: Stations = match (&args.loaded_stations, loaded, args.max_stations) {
+        (Some(stations), _, _) => Stations::clone(stations),
+        (None, true, _) => cache::load(&args.weather_stations, options)?,
+        (None, false, Some(max)) => sample_weather_stations(
+            args.weather_stations.clone(),
+            options,
+            max as usize,
+            &mut rng,
+        )?,
+        (None, false, None) => load_weather_stations(args.weather_stations.clone(), options)?,
+    };
+    if let (true, Some(max)) = (loaded, args.max_stations) {
+        stations.sample(max as usize, &mut rng);
+    }
+    if args.normalize != normalize::Form::None {
+        let changed = stations.normalize(args.normalize);
+        tracing::debug!(changed, "Normalized station names");
+    }
+    if let Some(params_path) = &args.station_params {
+        apply_station_params(&mut stations, params_path, args.normalize)?;
+    }
+    match stations.apply_name_policy(args.quote, args.bad_name_policy)? {
+        0 => {}
+        affected if args.bad_name_policy == NamePolicy::Skip => tracing::warn!(
+            "Skipped {} station names holding `;`, a line break, or a control character",
+            affected
+        ),
+        affected => tracing::info!(
+            "{} {} station names holding `;`, a line break, or a control character",
+            match args.bad_name_policy {
+                NamePolicy::Escape => "Escaped",
+                _ => "Quoted",
+            },
+            affected
+        ),
+    }
+    if stations.is_empty() {
+        return Err(eyre::eyre!("No stations"));
+    }
+    if let Some(pool) = args.entropy.station_pool() {
+        stations.shuffle(&mut rng);
+        stations.truncate(pool);
+    }
+    tracing::debug!(stations = stations.len(), "Loaded stations");
+    drop(loading);
+    let resized = args
+        .name_length_mix
+        .as_ref()
+        .map(|mix| mix.resize(&stations));
+    if args.enforce_official_limits {
+        check_official_limits(resized.as_ref().unwrap_or(&stations))?;
+    }
+    // Carried columns are written as part of each station's name
+    let extended = passthrough.map(|passthrough| passthrough.extend(&stations));
+    let written = extended.as_ref().or(resized.as_ref()).unwrap_or(&stations);
+    let average_station_name_length = match &args.name_length_mix {
+        Some(mix) => mix.mean_length(),
+        None => written.iter().map(|s| s.name.1).sum::<usize>() / written.len(),
+    };
+    // +6 for ";-99.9" and +1 for \n
+    let line_length_estimate = average_station_name_length + 7;
+    let encoder = &LineEncoder::new(written);
+
+    if args.pattern == Pattern::Cyclic {
+        return generate_lines(
+            args,
+            output,
+            checksum,
+            start,
+            line_length_estimate,
+            rng,
+            |row, _, out_buf| {
+                let (index, measurement) = Pattern::cyclic(row, stations.len());
+                encoder.push_line(out_buf, index, measurement);
+                Ok(())
+            },
+        );
+    }
+
+    if args.pattern == Pattern::Ramp {
+        let mut picker = StationPicker::new(args.order, &stations, args.rows, &mut rng)?;
+        return generate_lines(
+            args,
+            output,
+            checksum,
+            start,
+            line_length_estimate,
+            rng,
+            move |row, rng, out_buf| {
+                let index = picker.next(rng);
+                encoder.push_line(out_buf, index, Pattern::ramp(row));
+                Ok(())
+            },
+        );
+    }
+
+    let (station_repeat, value_repeat) =
+        (args.entropy.station_repeat(), args.entropy.value_repeat());
+    let mut previous: Option<(usize, i32)> = None;
+    let mut picker = StationPicker::new(args.order, &stations, args.rows, &mut rng)?;
+    let mut model = Model::new(args.model, args.distribution, &stations)?;
+    let distinct_temps = args.distinct_temps.map(DistinctTemps::new).transpose()?;
+    let negative_zero_rate = args.negative_zero_rate.unwrap_or(0.0);
+    let mut slots = Vec::new();
+    let (stations, script, distinct_temps) = (&stations, script.as_ref(), distinct_temps.as_ref());
+    let name_length_mix = args.name_length_mix.as_ref();
+    generate_lines(
+        args,
+        output,
+        checksum,
+        start,
+        line_length_estimate,
+        rng,
+        move |row, rng, out_buf| {
+            let index = match previous {
+                Some((index, _)) if picker.is_random() && chance(rng, station_repeat) => index,
+                _ => picker.next(rng),
+            };
+            let station = &stations[index];
+            let measurement = match script {
+                Some(script) => {
+                    slots.clear();
+                    slots.extend([Value::Str(stations.name(station)), Value::Num(row as f64)]);
+                    let value = script.eval(&mut slots, rng)?.num()?;
+                    ((value * 10.0).round() as i32).clamp(MIN_TEMP, MAX_TEMP)
+                }
+                None => model.sample(index, rng),
+            };
+            let measurement = match distinct_temps {
+                Some(distinct_temps) => distinct_temps.snap(measurement),
+                None => measurement,
+            };
+            let measurement = match previous {
+                Some((_, last)) if chance(rng, value_repeat) => last,
+                _ => measurement,
+            };
+            previous = Some((index, measurement));
+            // Each length has a copy of every station in the encoder
+            let index = match name_length_mix {
+                Some(mix) => mix.pick(rng) * stations.len() + index,
+                None => index,
+            };
+            if chance(rng, negative_zero_rate) {
+                encoder.push_negative_zero_line(out_buf, index);
+            } else {
+                encoder.push_line(out_buf, index, measurement);
+            }
+            Ok(())
+        },
+    )
+}
+
+/// Rejects stations the official challenge doesn't allow: more than 10,000
+/// distinct names, or any name over 100 bytes. Measurements are always
+/// generated within -99.9 to 99.9, so need no check.
+fn check_official_limits(stations: &Stations) -> Result<()> {
+    if let Some(name) = stations.names().find(|name| name.len() > MAX_NAME_BYTES) {
+        return Err(eyre::eyre!(
+            "Station {:?} is {} bytes long, over the {} --enforce-official-limits allows",
+            name,
+            name.len(),
+            MAX_NAME_BYTES
+        ));
+    }
+    let distinct = stations.names().collect::<HashSet<_>>().len();
+    if distinct > MAX_STATIONS {
+        return Err(eyre::eyre!(
+            "{} distinct stations are over the {} --enforce-official-limits allows; \
+             --max-stations can sample fewer",
+            distinct,
+            MAX_STATIONS
+        ));
+    }
+    Ok(())
+}
+
+/// `gen_bool`, without spending a draw on rates that are off
+fn chance<R: Rng>(rng: &mut R, rate: f64) -> bool {
+    rate > 0.0 && rng.gen_bool(rate)
+}
+
+fn parse_rate(s: &str) -> Result<f64> {
+    let rate: f64 = s.parse().map_err(|_| eyre::eyre!("Invalid rate `{}`", s))?;
+    if !(0.0..=1.0).contains(&rate) {
+        return Err(eyre::eyre!("Rates must be between 0 and 1"));
+    }
+    Ok(rate)
+}
+
+/// Parses a count written out in full, with `_` separators, in scientific
+/// notation such as `1e9`, or with a decimal `K`, `M`, `B`, or `T` suffix
+/// such as `1B` or `2.5M`. Decimals are worked out exactly, without floats.
+pub fn parse_count(s: &str) -> Result<u64> {
+    let invalid = || eyre::eyre!("Invalid count `{}`", s);
+    let text = s.replace('_', "").to_ascii_uppercase();
+    let (mantissa, exponent) = match text.split_once('E') {
+        Some((mantissa, exponent)) => (mantissa, exponent.parse().map_err(|_| invalid())?),
+        None => match text.strip_suffix(['K', 'M', 'B', 'G', 'T']) {
+            Some(mantissa) => {
+                let exponent = match text.chars().last() {
+                    Some('K') => 3,
+                    Some('M') => 6,
+                    Some('B' | 'G') => 9,
+                    _ => 12,
+                };
+                (mantissa, exponent)
+            }
+            None => (text.as_str(), 0i32),
+        },
+    };
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{}{}", whole, fraction);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    // The fraction's digits shift the point left, the exponent right
+    let shift = i32::try_from(fraction.len())
+        .ok()
+        .and_then(|len| exponent.checked_sub(len))
+        .ok_or_else(invalid)?;
+    let too_large = || eyre::eyre!("Count `{}` is too large", s);
+    // No u64 has more than 20 digits, so a bigger shift of any nonzero
+    // digit overflows; refused before it takes that many zeros
+    if shift > 19 {
+        return match digits.bytes().all(|b| b == b'0') {
+            true => Ok(0),
+            false => Err(too_large()),
+        };
+    }
+    let digits = match shift {
+        shift if shift >= 0 => format!("{}{}", digits, "0".repeat(shift as usize)),
+        shift => {
+            let kept = digits.len().saturating_sub(shift.unsigned_abs() as usize);
+            if digits[kept..].bytes().any(|b| b != b'0') {
+                return Err(eyre::eyre!("`{}` is not a whole count", s));
+            }
+            digits[..kept].to_string()
+        }
+    };
+    match digits.trim_start_matches('0') {
+        "" => Ok(0),
+        digits => digits.parse().map_err(|_| too_large()),
+    }
+}
+
+/// Parses a byte count with an optional `K`, `M`, or `G` suffix, binary
+/// whether written `KiB` or `K` and decimal when written `KB`
+fn parse_size(s: &str) -> Result<usize> {
+    let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(digits);
+    let number: usize = number
+        .parse()
+        .map_err(|_| eyre::eyre!("Invalid size `{}`", s))?;
+    let multiplier: usize = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KIB" => 1 << 10,
+        "M" | "MIB" => 1 << 20,
+        "G" | "GIB" => 1 << 30,
+        "KB" => 1_000,
+        "MB" => 1_000_000,
+        "GB" => 1_000_000_000,
+        _ => return Err(eyre::eyre!("Unknown size unit in `{}`", s)),
+    };
+    let size = number
+        .checked_mul(multiplier)
+        .ok_or_else(|| eyre::eyre!("Size `{}` is too large", s))?;
+    if size == 0 {
+        return Err(eyre::eyre!("Sizes must be greater than zero"));
+    }
+    Ok(size)
+}
+
+fn parse_interval(s: &str) -> Result<Duration> {
+    let digits = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(digits);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| eyre::eyre!("Invalid interval `{}`", s))?;
+    let seconds = match unit.trim() {
+        "" | "s" => number,
+        "ms" => number / 1000.0,
+        "m" => number * 60.0,
+        _ => return Err(eyre::eyre!("Unknown interval unit in `{}`", s)),
+    };
+    if seconds <= 0.0 {
+        return Err(eyre::eyre!("Intervals must be greater than zero"));
+    }
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+/// Rejects options that can't be combined, or that the build or terminal
+/// doesn't support
+fn check_options(args: &Args) -> Result<()> {
+    if args.threads() > 1 {
+        check_thread_options(args)?;
+    }
+    if let Some(depth) = args.queue_depth {
+        let fewest = args.io.min_buffer_count();
+        if depth < fewest {
+            return Err(eyre::eyre!(
+                "--queue-depth must be at least {} with --io {}",
+                fewest,
+                manifest::value_name(args.io)
+            ));
+        }
+    }
+    if args.compress_threads.is_some() && args.compress.is_none() {
+        return Err(eyre::eyre!("--compress-threads requires --compress"));
+    }
+    if args.pin_threads && args.threads() == 1 {
+        return Err(eyre::eyre!("--pin-threads requires --threads"));
+    }
+    if args.huge_pages {
+        huge_pages::check()?;
+    }
+    if args.compress.is_some() {
+        if args.compress.is_some_and(|c| c.codec == Codec::Gzip) && !cfg!(feature = "compression") {
+            return Err(eyre::eyre!(
+                "--compress gzip needs the `compression` feature, which this build leaves out"
+            ));
+        }
+        if args.threads() > 1 {
+            return Err(eyre::eyre!("--compress can't be combined with --threads"));
+        }
+        if args.order == Order::Shuffled {
+            return Err(eyre::eyre!(
+                "--compress can't be combined with --order shuffled"
+            ));
+        }
+    }
+    if args.format != Format::Text {
+        let conflicts = [
+            ("--compress", args.compress.is_some()),
+            ("--threads", args.threads() > 1),
+            ("--schema", args.schema.is_some()),
+            ("--order shuffled", args.order == Order::Shuffled),
+            ("--max-file-size", args.max_file_size.is_some()),
+            ("--resume", args.resume),
+            ("--append", args.append),
+            ("--checkpoint-interval", args.checkpoint_interval.is_some()),
+            (args.sink.option(), args.sink == Sink::Broker),
+        ];
+        if let Some((option, _)) = conflicts.iter().find(|(_, set)| *set) {
+            return Err(eyre::eyre!(
+                "{} can't be combined with {}",
+                option,
+                args.format.option()
+            ));
+        }
+    }
+    if args.sink != Sink::File {
+        // Messages are split from the rows, which compression would hide
+        let broker = args.sink == Sink::Broker;
+        let conflicts = [
+            ("--io", args.io != IoBackend::Write),
+            ("--order shuffled", args.order == Order::Shuffled),
+            ("--threads", broker && args.threads() > 1),
+            ("--compress", broker && args.compress.is_some()),
+            ("--archive", broker && args.archive.is_some()),
+        ];
+        if let Some((option, _)) = conflicts.iter().find(|(_, set)| *set) {
+            return Err(eyre::eyre!(
+                "{} can't be combined with {}",
+                option,
+                args.sink.option()
+            ));
+        }
+    }
+    if (args.tui || args.progress == Some(ProgressMode::Tui)) && !io::stderr().is_terminal() {
+        return Err(eyre::eyre!("--tui requires stderr to be a terminal"));
+    }
+    if args.write_through && args.io != IoBackend::Direct {
+        return Err(eyre::eyre!("--write-through requires --io direct"));
+    }
+    if args.drop_cache && args.io == IoBackend::Mmap {
+        return Err(eyre::eyre!("--drop-cache can't be combined with --io mmap"));
+    }
+    if args.resume || args.append || args.checkpoint_interval.is_some() {
+        check_checkpoint_options(args)?;
+    }
+    if args.max_file_size.is_some() {
+        check_rolling_options(args)?;
+    }
+    let reading_back = [
+        ("--verify-after", args.verify_after),
+        ("--spot-check", args.spot_check.is_some()),
+        ("--index", args.index.is_some()),
+        ("--station-counts", args.station_counts),
+        ("--emit-keyset", args.emit_keyset.is_some()),
+        ("--frequency-report", args.frequency_report.is_some()),
+        ("--answer-key", !args.answer_key.is_empty()),
+    ];
+    for (option, _) in reading_back.iter().filter(|(_, set)| *set) {
+        check_read_back_options(args, option)?;
+    }
+    if args.index == Some(0) {
+        return Err(eyre::eyre!("--index must be at least 1"));
+    }
+    if args.max_stations == Some(0) {
+        return Err(eyre::eyre!("--max-stations must be at least 1"));
+    }
+    Ok(())
+}
+
+/// The threads a run without `--threads` generates on: one per physical
+/// core it may use, within any CPU quota, when `--rng philox` and the other
+/// options let it generate in parallel into a regular file or the null sink,
+/// and otherwise one. Hyperthreads add little to a loop bound by memory
+/// bandwidth, and the output is the same on any number of threads.
+fn default_threads(args: &Args) -> usize {
+    let cores = affinity::physical_cores();
+    let special = args.sink == Sink::File
+        && fs::metadata(&args.output).is_ok_and(|metadata| !metadata.is_file());
+    // A row callback takes the rows on the calling thread
+    if cores == 1 || args.rng != RngKind::Philox || special || args.row_callback.is_some() {
+        return 1;
+    }
+    let parallel = Args {
+        threads: Some(cores),
+        ..args.clone()
+    };
+    match check_options(&parallel) {
+        Ok(()) => cores,
+        Err(_) => 1,
+    }
+}
+
+/// Sets the seed `--seed-label` stands for: the first 8 bytes of the
+/// SHA-256 of the label, big endian, so that other tools can work it out
+fn resolve_seed_label(args: &mut Args) {
+    if let Some(label) = &args.seed_label {
+        let mut sha = checksum::Sha256::default();
+        sha.update(label.as_bytes());
+        let digest = sha.finish();
+        args.seed = Some(u64::from_be_bytes(
+            digest[..8].try_into().expect("SHA-256 is 32 bytes"),
+        ));
+    }
+}
+
+/// Rejects the options that parallel generation can't honor: workers make
+/// each row from its index alone, so rows can't carry state between them
+pub fn check_thread_options(args: &Args) -> Result<()> {
+    if cfg!(not(unix)) {
+        return Err(eyre::eyre!("--threads is only supported on Unix"));
+    }
+    if args.rng != RngKind::Philox {
+        return Err(eyre::eyre!("--threads requires --rng philox"));
+    }
+    let conflicts = [
+        ("--model ar1", matches!(args.model, ModelSpec::Ar1(_))),
+        ("--entropy", args.entropy != Entropy::High),
+        ("--order by-station", args.order == Order::ByStation),
+        ("--io", args.io != IoBackend::Write),
+        // Each worker writes its own chunks, with no queue between them
+        ("--queue-depth", args.queue_depth.is_some()),
+        ("--stall-timeout", args.stall_timeout.is_some()),
+    ];
+    match conflicts.iter().find(|(_, set)| *set) {
+        Some((option, _)) => Err(eyre::eyre!("{} can't be combined with --threads", option)),
+        None => Ok(()),
+    }
+}
+
+/// Whether `estimate` bytes fit in the free space of the filesystem holding
+/// `path`, refusing to go on when they don't unless `--no-space-check`
+fn check_free_space(args: &Args, path: &str, estimate: u64) -> Result<bool> {
+    let Some(free) = output::free_space(path)? else {
+        return Ok(true);
+    };
+    if estimate <= free {
+        return Ok(true);
+    }
+    let message = format!(
+        "The output needs about {}, but only {} is free for {}",
+        human_readable(estimate),
+        human_readable(free),
+        path
+    );
+    if !args.no_space_check {
+        return Err(eyre::eyre!(
+            "{}; --no-space-check generates anyway",
+            message
+        ));
+    }
+    tracing::warn!("{}", message);
+    Ok(false)
+}
+
+/// Rejects the options checkpoints and appending can't follow: both continue
+/// from how far a single, buffered writer got through the final output
+fn check_checkpoint_options(args: &Args) -> Result<()> {
+    let option = if args.resume {
+        "--resume"
+    } else if args.append {
+        "--append"
+    } else {
+        "--checkpoint-interval"
+    };
+    // Rows are counted by line, which compressed output doesn't show
+    if args.append && args.compress.is_some() {
+        return Err(eyre::eyre!("--compress can't be combined with --append"));
+    }
+    let conflicts = [
+        ("--threads", args.threads() > 1),
+        ("--io", args.io != IoBackend::Write),
+        ("--order shuffled", args.order == Order::Shuffled),
+        (args.sink.option(), args.sink != Sink::File),
+    ];
+    match conflicts.iter().find(|(_, set)| *set) {
+        Some((conflict, _)) => Err(eyre::eyre!(
+            "{} can't be combined with {}",
+            conflict,
+            option
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Rejects the options a rolling output can't follow: parts are filled in
+/// order by a single, buffered writer, and split between lines
+fn check_rolling_options(args: &Args) -> Result<()> {
+    let conflicts = [
+        ("--threads", args.threads() > 1),
+        ("--io", args.io != IoBackend::Write),
+        ("--compress", args.compress.is_some()),
+        ("--order shuffled", args.order == Order::Shuffled),
+        (args.sink.option(), args.sink != Sink::File),
+        ("--drop-cache", args.drop_cache),
+        ("--resume", args.resume),
+        ("--append", args.append),
+        ("--checkpoint-interval", args.checkpoint_interval.is_some()),
+    ];
+    match conflicts.iter().find(|(_, set)| *set) {
+        Some((conflict, _)) => Err(eyre::eyre!(
+            "{} can't be combined with --max-file-size",
+            conflict
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Verifying and indexing read the output back as plain text from storage
+fn check_read_back_options(args: &Args, option: &str) -> Result<()> {
+    let conflicts = [
+        ("--compress", args.compress.is_some()),
+        (args.format.option(), args.format != Format::Text),
+        (args.sink.option(), args.sink != Sink::File),
+    ];
+    match conflicts.iter().find(|(_, set)| *set) {
+        Some((conflict, _)) => Err(eyre::eyre!(
+            "{} can't be combined with {}",
+            conflict,
+            option
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Whether every row is made from its index alone, so rows can be skipped
+/// without generating the ones before them
+fn rows_independent(args: &Args) -> bool {
+    args.rng == RngKind::Philox
+        && !matches!(args.model, ModelSpec::Ar1(_))
+        && args.entropy == Entropy::High
+        && args.order != Order::ByStation
+}
+
+/// Rejects the options shaping random generation, which fixed patterns
+/// would otherwise silently ignore
+fn check_pattern_options(args: &Args) -> Result<()> {
+    let conflicts = [
+        ("--script", args.script.is_some()),
+        ("--model", args.model != ModelSpec::Iid),
+        ("--distribution", args.distribution != Distribution::Uniform),
+        ("--distinct-temps", args.distinct_temps.is_some()),
+        ("--entropy", args.entropy != Entropy::High),
+        ("--order", args.order != Order::Random),
+        ("--negative-zero-rate", args.negative_zero_rate.is_some()),
+        ("--name-length-mix", args.name_length_mix.is_some()),
+    ];
+    match conflicts.iter().find(|(_, set)| *set) {
+        Some((option, _)) => Err(eyre::eyre!(
+            "{} can't be combined with --pattern {}",
+            option,
+            args.pattern
+                .to_possible_value()
+                .expect("Patterns have no skipped values")
+                .get_name()
+        )),
+        None => Ok(()),
+    }
+}
+
+fn generate_lines<F>(
+    args: &Args,
+    output_path: &str,
+    checksum: Option<&Mutex<Checksum>>,
+    start: &Checkpoint,
+    line_length_estimate: usize,
+    rng: GenRng,
+    mut generate_line: F,
+) -> Result<Written>
+where
+    F: FnMut(u64, &mut BatchRng, &mut Vec<u8>) -> Result<()> + Clone + Send,
+{
+    let compress_threads = args
+        .compress_threads
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()));
+    let plan = memory::Plan::new(args, line_length_estimate, compress_threads);
+    let plan = match args.memory_limit {
+        Some(limit) => {
+            let plan = plan.fit(limit)?;
+            tracing::info!(
+                chunk_rows = plan.chunk_rows,
+                write_buffer = plan.write_buffer,
+                threads = plan.threads,
+                buffers = plan.buffers,
+                compress_threads = plan.compress_threads,
+                "Fitted to --memory-limit"
+            );
+            plan
+        }
+        None => plan,
+    };
+    let range = args.row_range();
+    let (rows, chunk_rows) = (range.end - range.start, plan.chunk_rows);
+    let _span = tracing::debug_span!("generate", rows, threads = plan.threads).entered();
+    // A resumed run continues after the rows it kept
+    let first_row = range.start + start.written.rows;
+    let chunk_count = (range.end - first_row) / chunk_rows;
+    let mode = match args.tui {
+        true => ProgressMode::Tui,
+        false => args.progress.unwrap_or_else(ProgressMode::detect),
+    };
+    let progress = Progress::new(
+        chunk_count + 1,
+        rows,
+        chunk_rows,
+        start.written,
+        args.quiet,
+        mode,
+        args.progress_interval,
+    );
+    if let Some(batch) = &args.batch {
+        progress.join(batch);
+    }
+    progress.show_dashboard(&args.output, dashboard_config(args, &plan), plan.threads);
+    if let Some(path) = &args.progress_log {
+        progress.log_to(path, args.progress_interval)?;
+    }
+    let out_buf_len = plan.chunk_len();
+    let estimate = (rows - start.written.rows) * line_length_estimate as u64;
+    // Compressed output is far smaller than the estimate
+    let fits = args.sink != Sink::File
+        || args.compress.is_some()
+        || check_free_space(args, output_path, estimate)?;
+    // A memory map is sized up front anyway
+    let preallocate = fits
+        && !args.no_preallocate
+        && args.max_file_size.is_none()
+        && args.sink == Sink::File
+        && args.compress.is_none()
+        && args.io != IoBackend::Mmap;
+
+    #[cfg(unix)]
+    if plan.threads > 1 {
+        let file = match args.sink {
+            Sink::File => Some(fs::File::create(output_path)?),
+            Sink::Null | Sink::Broker => None,
+        };
+        if let Some(file) = file.as_ref().filter(|_| preallocate) {
+            output::preallocate(file, 0, estimate)?;
+        }
+        let workers = parallel::Workers {
+            threads: plan.threads,
+            pin: args.pin_threads,
+            huge_pages: args.huge_pages,
+            drop_cache: args.drop_cache,
+            checksum,
+            deadline: args.deadline,
+        };
+        let written = parallel::generate(
+            file.as_ref(),
+            range,
+            chunk_rows,
+            out_buf_len,
+            &workers,
+            &rng,
+            &progress,
+            generate_line,
+        )?;
+        if preallocate {
+            output::release_preallocated(output_path)?;
+        }
+        progress.finish(written, interrupt::out_of_time(args.deadline));
+        return Ok(written);
+    }
+
+    let mut rng = BatchRng::new(rng);
+    // Rows carrying state from one to the next are generated again, unseen,
+    // to bring that state and the generator to where the checkpoint was
+    if first_row > 0 && !rows_independent(args) {
+        let _span = tracing::debug_span!("replay", rows = first_row).entered();
+        let mut scratch = Vec::with_capacity(out_buf_len);
+        for chunk_start in (0..first_row).step_by(chunk_rows as usize) {
+            if interrupt::stopping(args.deadline) {
+                progress.finish(start.written, interrupt::out_of_time(args.deadline));
+                return Ok(start.written);
+            }
+            scratch.clear();
+            for row in chunk_start..(chunk_start + chunk_rows).min(first_row) {
+                rng.set_row(row);
+                generate_line(row, &mut rng, &mut scratch)?;
+            }
+        }
+    }
+    let checkpointer = args
+        .checkpoint_interval
+        .map(|interval| Checkpointer::new(&args.output, interval, *start, chunk_rows, rows));
+    let writer = match args.sink {
+        Sink::File if start.written.rows > 0 => {
+            Output::resume(output_path, plan.write_buffer, start.written.bytes)?
+        }
+        Sink::File => match args.max_file_size {
+            Some(max) => Output::Rolling(RollingFile::create(
+                output_path,
+                max as u64,
+                plan.write_buffer,
+            )?),
+            None => Output::create(
+                output_path,
+                args.io,
+                plan.write_buffer,
+                estimate as usize,
+                args.write_through,
+            )?,
+        },
+        Sink::Null => null_output(args),
+        Sink::Broker => Output::Publish(broker::Publisher::connect(
+            &args.output,
+            args.publish_batch as usize,
+        )?),
+    };
+    if preallocate {
+        writer.preallocate(start.written.bytes, estimate)?;
+    }
+
+    // Full chunks go to the writer thread, which sends each buffer back once
+    // written, so the next chunk is generated while the last one is written
+    let (full_sender, full) = mpsc::sync_channel(1);
+    let (empty_sender, empty) = mpsc::channel();
+    for _ in 0..plan.buffers {
+        empty_sender
+            .send(huge_pages::buffer(out_buf_len, args.huge_pages)?)
+            .expect("The receiver is still held");
+    }
+    // Whole chunks, then an extra chunk with the remainder rows
+    let chunks = (0..chunk_count)
+        .map(|chunk| first_row + chunk * chunk_rows..first_row + (chunk + 1) * chunk_rows)
+        .chain(iter::once(first_row + chunk_count * chunk_rows..range.end));
+
+    let written = thread::scope(|scope| -> Result<Written> {
+        // With compression, chunks pass through a pool of compressors that
+        // return the raw buffers, and the writer is fed compressed ones;
+        // ORC and protobuf are encoded the same way, by a single encoder
+        let mut encoder = None;
+        let (writer_chunks, writer_spent) = match args.compress {
+            None if args.format != Format::Text => {
+                let (encoded_sender, encoded) = mpsc::sync_channel(1);
+                let (dropped, _) = mpsc::channel();
+                encoder = Some(scope.spawn(move || match args.format {
+                    Format::Orc => {
+                        orc::encode_chunks(args.stripe_size, full, empty_sender, encoded_sender)
+                    }
+                    _ => protobuf::encode_chunks(full, empty_sender, encoded_sender),
+                }));
+                (encoded, dropped)
+            }
+            Some(compression) => {
+                let (compressed_sender, compressed) = mpsc::sync_channel(1);
+                let (dropped, _) = mpsc::channel();
+                scope.spawn(move || {
+                    compress::compress_chunks(
+                        compression,
+                        plan.compress_threads,
+                        full,
+                        empty_sender,
+                        compressed_sender,
+                    )
+                });
+                (compressed, dropped)
+            }
+            None => (full, empty_sender),
+        };
+        let writer_progress = progress.clone();
+        let writer = scope.spawn(move || {
+            output::write_chunks(
+                writer,
+                args.flush,
+                args.drop_cache,
+                checksum,
+                checkpointer,
+                writer_chunks,
+                writer_spent,
+                writer_progress,
+            )
+        });
+        // The rows handed to the writer, stopping between chunks on Ctrl-C
+        // so the output still ends on a whole line
+        let generated = (|| -> Result<u64> {
+            let mut rows = first_row;
+            for chunk in chunks {
+                if interrupt::paused() {
+                    progress.pause();
+                    interrupt::wait_while_paused();
+                    progress.resume();
+                }
+                if interrupt::stopping(args.deadline) {
+                    break;
+                }
+                // The writer only hangs up after failing, which join reports
+                let Some(mut out_buf) = next_buffer(args, &empty)? else {
+                    break;
+                };
+                let began = Instant::now();
+                out_buf.clear();
+                let end = chunk.end;
+                for row in chunk {
+                    rng.set_row(row);
+                    generate_line(row, &mut rng, &mut out_buf)?;
+                }
+                progress.busy(0, began.elapsed());
+                if full_sender.send(out_buf).is_err() {
+                    break;
+                }
+                rows = end;
+            }
+            Ok(rows)
+        })();
+        drop(full_sender);
+        let bytes = writer.join().expect("The writer thread panicked");
+        if let Some(encoder) = encoder {
+            encoder.join().expect("The encoder panicked")?;
+        }
+        Ok(Written {
+            rows: generated? - range.start,
+            bytes: start.written.bytes + bytes?,
+        })
+    })?;
+    if preallocate {
+        output::release_preallocated(output_path)?;
+    }
+
+    progress.finish(written, interrupt::out_of_time(args.deadline));
+    Ok(written)
+}
+
+/// The next buffer the writer has finished with, waiting while a slow
+/// output holds all of them, or `None` once the writer hangs up. Waits
+/// longer than `--stall-timeout` are warned about, or fail with
+/// `--on-stall fail`.
+fn next_buffer(args: &Args, empty: &mpsc::Receiver<Vec<u8>>) -> Result<Option<Vec<u8>>> {
+    let Some(timeout) = args.stall_timeout else {
+        return Ok(empty.recv().ok());
+    };
+    let began = Instant::now();
+    loop {
+        match empty.recv_timeout(timeout) {
+            Ok(buffer) => return Ok(Some(buffer)),
+            Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(None),
+            Err(mpsc::RecvTimeoutError::Timeout) => match args.on_stall {
+                OnStall::Warn => tracing::warn!(
+                    "The output hasn't taken a chunk for {:.0?}; generation is paused",
+                    began.elapsed()
+                ),
+                OnStall::Fail => {
+                    return Err(errors::coded(
+                        "stalled",
+                        true,
+                        format!(
+                            "The output took no chunk for {:?}, past --stall-timeout",
+                            timeout
+                        ),
+                    ))
+                }
+            },
+        }
+    }
+}
+
+/// Where `--sink null` sends the rows: nowhere, unless a row callback is
+/// taking them
+fn null_output(args: &Args) -> Output {
+    match &args.row_callback {
+        Some(callback) => Output::Rows(callback.clone()),
+        None => Output::Null,
+    }
+}
+
+/// The options shaping how fast rows are generated and written, as the
+/// dashboard lists them
+fn dashboard_config(args: &Args, plan: &memory::Plan) -> Vec<(&'static str, String)> {
+    let mut config = vec![
+        ("rows", args.row_count().to_string()),
+        ("threads", plan.threads.to_string()),
+        ("rng", manifest::value_name(args.rng)),
+        ("io", manifest::value_name(args.io)),
+        ("chunk rows", plan.chunk_rows.to_string()),
+        ("write buffer", human_readable(plan.write_buffer as u64)),
+    ];
+    if let Some(compression) = args.compress {
+        config.push(("compress", compression.to_string()));
+        config.push(("compress threads", plan.compress_threads.to_string()));
+    }
+    if args.order != Order::Random {
+        config.push(("order", manifest::value_name(args.order)));
+    }
+    config
+}
+
+const BYTE_POSTFIXES: [&str; 9] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB", "ZiB", "YiB"];
+pub fn human_readable(value: u64) -> String {
+    let mut value = value as f64;
+    let mut i = 0;
+    while value > 1024.0 && i < BYTE_POSTFIXES.len() {
+        value /= 1024.0;
+        i += 1;
+    }
+
+    format!("{:.2} {}", value, BYTE_POSTFIXES[i])
+}
@@ -0,0 +1,391 @@
+use std::fmt::Write;
+
+use clap::{Arg, ArgAction, Command, ValueEnum};
+
+/// A shell to print completions for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+/// A command or subcommand, with the path of names leading to it
+struct Node<'a> {
+    path: Vec<&'a str>,
+    command: &'a Command,
+}
+
+/// What completing an option or positional argument offers
+struct Opt {
+    long: Option<String>,
+    short: Option<char>,
+    help: String,
+    /// Whether a value follows, and the values it is limited to, if any
+    value: Option<Vec<String>>,
+}
+
+/// Completions for `command` and its subcommands in `shell`'s own script
+/// language, walking the same definition the arguments are parsed from
+pub fn generate(shell: Shell, command: &mut Command) -> String {
+    command.build();
+    let nodes = nodes(command, vec![command.get_name()]);
+    match shell {
+        Shell::Bash => bash(command.get_name(), &nodes),
+        Shell::Zsh => zsh(command.get_name(), &nodes),
+        Shell::Fish => fish(command.get_name(), &nodes),
+        Shell::Powershell => powershell(command.get_name(), &nodes),
+    }
+}
+
+fn nodes<'a>(command: &'a Command, path: Vec<&'a str>) -> Vec<Node<'a>> {
+    let mut nodes = vec![Node {
+        path: path.clone(),
+        command,
+    }];
+    for sub in command.get_subcommands().filter(|sub| !sub.is_hide_set()) {
+        let mut path = path.clone();
+        path.push(sub.get_name());
+        nodes.extend(self::nodes(sub, path));
+    }
+    nodes
+}
+
+impl Node<'_> {
+    fn subcommands(&self) -> Vec<(&str, String)> {
+        self.command
+            .get_subcommands()
+            .filter(|sub| !sub.is_hide_set())
+            .map(|sub| (sub.get_name(), first_line(sub.get_about())))
+            .collect()
+    }
+
+    fn opts(&self) -> Vec<Opt> {
+        self.command
+            .get_arguments()
+            .filter(|arg| !arg.is_hide_set() && !arg.is_positional())
+            .map(opt)
+            .collect()
+    }
+
+    /// What the positional arguments are limited to, empty for files, or
+    /// `None` if there are none
+    fn positionals(&self) -> Option<Vec<String>> {
+        let arg = self.command.get_positionals().next()?;
+        Some(opt(arg).value.unwrap_or_default())
+    }
+
+    /// The path's names joined by `sep`, as an identifier in a script
+    fn ident(&self, sep: &str) -> String {
+        self.path
+            .iter()
+            .map(|name| name.replace('-', "_"))
+            .collect::<Vec<_>>()
+            .join(sep)
+    }
+}
+
+fn opt(arg: &Arg) -> Opt {
+    let takes_value = !matches!(
+        arg.get_action(),
+        ArgAction::SetTrue
+            | ArgAction::SetFalse
+            | ArgAction::Count
+            | ArgAction::Help
+            | ArgAction::HelpShort
+            | ArgAction::HelpLong
+            | ArgAction::Version
+    );
+    Opt {
+        long: arg.get_long().map(str::to_string),
+        short: arg.get_short(),
+        help: first_line(arg.get_help()),
+        value: takes_value.then(|| {
+            arg.get_possible_values()
+                .iter()
+                .filter(|value| !value.is_hide_set())
+                .map(|value| value.get_name().to_string())
+                .collect()
+        }),
+    }
+}
+
+impl Opt {
+    /// `--long` and `-s`, whichever the option has
+    fn flags(&self) -> Vec<String> {
+        let mut flags: Vec<String> = self.long.iter().map(|long| format!("--{}", long)).collect();
+        flags.extend(self.short.map(|short| format!("-{}", short)));
+        flags
+    }
+}
+
+/// The first sentence of a help text, which is all a completion menu shows
+fn first_line(help: Option<&clap::builder::StyledStr>) -> String {
+    let help = help.map(|help| help.to_string()).unwrap_or_default();
+    let line = help.lines().next().unwrap_or_default();
+    line.split(". ")
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches('.')
+        .to_string()
+}
+
+fn bash(name: &str, nodes: &[Node]) -> String {
+    let function = format!("_{}", name.replace('-', "_"));
+    let mut out = String::new();
+    let _ = writeln!(out, "{}() {{", function);
+    out.push_str(
+        "    local cur prev cmd i\n    \
+         cur=\"${COMP_WORDS[COMP_CWORD]}\"\n    \
+         prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n",
+    );
+    let _ = writeln!(out, "    cmd=\"{}\"", nodes[0].ident("__"));
+    out.push_str("    for ((i = 1; i < COMP_CWORD; i++)); do\n");
+    out.push_str("        case \"${cmd}__${COMP_WORDS[i]//-/_}\" in\n");
+    for node in &nodes[1..] {
+        let ident = node.ident("__");
+        let _ = writeln!(out, "            {}) cmd=\"{}\" ;;", ident, ident);
+    }
+    out.push_str("        esac\n    done\n\n    case \"${cmd}\" in\n");
+    for node in nodes {
+        let _ = writeln!(out, "        {})", node.ident("__"));
+        out.push_str("            case \"${prev}\" in\n");
+        for opt in node.opts() {
+            let Some(values) = &opt.value else {
+                continue;
+            };
+            let completion = match values.is_empty() {
+                true => "compgen -f -- \"${cur}\"".to_string(),
+                false => format!("compgen -W \"{}\" -- \"${{cur}}\"", values.join(" ")),
+            };
+            let _ = writeln!(
+                out,
+                "                {})\n                    \
+                 COMPREPLY=($({}))\n                    return 0\n                    ;;",
+                opt.flags().join("|"),
+                completion
+            );
+        }
+        out.push_str("            esac\n");
+        let mut words: Vec<String> = node.opts().iter().flat_map(Opt::flags).collect();
+        words.extend(
+            node.subcommands()
+                .into_iter()
+                .map(|(name, _)| name.to_string()),
+        );
+        let _ = writeln!(
+            out,
+            "            COMPREPLY=($(compgen -W \"{}\" -- \"${{cur}}\"))",
+            words.join(" ")
+        );
+        match node.positionals() {
+            Some(values) if values.is_empty() => out.push_str(
+                "            [[ \"${cur}\" != -* ]] && COMPREPLY+=($(compgen -f -- \"${cur}\"))\n",
+            ),
+            Some(values) => {
+                let _ = writeln!(
+                    out,
+                    "            COMPREPLY+=($(compgen -W \"{}\" -- \"${{cur}}\"))",
+                    values.join(" ")
+                );
+            }
+            None => {}
+        }
+        out.push_str("            ;;\n");
+    }
+    out.push_str("    esac\n}\n\n");
+    let _ = writeln!(
+        out,
+        "complete -F {} -o bashdefault -o default {}",
+        function, name
+    );
+    out
+}
+
+fn zsh(name: &str, nodes: &[Node]) -> String {
+    let mut out = format!("#compdef {}\n\n", name);
+    for node in nodes {
+        let _ = writeln!(out, "_{}() {{", node.ident("__"));
+        out.push_str("    local curcontext=\"$curcontext\" state line\n");
+        out.push_str("    _arguments -s -C \\\n");
+        for opt in node.opts() {
+            let help = zsh_escape(&opt.help);
+            let value = match &opt.value {
+                None => String::new(),
+                Some(values) if values.is_empty() => ": :_files".to_string(),
+                Some(values) => format!(": :({})", values.join(" ")),
+            };
+            for flag in opt.flags() {
+                let equals = match (&opt.value, flag.starts_with("--")) {
+                    (Some(_), true) => "=",
+                    (Some(_), false) => "+",
+                    (None, _) => "",
+                };
+                let _ = writeln!(out, "        '{}{}[{}]{}' \\", flag, equals, help, value);
+            }
+        }
+        let subcommands = node.subcommands();
+        if !subcommands.is_empty() {
+            let _ = writeln!(out, "        ': :_{}_commands' \\", node.ident("__"));
+            out.push_str("        '*:: :->args' \\\n");
+        } else {
+            match node.positionals() {
+                Some(values) if values.is_empty() => out.push_str("        '*: :_files' \\\n"),
+                Some(values) => {
+                    let _ = writeln!(out, "        ': :({})' \\", values.join(" "));
+                }
+                None => {}
+            }
+        }
+        out.push_str("        && return 0\n");
+        if !subcommands.is_empty() {
+            out.push_str("    case $state in\n        args)\n");
+            out.push_str("            case $words[1] in\n");
+            for (sub, _) in &subcommands {
+                let _ = writeln!(
+                    out,
+                    "                {}) _{}__{} ;;",
+                    sub,
+                    node.ident("__"),
+                    sub.replace('-', "_")
+                );
+            }
+            out.push_str("            esac\n            ;;\n    esac\n");
+        }
+        out.push_str("}\n\n");
+        if !subcommands.is_empty() {
+            let _ = writeln!(out, "_{}_commands() {{", node.ident("__"));
+            out.push_str("    local commands; commands=(\n");
+            for (sub, about) in &subcommands {
+                let _ = writeln!(out, "        '{}:{}'", sub, zsh_escape(about));
+            }
+            out.push_str("    )\n");
+            let _ = writeln!(out, "    _describe -t commands 'command' commands\n}}\n");
+        }
+    }
+    let _ = writeln!(out, "_{} \"$@\"", nodes[0].ident("__"));
+    out
+}
+
+/// `s` inside single quotes and an `_arguments` description
+fn zsh_escape(s: &str) -> String {
+    s.replace('\'', "'\\''")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+}
+
+fn fish(name: &str, nodes: &[Node]) -> String {
+    let mut out = String::new();
+    for node in nodes {
+        // Fish only tells which subcommands have been seen, not their order
+        let condition = match node.path.len() {
+            1 => "__fish_use_subcommand".to_string(),
+            _ => node.path[1..]
+                .iter()
+                .map(|sub| format!("__fish_seen_subcommand_from {}", sub))
+                .collect::<Vec<_>>()
+                .join("; and "),
+        };
+        for opt in node.opts() {
+            let _ = write!(out, "complete -c {} -n \"{}\"", name, condition);
+            if let Some(long) = &opt.long {
+                let _ = write!(out, " -l {}", long);
+            }
+            if let Some(short) = opt.short {
+                let _ = write!(out, " -s {}", short);
+            }
+            match &opt.value {
+                None => {}
+                Some(values) if values.is_empty() => out.push_str(" -r -F"),
+                Some(values) => {
+                    let _ = write!(out, " -r -f -a \"{}\"", values.join(" "));
+                }
+            }
+            let _ = writeln!(out, " -d '{}'", opt.help.replace('\'', "\\'"));
+        }
+        for (sub, about) in node.subcommands() {
+            let _ = writeln!(
+                out,
+                "complete -c {} -n \"{}\" -f -a \"{}\" -d '{}'",
+                name,
+                condition,
+                sub,
+                about.replace('\'', "\\'")
+            );
+        }
+        if let Some(values) = node.positionals().filter(|values| !values.is_empty()) {
+            let _ = writeln!(
+                out,
+                "complete -c {} -n \"{}\" -f -a \"{}\"",
+                name,
+                condition,
+                values.join(" ")
+            );
+        }
+    }
+    out
+}
+
+fn powershell(name: &str, nodes: &[Node]) -> String {
+    let mut out = String::from("using namespace System.Management.Automation\n\n");
+    let _ = writeln!(
+        out,
+        "Register-ArgumentCompleter -Native -CommandName '{}' -ScriptBlock {{",
+        name
+    );
+    out.push_str(
+        "    param($wordToComplete, $commandAst, $cursorPosition)\n\n    \
+         $command = @(\n        \
+         foreach ($element in $commandAst.CommandElements) {\n            \
+         if ($element -isnot [Language.StringConstantExpressionAst] -or\n                \
+         $element.StringConstantType -ne [Language.StringConstantType]::BareWord -or\n                \
+         $element.Value.StartsWith('-') -or\n                \
+         $element.Value -eq $wordToComplete) {\n                \
+         break\n            }\n            \
+         $element.Value\n        }\n    ) -join ';'\n\n    \
+         $completions = @(switch ($command) {\n",
+    );
+    for node in nodes {
+        let _ = writeln!(out, "        '{}' {{", node.path.join(";"));
+        for opt in node.opts() {
+            let help = opt.help.replace('\'', "''");
+            for flag in opt.flags() {
+                let _ = writeln!(
+                    out,
+                    "            [CompletionResult]::new('{}', '{}', \
+                     [CompletionResultType]::ParameterName, '{}')",
+                    flag,
+                    flag.trim_start_matches('-'),
+                    help
+                );
+            }
+        }
+        for (sub, about) in node.subcommands() {
+            let _ = writeln!(
+                out,
+                "            [CompletionResult]::new('{}', '{}', \
+                 [CompletionResultType]::ParameterValue, '{}')",
+                sub,
+                sub,
+                about.replace('\'', "''")
+            );
+        }
+        for value in node.positionals().unwrap_or_default() {
+            let _ = writeln!(
+                out,
+                "            [CompletionResult]::new('{}', '{}', \
+                 [CompletionResultType]::ParameterValue, '{}')",
+                value, value, value
+            );
+        }
+        out.push_str("            break\n        }\n");
+    }
+    out.push_str(
+        "    })\n\n    \
+         $completions.Where{ $_.CompletionText -like \"$wordToComplete*\" } |\n        \
+         Sort-Object -Property ListItemText\n}\n",
+    );
+    out
+}
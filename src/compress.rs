@@ -0,0 +1,211 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    ops::RangeInclusive,
+    str::FromStr,
+    sync::{
+        mpsc::{self, Receiver, Sender, SyncSender},
+        Mutex,
+    },
+    thread,
+};
+
+use clap::ValueEnum;
+use eyre::{eyre, Report, Result};
+
+use crate::{bzip2, manifest, snappy};
+
+/// Codecs the output can be compressed with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Codec {
+    /// gzip, as one member per chunk; concatenated members are a valid gzip
+    /// file that any decompressor reads as one stream
+    Gzip,
+    /// bzip2, as one stream of 900k blocks per chunk; the bzip2 tool reads
+    /// concatenated streams back as one
+    Bzip2,
+    /// Snappy's framing format, as one stream per chunk; the stream
+    /// identifier may repeat, so concatenated streams read back as one
+    Snappy,
+}
+
+impl Codec {
+    /// The levels the codec takes, and the one it uses unless told
+    /// otherwise: gzip's deflate levels and bzip2's block sizes in units of
+    /// 100k, both defaulting as their tools do
+    fn levels(self) -> Option<(RangeInclusive<u32>, u32)> {
+        match self {
+            Self::Gzip => Some((0..=9, 6)),
+            Self::Bzip2 => Some((1..=9, 9)),
+            Self::Snappy => None,
+        }
+    }
+}
+
+/// A codec and, for those that have them, the level to compress at, written
+/// `codec` or `codec:level`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Compression {
+    pub codec: Codec,
+    pub level: Option<u32>,
+}
+
+impl Compression {
+    fn compress(self, chunk: &[u8]) -> Vec<u8> {
+        let level = self.level.or(self.codec.levels().map(|(_, level)| level));
+        let level = level.unwrap_or_default();
+        match self.codec {
+            Codec::Gzip => gzip_member(chunk, level as u8),
+            Codec::Bzip2 => bzip2::stream(chunk, level as usize),
+            Codec::Snappy => snappy::framed(chunk),
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, level) = match s.split_once(':') {
+            Some((name, level)) => (name, Some(level)),
+            None => (s, None),
+        };
+        let codec = Codec::from_str(name, false).map_err(|_| {
+            let names: Vec<_> = Codec::value_variants()
+                .iter()
+                .map(|codec| manifest::value_name(*codec))
+                .collect();
+            eyre!(
+                "Expected one of {}, optionally followed by `:<level>`",
+                names.join(", ")
+            )
+        })?;
+        let Some(level) = level else {
+            return Ok(Self { codec, level: None });
+        };
+        let Some((levels, _)) = codec.levels() else {
+            return Err(eyre!("{} has no levels", name));
+        };
+        match level.parse() {
+            Ok(level) if levels.contains(&level) => Ok(Self {
+                codec,
+                level: Some(level),
+            }),
+            _ => Err(eyre!(
+                "The {} level must be from {} to {}",
+                name,
+                levels.start(),
+                levels.end()
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", manifest::value_name(self.codec))?;
+        match self.level {
+            Some(level) => write!(f, ":{}", level),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Compresses chunks on `threads` threads between the generator and the
+/// writer. Chunks are numbered as they arrive and handed on in that order
+/// once compressed, and each raw buffer is sent back on `spent` as soon as
+/// it has been compressed.
+pub fn compress_chunks(
+    compression: Compression,
+    threads: usize,
+    chunks: Receiver<Vec<u8>>,
+    spent: Sender<Vec<u8>>,
+    compressed: SyncSender<Vec<u8>>,
+) {
+    let chunks = Mutex::new(chunks.into_iter().enumerate());
+    let (done_sender, done) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let (chunks, spent, done_sender) = (&chunks, spent.clone(), done_sender.clone());
+            scope.spawn(move || {
+                let _span = tracing::debug_span!("compressor").entered();
+                loop {
+                    let Some((index, chunk)) = chunks.lock().expect("A compressor panicked").next()
+                    else {
+                        break;
+                    };
+                    let output = compression.compress(&chunk);
+                    // The generator stops taking buffers once it has run out of rows
+                    let _ = spent.send(chunk);
+                    // Only fails once the writer has failed, which it reports
+                    if done_sender.send((index, output)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop((spent, done_sender));
+
+        let mut pending = BTreeMap::new();
+        let mut next = 0;
+        for (index, output) in done {
+            pending.insert(index, output);
+            while let Some(output) = pending.remove(&next) {
+                if compressed.send(output).is_err() {
+                    return;
+                }
+                next += 1;
+            }
+        }
+    });
+}
+
+/// A complete gzip member holding `data`, deflated at `level`
+#[cfg(feature = "compression")]
+fn gzip_member(data: &[u8], level: u8) -> Vec<u8> {
+    // Magic, deflate, no flags, no modification time, no extra flags, and
+    // an unknown operating system
+    let mut member = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+    member.extend(miniz_oxide::deflate::compress_to_vec(data, level));
+    member.extend(crc32(data).to_le_bytes());
+    member.extend((data.len() as u32).to_le_bytes());
+    member
+}
+
+#[cfg(not(feature = "compression"))]
+fn gzip_member(_: &[u8], _: u8) -> Vec<u8> {
+    unreachable!("--compress gzip is refused up front without the compression feature")
+}
+
+/// The CRC-32 gzip uses, with the reflected 0xedb88320 polynomial
+#[cfg(feature = "compression")]
+fn crc32(data: &[u8]) -> u32 {
+    !crc32_update(!0, data)
+}
+
+/// Continues a CRC-32 over `data`, from a register that starts as `!0` and
+/// is inverted once all the data is in
+pub fn crc32_update(crc: u32, data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 == 1 {
+                    0xedb8_8320 ^ (crc >> 1)
+                } else {
+                    crc >> 1
+                };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+    data.iter().fold(crc, |crc, &byte| {
+        TABLE[((crc ^ u32::from(byte)) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
@@ -0,0 +1,244 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write as _,
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use indicatif::FormattedDuration;
+
+use crate::{cli::human_readable, progress::human_count};
+
+/// Time between frames, and between the throughput samples graphed
+const FRAME: Duration = Duration::from_secs(1);
+
+/// Throughput samples kept for the graph, one per frame
+const HISTORY: usize = 60;
+
+const METER_WIDTH: usize = 30;
+
+/// Where the run is, as the progress counters see it when a frame is drawn
+pub struct Snapshot {
+    pub rows: u64,
+    pub total_rows: u64,
+    pub bytes: u64,
+    pub projected: u64,
+    pub elapsed: Duration,
+    /// Time each thread has spent working, in nanoseconds
+    pub busy: Vec<u64>,
+    pub paused: bool,
+}
+
+/// What a dashboard shows besides the counters: the configuration and the
+/// threads whose utilization is measured, and what throughput and
+/// utilization are worked out from between frames
+pub struct View {
+    title: String,
+    config: Vec<(&'static str, String)>,
+    threads: Vec<String>,
+    history: VecDeque<f64>,
+    last: Option<(Instant, u64, Vec<u64>)>,
+}
+
+impl View {
+    pub fn new(title: String, config: Vec<(&'static str, String)>, threads: Vec<String>) -> Self {
+        Self {
+            title,
+            config,
+            threads,
+            history: VecDeque::with_capacity(HISTORY),
+            last: None,
+        }
+    }
+
+    /// The lines of the next frame, always as many for the same view
+    pub fn frame(&mut self, snapshot: Snapshot) -> Vec<String> {
+        let now = Instant::now();
+        let (rate, utilization) = match &self.last {
+            Some((then, rows, busy)) => {
+                let seconds = now.duration_since(*then).as_secs_f64().max(f64::EPSILON);
+                let utilization = snapshot
+                    .busy
+                    .iter()
+                    .zip(busy)
+                    .map(|(now, then)| ((now - then) as f64 / 1e9 / seconds).min(1.0))
+                    .collect();
+                ((snapshot.rows - rows) as f64 / seconds, utilization)
+            }
+            None => (0.0, vec![0.0; snapshot.busy.len()]),
+        };
+        self.last = Some((now, snapshot.rows, snapshot.busy.clone()));
+        if self.history.len() == HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(rate);
+
+        let seconds = snapshot.elapsed.as_secs_f64().max(f64::EPSILON);
+        let average = snapshot.rows as f64 / seconds;
+        let done = snapshot.rows as f64 / snapshot.total_rows.max(1) as f64;
+        let remaining = match rate {
+            rate if rate > 0.0 => {
+                let rows = snapshot.total_rows - snapshot.rows;
+                FormattedDuration(Duration::from_secs_f64(rows as f64 / rate)).to_string()
+            }
+            _ => "--:--:--".to_string(),
+        };
+        let peak = self.history.iter().copied().fold(0.0, f64::max);
+        let graph: String = self
+            .history
+            .iter()
+            .map(|&sample| spark(sample, peak))
+            .collect();
+
+        let mut lines = vec![format!(
+            "\x1b[1m{}\x1b[0m{}",
+            self.title,
+            if snapshot.paused {
+                "  (paused, send SIGUSR1 again to resume)"
+            } else {
+                ""
+            }
+        )];
+        let mut config = String::from(" ");
+        for (name, value) in &self.config {
+            let _ = write!(config, " {} \x1b[36m{}\x1b[0m ", name, value);
+        }
+        lines.push(config);
+        lines.push(String::new());
+        lines.push(format!(
+            "  Progress  {} {:6.2}%",
+            meter(done, METER_WIDTH),
+            done * 100.0
+        ));
+        lines.push(format!(
+            "  Rows      {} of {}",
+            separated(snapshot.rows),
+            separated(snapshot.total_rows)
+        ));
+        lines.push(format!(
+            "  Written   {} of ~{}",
+            human_readable(snapshot.bytes),
+            human_readable(snapshot.projected)
+        ));
+        lines.push(format!(
+            "  Speed     {} rows/s now, {} rows/s on average, {}/s",
+            human_count(rate),
+            human_count(average),
+            human_readable((snapshot.bytes as f64 / seconds) as u64)
+        ));
+        lines.push(format!(
+            "  Time      {} elapsed, {} remaining",
+            FormattedDuration(snapshot.elapsed),
+            remaining
+        ));
+        lines.push(format!(
+            "  History   {:<width$} peak {} rows/s",
+            graph,
+            human_count(peak),
+            width = HISTORY
+        ));
+        lines.push(String::new());
+        for (name, utilization) in self.threads.iter().zip(utilization) {
+            lines.push(format!(
+                "  {:<10}{} {:3.0}%",
+                name,
+                meter(utilization, METER_WIDTH),
+                utilization * 100.0
+            ));
+        }
+        lines
+    }
+}
+
+/// Redraws a frame in place on stderr every second, on a thread of its own,
+/// until dropped, which draws the final frame
+pub struct Dashboard {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Dashboard {
+    pub fn start<F>(mut frame: F) -> Self
+    where
+        F: FnMut() -> Vec<String> + Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopping = stop.clone();
+        let handle = thread::spawn(move || {
+            let mut drawn = 0;
+            // The cursor is hidden while frames are drawn over each other
+            eprint!("\x1b[?25l");
+            loop {
+                let last = stopping.load(Ordering::Relaxed);
+                let lines = frame();
+                let mut out = String::new();
+                if drawn > 0 {
+                    let _ = write!(out, "\x1b[{}A", drawn);
+                }
+                for line in &lines {
+                    let _ = writeln!(out, "\x1b[2K{}", line);
+                }
+                let mut stderr = io::stderr().lock();
+                let _ = stderr.write_all(out.as_bytes());
+                let _ = stderr.flush();
+                drawn = lines.len();
+                if last {
+                    break;
+                }
+                thread::park_timeout(FRAME);
+            }
+            eprint!("\x1b[?25h");
+        });
+        Self {
+            stop,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+/// A bar `width` characters wide, filled to `fraction`
+fn meter(fraction: f64, width: usize) -> String {
+    let filled = ((fraction.clamp(0.0, 1.0) * width as f64).round() as usize).min(width);
+    format!(
+        "\x1b[36m{}\x1b[34m{}\x1b[0m",
+        "█".repeat(filled),
+        "░".repeat(width - filled)
+    )
+}
+
+/// One column of the throughput graph, as high as `sample` is of `peak`
+fn spark(sample: f64, peak: f64) -> char {
+    const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    if peak <= 0.0 {
+        return BLOCKS[0];
+    }
+    BLOCKS[((sample / peak * 7.0).round() as usize).min(7)]
+}
+
+/// `value` with its thousands separated by commas
+fn separated(value: u64) -> String {
+    let digits = value.to_string();
+    let mut out = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(digit);
+    }
+    out
+}
@@ -0,0 +1,152 @@
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+#[cfg(windows)]
+use std::os::windows::fs::OpenOptionsExt;
+use std::{
+    alloc::{self, Layout},
+    fs::{File, OpenOptions},
+    io::Write,
+    ptr, slice,
+};
+
+use eyre::Result;
+
+/// Alignment O_DIRECT and FILE_FLAG_NO_BUFFERING require of buffers,
+/// lengths, and file offsets; a logical block size every common device
+/// divides
+const ALIGNMENT: usize = 4096;
+
+/// The Windows flags for bypassing the system cache and for writes that
+/// reach the device before they complete, as winbase.h defines them
+#[cfg(windows)]
+const FILE_FLAG_NO_BUFFERING: u32 = 0x2000_0000;
+#[cfg(windows)]
+const FILE_FLAG_WRITE_THROUGH: u32 = 0x8000_0000;
+
+/// A file opened with O_DIRECT on Linux, F_NOCACHE on macOS, or
+/// FILE_FLAG_NO_BUFFERING on Windows, bypassing the page cache. Chunks are
+/// staged in an aligned buffer and written in whole blocks; the final
+/// partial block is padded, written, and then truncated away.
+pub struct DirectFile {
+    file: File,
+    staging: *mut u8,
+    capacity: usize,
+    staged: usize,
+    written: u64,
+}
+
+// The staging buffer is owned, and only accessed through `&mut self`
+unsafe impl Send for DirectFile {}
+
+impl DirectFile {
+    /// With `write_through`, each write also waits for the device, as
+    /// O_DSYNC or FILE_FLAG_WRITE_THROUGH have it
+    pub fn create(path: &str, buffer_size: usize, write_through: bool) -> Result<Self> {
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(target_os = "linux")]
+        options.custom_flags(libc::O_DIRECT | if write_through { libc::O_DSYNC } else { 0 });
+        #[cfg(target_os = "macos")]
+        options.custom_flags(if write_through { libc::O_DSYNC } else { 0 });
+        #[cfg(windows)]
+        options.custom_flags(
+            FILE_FLAG_NO_BUFFERING
+                | if write_through {
+                    FILE_FLAG_WRITE_THROUGH
+                } else {
+                    0
+                },
+        );
+        let file = options.open(path)?;
+        // macOS has no O_DIRECT, and bypasses the cache per descriptor instead
+        #[cfg(target_os = "macos")]
+        {
+            use std::os::unix::io::AsRawFd;
+
+            // SAFETY: a plain system call on an open file descriptor
+            if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_NOCACHE, 1) } == -1 {
+                return Err(std::io::Error::last_os_error().into());
+            }
+        }
+        let capacity = buffer_size.next_multiple_of(ALIGNMENT);
+        // SAFETY: the layout has a non-zero size
+        let staging = unsafe { alloc::alloc(Self::layout(capacity)) };
+        if staging.is_null() {
+            alloc::handle_alloc_error(Self::layout(capacity));
+        }
+        Ok(Self {
+            file,
+            staging,
+            capacity,
+            staged: 0,
+            written: 0,
+        })
+    }
+
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    fn layout(capacity: usize) -> Layout {
+        Layout::from_size_align(capacity, ALIGNMENT).expect("The staging layout is valid")
+    }
+
+    pub fn write_all(&mut self, mut bytes: &[u8]) -> Result<()> {
+        while !bytes.is_empty() {
+            let take = bytes.len().min(self.capacity - self.staged);
+            // SAFETY: `take` bytes fit in the staging buffer after `staged`
+            unsafe {
+                ptr::copy_nonoverlapping(bytes.as_ptr(), self.staging.add(self.staged), take);
+            }
+            self.staged += take;
+            bytes = &bytes[take..];
+            if self.staged == self.capacity {
+                self.write_staged(self.capacity)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes every whole block staged so far, keeping the partial tail
+    pub fn flush(&mut self) -> Result<()> {
+        let tail = self.staged % ALIGNMENT;
+        let whole = self.staged - tail;
+        if whole > 0 {
+            self.write_staged(whole)?;
+            // SAFETY: both ranges lie within the staging buffer
+            unsafe { ptr::copy(self.staging.add(whole), self.staging, tail) };
+            self.staged = tail;
+        }
+        Ok(())
+    }
+
+    /// Pads and writes whatever is staged, then trims the padding off
+    pub fn finish(mut self) -> Result<()> {
+        let len = self.written + self.staged as u64;
+        if self.staged > 0 {
+            let padded = self.staged.next_multiple_of(ALIGNMENT);
+            // SAFETY: `padded` is within the staging buffer's capacity
+            unsafe { ptr::write_bytes(self.staging.add(self.staged), 0, padded - self.staged) };
+            self.write_staged(padded)?;
+        }
+        self.file.set_len(len)?;
+        Ok(())
+    }
+
+    /// Writes the first `len` staged bytes, a multiple of the alignment
+    fn write_staged(&mut self, len: usize) -> Result<()> {
+        // SAFETY: the staging buffer holds at least `len` initialized bytes
+        let staged = unsafe { slice::from_raw_parts(self.staging, len) };
+        self.file.write_all(staged)?;
+        self.written += len as u64;
+        self.staged = 0;
+        Ok(())
+    }
+}
+
+impl Drop for DirectFile {
+    fn drop(&mut self) {
+        // SAFETY: allocated in `create` with this layout
+        unsafe { alloc::dealloc(self.staging, Self::layout(self.capacity)) };
+    }
+}
@@ -0,0 +1,45 @@
+#[cfg(feature = "cli")]
+use clap::ValueEnum;
+use rand::Rng;
+
+/// The family independent measurements are drawn from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum Distribution {
+    /// Uniform over the whole temperature range, ignoring station means
+    Uniform,
+    /// Normal around the station's mean
+    Normal,
+    /// An even mix of two normals, two standard deviations either side of the
+    /// station's mean
+    Bimodal,
+    /// The station's mean plus an exponential tail towards hotter values
+    Exponential,
+}
+
+impl Distribution {
+    /// Samples a temperature in degrees, from a station with the given mean
+    /// and standard deviation
+    pub fn sample<R: Rng + ?Sized>(self, mean: f64, stddev: f64, rng: &mut R) -> f64 {
+        match self {
+            Self::Uniform => rng.gen_range(-99.9..=99.9),
+            Self::Normal => mean + stddev * standard_normal(rng),
+            Self::Bimodal => {
+                let peak = if rng.gen_bool(0.5) {
+                    mean - 2.0 * stddev
+                } else {
+                    mean + 2.0 * stddev
+                };
+                peak + stddev / 2.0 * standard_normal(rng)
+            }
+            Self::Exponential => mean - stddev * (1.0 - rng.gen::<f64>()).ln(),
+        }
+    }
+}
+
+/// Box-Muller transform, since the crate doesn't depend on `rand_distr`
+pub fn standard_normal<R: Rng + ?Sized>(rng: &mut R) -> f64 {
+    let u1: f64 = 1.0 - rng.gen::<f64>();
+    let u2: f64 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+}
@@ -0,0 +1,376 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, thread,
+};
+
+use eyre::{eyre, Result};
+
+use crate::{
+    census::{self, Stats},
+    verify,
+};
+
+/// Stations listed by name when only one file holds them; past this only
+/// the count is given
+const MAX_LISTED: usize = 10;
+
+/// Measurements in tenths counted in place; the rare ones beyond are kept
+/// in a map
+const HISTOGRAM_RANGE: i64 = 999;
+
+/// Differences more likely than this by chance alone aren't reported as
+/// drift
+const SIGNIFICANCE: f64 = 0.05;
+
+/// What the rows of one measurements file add up to
+struct Profile {
+    rows: u64,
+    stations: HashMap<Vec<u8>, Stats>,
+    /// Rows with each measurement, in tenths
+    histogram: BTreeMap<i64, u64>,
+    /// Sum of the squared measurements, in tenths
+    squares: f64,
+}
+
+impl Profile {
+    /// Reads `path` in parallel
+    fn read(path: &str) -> Result<Self> {
+        let size = fs::metadata(path)
+            .map_err(|e| eyre!("Could not open file {}: {}", path, e))?
+            .len();
+        let parts = thread::scope(|scope| {
+            let handles: Vec<_> = verify::ranges(size)
+                .into_iter()
+                .map(|range| scope.spawn(move || Part::read(path, range)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("A reader panicked"))
+                .collect::<Result<Vec<_>>>()
+        })?;
+        let mut profile = Self {
+            rows: 0,
+            stations: HashMap::new(),
+            histogram: BTreeMap::new(),
+            squares: 0.0,
+        };
+        for part in parts {
+            for (station, stats) in part.stations {
+                match profile.stations.get_mut(&station) {
+                    Some(total) => total.merge(stats),
+                    None => {
+                        profile.stations.insert(station, stats);
+                    }
+                }
+            }
+            let counted = part.counts.iter().enumerate().filter(|(_, &rows)| rows > 0);
+            for (index, &rows) in counted {
+                *profile
+                    .histogram
+                    .entry(index as i64 - HISTOGRAM_RANGE)
+                    .or_default() += rows;
+            }
+            for (measurement, rows) in part.beyond {
+                *profile.histogram.entry(measurement).or_default() += rows;
+            }
+            profile.squares += part.squares;
+        }
+        profile.rows = profile.histogram.values().sum();
+        Ok(profile)
+    }
+
+    /// Mean and standard deviation of the measurements, in degrees
+    fn moments(&self) -> (f64, f64) {
+        let rows = self.rows.max(1) as f64;
+        let sum: i64 = self.stations.values().map(|stats| stats.sum).sum();
+        let mean = sum as f64 / rows;
+        let variance = (self.squares / rows - mean * mean).max(0.0);
+        (mean / 10.0, variance.sqrt() / 10.0)
+    }
+
+    fn range(&self) -> (f64, f64) {
+        let min = self.histogram.keys().next().copied().unwrap_or_default();
+        let max = self
+            .histogram
+            .keys()
+            .next_back()
+            .copied()
+            .unwrap_or_default();
+        (min as f64 / 10.0, max as f64 / 10.0)
+    }
+}
+
+/// What one range of a file adds up to
+struct Part {
+    stations: HashMap<Vec<u8>, Stats>,
+    /// Rows with each measurement from `-HISTOGRAM_RANGE` up
+    counts: Vec<u64>,
+    beyond: HashMap<i64, u64>,
+    squares: f64,
+}
+
+impl Part {
+    fn read(path: &str, range: std::ops::Range<u64>) -> Result<Self> {
+        let mut part = Self {
+            stations: HashMap::new(),
+            counts: vec![0; 2 * HISTOGRAM_RANGE as usize + 1],
+            beyond: HashMap::new(),
+            squares: 0.0,
+        };
+        let mut malformed = None;
+        verify::read_lines(path, range, |offset, line| {
+            let line = line.strip_suffix(b"\n").unwrap_or(line);
+            let parsed = line
+                .iter()
+                .rposition(|&b| b == b';')
+                .and_then(|end| Some((&line[..end], census::tenths(&line[end + 1..])?)));
+            let Some((station, measurement)) = parsed else {
+                malformed.get_or_insert(offset);
+                return;
+            };
+            match part.stations.get_mut(station) {
+                Some(stats) => stats.add(measurement),
+                None => {
+                    part.stations
+                        .insert(station.to_vec(), Stats::new(measurement));
+                }
+            }
+            match part
+                .counts
+                .get_mut((measurement + HISTOGRAM_RANGE) as usize)
+            {
+                Some(rows) if measurement >= -HISTOGRAM_RANGE => *rows += 1,
+                _ => *part.beyond.entry(measurement).or_default() += 1,
+            }
+            part.squares += (measurement * measurement) as f64;
+        })?;
+        match malformed {
+            Some(offset) => Err(eyre!(
+                "{} holds a line that isn't a measurement at byte {}",
+                path,
+                offset
+            )),
+            None => Ok(part),
+        }
+    }
+}
+
+/// Reports how the measurements in `a` and `b` differ: their row counts,
+/// the stations only one holds, how the rows are shared between stations,
+/// the spread of temperatures, and each station's mean. Fails if the shares
+/// or the temperatures differ by more than chance explains, so a
+/// regenerated dataset can be confirmed to match the original; a station
+/// with few rows may be missing from either by chance alone.
+pub fn compare(a: &str, b: &str) -> Result<()> {
+    let _span = tracing::debug_span!("compare_data").entered();
+    let (first, second) = (Profile::read(a)?, Profile::read(b)?);
+    let width = a.len().max(b.len()).max(12);
+    let row = |label: &str, first: String, second: String| {
+        println!(
+            "{:<22} {:>width$} {:>width$}",
+            label,
+            first,
+            second,
+            width = width
+        );
+    };
+    row("", a.to_string(), b.to_string());
+    row("Rows", first.rows.to_string(), second.rows.to_string());
+    row(
+        "Distinct stations",
+        first.stations.len().to_string(),
+        second.stations.len().to_string(),
+    );
+    let ((mean_a, stddev_a), (mean_b, stddev_b)) = (first.moments(), second.moments());
+    row("Mean", format!("{:.3}", mean_a), format!("{:.3}", mean_b));
+    row(
+        "Standard deviation",
+        format!("{:.3}", stddev_a),
+        format!("{:.3}", stddev_b),
+    );
+    let ((min_a, max_a), (min_b, max_b)) = (first.range(), second.range());
+    row(
+        "Range",
+        format!("{:.1} to {:.1}", min_a, max_a),
+        format!("{:.1} to {:.1}", min_b, max_b),
+    );
+    println!();
+
+    let mut differences = Vec::new();
+    for (path, only) in [(a, only_in(&first, &second)), (b, only_in(&second, &first))] {
+        if only.is_empty() {
+            continue;
+        }
+        let mut listed: Vec<_> = only.iter().take(MAX_LISTED).cloned().collect();
+        if only.len() > MAX_LISTED {
+            listed.push(format!("and {} more", only.len() - MAX_LISTED));
+        }
+        println!(
+            "Only in {}: {} station{}: {}",
+            path,
+            only.len(),
+            if only.len() == 1 { "" } else { "s" },
+            listed.join(", ")
+        );
+    }
+
+    let (distance, chi_square, freedom) = share_difference(&first, &second);
+    let p = chi_square_p(chi_square, freedom);
+    println!(
+        "Station shares: total variation distance {:.4}, chi-square {:.1} on {} degrees of \
+         freedom, p = {:.3}",
+        distance, chi_square, freedom, p
+    );
+    if p < SIGNIFICANCE {
+        differences.push("station shares");
+    }
+
+    let (statistic, critical) = kolmogorov_smirnov(&first, &second);
+    println!(
+        "Temperatures: Kolmogorov-Smirnov D = {:.5}, {:.5} at the {}% level",
+        statistic,
+        critical,
+        SIGNIFICANCE * 100.0
+    );
+    if statistic > critical {
+        differences.push("temperatures");
+    }
+
+    if let Some((station, average, largest)) = mean_difference(&first, &second) {
+        println!(
+            "Station means: {:.3} apart on average, at most {:.3} for {}",
+            average, largest, station
+        );
+    }
+
+    if !differences.is_empty() {
+        return Err(eyre!(
+            "The datasets differ in their {}",
+            differences.join(", ")
+        ));
+    }
+    println!(
+        "No difference beyond chance at the {}% level",
+        SIGNIFICANCE * 100.0
+    );
+    Ok(())
+}
+
+/// The stations in `profile` that `other` lacks, sorted
+fn only_in(profile: &Profile, other: &Profile) -> Vec<String> {
+    let mut only: Vec<_> = profile
+        .stations
+        .keys()
+        .filter(|station| !other.stations.contains_key(*station))
+        .map(|station| String::from_utf8_lossy(station).into_owned())
+        .collect();
+    only.sort_unstable();
+    only
+}
+
+/// How differently the rows are shared between stations: half the summed
+/// differences in share, and the chi-square statistic for both files
+/// drawing from the same shares with its degrees of freedom
+fn share_difference(first: &Profile, second: &Profile) -> (f64, f64, usize) {
+    let (rows_a, rows_b) = (first.rows.max(1) as f64, second.rows.max(1) as f64);
+    let total = rows_a + rows_b;
+    let mut stations: Vec<_> = first.stations.keys().collect();
+    stations.extend(
+        second
+            .stations
+            .keys()
+            .filter(|station| !first.stations.contains_key(*station)),
+    );
+    let (mut distance, mut chi_square) = (0.0, 0.0);
+    for station in &stations {
+        let count = |profile: &Profile| profile.stations.get(*station).map_or(0, |s| s.count);
+        let (a, b) = (count(first) as f64, count(second) as f64);
+        distance += (a / rows_a - b / rows_b).abs() / 2.0;
+        let (expected_a, expected_b) = ((a + b) * rows_a / total, (a + b) * rows_b / total);
+        chi_square += (a - expected_a).powi(2) / expected_a + (b - expected_b).powi(2) / expected_b;
+    }
+    (distance, chi_square, stations.len().saturating_sub(1))
+}
+
+/// The chance of a chi-square statistic at least `x` with `freedom`
+/// degrees of freedom, from the Wilson-Hilferty normal approximation
+fn chi_square_p(x: f64, freedom: usize) -> f64 {
+    if freedom == 0 {
+        return 1.0;
+    }
+    let k = freedom as f64;
+    let z = ((x / k).cbrt() - (1.0 - 2.0 / (9.0 * k))) / (2.0 / (9.0 * k)).sqrt();
+    erfc(z / std::f64::consts::SQRT_2) / 2.0
+}
+
+/// The complementary error function, to within 1.2e-7
+fn erfc(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.5 * x.abs());
+    let polynomial = [
+        -1.26551223,
+        1.00002368,
+        0.37409196,
+        0.09678418,
+        -0.18628806,
+        0.27886807,
+        -1.13520398,
+        1.48851587,
+        -0.82215223,
+        0.17087277,
+    ]
+    .iter()
+    .rev()
+    .fold(0.0, |sum, coefficient| sum * t + coefficient);
+    let value = t * (-x * x + polynomial).exp();
+    match x >= 0.0 {
+        true => value,
+        false => 2.0 - value,
+    }
+}
+
+/// The largest gap between the two files' cumulative distributions of
+/// temperatures, and the gap that two samples of their sizes from the same
+/// distribution stay under but for the significance level
+fn kolmogorov_smirnov(first: &Profile, second: &Profile) -> (f64, f64) {
+    let (rows_a, rows_b) = (first.rows.max(1) as f64, second.rows.max(1) as f64);
+    let mut measurements: Vec<_> = first.histogram.keys().collect();
+    measurements.extend(second.histogram.keys());
+    measurements.sort_unstable();
+    measurements.dedup();
+    let (mut cumulative_a, mut cumulative_b, mut statistic) = (0.0, 0.0, 0.0_f64);
+    for measurement in measurements {
+        cumulative_a += first.histogram.get(measurement).copied().unwrap_or(0) as f64 / rows_a;
+        cumulative_b += second.histogram.get(measurement).copied().unwrap_or(0) as f64 / rows_b;
+        statistic = statistic.max((cumulative_a - cumulative_b).abs());
+    }
+    // c(0.05) = sqrt(-ln(0.05 / 2) / 2)
+    let coefficient = (-(SIGNIFICANCE / 2.0).ln() / 2.0).sqrt();
+    (
+        statistic,
+        coefficient * ((rows_a + rows_b) / (rows_a * rows_b)).sqrt(),
+    )
+}
+
+/// The average and largest difference between the mean temperatures of the
+/// stations in both files, with the station differing most
+fn mean_difference(first: &Profile, second: &Profile) -> Option<(String, f64, f64)> {
+    let (mut total, mut shared, mut largest) = (0.0, 0, None::<(&[u8], f64)>);
+    for (station, a) in &first.stations {
+        let Some(b) = second.stations.get(station) else {
+            continue;
+        };
+        let difference =
+            (a.sum as f64 / a.count as f64 - b.sum as f64 / b.count as f64).abs() / 10.0;
+        total += difference;
+        shared += 1;
+        if largest.is_none_or(|(_, most)| difference > most) {
+            largest = Some((station, difference));
+        }
+    }
+    let (station, largest) = largest?;
+    Some((
+        String::from_utf8_lossy(station).into_owned(),
+        total / shared as f64,
+        largest,
+    ))
+}
@@ -0,0 +1,168 @@
+use std::{
+    cmp::Reverse,
+    collections::{hash_map::DefaultHasher, HashMap},
+    fs::{self, File},
+    hash::{Hash, Hasher},
+    io::{BufRead, BufReader, BufWriter, Read, Write},
+};
+
+use eyre::{eyre, Result, WrapErr};
+use indicatif::ProgressBar;
+
+use crate::progress;
+
+/// Target size of each spill file, which bounds the memory needed to count
+/// its lines
+const BUCKET_SIZE: u64 = 256 * 1024 * 1024;
+
+/// What counting the lines of a file finds
+#[derive(Default)]
+struct Tally {
+    rows: u64,
+    distinct: u64,
+    /// Distinct lines seen more than once
+    repeated: u64,
+    /// The most repeated lines, most first
+    top: Vec<(u64, Vec<u8>)>,
+}
+
+impl Tally {
+    /// Adds the counts of one bucket, whose lines no other bucket holds
+    fn add(&mut self, counts: HashMap<Vec<u8>, u64>, top: usize) {
+        self.distinct += counts.len() as u64;
+        for (line, count) in counts {
+            self.rows += count;
+            if count < 2 {
+                continue;
+            }
+            self.repeated += 1;
+            // Ties go to the line sorting first, so the listing doesn't
+            // depend on the order of the buckets
+            let listed = match self.top.last() {
+                _ if self.top.len() < top => true,
+                Some((least, last)) => (count, Reverse(&line)) > (*least, Reverse(last)),
+                None => false,
+            };
+            if listed {
+                self.top.push((count, line));
+                self.top
+                    .sort_unstable_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+                self.top.truncate(top);
+            }
+        }
+    }
+}
+
+/// Reports how many rows of `path` exactly duplicate an earlier one and the
+/// `top` most repeated lines. Lines are counted a bucket at a time: when
+/// the file is bigger than a bucket, lines are first scattered by hash to
+/// spill files beside it, so every copy of a line lands in the same one.
+/// With a `memory_limit`, buckets are kept small enough to count within it.
+pub fn dups(path: &str, top: usize, memory_limit: Option<usize>, quiet: bool) -> Result<()> {
+    let _span = tracing::debug_span!("dups").entered();
+    let size = fs::metadata(path)
+        .map_err(|e| eyre!("Could not open file {}: {}", path, e))?
+        .len();
+    // A map entry takes about as much again as its line
+    let bucket_size = memory_limit.map_or(BUCKET_SIZE, |limit| (limit as u64 / 3).min(BUCKET_SIZE));
+    let bucket_count = (size / bucket_size + 1) as usize;
+    let bar = if quiet {
+        ProgressBar::hidden()
+    } else {
+        let passes = if bucket_count == 1 { 1 } else { 2 };
+        ProgressBar::new(size * passes).with_style(progress::style())
+    };
+    bar.set_message("Counting lines");
+
+    let mut tally = Tally::default();
+    if bucket_count == 1 {
+        tally.add(count(File::open(path)?, &bar)?, top);
+    } else {
+        let paths: Vec<String> = (0..bucket_count)
+            .map(|i| format!("{}.dups.{}", path, i))
+            .collect();
+        let counted = scatter(path, &paths, &bar).and_then(|()| {
+            paths.iter().try_for_each(|bucket| {
+                tally.add(count(File::open(bucket)?, &bar)?, top);
+                Ok(fs::remove_file(bucket)?)
+            })
+        });
+        if counted.is_err() {
+            for bucket in &paths {
+                let _ = fs::remove_file(bucket);
+            }
+        }
+        counted?;
+    }
+    bar.finish_and_clear();
+
+    let duplicates = tally.rows - tally.distinct;
+    println!("Rows: {}", tally.rows);
+    println!("Distinct lines: {}", tally.distinct);
+    println!(
+        "Duplicate rows: {} ({:.3}% of rows), copies of {} distinct lines",
+        duplicates,
+        duplicates as f64 * 100.0 / tally.rows.max(1) as f64,
+        tally.repeated
+    );
+    if !tally.top.is_empty() {
+        println!("Most repeated:");
+        let width = tally.top[0].0.to_string().len();
+        for (count, line) in &tally.top {
+            println!(
+                "{:>width$}  {}",
+                count,
+                String::from_utf8_lossy(line),
+                width = width
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Writes each line of `path` to the one of `paths` its hash picks
+fn scatter(path: &str, paths: &[String], bar: &ProgressBar) -> Result<()> {
+    let _span = tracing::debug_span!("dups_scatter", buckets = paths.len()).entered();
+    let mut buckets = paths
+        .iter()
+        .map(|path| {
+            File::create(path)
+                .map(BufWriter::new)
+                .wrap_err_with(|| format!("Could not create spill file {}", path))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut reader = BufReader::with_capacity(1 << 20, File::open(path)?);
+    let mut line = Vec::new();
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        bar.inc(line.len() as u64);
+        if !line.ends_with(b"\n") {
+            line.push(b'\n');
+        }
+        let mut hasher = DefaultHasher::new();
+        line.hash(&mut hasher);
+        buckets[(hasher.finish() % paths.len() as u64) as usize].write_all(&line)?;
+        line.clear();
+    }
+    for mut bucket in buckets {
+        bucket.flush()?;
+    }
+    Ok(())
+}
+
+/// How many times each line of `file` appears
+fn count(file: impl Read, bar: &ProgressBar) -> Result<HashMap<Vec<u8>, u64>> {
+    let mut reader = BufReader::with_capacity(1 << 20, file);
+    let (mut counts, mut line) = (HashMap::<Vec<u8>, u64>::new(), Vec::new());
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        bar.inc(line.len() as u64);
+        let text = line.strip_suffix(b"\n").unwrap_or(&line);
+        match counts.get_mut(text) {
+            Some(count) => *count += 1,
+            None => {
+                counts.insert(text.to_vec(), 1);
+            }
+        }
+        line.clear();
+    }
+    Ok(counts)
+}
@@ -0,0 +1,182 @@
+use std::ptr;
+
+use crate::{station::Stations, MAX_TEMP, MIN_TEMP};
+
+/// Width of the widest stores used to copy station prefixes
+const LANE: usize = 32;
+
+/// Writes `<station>;<measurement>\n` lines. Measurements are looked up in a
+/// table of preformatted strings, since formatting dominates the cost of
+/// generating a line. On x86_64, lines are assembled with whole 32 byte AVX2
+/// or 16 byte SSE2 stores of the prefix, whichever the CPU running it has,
+/// and one 8 byte store of the measurement, writing past the line into the
+/// buffer's spare capacity instead of copying exact lengths.
+pub struct LineEncoder {
+    /// Every station's `id;` prefix, each starting on a lane boundary and
+    /// zero padded to a whole number of lanes, so wide loads stay in bounds
+    prefixes: Vec<u8>,
+    /// Offset into `prefixes` and length of each station's prefix
+    spans: Vec<(usize, usize)>,
+    /// `-99.9\n` style strings packed little endian into words, indexed by
+    /// `measurement - MIN_TEMP`, followed by `-0.0\n`
+    temperatures: Vec<(u64, usize)>,
+    kernel: Kernel,
+}
+
+/// The ways of copying a line, fastest first, chosen once at runtime so a
+/// single binary runs well on every x86_64 machine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kernel {
+    Avx2,
+    Sse2,
+    /// Exact length copies, on other architectures
+    #[cfg_attr(target_arch = "x86_64", allow(dead_code))]
+    Scalar,
+}
+
+impl LineEncoder {
+    pub fn new(stations: &Stations) -> Self {
+        let mut prefixes = Vec::new();
+        let mut spans = Vec::with_capacity(stations.len());
+        for name in stations.names() {
+            spans.push((prefixes.len(), name.len() + 1));
+            prefixes.extend_from_slice(name.as_bytes());
+            prefixes.push(b';');
+            prefixes.resize(prefixes.len().next_multiple_of(LANE), 0);
+        }
+        let temperatures = (MIN_TEMP..=MAX_TEMP)
+            .map(|measurement| {
+                let magnitude = measurement.unsigned_abs();
+                let sign = if measurement < 0 { "-" } else { "" };
+                format!("{}{}.{}\n", sign, magnitude / 10, magnitude % 10)
+            })
+            .chain(["-0.0\n".to_string()])
+            .map(|text| {
+                let mut word = [0; 8];
+                word[..text.len()].copy_from_slice(text.as_bytes());
+                (u64::from_le_bytes(word), text.len())
+            })
+            .collect();
+        Self {
+            prefixes,
+            spans,
+            temperatures,
+            kernel: detect_kernel(),
+        }
+    }
+
+    pub fn push_line(&self, out_buf: &mut Vec<u8>, station: usize, measurement: i32) {
+        self.push(out_buf, station, (measurement - MIN_TEMP) as usize);
+    }
+
+    pub fn push_negative_zero_line(&self, out_buf: &mut Vec<u8>, station: usize) {
+        self.push(out_buf, station, self.temperatures.len() - 1);
+    }
+
+    #[inline]
+    fn push(&self, out_buf: &mut Vec<u8>, station: usize, temperature: usize) {
+        let (offset, len) = self.spans[station];
+        let (word, word_len) = self.temperatures[temperature];
+        match self.kernel {
+            // SAFETY: AVX2 support was detected at runtime
+            Kernel::Avx2 => unsafe { self.push_avx2(out_buf, offset, len, word, word_len) },
+            // SAFETY: every x86_64 CPU has SSE2
+            Kernel::Sse2 => unsafe { self.push_sse2(out_buf, offset, len, word, word_len) },
+            Kernel::Scalar => {
+                out_buf.extend_from_slice(&self.prefixes[offset..offset + len]);
+                out_buf.extend_from_slice(&word.to_le_bytes()[..word_len]);
+            }
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2")]
+    unsafe fn push_avx2(
+        &self,
+        out_buf: &mut Vec<u8>,
+        offset: usize,
+        len: usize,
+        word: u64,
+        word_len: usize,
+    ) {
+        use std::arch::x86_64::{__m256i, _mm256_loadu_si256, _mm256_storeu_si256};
+
+        let padded = len.next_multiple_of(LANE);
+        out_buf.reserve(padded + 8);
+        let start = out_buf.len();
+        let dst = out_buf.as_mut_ptr().add(start);
+        let src = self.prefixes.as_ptr().add(offset);
+        // The prefix's padding keeps these loads in bounds, and the reserve
+        // above covers the stores running past the line
+        for lane in (0..padded).step_by(LANE) {
+            let bytes = _mm256_loadu_si256(src.add(lane).cast::<__m256i>());
+            _mm256_storeu_si256(dst.add(lane).cast::<__m256i>(), bytes);
+        }
+        ptr::write_unaligned(dst.add(len).cast::<u64>(), word.to_le());
+        out_buf.set_len(start + len + word_len);
+    }
+
+    /// As [`Self::push_avx2`], with 16 byte stores
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "sse2")]
+    unsafe fn push_sse2(
+        &self,
+        out_buf: &mut Vec<u8>,
+        offset: usize,
+        len: usize,
+        word: u64,
+        word_len: usize,
+    ) {
+        use std::arch::x86_64::{__m128i, _mm_loadu_si128, _mm_storeu_si128};
+
+        const HALF_LANE: usize = LANE / 2;
+        let padded = len.next_multiple_of(HALF_LANE);
+        out_buf.reserve(padded + 8);
+        let start = out_buf.len();
+        let dst = out_buf.as_mut_ptr().add(start);
+        let src = self.prefixes.as_ptr().add(offset);
+        // Padding to a whole lane also pads to a whole half lane
+        for lane in (0..padded).step_by(HALF_LANE) {
+            let bytes = _mm_loadu_si128(src.add(lane).cast::<__m128i>());
+            _mm_storeu_si128(dst.add(lane).cast::<__m128i>(), bytes);
+        }
+        ptr::write_unaligned(dst.add(len).cast::<u64>(), word.to_le());
+        out_buf.set_len(start + len + word_len);
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    unsafe fn push_avx2(
+        &self,
+        _out_buf: &mut Vec<u8>,
+        _offset: usize,
+        _len: usize,
+        _word: u64,
+        _word_len: usize,
+    ) {
+        unreachable!("AVX2 is only detected on x86_64")
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    unsafe fn push_sse2(
+        &self,
+        _out_buf: &mut Vec<u8>,
+        _offset: usize,
+        _len: usize,
+        _word: u64,
+        _word_len: usize,
+    ) {
+        unreachable!("SSE2 is only chosen on x86_64")
+    }
+}
+
+fn detect_kernel() -> Kernel {
+    #[cfg(target_arch = "x86_64")]
+    let kernel = match is_x86_feature_detected!("avx2") {
+        true => Kernel::Avx2,
+        false => Kernel::Sse2,
+    };
+    #[cfg(not(target_arch = "x86_64"))]
+    let kernel = Kernel::Scalar;
+    tracing::debug!(?kernel, "Chose the line encoder");
+    kernel
+}
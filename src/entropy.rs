@@ -0,0 +1,39 @@
+use clap::ValueEnum;
+
+/// How compressible the generated output is, from long runs of repeated
+/// stations and values over a small keyset down to independent rows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Entropy {
+    Low,
+    Medium,
+    High,
+}
+
+impl Entropy {
+    /// How many stations rows are drawn from, when restricted
+    pub fn station_pool(self) -> Option<usize> {
+        match self {
+            Self::Low => Some(100),
+            Self::Medium => Some(1_000),
+            Self::High => None,
+        }
+    }
+
+    /// Chance that a row repeats the previous row's station
+    pub fn station_repeat(self) -> f64 {
+        match self {
+            Self::Low => 0.9,
+            Self::Medium => 0.5,
+            Self::High => 0.0,
+        }
+    }
+
+    /// Chance that a row repeats the previous row's measurement
+    pub fn value_repeat(self) -> f64 {
+        match self {
+            Self::Low => 0.9,
+            Self::Medium => 0.5,
+            Self::High => 0.0,
+        }
+    }
+}
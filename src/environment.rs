@@ -0,0 +1,112 @@
+use std::{fs, path::Path, thread};
+
+use crate::summary::json_string;
+
+/// What generated a dataset and where, as a JSON object for its manifest:
+/// the host, its OS, CPU, and cores, the compiler and build, and the
+/// filesystem holding `output`. Whatever can't be found out is `null`.
+pub fn json(output: &str) -> String {
+    let string = |value: Option<String>| value.as_deref().map_or("null".to_string(), json_string);
+    let (hostname, kernel) = uname();
+    let filesystem = match mount(output) {
+        Some(mount) => format!(
+            "{{ \"type\": {}, \"mount_point\": {}, \"source\": {} }}",
+            json_string(&mount.fs_type),
+            json_string(&mount.point),
+            json_string(&mount.source)
+        ),
+        None => "null".to_string(),
+    };
+    format!(
+        "{{ \"hostname\": {}, \"os\": {}, \"kernel\": {}, \"arch\": {}, \"cpu\": {}, \
+         \"cores\": {}, \"rustc\": {}, \"profile\": {}, \"filesystem\": {} }}",
+        string(hostname),
+        json_string(std::env::consts::OS),
+        string(kernel),
+        json_string(std::env::consts::ARCH),
+        string(cpu_model()),
+        thread::available_parallelism().map_or(1, |n| n.get()),
+        string(
+            Some(env!("BRG_RUSTC_VERSION"))
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+        ),
+        json_string(if cfg!(debug_assertions) {
+            "debug"
+        } else {
+            "release"
+        }),
+        filesystem
+    )
+}
+
+/// The host's name and kernel release
+#[cfg(unix)]
+fn uname() -> (Option<String>, Option<String>) {
+    use std::ffi::CStr;
+
+    // SAFETY: `names` is only read once uname has filled it, with NUL
+    // terminated fields
+    unsafe {
+        let mut names: libc::utsname = std::mem::zeroed();
+        if libc::uname(&mut names) != 0 {
+            return (None, None);
+        }
+        let field = |field: &[libc::c_char]| {
+            Some(
+                CStr::from_ptr(field.as_ptr())
+                    .to_string_lossy()
+                    .into_owned(),
+            )
+        };
+        (field(&names.nodename), field(&names.release))
+    }
+}
+
+#[cfg(not(unix))]
+fn uname() -> (Option<String>, Option<String>) {
+    (std::env::var("COMPUTERNAME").ok(), None)
+}
+
+/// The CPU's model name, as Linux reports it
+fn cpu_model() -> Option<String> {
+    let cpuinfo = fs::read_to_string("/proc/cpuinfo").ok()?;
+    cpuinfo.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        matches!(key.trim(), "model name" | "Hardware" | "cpu model")
+            .then(|| value.trim().to_string())
+    })
+}
+
+/// A mounted filesystem
+struct Mount {
+    point: String,
+    fs_type: String,
+    source: String,
+}
+
+/// The filesystem holding `path`, the mount with the longest mount point
+/// its directory is under, as Linux lists them
+fn mount(path: &str) -> Option<Mount> {
+    let dir = match Path::new(path).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let dir = fs::canonicalize(dir).ok()?;
+    let mountinfo = fs::read_to_string("/proc/self/mountinfo").ok()?;
+    mountinfo
+        .lines()
+        .filter_map(|line| {
+            // `<id> <parent> <dev> <root> <point> <options> [optional...] - <type> <source> ...`
+            let (mounted, described) = line.split_once(" - ")?;
+            let point = mounted.split(' ').nth(4)?.replace("\\040", " ");
+            let mut described = described.split(' ');
+            let (fs_type, source) = (described.next()?, described.next()?);
+            dir.starts_with(&point).then(|| Mount {
+                point,
+                fs_type: fs_type.to_string(),
+                source: source.to_string(),
+            })
+        })
+        .max_by_key(|mount| mount.point.len())
+}
@@ -0,0 +1,114 @@
+use std::{error::Error, fmt, io};
+
+use clap::ValueEnum;
+use eyre::Report;
+
+use crate::summary::json_string;
+
+/// How a failure is reported on stderr
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ErrorFormat {
+    /// A readable report, with what caused it
+    Pretty,
+    /// One line of JSON with a stable `code`, whether running again may
+    /// succeed, the message, and its causes
+    Json,
+}
+
+/// A failure whose code and retryability are known where it happens,
+/// rather than from the error kind behind it
+#[derive(Debug)]
+pub struct Coded {
+    code: &'static str,
+    retryable: bool,
+    message: String,
+}
+
+/// A report of `message` as a failure with `code`
+pub fn coded(code: &'static str, retryable: bool, message: String) -> Report {
+    Report::new(Coded {
+        code,
+        retryable,
+        message,
+    })
+}
+
+impl fmt::Display for Coded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for Coded {}
+
+/// Whether the process's arguments ask for `--errors json`, for failures
+/// parsing them, before the rest are known
+pub fn json_requested() -> bool {
+    let args: Vec<_> = std::env::args_os().collect();
+    args.iter().any(|arg| arg == "--errors=json")
+        || args
+            .windows(2)
+            .any(|pair| pair[0] == "--errors" && pair[1] == "json")
+}
+
+/// `report` as a line of JSON
+pub fn json(report: &Report) -> String {
+    let (code, retryable) = classify(report);
+    let causes: Vec<_> = report
+        .chain()
+        .skip(1)
+        .map(|cause| json_string(&cause.to_string()))
+        .collect();
+    line(code, retryable, &report.to_string(), &causes)
+}
+
+/// A failure to parse the arguments as a line of JSON
+pub fn arguments_json(error: &clap::Error) -> String {
+    line("invalid_arguments", false, &problem(error), &[])
+}
+
+/// What was wrong with the arguments, without the pointers to `--help`
+pub fn problem(error: &clap::Error) -> String {
+    let message = error.to_string();
+    let problem = message.lines().next().unwrap_or_default();
+    problem.trim_start_matches("error: ").to_string()
+}
+
+fn line(code: &str, retryable: bool, message: &str, causes: &[String]) -> String {
+    format!(
+        "{{\"code\": {}, \"retryable\": {}, \"message\": {}, \"causes\": [{}]}}",
+        json_string(code),
+        retryable,
+        json_string(message),
+        causes.join(", ")
+    )
+}
+
+/// The code and retryability of a failure: those it was given, or those of
+/// the first I/O error behind it. Anything else is a problem with the
+/// options or the files they name, which running again won't fix.
+fn classify(report: &Report) -> (&'static str, bool) {
+    if let Some(coded) = report.chain().find_map(|e| e.downcast_ref::<Coded>()) {
+        return (coded.code, coded.retryable);
+    }
+    let Some(error) = report.chain().find_map(|e| e.downcast_ref::<io::Error>()) else {
+        return ("invalid_input", false);
+    };
+    match error.kind() {
+        io::ErrorKind::NotFound => ("not_found", false),
+        io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem => {
+            ("permission_denied", false)
+        }
+        io::ErrorKind::AlreadyExists => ("already_exists", false),
+        io::ErrorKind::StorageFull | io::ErrorKind::QuotaExceeded => ("disk_full", true),
+        io::ErrorKind::Interrupted
+        | io::ErrorKind::TimedOut
+        | io::ErrorKind::WouldBlock
+        | io::ErrorKind::ResourceBusy
+        | io::ErrorKind::ConnectionRefused
+        | io::ErrorKind::ConnectionReset
+        | io::ErrorKind::ConnectionAborted
+        | io::ErrorKind::NotConnected => ("transient_io", true),
+        _ => ("io", false),
+    }
+}
@@ -0,0 +1,518 @@
+use std::{cmp::Ordering, fs};
+
+use eyre::{eyre, Result};
+use rand::{Rng, RngCore};
+
+use crate::distribution::standard_normal;
+
+/// A small expression language used by `--script`.
+///
+/// A program is any number of `let name = <expr>;` bindings followed by a
+/// final expression. Expressions support numbers, `"strings"`, the arithmetic
+/// operators `+ - * / %`, comparisons, `&&`, `||`, `!`, `cond ? a : b` and
+/// the functions listed in [`Func`]. Lines starting with `#` are comments.
+#[derive(Debug)]
+pub struct Program {
+    lets: Vec<Expr>,
+    body: Expr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value<'a> {
+    Num(f64),
+    Str(&'a str),
+}
+
+impl<'a> Value<'a> {
+    pub fn num(self) -> Result<f64> {
+        match self {
+            Self::Num(n) => Ok(n),
+            Self::Str(s) => Err(eyre!("Expected a number, found \"{}\"", s)),
+        }
+    }
+
+    fn str(self) -> Result<&'a str> {
+        match self {
+            Self::Str(s) => Ok(s),
+            Self::Num(n) => Err(eyre!("Expected a string, found {}", n)),
+        }
+    }
+
+    pub fn truthy(self) -> bool {
+        match self {
+            Self::Num(n) => n != 0.0,
+            Self::Str(s) => !s.is_empty(),
+        }
+    }
+}
+
+impl From<bool> for Value<'_> {
+    fn from(b: bool) -> Self {
+        Self::Num(if b { 1.0 } else { 0.0 })
+    }
+}
+
+#[derive(Debug)]
+enum Expr {
+    Num(f64),
+    Str(String),
+    Var(usize),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    Cond(Box<Expr>, Box<Expr>, Box<Expr>),
+    Call(Func, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+/// Built-in functions
+#[derive(Debug, Clone, Copy)]
+enum Func {
+    /// `rand()`: uniform in `[0, 1)`
+    Rand,
+    /// `uniform(lo, hi)`: uniform in `[lo, hi)`
+    Uniform,
+    /// `normal(mean, stddev)`
+    Normal,
+    /// `hash(s)`: a stable pseudo-random number in `[0, 1)` derived from `s`
+    Hash,
+    /// `len(s)`: length of `s` in bytes
+    Len,
+    Abs,
+    Floor,
+    Ceil,
+    Round,
+    Sqrt,
+    Sin,
+    Cos,
+    Min,
+    Max,
+    Clamp,
+}
+
+impl Func {
+    fn lookup(name: &str) -> Option<(Self, usize)> {
+        Some(match name {
+            "rand" => (Self::Rand, 0),
+            "uniform" => (Self::Uniform, 2),
+            "normal" => (Self::Normal, 2),
+            "hash" => (Self::Hash, 1),
+            "len" => (Self::Len, 1),
+            "abs" => (Self::Abs, 1),
+            "floor" => (Self::Floor, 1),
+            "ceil" => (Self::Ceil, 1),
+            "round" => (Self::Round, 1),
+            "sqrt" => (Self::Sqrt, 1),
+            "sin" => (Self::Sin, 1),
+            "cos" => (Self::Cos, 1),
+            "min" => (Self::Min, 2),
+            "max" => (Self::Max, 2),
+            "clamp" => (Self::Clamp, 3),
+            _ => return None,
+        })
+    }
+}
+
+impl Program {
+    pub fn load(path: &str, vars: &[&str]) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| eyre!("Could not read script file {}: {}", path, e))?;
+        Self::parse(&text, vars).map_err(|e| eyre!("{}: {}", path, e))
+    }
+
+    /// Compiles `text`, where `vars` names the values that will be supplied,
+    /// in order, to [`Program::eval`]
+    pub fn parse(text: &str, vars: &[&str]) -> Result<Self> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            names: vars.iter().map(|v| v.to_string()).collect(),
+        };
+        let mut lets = Vec::new();
+        while parser.eat_ident("let") {
+            let name = parser.ident()?;
+            parser.expect(&Token::Assign)?;
+            lets.push(parser.expr()?);
+            parser.expect(&Token::Semi)?;
+            parser.names.push(name);
+        }
+        let body = parser.expr()?;
+        if parser.pos < parser.tokens.len() {
+            return Err(eyre!("Unexpected {:?}", parser.tokens[parser.pos]));
+        }
+        Ok(Self { lets, body })
+    }
+
+    /// Evaluates the program. `slots` must hold exactly the variables named
+    /// at parse time; it is used as scratch space for `let` bindings.
+    pub fn eval<'a>(
+        &'a self,
+        slots: &mut Vec<Value<'a>>,
+        rng: &mut dyn RngCore,
+    ) -> Result<Value<'a>> {
+        for expr in &self.lets {
+            let value = expr.eval(slots, rng)?;
+            slots.push(value);
+        }
+        self.body.eval(slots, rng)
+    }
+}
+
+impl Expr {
+    fn eval<'a>(&'a self, slots: &[Value<'a>], rng: &mut dyn RngCore) -> Result<Value<'a>> {
+        Ok(match self {
+            Self::Num(n) => Value::Num(*n),
+            Self::Str(s) => Value::Str(s),
+            Self::Var(i) => slots[*i],
+            Self::Neg(e) => Value::Num(-e.eval(slots, rng)?.num()?),
+            Self::Not(e) => (!e.eval(slots, rng)?.truthy()).into(),
+            Self::Cond(c, a, b) => {
+                if c.eval(slots, rng)?.truthy() {
+                    a.eval(slots, rng)?
+                } else {
+                    b.eval(slots, rng)?
+                }
+            }
+            Self::Binary(BinOp::And, a, b) => {
+                (a.eval(slots, rng)?.truthy() && b.eval(slots, rng)?.truthy()).into()
+            }
+            Self::Binary(BinOp::Or, a, b) => {
+                (a.eval(slots, rng)?.truthy() || b.eval(slots, rng)?.truthy()).into()
+            }
+            Self::Binary(op, a, b) => {
+                let (a, b) = (a.eval(slots, rng)?, b.eval(slots, rng)?);
+                match op {
+                    BinOp::Eq => (a == b).into(),
+                    BinOp::Ne => (a != b).into(),
+                    BinOp::Lt | BinOp::Le | BinOp::Gt | BinOp::Ge => {
+                        let ordering = match (a, b) {
+                            (Value::Str(a), Value::Str(b)) => a.cmp(b),
+                            (a, b) => a
+                                .num()?
+                                .partial_cmp(&b.num()?)
+                                .ok_or_else(|| eyre!("Cannot compare NaN"))?,
+                        };
+                        match op {
+                            BinOp::Lt => ordering == Ordering::Less,
+                            BinOp::Le => ordering != Ordering::Greater,
+                            BinOp::Gt => ordering == Ordering::Greater,
+                            _ => ordering != Ordering::Less,
+                        }
+                        .into()
+                    }
+                    _ => {
+                        let (a, b) = (a.num()?, b.num()?);
+                        Value::Num(match op {
+                            BinOp::Add => a + b,
+                            BinOp::Sub => a - b,
+                            BinOp::Mul => a * b,
+                            BinOp::Div => a / b,
+                            _ => a % b,
+                        })
+                    }
+                }
+            }
+            Self::Call(func, args) => {
+                let mut values = [Value::Num(0.0); 3];
+                for (value, arg) in values.iter_mut().zip(args) {
+                    *value = arg.eval(slots, rng)?;
+                }
+                let [a, b, c] = values;
+                Value::Num(match func {
+                    Func::Rand => rng.gen(),
+                    Func::Uniform => {
+                        let (lo, hi) = (a.num()?, b.num()?);
+                        lo + (hi - lo) * rng.gen::<f64>()
+                    }
+                    Func::Normal => a.num()? + b.num()? * standard_normal(rng),
+                    Func::Hash => {
+                        // FNV-1a, stable across runs and platforms
+                        let hash = a.str()?.bytes().fold(0xcbf2_9ce4_8422_2325u64, |h, byte| {
+                            (h ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+                        });
+                        (hash >> 11) as f64 / (1u64 << 53) as f64
+                    }
+                    Func::Len => a.str()?.len() as f64,
+                    Func::Abs => a.num()?.abs(),
+                    Func::Floor => a.num()?.floor(),
+                    Func::Ceil => a.num()?.ceil(),
+                    Func::Round => a.num()?.round(),
+                    Func::Sqrt => a.num()?.sqrt(),
+                    Func::Sin => a.num()?.sin(),
+                    Func::Cos => a.num()?.cos(),
+                    Func::Min => a.num()?.min(b.num()?),
+                    Func::Max => a.num()?.max(b.num()?),
+                    Func::Clamp => a.num()?.max(b.num()?).min(c.num()?),
+                })
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(f64),
+    Str(String),
+    Ident(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+    Comma,
+    Question,
+    Colon,
+    Semi,
+    Assign,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>> {
+    const OPS: [&str; 15] = [
+        "==", "!=", "<=", ">=", "&&", "||", "<", ">", "+", "-", "*", "/", "%", "!", "=",
+    ];
+    let mut tokens = Vec::new();
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    'outer: while i < bytes.len() {
+        let c = bytes[i];
+        match c {
+            b'#' => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            c if c.is_ascii_whitespace() => i += 1,
+            b'0'..=b'9' | b'.' => {
+                let start = i;
+                while i < bytes.len()
+                    && (bytes[i].is_ascii_digit()
+                        || bytes[i] == b'.'
+                        || bytes[i] == b'e'
+                        || (matches!(bytes[i], b'+' | b'-') && bytes[i - 1] == b'e'))
+                {
+                    i += 1;
+                }
+                let number = &text[start..i];
+                tokens.push(Token::Num(
+                    number
+                        .parse()
+                        .map_err(|_| eyre!("Invalid number `{}`", number))?,
+                ));
+            }
+            b'"' => {
+                let mut s = String::new();
+                let mut chars = text[i + 1..].char_indices();
+                while let Some((j, c)) = chars.next() {
+                    match c {
+                        '"' => {
+                            tokens.push(Token::Str(s));
+                            i += j + 2;
+                            continue 'outer;
+                        }
+                        '\\' => match chars.next() {
+                            Some((_, 'n')) => s.push('\n'),
+                            Some((_, 't')) => s.push('\t'),
+                            Some((_, c @ ('"' | '\\'))) => s.push(c),
+                            _ => return Err(eyre!("Invalid escape in string")),
+                        },
+                        c => s.push(c),
+                    }
+                }
+                return Err(eyre!("Unterminated string"));
+            }
+            c if c.is_ascii_alphabetic() || c == b'_' => {
+                let start = i;
+                while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'_') {
+                    i += 1;
+                }
+                tokens.push(Token::Ident(text[start..i].to_string()));
+            }
+            _ => {
+                let simple = match c {
+                    b'(' => Some(Token::LParen),
+                    b')' => Some(Token::RParen),
+                    b',' => Some(Token::Comma),
+                    b'?' => Some(Token::Question),
+                    b':' => Some(Token::Colon),
+                    b';' => Some(Token::Semi),
+                    _ => None,
+                };
+                if let Some(token) = simple {
+                    tokens.push(token);
+                    i += 1;
+                    continue;
+                }
+                let op = OPS
+                    .iter()
+                    .find(|op| text[i..].starts_with(**op))
+                    .ok_or_else(|| {
+                        eyre!(
+                            "Unexpected character `{}`",
+                            text[i..].chars().next().unwrap_or(' ')
+                        )
+                    })?;
+                tokens.push(if *op == "=" {
+                    Token::Assign
+                } else {
+                    Token::Op(op)
+                });
+                i += op.len();
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    names: Vec<String>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| eyre!("Unexpected end of script"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: &Token) -> Result<()> {
+        match self.next()? {
+            ref token if token == expected => Ok(()),
+            token => Err(eyre!("Expected {:?}, found {:?}", expected, token)),
+        }
+    }
+
+    fn eat_op(&mut self, op: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Op(o)) if *o == op) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn eat_ident(&mut self, ident: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Ident(i)) if i == ident) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        match self.next()? {
+            Token::Ident(name) => Ok(name),
+            token => Err(eyre!("Expected a name, found {:?}", token)),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        let cond = self.binary(0)?;
+        if self.peek() != Some(&Token::Question) {
+            return Ok(cond);
+        }
+        self.pos += 1;
+        let a = self.expr()?;
+        self.expect(&Token::Colon)?;
+        let b = self.expr()?;
+        Ok(Expr::Cond(Box::new(cond), Box::new(a), Box::new(b)))
+    }
+
+    /// Precedence climbing over the binary operators, loosest first
+    fn binary(&mut self, level: usize) -> Result<Expr> {
+        const LEVELS: [&[(&str, BinOp)]; 5] = [
+            &[("||", BinOp::Or)],
+            &[("&&", BinOp::And)],
+            &[
+                ("==", BinOp::Eq),
+                ("!=", BinOp::Ne),
+                ("<=", BinOp::Le),
+                (">=", BinOp::Ge),
+                ("<", BinOp::Lt),
+                (">", BinOp::Gt),
+            ],
+            &[("+", BinOp::Add), ("-", BinOp::Sub)],
+            &[("*", BinOp::Mul), ("/", BinOp::Div), ("%", BinOp::Rem)],
+        ];
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut lhs = self.binary(level + 1)?;
+        'outer: loop {
+            for (op, bin_op) in LEVELS[level] {
+                if self.eat_op(op) {
+                    let rhs = self.binary(level + 1)?;
+                    lhs = Expr::Binary(*bin_op, Box::new(lhs), Box::new(rhs));
+                    continue 'outer;
+                }
+            }
+            return Ok(lhs);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat_op("-") {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        if self.eat_op("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        match self.next()? {
+            Token::Num(n) => Ok(Expr::Num(n)),
+            Token::Str(s) => Ok(Expr::Str(s)),
+            Token::LParen => {
+                let expr = self.expr()?;
+                self.expect(&Token::RParen)?;
+                Ok(expr)
+            }
+            Token::Ident(name) if self.peek() == Some(&Token::LParen) => {
+                self.pos += 1;
+                let (func, arity) =
+                    Func::lookup(&name).ok_or_else(|| eyre!("Unknown function `{}`", name))?;
+                let mut args = Vec::new();
+                if self.peek() != Some(&Token::RParen) {
+                    args.push(self.expr()?);
+                    while self.peek() == Some(&Token::Comma) {
+                        self.pos += 1;
+                        args.push(self.expr()?);
+                    }
+                }
+                self.expect(&Token::RParen)?;
+                if args.len() != arity {
+                    return Err(eyre!("`{}` takes {} argument(s)", name, arity));
+                }
+                Ok(Expr::Call(func, args))
+            }
+            Token::Ident(name) => self
+                .names
+                .iter()
+                .rposition(|n| *n == name)
+                .map(Expr::Var)
+                .ok_or_else(|| eyre!("Unknown variable `{}`", name)),
+            token => Err(eyre!("Unexpected {:?}", token)),
+        }
+    }
+}
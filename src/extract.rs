@@ -0,0 +1,193 @@
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use eyre::{eyre, Result, WrapErr};
+
+use crate::{
+    output::partial_path,
+    station::{load_weather_stations, ListOptions},
+};
+
+/// Writes the rows of the measurements file `path` for the stations named
+/// in `stations` or listed in the station list `list` to stdout, in the
+/// order they are in the file
+pub fn extract(path: &str, stations: &[String], list: Option<&str>) -> Result<()> {
+    let _span = tracing::debug_span!("extract").entered();
+    let mut names: HashSet<Vec<u8>> = stations
+        .iter()
+        .map(|station| station.as_bytes().to_vec())
+        .collect();
+    if let Some(list) = list {
+        // A list of bare names has no header to skip
+        let options = ListOptions {
+            no_header: true,
+            ..ListOptions::default()
+        };
+        let listed = load_weather_stations(list.to_string(), options)?;
+        names.extend(listed.names().map(|name| name.as_bytes().to_vec()));
+    }
+    if names.is_empty() {
+        return Err(eyre!("Name the stations with --station or --stations-file"));
+    }
+    let mut writer = BufWriter::with_capacity(1 << 20, io::stdout().lock());
+    let copied = copy_rows(path, &mut writer, |station, _| {
+        Ok(names.contains(unquoted(station).as_ref()))
+    })
+    .and_then(|rows| Ok(writer.flush().map(|()| rows)?));
+    match copied {
+        Ok(rows) => {
+            tracing::debug!(rows, "Extracted rows");
+            Ok(())
+        }
+        // A reader such as `head` closes the pipe once it has seen enough
+        Err(e)
+            if e.downcast_ref::<io::Error>()
+                .is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) =>
+        {
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Copies the rows of the measurements file `path` that `keep` accepts,
+/// given each row's station and measurement, to `writer`, returning how many
+/// it copied, or the first error `keep` returns. Every row copied ends in a
+/// newline.
+pub fn copy_rows<W: Write>(
+    path: &str,
+    writer: &mut W,
+    mut keep: impl FnMut(&[u8], &[u8]) -> Result<bool>,
+) -> Result<u64> {
+    let mut copied = 0;
+    for_each_row(path, |station, measurement| {
+        if keep(station, measurement)? {
+            writer.write_all(station)?;
+            writer.write_all(b";")?;
+            writer.write_all(measurement)?;
+            writer.write_all(b"\n")?;
+            copied += 1;
+        }
+        Ok(())
+    })?;
+    Ok(copied)
+}
+
+/// Calls `row` with the station and measurement of each row of the
+/// measurements file `path`, in order, stopping at the first error it
+/// returns
+pub fn for_each_row(path: &str, mut row: impl FnMut(&[u8], &[u8]) -> Result<()>) -> Result<()> {
+    let file = File::open(path).wrap_err_with(|| format!("Could not open file {}", path))?;
+    let mut reader = BufReader::with_capacity(1 << 20, file);
+    let (mut line, mut offset) = (Vec::new(), 0);
+    loop {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)?;
+        if read == 0 {
+            return Ok(());
+        }
+        let text = line.strip_suffix(b"\n").unwrap_or(&line);
+        let Some(split) = text.iter().rposition(|&b| b == b';') else {
+            return Err(eyre!(
+                "{} holds a line that isn't a measurement at byte {}",
+                path,
+                offset
+            ));
+        };
+        row(&text[..split], &text[split + 1..])
+            .wrap_err_with(|| format!("At byte {} of {}", offset, path))?;
+        offset += read as u64;
+    }
+}
+
+/// Writes a file made from the measurements file `input` to `output` with
+/// `write`, which returns how many rows it wrote, by way of a partial file
+/// renamed once complete. An existing `output` is only replaced when
+/// `force` is set, and never when it is `input` itself.
+pub fn write_rows(
+    input: &str,
+    output: &str,
+    force: bool,
+    write: impl FnOnce(&mut BufWriter<File>) -> Result<u64>,
+) -> Result<u64> {
+    check_replaceable(input, output, force)?;
+    let partial = partial_path(output);
+    let file = File::create(&partial).wrap_err_with(|| format!("Could not create {}", partial))?;
+    let mut writer = BufWriter::with_capacity(1 << 20, file);
+    let written = write(&mut writer).and_then(|rows| {
+        let file = writer.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        Ok(rows)
+    });
+    match written {
+        Ok(rows) => {
+            fs::rename(&partial, output)?;
+            Ok(rows)
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&partial);
+            Err(e)
+        }
+    }
+}
+
+/// Refuses to write `output` from `input` if `output` exists, unless
+/// `force` is set and it isn't `input` itself
+pub fn check_replaceable(input: &str, output: &str, force: bool) -> Result<()> {
+    if Path::new(output).exists() {
+        if !force {
+            return Err(eyre!(
+                "{} already exists, pass --force to replace it",
+                output
+            ));
+        }
+        if fs::canonicalize(input).ok() == fs::canonicalize(output).ok() {
+            return Err(eyre!(
+                "{} can't be replaced by rows read from itself",
+                input
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// A measurement such as `-12.3` as a number, allowing a CRLF line ending
+pub fn measurement(measurement: &[u8]) -> Result<f64> {
+    std::str::from_utf8(measurement)
+        .ok()
+        .and_then(|text| text.trim_end_matches('\r').parse::<f64>().ok())
+        .ok_or_else(|| {
+            eyre!(
+                "The measurement {:?} isn't a number",
+                String::from_utf8_lossy(measurement)
+            )
+        })
+}
+
+/// A station name as written, without the quotes around an RFC 4180
+/// quoted field
+pub fn unquoted(station: &[u8]) -> Cow<'_, [u8]> {
+    match station
+        .strip_prefix(b"\"")
+        .and_then(|inner| inner.strip_suffix(b"\""))
+    {
+        Some(inner) if inner.windows(2).any(|pair| pair == b"\"\"") => {
+            let mut name = Vec::with_capacity(inner.len());
+            let mut bytes = inner.iter();
+            while let Some(&b) = bytes.next() {
+                name.push(b);
+                if b == b'"' {
+                    bytes.next();
+                }
+            }
+            Cow::Owned(name)
+        }
+        Some(inner) => Cow::Borrowed(inner),
+        None => Cow::Borrowed(station),
+    }
+}
@@ -0,0 +1,176 @@
+//! A C API over generation, for harnesses written in other languages. Build
+//! it with `cargo rustc --release --lib --features ffi --crate-type cdylib`
+//! and declare it with `include/billion_row_gen.h`.
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_int, c_void, CStr, CString},
+    io,
+    panic::{self, AssertUnwindSafe},
+    ptr,
+    sync::{Arc, Mutex},
+};
+
+use clap::Parser;
+use eyre::{eyre, Result};
+
+use crate::{
+    cli::{generate_dataset, Args},
+    errors,
+    json::{self, Value},
+    output::{RowCallback, Sink},
+};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Called with each row, without its newline, and the `user_data` given;
+/// returning anything but 0 stops generation
+pub type RowFn = extern "C" fn(row: *const u8, len: usize, user_data: *mut c_void) -> c_int;
+
+/// Generates the dataset `config_json` describes into `out_path`, as the
+/// command line would, returning 0, or -1 with the reason left for
+/// `brg_last_error`. The config is a JSON object of command line options
+/// named as their long flags, such as
+/// `{"rows": 1000000, "seed": 1, "order": "by-station", "force": true}`.
+///
+/// # Safety
+///
+/// Both arguments must be null or point to NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn brg_generate(
+    config_json: *const c_char,
+    out_path: *const c_char,
+) -> c_int {
+    catch(|| {
+        let mut args = parse_config(string(config_json, "config")?)?;
+        args.output = string(out_path, "output path")?.to_string();
+        generate_dataset(args).map(drop)
+    })
+}
+
+/// Generates the rows `config_json` describes, handing each to `callback`
+/// with `user_data` instead of writing them anywhere, on the calling thread.
+/// Returns as `brg_generate` does, treating a callback stopping generation
+/// as an error. Options writing files, `threads` above 1, and `compress`
+/// are refused.
+///
+/// # Safety
+///
+/// `config_json` must be null or point to a NUL-terminated string, and
+/// `callback` must be safe to call with `user_data` for as long as this
+/// runs.
+#[no_mangle]
+pub unsafe extern "C" fn brg_generate_rows(
+    config_json: *const c_char,
+    callback: RowFn,
+    user_data: *mut c_void,
+) -> c_int {
+    let user_data = UserData(user_data);
+    catch(move || {
+        let mut args = parse_config(string(config_json, "config")?)?;
+        if args.threads() > 1 || args.compress.is_some() {
+            return Err(eyre!(
+                "brg_generate_rows generates on one thread, uncompressed"
+            ));
+        }
+        args.sink = Sink::Null;
+        args.quiet = true;
+        args.no_manifest = true;
+        args.row_callback = Some(RowCallback(Arc::new(Mutex::new(
+            move |row: &[u8]| match callback(row.as_ptr(), row.len(), user_data.get()) {
+                0 => Ok(()),
+                _ => Err(io::Error::other("The row callback stopped generation")),
+            },
+        ))));
+        generate_dataset(args).map(drop)
+    })
+}
+
+/// Why the last call on this thread failed, valid until the next call, or
+/// null if it didn't
+#[no_mangle]
+pub extern "C" fn brg_last_error() -> *const c_char {
+    LAST_ERROR.with(|error| error.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
+
+/// The caller's pointer for the row callback, which it promises may be used
+/// from the generating thread
+struct UserData(*mut c_void);
+
+// SAFETY: rows are only handed to the callback while the call that was
+// given `user_data` is running, as `brg_generate_rows` documents
+unsafe impl Send for UserData {}
+
+impl UserData {
+    fn get(&self) -> *mut c_void {
+        self.0
+    }
+}
+
+/// Runs `call`, recording its error or panic for `brg_last_error`
+fn catch(call: impl FnOnce() -> Result<()>) -> c_int {
+    let result = panic::catch_unwind(AssertUnwindSafe(call))
+        .unwrap_or_else(|_| Err(eyre!("billion-row-gen panicked")));
+    let (code, error) = match result {
+        Ok(()) => (0, None),
+        Err(e) => {
+            // Messages never hold NUL, but the C string mustn't either way
+            let message = format!("{:#}", e).replace('\0', " ");
+            (-1, Some(CString::new(message).expect("NULs were replaced")))
+        }
+    };
+    LAST_ERROR.with(|last| *last.borrow_mut() = error);
+    code
+}
+
+/// # Safety
+///
+/// `pointer` must be null or point to a NUL-terminated string.
+unsafe fn string<'a>(pointer: *const c_char, what: &str) -> Result<&'a str> {
+    if pointer.is_null() {
+        return Err(eyre!("The {} is null", what));
+    }
+    CStr::from_ptr(pointer)
+        .to_str()
+        .map_err(|_| eyre!("The {} isn't UTF-8", what))
+}
+
+/// The options a JSON object of long flags and their values describes;
+/// `true` sets a flag, `false` and `null` leave an option out, and arrays
+/// give comma-separated values
+fn parse_config(config: &str) -> Result<Args> {
+    let Value::Object(members) = json::parse(config)? else {
+        return Err(eyre!("The config must be a JSON object"));
+    };
+    let mut words = vec!["billion-row-gen".to_string()];
+    for (name, value) in members {
+        let flag = format!("--{}", name.replace('_', "-"));
+        let value = match value {
+            Value::Bool(true) => {
+                words.push(flag);
+                continue;
+            }
+            Value::Bool(false) | Value::Null => continue,
+            Value::Array(items) => items
+                .iter()
+                .map(|item| scalar(item, &name))
+                .collect::<Result<Vec<_>>>()?
+                .join(","),
+            value => scalar(&value, &name)?,
+        };
+        words.push(format!("{}={}", flag, value));
+    }
+    Args::try_parse_from(words).map_err(|e| eyre!("{}", errors::problem(&e)))
+}
+
+fn scalar(value: &Value, name: &str) -> Result<String> {
+    match value {
+        Value::String(string) => Ok(string.clone()),
+        Value::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => Ok((*n as i64).to_string()),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        _ => Err(eyre!("The option {} can't take {:?}", name, value)),
+    }
+}
@@ -0,0 +1,33 @@
+use eyre::{eyre, Result, WrapErr};
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{
+    expr::{Program, Value},
+    extract::{copy_rows, measurement, unquoted, write_rows},
+};
+
+/// Writes the rows of the measurements file `path` for which `predicate`,
+/// an expression over `station` and `temp` in the `--script` language, is
+/// true to `output`, keeping their order. `rand()` in the predicate draws
+/// from `seed`. An existing `output` is only replaced when `force` is set.
+pub fn filter(path: &str, predicate: &str, output: &str, seed: u64, force: bool) -> Result<()> {
+    let _span = tracing::debug_span!("filter").entered();
+    let program = Program::parse(predicate, &["station", "temp"])
+        .wrap_err_with(|| format!("Invalid --where expression `{}`", predicate))?;
+    let mut rng = StdRng::seed_from_u64(seed);
+    let rows = write_rows(path, output, force, |writer| {
+        copy_rows(path, writer, |station, temp| {
+            let station = unquoted(station);
+            let station = std::str::from_utf8(&station).map_err(|_| {
+                eyre!(
+                    "The station {:?} isn't UTF-8",
+                    String::from_utf8_lossy(&station)
+                )
+            })?;
+            let mut slots = vec![Value::Str(station), Value::Num(measurement(temp)?)];
+            Ok(program.eval(&mut slots, &mut rng)?.truthy())
+        })
+    })?;
+    eprintln!("Wrote {} matching rows to {}", rows, output);
+    Ok(())
+}
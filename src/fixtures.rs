@@ -0,0 +1,73 @@
+use std::{fs, path::Path};
+
+use clap::ValueEnum;
+use eyre::{eyre, Result, WrapErr};
+
+/// Bumped whenever a fixture's rows or results change, and part of every
+/// file name written, so suites pinned to one version never see another's
+pub const VERSION: u32 = 1;
+
+/// A small canonical dataset built into the binary, with the results the
+/// challenge expects for it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Fixture {
+    /// 100 rows over 8 stations, generated with `--seed 1 --max-stations 8`
+    Tiny,
+    /// Hand-written rows at the limits: a 100 byte name, one byte names,
+    /// ±99.9, `-0.0`, means rounding halfway either side of zero, and names
+    /// that differ only in case or are prefixes of each other
+    EdgeCases,
+    /// Names in many scripts: CJK, Cyrillic, Arabic and Hebrew with bidi
+    /// controls, emoji sequences, the same name composed and decomposed,
+    /// zero width spaces, and characters outside the Basic Multilingual Plane
+    Unicode,
+}
+
+impl Fixture {
+    /// The fixture's measurements and its results in the official format
+    fn files(self) -> (&'static [u8], &'static [u8]) {
+        match self {
+            Self::Tiny => (
+                include_bytes!("../data/fixtures/tiny.txt"),
+                include_bytes!("../data/fixtures/tiny.out"),
+            ),
+            Self::EdgeCases => (
+                include_bytes!("../data/fixtures/edge-cases.txt"),
+                include_bytes!("../data/fixtures/edge-cases.out"),
+            ),
+            Self::Unicode => (
+                include_bytes!("../data/fixtures/unicode.txt"),
+                include_bytes!("../data/fixtures/unicode.out"),
+            ),
+        }
+    }
+}
+
+/// Writes `fixture` to the directory `dir`, creating it if need be, as
+/// `<name>-v<version>.txt` for its measurements and `<name>-v<version>.out`
+/// for its results. Existing files are only replaced when `force` is set.
+pub fn write(fixture: Fixture, dir: &str, force: bool) -> Result<()> {
+    fs::create_dir_all(dir).wrap_err_with(|| format!("Could not create {}", dir))?;
+    let name = format!("{}-v{}", crate::manifest::value_name(fixture), VERSION);
+    let (measurements, results) = fixture.files();
+    let files = [("txt", measurements), ("out", results)].map(|(extension, contents)| {
+        (
+            Path::new(dir).join(format!("{}.{}", name, extension)),
+            contents,
+        )
+    });
+    if !force {
+        if let Some((path, _)) = files.iter().find(|(path, _)| path.exists()) {
+            return Err(eyre!(
+                "{} already exists, pass --force to replace it",
+                path.display()
+            ));
+        }
+    }
+    for (path, contents) in &files {
+        fs::write(path, contents)
+            .wrap_err_with(|| format!("Could not write {}", path.display()))?;
+    }
+    eprintln!("Wrote {}.txt and {}.out to {}", name, name, dir);
+    Ok(())
+}
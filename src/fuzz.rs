@@ -0,0 +1,255 @@
+use std::{fs, process::Command};
+
+use clap::ValueEnum;
+use eyre::{eyre, Result};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::{
+    cli::Args,
+    distribution::Distribution,
+    entropy::Entropy,
+    manifest::value_name,
+    output::Sink,
+    preset::{self, Preset},
+    results,
+};
+
+/// Stations in a list generated from a preset for one case
+const PRESET_STATIONS: usize = 500;
+
+/// One randomized dataset: the options the other flags are overridden with
+#[derive(Debug, Clone)]
+struct Case {
+    seed: u64,
+    rows: u64,
+    /// Stations from a preset, or from `--weather-stations` when `None`
+    preset: Option<Preset>,
+    max_stations: Option<u64>,
+    distribution: Distribution,
+    entropy: Entropy,
+    /// Few distinct values, so rows land on the range's edges
+    distinct_temps: Option<u32>,
+    negative_zero_rate: Option<f64>,
+}
+
+impl Case {
+    fn new(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut pick = |options: &[u64]| *options.choose(&mut rng).expect("Options aren't empty");
+        let rows = pick(&[1, 10, 100, 1_000, 10_000]);
+        let max_stations = Some(pick(&[1, 2, 10, 100, 1_000])).filter(|&max| max < 1_000);
+        Self {
+            seed,
+            rows: rng.gen_range(1..=rows),
+            preset: [None, Some(Preset::Emoji), Some(Preset::Rtl)]
+                .choose(&mut rng)
+                .copied()
+                .flatten(),
+            max_stations,
+            distribution: *Distribution::value_variants()
+                .choose(&mut rng)
+                .expect("Distributions aren't empty"),
+            entropy: *Entropy::value_variants()
+                .choose(&mut rng)
+                .expect("Entropies aren't empty"),
+            distinct_temps: rng.gen_bool(0.2).then_some(2),
+            negative_zero_rate: rng.gen_bool(0.2).then_some(0.1),
+        }
+    }
+
+    /// `base` generating this case's dataset into `path`, with its station
+    /// list, if any, at `stations`
+    fn args(&self, base: &Args, path: &str, stations: &str) -> Args {
+        Args {
+            command: None,
+            rows: self.rows,
+            start_row: 0,
+            end_row: None,
+            weather_stations: match self.preset {
+                Some(_) => stations.to_string(),
+                None => base.weather_stations.clone(),
+            },
+            max_stations: self.max_stations,
+            distribution: self.distribution,
+            entropy: self.entropy,
+            distinct_temps: self.distinct_temps,
+            negative_zero_rate: self.negative_zero_rate,
+            output: path.to_string(),
+            sink: Sink::File,
+            quiet: true,
+            checkpoint_interval: None,
+            max_file_size: None,
+            threads: Some(1),
+            ..base.clone()
+        }
+    }
+
+    /// The options reproducing this case
+    fn describe(&self) -> String {
+        let mut flags = format!(
+            "--seed {} --rows {} --distribution {} --entropy {}",
+            self.seed,
+            self.rows,
+            value_name(self.distribution),
+            value_name(self.entropy)
+        );
+        if let Some(max) = self.max_stations {
+            flags += &format!(" --max-stations {}", max);
+        }
+        if let Some(count) = self.distinct_temps {
+            flags += &format!(" --distinct-temps {}", count);
+        }
+        if let Some(rate) = self.negative_zero_rate {
+            flags += &format!(" --negative-zero-rate {}", rate);
+        }
+        if let Some(preset) = self.preset {
+            flags += &format!(
+                ", with stations from `stations generate --preset {} --count {}`",
+                value_name(preset),
+                PRESET_STATIONS
+            );
+        }
+        flags
+    }
+}
+
+/// Runs `solver_a` and `solver_b` on `iterations` small random datasets,
+/// each generated from the next seed after `--seed` into a scratch file next
+/// to the output, until their results differ. The first case they disagree
+/// on is shrunk to as few rows and stations as still show it, and left on
+/// disk. `generate` writes the rows `args` describe, from the seed given, to
+/// the path given.
+pub fn fuzz<G>(
+    args: &Args,
+    solver_a: &str,
+    solver_b: &str,
+    iterations: u64,
+    generate: G,
+) -> Result<()>
+where
+    G: Fn(&Args, &str, u64) -> Result<u64>,
+{
+    let path = format!("{}.fuzz", args.output);
+    let stations = format!("{}.fuzz-stations", args.output);
+    let first_seed = args.seed.unwrap_or_else(rand::random);
+    let run = |case: &Case| -> Result<Option<String>> {
+        if let Some(preset) = case.preset {
+            preset::generate(preset, PRESET_STATIONS, case.seed, Some(&stations))?;
+        }
+        generate(&case.args(args, &path, &stations), &path, case.seed)?;
+        compare(solver_a, solver_b, &path)
+    };
+
+    for iteration in 0..iterations {
+        let case = Case::new(first_seed.wrapping_add(iteration));
+        tracing::debug!(case = case.describe(), "Fuzzing");
+        let Some(difference) = run(&case)? else {
+            continue;
+        };
+        let (case, difference) = shrink(case, difference, &run)?;
+        // Regenerated, since the last case tried may have been a passing one
+        run(&case)?;
+        let _ = fs::remove_file(&stations);
+        return Err(eyre!(
+            "The solvers disagree on iteration {}, {}, with the dataset left \
+             at {}:\n{}",
+            iteration + 1,
+            case.describe(),
+            path,
+            difference
+        ));
+    }
+    let _ = fs::remove_file(&path);
+    let _ = fs::remove_file(&stations);
+    println!("The solvers agreed on all {} datasets", iterations);
+    Ok(())
+}
+
+/// The smallest case found by halving the rows, then the stations, of
+/// `case` for as long as the solvers still disagree
+fn shrink<F>(mut case: Case, mut difference: String, run: &F) -> Result<(Case, String)>
+where
+    F: Fn(&Case) -> Result<Option<String>>,
+{
+    loop {
+        let smaller_rows = Case {
+            rows: case.rows / 2,
+            ..case.clone()
+        };
+        let smaller_stations = Case {
+            max_stations: Some(case.max_stations.unwrap_or(1_000) / 2),
+            ..case.clone()
+        };
+        let candidates = [
+            (case.rows > 1).then_some(smaller_rows),
+            (case.max_stations != Some(1)).then_some(smaller_stations),
+        ];
+        let mut shrunk = false;
+        for candidate in candidates.into_iter().flatten() {
+            if let Some(found) = run(&candidate)? {
+                (case, difference, shrunk) = (candidate, found, true);
+                break;
+            }
+        }
+        if !shrunk {
+            return Ok((case, difference));
+        }
+    }
+}
+
+/// How the results of the two solvers on `path` differ, if they do
+fn compare(solver_a: &str, solver_b: &str, path: &str) -> Result<Option<String>> {
+    let (a, b) = (solve(solver_a, path)?, solve(solver_b, path)?);
+    let (a, b) = match (a, b) {
+        (Ok(a), Ok(b)) => (a, b),
+        (Err(failure), _) | (_, Err(failure)) => return Ok(Some(failure)),
+    };
+    // Only differences in the results matter, not their order or spacing,
+    // unless one of them isn't results at all
+    let (a, b) = match (results::canonical(&a), results::canonical(&b)) {
+        (Ok(a), Ok(b)) => (a, b),
+        _ => (a, b),
+    };
+    let (a, b) = (a.trim_end(), b.trim_end());
+    if a == b {
+        return Ok(None);
+    }
+    let at = a
+        .char_indices()
+        .zip(b.chars())
+        .find(|((_, x), y)| x != y)
+        .map_or(a.len().min(b.len()), |((at, _), _)| at);
+    let context = |output: &str| -> String {
+        let start = output.floor_char_boundary(at.saturating_sub(40));
+        output[start..].chars().take(80).collect()
+    };
+    Ok(Some(format!(
+        "  {}: ...{}\n  {}: ...{}",
+        solver_a,
+        context(a),
+        solver_b,
+        context(b)
+    )))
+}
+
+/// The stdout of the `solver` command run on `path`, or how it failed
+fn solve(solver: &str, path: &str) -> Result<Result<String, String>> {
+    let mut words = solver.split_whitespace();
+    let program = words
+        .next()
+        .ok_or_else(|| eyre!("A solver's command can't be empty"))?;
+    let output = Command::new(program)
+        .args(words)
+        .arg(path)
+        .output()
+        .map_err(|e| eyre!("Could not run {}: {}", solver, e))?;
+    if !output.status.success() {
+        return Ok(Err(format!(
+            "  {} failed with {}: {}",
+            solver,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        )));
+    }
+    Ok(Ok(String::from_utf8_lossy(&output.stdout).into_owned()))
+}
@@ -0,0 +1,73 @@
+#[cfg(target_os = "linux")]
+use std::{fs, io};
+
+use eyre::{eyre, Result};
+
+/// The size of a transparent huge page on x86_64 and most arm64 kernels
+#[cfg(target_os = "linux")]
+const HUGE_PAGE: usize = 2 << 20;
+
+/// Where the kernel reports when it backs memory with transparent huge pages
+#[cfg(target_os = "linux")]
+const THP_ENABLED: &str = "/sys/kernel/mm/transparent_hugepage/enabled";
+
+/// Refuses `--huge-pages` where the kernel would ignore it, with
+/// transparent huge pages disabled or not built in
+#[cfg(target_os = "linux")]
+pub fn check() -> Result<()> {
+    let enabled = fs::read_to_string(THP_ENABLED).map_err(|e| {
+        eyre!(
+            "--huge-pages needs transparent huge pages, but {} can't be read: {}",
+            THP_ENABLED,
+            e
+        )
+    })?;
+    if enabled.contains("[never]") {
+        return Err(eyre!(
+            "--huge-pages needs transparent huge pages, which {} disables",
+            THP_ENABLED
+        ));
+    }
+    Ok(())
+}
+
+/// An empty buffer of at least `capacity` bytes. With `huge`, every whole
+/// 2 MiB page of it is marked for transparent huge pages, so the generation
+/// loop filling it misses the TLB far less often; a buffer smaller than a
+/// huge page gains nothing.
+#[cfg(target_os = "linux")]
+pub fn buffer(capacity: usize, huge: bool) -> Result<Vec<u8>> {
+    let mut buffer = Vec::with_capacity(capacity);
+    if !huge {
+        return Ok(buffer);
+    }
+    let start = buffer.as_mut_ptr() as usize;
+    let first = start.next_multiple_of(HUGE_PAGE);
+    let end = (start + buffer.capacity()) / HUGE_PAGE * HUGE_PAGE;
+    if end > first {
+        // SAFETY: the range lies within the buffer's allocation, and the
+        // advice only changes which pages back it
+        let result =
+            unsafe { libc::madvise(first as *mut libc::c_void, end - first, libc::MADV_HUGEPAGE) };
+        if result != 0 {
+            return Err(eyre!(
+                "Could not back a buffer with huge pages: {}",
+                io::Error::last_os_error()
+            ));
+        }
+    }
+    Ok(buffer)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn check() -> Result<()> {
+    Err(eyre!("--huge-pages is only supported on Linux"))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn buffer(capacity: usize, huge: bool) -> Result<Vec<u8>> {
+    if huge {
+        return Err(eyre!("--huge-pages is only supported on Linux"));
+    }
+    Ok(Vec::with_capacity(capacity))
+}
@@ -0,0 +1,150 @@
+//! Generation into memory, so Rust tests can make a small dataset inline
+//! without a file to create and clean up. `generate_into` and `fill` take
+//! command line options, so they come with the `cli` feature;
+//! `generate_rows_into` and `fill_rows` take a [`RowsConfig`] without it.
+
+#[cfg(feature = "cli")]
+use std::{
+    io, iter, mem,
+    sync::{Arc, Mutex},
+};
+
+#[cfg(feature = "cli")]
+use clap::Parser;
+#[cfg(feature = "cli")]
+use eyre::eyre;
+use eyre::Result;
+
+use crate::rows::{Rows, RowsConfig};
+#[cfg(feature = "cli")]
+use crate::{
+    cli::{generate_dataset, Args},
+    errors,
+    output::{Format, RowCallback, Sink},
+};
+
+/// Appends the rows `config` describes to `buffer`, each ending in a
+/// newline, and returns how many there were. They are the rows
+/// `generate_into` appends for the same `--weather-stations`, `--rows`, and
+/// `--seed`.
+pub fn generate_rows_into(buffer: &mut Vec<u8>, config: &RowsConfig) -> Result<u64> {
+    let mut count = 0;
+    for row in Rows::new(config)? {
+        buffer.extend_from_slice(row.as_bytes());
+        buffer.push(b'\n');
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Fills `buffer` with as many of the rows `config` describes as fit whole,
+/// each ending in a newline, and returns the bytes they take, as `fill`
+/// does
+pub fn fill_rows(buffer: &mut [u8], config: &RowsConfig) -> Result<usize> {
+    let mut len = 0;
+    for row in Rows::new(config)? {
+        let end = len + row.len() + 1;
+        if end > buffer.len() {
+            break;
+        }
+        buffer[len..end - 1].copy_from_slice(row.as_bytes());
+        buffer[end - 1] = b'\n';
+        len = end;
+    }
+    Ok(len)
+}
+
+/// Appends the rows the command line options `config` describe, such as
+/// `["--rows", "1000", "--seed", "1"]`, to `buffer`, each ending in a
+/// newline, and returns how many there were. The rows are those the command
+/// line would write with the same options. Options writing files beside
+/// the output (`--summary`, `--progress-log`, `--emit-keyset`,
+/// `--frequency-report`, `--answer-key`, `--index`, `--station-counts`,
+/// `--checkpoint-interval`, `--resume`, `--append`, `--max-file-size`, and
+/// `--archive`), `--threads` above 1, `--compress`, and formats other than
+/// text are refused.
+#[cfg(feature = "cli")]
+pub fn generate_into(buffer: &mut Vec<u8>, config: &[&str]) -> Result<u64> {
+    let rows = Arc::new(Mutex::new((mem::take(buffer), 0)));
+    let taken = Arc::clone(&rows);
+    let generated = generate(config, move |row| {
+        let (buffer, count) = &mut *taken.lock().expect("The row callback panicked");
+        buffer.extend_from_slice(row);
+        buffer.push(b'\n');
+        *count += 1;
+        Ok(())
+    });
+    let (rows, count) = mem::take(&mut *rows.lock().expect("The row callback panicked"));
+    *buffer = rows;
+    generated.map(|()| count)
+}
+
+/// Fills `buffer` with as many of the rows the command line options
+/// `config` describe as fit whole, each ending in a newline, and returns
+/// the bytes they take. Generation stops at the first row that doesn't fit,
+/// or once the rows run out. Options are refused as [`generate_into`]
+/// refuses them.
+#[cfg(feature = "cli")]
+pub fn fill(buffer: &mut [u8], config: &[&str]) -> Result<usize> {
+    let capacity = buffer.len();
+    let rows = Arc::new(Mutex::new((Vec::with_capacity(capacity), false)));
+    let taken = Arc::clone(&rows);
+    let generated = generate(config, move |row| {
+        let (rows, full) = &mut *taken.lock().expect("The row callback panicked");
+        if rows.len() + row.len() + 1 > capacity {
+            *full = true;
+            return Err(io::Error::other("The buffer is full"));
+        }
+        rows.extend_from_slice(row);
+        rows.push(b'\n');
+        Ok(())
+    });
+    let (rows, full) = &*rows.lock().expect("The row callback panicked");
+    // Running out of room is how filling ends
+    if !full {
+        generated?;
+    }
+    buffer[..rows.len()].copy_from_slice(rows);
+    Ok(rows.len())
+}
+
+/// Generates the rows `config` describes on the calling thread, handing
+/// each to `take` without its newline
+#[cfg(feature = "cli")]
+fn generate(
+    config: &[&str],
+    take: impl FnMut(&[u8]) -> io::Result<()> + Send + 'static,
+) -> Result<()> {
+    let words = iter::once("billion-row-gen").chain(config.iter().copied());
+    let mut args = Args::try_parse_from(words).map_err(|e| eyre!("{}", errors::problem(&e)))?;
+    let writing = [
+        ("--summary", args.summary.is_some()),
+        ("--progress-log", args.progress_log.is_some()),
+        ("--emit-keyset", args.emit_keyset.is_some()),
+        ("--frequency-report", args.frequency_report.is_some()),
+        ("--answer-key", !args.answer_key.is_empty()),
+        ("--index", args.index.is_some()),
+        ("--station-counts", args.station_counts),
+        ("--checkpoint-interval", args.checkpoint_interval.is_some()),
+        ("--resume", args.resume),
+        ("--append", args.append),
+        ("--max-file-size", args.max_file_size.is_some()),
+        ("--archive", args.archive.is_some()),
+    ];
+    if let Some((option, _)) = writing.iter().find(|(_, set)| *set) {
+        return Err(eyre!(
+            "{} can't be combined with generating into memory",
+            option
+        ));
+    }
+    if args.threads() > 1 || args.compress.is_some() || args.format != Format::Text {
+        return Err(eyre!(
+            "Rows are generated into memory on one thread, as uncompressed text"
+        ));
+    }
+    args.sink = Sink::Null;
+    args.quiet = true;
+    args.no_manifest = true;
+    args.row_callback = Some(RowCallback(Arc::new(Mutex::new(take))));
+    generate_dataset(args).map(drop)
+}
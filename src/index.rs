@@ -0,0 +1,106 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Read, Seek, SeekFrom, Write},
+    ops::Range,
+    thread,
+};
+
+use eyre::Result;
+
+/// Writes `<file>.idx` beside each of `paths`, the parts of the output in
+/// order, returning their paths. Each lists the byte offset of every
+/// `interval`th row in its file as `<row> <offset>` lines, with rows counted
+/// from the first of the first part, so a reader can seek to any row and
+/// skip at most `interval - 1` lines from there.
+pub fn write(paths: &[String], interval: u64) -> Result<Vec<String>> {
+    let _span = tracing::debug_span!("index", interval).entered();
+    let mut first_row = 0;
+    let mut indexes = Vec::with_capacity(paths.len());
+    for path in paths {
+        let size = fs::metadata(path)?.len();
+        let threads = thread::available_parallelism().map_or(1, |n| n.get()) as u64;
+        let span = size.div_ceil(threads).max(1);
+        let ranges: Vec<Range<u64>> = (0..size)
+            .step_by(span as usize)
+            .map(|start| start..(start + span).min(size))
+            .collect();
+        // Each range's rows are numbered from the line breaks before it
+        let counts = in_parallel(&ranges, |range| {
+            let mut count = 0;
+            scan(path, range.clone(), |block, _| {
+                count += block.iter().filter(|&&b| b == b'\n').count() as u64;
+            })?;
+            Ok(count)
+        })?;
+        let bases: Vec<u64> = counts
+            .iter()
+            .scan(first_row, |row, count| {
+                let base = *row;
+                *row += count;
+                Some(base)
+            })
+            .collect();
+        let ranges: Vec<(Range<u64>, u64)> = ranges.into_iter().zip(bases).collect();
+        let offsets = in_parallel(&ranges, |(range, base)| {
+            let (mut row, mut offsets) = (*base, Vec::new());
+            scan(path, range.clone(), |block, at| {
+                for (i, _) in block.iter().enumerate().filter(|(_, &b)| b == b'\n') {
+                    row += 1;
+                    let start = at + i as u64 + 1;
+                    if row % interval == 0 && start < size {
+                        offsets.push((row, start));
+                    }
+                }
+            })?;
+            Ok(offsets)
+        })?;
+
+        let index = format!("{}.idx", path);
+        let mut writer = BufWriter::new(File::create(&index)?);
+        if first_row % interval == 0 && size > 0 {
+            writeln!(writer, "{} 0", first_row)?;
+        }
+        for (row, offset) in offsets.into_iter().flatten() {
+            writeln!(writer, "{} {}", row, offset)?;
+        }
+        writer.flush()?;
+        indexes.push(index);
+        first_row += counts.iter().sum::<u64>();
+    }
+    Ok(indexes)
+}
+
+/// `work` done on every one of `items` at once, in order
+fn in_parallel<T: Sync, R: Send>(
+    items: &[T],
+    work: impl Fn(&T) -> Result<R> + Sync,
+) -> Result<Vec<R>> {
+    thread::scope(|scope| {
+        let handles: Vec<_> = items
+            .iter()
+            .map(|item| scope.spawn(|| work(item)))
+            .collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("An indexer panicked"))
+            .collect()
+    })
+}
+
+/// Hands `range` of `path` to `visit` a block at a time, with the offset
+/// each block starts at
+fn scan(path: &str, range: Range<u64>, mut visit: impl FnMut(&[u8], u64)) -> Result<()> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(range.start))?;
+    let mut reader = file.take(range.end - range.start);
+    let mut block = vec![0; 1 << 20];
+    let mut at = range.start;
+    loop {
+        let read = reader.read(&mut block)?;
+        if read == 0 {
+            return Ok(());
+        }
+        visit(&block[..read], at);
+        at += read as u64;
+    }
+}
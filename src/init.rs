@@ -0,0 +1,130 @@
+use std::{
+    fs,
+    io::{self, BufRead},
+    path::Path,
+};
+
+use eyre::{eyre, Result, WrapErr};
+
+use crate::{
+    cli::{parse_count, with_stations, Args},
+    compress::{Codec, Compression},
+    summary::json_string,
+    summary::Summary,
+};
+
+/// Asks for the row count, station list, output format, and output path,
+/// offering those of `args`, writes them to `config` as a config `batch`
+/// reads, and, if asked to, generates the dataset with `generate`
+pub fn init<G>(args: &Args, config: &str, generate: G) -> Result<()>
+where
+    G: Fn(Args) -> Result<Summary>,
+{
+    let mut input = io::stdin().lock();
+    eprintln!("Answer each question, or press enter for the answer in brackets.");
+    let rows = ask(
+        &mut input,
+        "Rows to generate, such as 1B or 10M",
+        &args.rows.to_string(),
+        parse_count,
+    )?;
+    let weather_stations = ask(
+        &mut input,
+        "Station list, as a path or an http(s) URL",
+        &args.weather_stations,
+        |answer| {
+            let url = answer.starts_with("http://") || answer.starts_with("https://");
+            match url || Path::new(answer).is_file() {
+                true => Ok(answer.to_string()),
+                false => Err(eyre!("{} isn't a file", answer)),
+            }
+        },
+    )?;
+    let gzip = match cfg!(feature = "compression") {
+        true => ask(
+            &mut input,
+            "Format, plain or gzip",
+            "plain",
+            |answer| match answer {
+                "plain" => Ok(false),
+                "gzip" => Ok(true),
+                _ => Err(eyre!("Answer plain or gzip")),
+            },
+        )?,
+        false => false,
+    };
+    let default_output = match gzip {
+        true if !args.output.ends_with(".gz") => format!("{}.gz", args.output),
+        _ => args.output.clone(),
+    };
+    let output = ask(&mut input, "Output path", &default_output, |answer| {
+        Ok(answer.to_string())
+    })?;
+    if Path::new(config).exists() && !ask(&mut input, &format!("Replace {}?", config), "n", yes)? {
+        return Err(eyre!("{} already exists", config));
+    }
+
+    let mut toml = format!(
+        "# Generate with `billion-row-gen batch --config {}`\n\
+         rows = {}\nweather-stations = {}\noutput = {}\n",
+        config,
+        rows,
+        json_string(&weather_stations),
+        json_string(&output)
+    );
+    if gzip {
+        toml.push_str("compress = \"gzip\"\n");
+    }
+    fs::write(config, toml).wrap_err_with(|| format!("Could not write {}", config))?;
+    eprintln!("Wrote {}", config);
+
+    if !ask(&mut input, "Generate the dataset now?", "y", yes)? {
+        return Ok(());
+    }
+    drop(input);
+    generate(with_stations(&Args {
+        command: None,
+        rows,
+        weather_stations,
+        output,
+        compress: gzip.then_some(Compression {
+            codec: Codec::Gzip,
+            level: None,
+        }),
+        ..args.clone()
+    })?)
+    .map(drop)
+}
+
+/// Asks `question` until `parse` accepts the answer, or an empty one stands
+/// for `default`
+fn ask<T>(
+    input: &mut impl BufRead,
+    question: &str,
+    default: &str,
+    parse: impl Fn(&str) -> Result<T>,
+) -> Result<T> {
+    loop {
+        eprint!("{} [{}]: ", question, default);
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Err(eyre!("Stdin ended before `{}` was answered", question));
+        }
+        let answer = match line.trim() {
+            "" => default,
+            answer => answer,
+        };
+        match parse(answer) {
+            Ok(value) => return Ok(value),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+}
+
+fn yes(answer: &str) -> Result<bool> {
+    match answer.to_ascii_lowercase().as_str() {
+        "y" | "yes" => Ok(true),
+        "n" | "no" => Ok(false),
+        _ => Err(eyre!("Answer y or n")),
+    }
+}
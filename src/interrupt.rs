@@ -0,0 +1,95 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use eyre::Result;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+static PAUSED: AtomicBool = AtomicBool::new(false);
+
+/// Whether Ctrl-C has been pressed
+pub fn requested() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+/// Whether a run should stop: on Ctrl-C, or once its `--duration`
+/// `deadline` has passed. Generation checks this between chunks, so the
+/// output still ends on a whole line.
+pub fn stopping(deadline: Option<Instant>) -> bool {
+    requested() || past(deadline)
+}
+
+/// Whether a run stopped for running out of time rather than Ctrl-C, which
+/// finishes it as if it had reached `--rows`
+pub fn out_of_time(deadline: Option<Instant>) -> bool {
+    !requested() && past(deadline)
+}
+
+fn past(deadline: Option<Instant>) -> bool {
+    deadline.is_some_and(|deadline| Instant::now() >= deadline)
+}
+
+/// Whether SIGUSR1 has paused generation, until the next one resumes it
+pub fn paused() -> bool {
+    PAUSED.load(Ordering::Relaxed)
+}
+
+/// Blocks until generation is resumed, or interrupted
+pub fn wait_while_paused() {
+    while paused() && !requested() {
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Catches the first Ctrl-C, asking generation to stop after the chunks in
+/// flight, after which a second one exits immediately; and SIGUSR1, which
+/// pauses and resumes generation in turn
+#[cfg(unix)]
+pub fn install() -> Result<()> {
+    extern "C" fn on_interrupt(_: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::Relaxed);
+    }
+    extern "C" fn on_pause(_: libc::c_int) {
+        PAUSED.fetch_xor(true, Ordering::Relaxed);
+    }
+
+    // Back to the default after the first Ctrl-C, so the next one kills
+    catch(libc::SIGINT, on_interrupt, libc::SA_RESETHAND)?;
+    catch(libc::SIGUSR1, on_pause, 0)
+}
+
+#[cfg(unix)]
+fn catch(
+    signal: libc::c_int,
+    handler: extern "C" fn(libc::c_int),
+    flags: libc::c_int,
+) -> Result<()> {
+    use std::{io, mem, ptr};
+
+    use eyre::eyre;
+
+    // SAFETY: the handlers only update atomics, which is async signal safe,
+    // and the action is fully initialized before it is installed
+    unsafe {
+        let mut action: libc::sigaction = mem::zeroed();
+        action.sa_sigaction = handler as libc::sighandler_t;
+        action.sa_flags = flags | libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        if libc::sigaction(signal, &action, ptr::null_mut()) != 0 {
+            return Err(eyre!(
+                "Could not catch signal {}: {}",
+                signal,
+                io::Error::last_os_error()
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn install() -> Result<()> {
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use std::{fmt, iter, str::FromStr};
+
+use eyre::{eyre, Report, Result};
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng};
+
+use crate::station::Stations;
+
+/// What separates the repeats of a name padded out to a longer length
+const REPEAT_SEPARATOR: char = '-';
+
+/// `<bytes>:<weight>` pairs such as `1:10,50:60,100:30`: the lengths station
+/// names are written at, each in a share of the rows set by its weight
+#[derive(Debug, Clone)]
+pub struct LengthMix {
+    classes: Vec<(usize, f64)>,
+    sampler: WeightedIndex<f64>,
+}
+
+impl FromStr for LengthMix {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut classes = Vec::new();
+        for pair in s.split(',') {
+            let (length, weight) = pair
+                .split_once(':')
+                .ok_or_else(|| eyre!("Expected `<bytes>:<weight>`, not `{}`", pair))?;
+            let length: usize = length
+                .trim()
+                .parse()
+                .map_err(|_| eyre!("Invalid name length `{}`", length))?;
+            let weight: f64 = weight
+                .trim()
+                .parse()
+                .map_err(|_| eyre!("Invalid weight `{}`", weight))?;
+            if length == 0 {
+                return Err(eyre!("Name lengths must be at least 1 byte"));
+            }
+            if classes.iter().any(|&(seen, _)| seen == length) {
+                return Err(eyre!("The name length {} is given twice", length));
+            }
+            classes.push((length, weight));
+        }
+        let sampler = WeightedIndex::new(classes.iter().map(|&(_, weight)| weight))
+            .map_err(|e| eyre!("Invalid name length weights: {}", e))?;
+        Ok(Self { classes, sampler })
+    }
+}
+
+impl fmt::Display for LengthMix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pairs: Vec<_> = self
+            .classes
+            .iter()
+            .map(|(length, weight)| format!("{}:{}", length, weight))
+            .collect();
+        f.write_str(&pairs.join(","))
+    }
+}
+
+impl LengthMix {
+    /// A copy of `stations` at each length in turn, every name cut or
+    /// padded to exactly that many bytes; station `i` at the `class`th
+    /// length is at `class * stations.len() + i`
+    pub fn resize(&self, stations: &Stations) -> Stations {
+        let mut resized = Stations::default();
+        for &(length, _) in &self.classes {
+            for station in stations.iter() {
+                let name = resized_name(stations.name(station), length);
+                resized.push(&name, station.mean, station.stddev, station.weight);
+            }
+        }
+        resized
+    }
+
+    /// Which length the next row's name is written at
+    pub fn pick<R: Rng>(&self, rng: &mut R) -> usize {
+        self.sampler.sample(rng)
+    }
+
+    /// The average length of a name over many rows
+    pub fn mean_length(&self) -> usize {
+        let total: f64 = self.classes.iter().map(|(_, weight)| weight).sum();
+        let weighted: f64 = self
+            .classes
+            .iter()
+            .map(|&(length, weight)| length as f64 * weight)
+            .sum();
+        (weighted / total).round() as usize
+    }
+}
+
+/// `name` cut to its longest prefix of whole characters within `length`
+/// bytes, or repeated until it fills them, topped up with the separator
+/// where a character wouldn't fit
+fn resized_name(name: &str, length: usize) -> String {
+    let mut resized = String::with_capacity(length);
+    let repeated = name.chars().chain(iter::once(REPEAT_SEPARATOR)).cycle();
+    for c in repeated {
+        if resized.len() + c.len_utf8() > length {
+            break;
+        }
+        resized.push(c);
+    }
+    while resized.len() < length {
+        resized.push(REPEAT_SEPARATOR);
+    }
+    resized
+}
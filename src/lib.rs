@@ -0,0 +1,151 @@
+// Without the command line, the modules the row iterator shares with it
+// hold more than the iterator uses
+#![cfg_attr(not(feature = "cli"), allow(dead_code))]
+
+#[cfg(feature = "cli")]
+pub use cli::run;
+#[cfg(feature = "cli")]
+pub use in_memory::{fill, generate_into};
+pub use in_memory::{fill_rows, generate_rows_into};
+pub use rows::{Rows, RowsConfig};
+
+#[cfg(feature = "cli")]
+mod affinity;
+#[cfg(feature = "cli")]
+mod answer;
+#[cfg(feature = "cli")]
+mod archive;
+#[cfg(feature = "cli")]
+mod batch;
+#[cfg(feature = "cli")]
+mod broker;
+#[cfg(feature = "cli")]
+mod bzip2;
+#[cfg(feature = "cli")]
+mod cache;
+#[cfg(feature = "cli")]
+mod census;
+#[cfg(feature = "cli")]
+mod checkpoint;
+#[cfg(feature = "cli")]
+mod checksum;
+#[cfg(feature = "cli")]
+mod cli;
+#[cfg(feature = "cli")]
+mod completions;
+#[cfg(feature = "cli")]
+mod compress;
+#[cfg(feature = "cli")]
+mod dashboard;
+#[cfg(all(
+    feature = "cli",
+    any(target_os = "linux", target_os = "macos", windows)
+))]
+mod direct;
+mod distribution;
+#[cfg(feature = "cli")]
+mod drift;
+#[cfg(feature = "cli")]
+mod dups;
+mod encode;
+#[cfg(feature = "cli")]
+mod entropy;
+#[cfg(feature = "cli")]
+mod environment;
+#[cfg(feature = "cli")]
+mod errors;
+#[cfg(feature = "cli")]
+mod expr;
+#[cfg(feature = "cli")]
+mod extract;
+#[cfg(feature = "ffi")]
+mod ffi;
+#[cfg(feature = "cli")]
+mod filter;
+#[cfg(feature = "cli")]
+mod fixtures;
+#[cfg(feature = "cli")]
+mod fuzz;
+#[cfg(feature = "cli")]
+mod huge_pages;
+mod in_memory;
+#[cfg(feature = "cli")]
+mod index;
+#[cfg(feature = "cli")]
+mod init;
+#[cfg(feature = "cli")]
+mod interrupt;
+mod json;
+#[cfg(feature = "cli")]
+mod length_mix;
+#[cfg(feature = "cli")]
+mod logging;
+#[cfg(feature = "cli")]
+mod manifest;
+#[cfg(feature = "cli")]
+mod memory;
+#[cfg(unix)]
+mod mmap;
+mod model;
+#[cfg(feature = "cli")]
+mod noaa;
+mod normalize;
+#[cfg(feature = "cli")]
+mod notify;
+#[cfg(feature = "cli")]
+mod orc;
+mod order;
+#[cfg(feature = "cli")]
+mod output;
+#[cfg(all(feature = "cli", unix))]
+mod parallel;
+#[cfg(feature = "cli")]
+mod passthrough;
+#[cfg(feature = "cli")]
+mod pattern;
+#[cfg(feature = "cli")]
+mod preset;
+#[cfg(feature = "cli")]
+mod progress;
+#[cfg(feature = "cli")]
+mod protobuf;
+#[cfg(feature = "cli")]
+mod regen;
+#[cfg(feature = "cli")]
+mod repro;
+#[cfg(feature = "cli")]
+mod results;
+mod rng;
+#[cfg(feature = "cli")]
+mod rolling;
+mod rows;
+#[cfg(feature = "cli")]
+mod schema;
+#[cfg(feature = "cli")]
+mod shuffle;
+#[cfg(feature = "cli")]
+mod snappy;
+#[cfg(feature = "cli")]
+mod solve;
+#[cfg(feature = "cli")]
+mod sort;
+mod station;
+#[cfg(feature = "cli")]
+mod summary;
+#[cfg(feature = "cli")]
+mod template;
+#[cfg(feature = "cli")]
+mod toml;
+#[cfg(feature = "cli")]
+mod transform;
+#[cfg(feature = "cli")]
+mod tune;
+#[cfg(all(feature = "cli", target_os = "linux"))]
+mod uring;
+#[cfg(feature = "cli")]
+mod verify;
+#[cfg(feature = "cli")]
+mod watch;
+
+const MIN_TEMP: i32 = -999; // -99.9C
+const MAX_TEMP: i32 = 999; // 99.9C
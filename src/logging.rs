@@ -0,0 +1,38 @@
+use std::io;
+
+use clap::ValueEnum;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::fmt::format::FmtSpan;
+
+/// The most detailed messages logged to stderr
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogLevel {
+    Off,
+    Error,
+    Warn,
+    /// Decisions made on the user's behalf, such as auto-tuned settings
+    Info,
+    /// Also the time spent in each phase: loading stations, generating,
+    /// writing, compressing, and shuffling
+    Debug,
+    Trace,
+}
+
+/// Logs to stderr from here on, reporting how long each span took as it
+/// closes
+pub fn init(level: LogLevel) {
+    let filter = match level {
+        LogLevel::Off => LevelFilter::OFF,
+        LogLevel::Error => LevelFilter::ERROR,
+        LogLevel::Warn => LevelFilter::WARN,
+        LogLevel::Info => LevelFilter::INFO,
+        LogLevel::Debug => LevelFilter::DEBUG,
+        LogLevel::Trace => LevelFilter::TRACE,
+    };
+    tracing_subscriber::fmt()
+        .with_max_level(filter)
+        .with_writer(io::stderr)
+        .with_target(false)
+        .with_span_events(FmtSpan::CLOSE)
+        .init();
+}
@@ -0,0 +1,213 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+};
+
+use clap::ValueEnum;
+use eyre::Result;
+
+use crate::{
+    checksum::{Algorithm, Checksum},
+    cli::{complete, Args, Written},
+    environment,
+    model::ModelSpec,
+    output::Format,
+    summary::json_string,
+    template,
+};
+
+/// The options that shape the rows generated, as the arguments that
+/// reproduce them, leaving out those that only change how rows are written
+pub fn generation_args(args: &Args, seed: u64) -> Vec<(&'static str, String)> {
+    let mut options = vec![("--rows", args.rows.to_string())];
+    if args.start_row > 0 {
+        options.push(("--start-row", args.start_row.to_string()));
+    }
+    if let Some(end_row) = args.end_row {
+        options.push(("--end-row", end_row.to_string()));
+    }
+    match &args.schema {
+        Some(schema) => options.push(("--schema", schema.clone())),
+        None => options.push((
+            "--weather-stations",
+            args.stations_url
+                .clone()
+                .unwrap_or_else(|| args.weather_stations.clone()),
+        )),
+    }
+    if let (None, Some(sha256)) = (&args.schema, &args.stations_sha256) {
+        options.push(("--stations-sha256", sha256.clone()));
+    }
+    if let (None, Some(max)) = (&args.schema, args.max_stations) {
+        options.push(("--max-stations", max.to_string()));
+    }
+    if args.schema.is_none() {
+        options.push(("--normalize", value_name(args.normalize)));
+        options.push(("--bad-name-policy", value_name(args.bad_name_policy)));
+        options.push(("--quote", value_name(args.quote)));
+    }
+    let files = [
+        ("--station-params", &args.station_params),
+        ("--script", &args.script),
+    ];
+    for (option, path) in files {
+        if let Some(path) = path {
+            options.push((option, path.clone()));
+        }
+    }
+    let model = match args.model {
+        ModelSpec::Iid => "iid".to_string(),
+        ModelSpec::Ar1(phi) => format!("ar1:{}", phi),
+    };
+    options.extend([
+        ("--model", model),
+        ("--distribution", value_name(args.distribution)),
+        ("--pattern", value_name(args.pattern)),
+        ("--entropy", value_name(args.entropy)),
+        ("--order", value_name(args.order)),
+        ("--rng", value_name(args.rng)),
+        ("--seed", seed.to_string()),
+    ]);
+    if let Some(distinct_temps) = args.distinct_temps {
+        options.push(("--distinct-temps", distinct_temps.to_string()));
+    }
+    if let Some(rate) = args.negative_zero_rate {
+        options.push(("--negative-zero-rate", rate.to_string()));
+    }
+    if let Some(mix) = &args.name_length_mix {
+        options.push(("--name-length-mix", mix.to_string()));
+    }
+    if !args.passthrough_columns.is_empty() {
+        options.push(("--passthrough-columns", args.passthrough_columns.join(",")));
+    }
+    if let Some(compression) = args.compress {
+        options.push(("--compress", compression.to_string()));
+    }
+    if let Some(threads) = args.compress_threads {
+        options.push(("--compress-threads", threads.to_string()));
+    }
+    if let Some(size) = args.max_file_size {
+        options.push(("--max-file-size", size.to_string()));
+    }
+    if args.format != Format::Text {
+        options.push(("--format", value_name(args.format)));
+        if args.format == Format::Orc {
+            options.push(("--stripe-size", args.stripe_size.to_string()));
+        }
+    }
+    options
+}
+
+pub fn value_name<T: ValueEnum>(value: T) -> String {
+    value
+        .to_possible_value()
+        .expect("Options have no skipped values")
+        .get_name()
+        .to_string()
+}
+
+/// Writes `<output>.manifest.json`, recording the version, the arguments
+/// that regenerate the output, any `--seed-label` the seed came from, the
+/// SHA-256 of every input file they name, and what was written. An
+/// interrupted run is recorded as incomplete, with the rows it did write,
+/// and the `output_path` they were written to. Output split with
+/// `--max-file-size` also lists its `parts` in order.
+pub fn write(
+    args: &Args,
+    seed: u64,
+    written: Written,
+    output_path: &str,
+    parts: &[String],
+    checksum: Option<&str>,
+) -> Result<()> {
+    let options = generation_args(args, seed);
+    let command = ["billion-row-gen".to_string()]
+        .into_iter()
+        .chain(
+            options
+                .iter()
+                .flat_map(|(option, value)| [option.to_string(), value.clone()]),
+        )
+        .chain(["--output".to_string(), args.output.clone()])
+        .map(|arg| json_string(&arg))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut files = Vec::new();
+    for (option, value) in &options {
+        let is_file = matches!(
+            *option,
+            "--weather-stations" | "--schema" | "--station-params" | "--script"
+        );
+        if is_file {
+            // A downloaded station list is recorded by its URL
+            let path = match *option {
+                "--weather-stations" => &args.weather_stations,
+                _ => value,
+            };
+            files.push(format!(
+                "    {}: {{ \"path\": {}, \"sha256\": \"{}\" }}",
+                json_string(option.trim_start_matches('-')),
+                json_string(value),
+                file_sha256(path)?
+            ));
+        }
+    }
+    let checksum = match checksum {
+        Some(checksum) => json_string(checksum),
+        None => "null".to_string(),
+    };
+    let parts = match parts {
+        [] => String::new(),
+        parts => format!(
+            ", \"parts\": [{}]",
+            parts
+                .iter()
+                .map(|part| json_string(part))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    let seed_label = match &args.seed_label {
+        Some(label) => format!("\n  \"seed_label\": {},", json_string(label)),
+        None => String::new(),
+    };
+    let json = format!(
+        "{{\n  \"version\": {},\n  \"command\": [{}],\n  \"seed\": {},{}\n  \
+         \"rows\": {},\n  \"complete\": {},\n  \"files\": {{\n{}\n  }},\n  \"output\": {{ \"path\": {}, \
+         \"bytes\": {}, \"checksum\": {}{} }},\n  \"environment\": {}\n}}\n",
+        json_string(env!("CARGO_PKG_VERSION")),
+        command,
+        seed,
+        seed_label,
+        written.rows,
+        complete(args, written),
+        files.join(",\n"),
+        json_string(output_path),
+        written.bytes,
+        checksum,
+        parts,
+        environment::json(output_path)
+    );
+    std::fs::write(path(&args.output), json)?;
+    Ok(())
+}
+
+/// Named after `output`, or what its parts' names share when it has a
+/// `{shard}` placeholder
+pub fn path(output: &str) -> String {
+    format!("{}.manifest.json", template::without_shard(output))
+}
+
+pub fn file_sha256(path: &str) -> Result<String> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut checksum = Checksum::new(Algorithm::Sha256);
+    let mut buf = vec![0; 64 << 10];
+    loop {
+        let read = reader.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        checksum.update(&buf[..read]);
+    }
+    Ok(checksum.finish())
+}
@@ -0,0 +1,104 @@
+use eyre::{eyre, Result};
+
+use crate::{
+    cli::{human_readable, Args},
+    output::IoBackend,
+};
+
+/// Smallest write buffer a memory limit shrinks `--write-buffer` to
+const MIN_WRITE_BUFFER: usize = 64 << 10;
+
+/// Smallest chunk a memory limit shrinks `--chunk-rows` to before it starts
+/// taking away threads
+const MIN_CHUNK_ROWS: u64 = 1_000;
+
+/// The settings that decide how much memory generation holds in buffers
+#[derive(Debug, Clone, Copy)]
+pub struct Plan {
+    pub chunk_rows: u64,
+    pub write_buffer: usize,
+    pub threads: usize,
+    /// Chunk buffers circulating between the generator and the writer
+    pub buffers: usize,
+    pub compress_threads: usize,
+    line_length: usize,
+    io: IoBackend,
+    compress: bool,
+}
+
+impl Plan {
+    pub fn new(args: &Args, line_length_estimate: usize, compress_threads: usize) -> Self {
+        let compress = args.compress.is_some();
+        Self {
+            chunk_rows: args.chunk_rows,
+            write_buffer: args.write_buffer,
+            threads: args.threads(),
+            buffers: args.queue_depth.unwrap_or(args.io.buffer_count())
+                + if compress { compress_threads } else { 0 },
+            compress_threads,
+            line_length: line_length_estimate,
+            io: args.io,
+            compress,
+        }
+    }
+
+    /// The expected size of one chunk, in bytes
+    pub fn chunk_len(&self) -> usize {
+        self.chunk_rows as usize * self.line_length
+    }
+
+    /// The bytes held in buffers at once
+    pub fn usage(&self) -> usize {
+        if self.threads > 1 {
+            return self.threads * self.chunk_len();
+        }
+        // Every compressor holds its output too, and one more waits for
+        // the writer
+        let compressed = if self.compress {
+            self.compress_threads + 1
+        } else {
+            0
+        };
+        let write_buffer = if self.io.uses_write_buffer() {
+            self.write_buffer
+        } else {
+            0
+        };
+        (self.buffers + compressed) * self.chunk_len() + write_buffer
+    }
+
+    /// Scales the plan down until its buffers fit in `limit` bytes: first
+    /// fewer buffers and compressors, then a smaller write buffer, smaller
+    /// chunks, fewer threads, and finally chunks of a single row
+    pub fn fit(mut self, limit: usize) -> Result<Self> {
+        let min_buffers = self.io.min_buffer_count();
+        while self.usage() > limit && (self.compress_threads > 1 || self.buffers > min_buffers) {
+            if self.compress_threads > 1 {
+                self.compress_threads -= 1;
+            }
+            if self.buffers > min_buffers {
+                self.buffers -= 1;
+            }
+        }
+        while self.usage() > limit && self.write_buffer > MIN_WRITE_BUFFER {
+            self.write_buffer = (self.write_buffer / 2).max(MIN_WRITE_BUFFER);
+        }
+        while self.usage() > limit && self.chunk_rows > MIN_CHUNK_ROWS {
+            self.chunk_rows = (self.chunk_rows / 2).max(MIN_CHUNK_ROWS);
+        }
+        while self.usage() > limit && self.threads > 1 {
+            self.threads -= 1;
+        }
+        while self.usage() > limit && self.chunk_rows > 1 {
+            self.chunk_rows /= 2;
+        }
+        if self.usage() > limit {
+            return Err(eyre!(
+                "--memory-limit {} is too small, generating needs at least {}",
+                human_readable(limit as u64),
+                human_readable(self.usage() as u64)
+            ));
+        }
+        Ok(self)
+    }
+}
@@ -0,0 +1,155 @@
+use std::{fs::File, os::unix::io::AsRawFd, ptr};
+
+use eyre::{eyre, Result};
+
+/// A file written through a shared memory map. The file is sized up front
+/// from an estimate, grown by remapping if the estimate falls short, and
+/// truncated to what was written once finished.
+pub struct MappedFile {
+    file: File,
+    map: *mut u8,
+    capacity: usize,
+    len: usize,
+}
+
+// The mapping is only ever accessed through `&mut self`
+unsafe impl Send for MappedFile {}
+
+impl MappedFile {
+    pub fn create(file: File, estimate: usize) -> Result<Self> {
+        let mut mapped = Self {
+            file,
+            map: ptr::null_mut(),
+            capacity: 0,
+            len: 0,
+        };
+        mapped.remap(estimate.max(page_size()))?;
+        Ok(mapped)
+    }
+
+    pub fn write_all(&mut self, bytes: &[u8]) -> Result<()> {
+        let needed = self.len + bytes.len();
+        if needed > self.capacity {
+            self.remap(needed.max(self.capacity * 2))?;
+        }
+        // SAFETY: the mapping covers `capacity` bytes, and `needed` fits
+        unsafe {
+            ptr::copy_nonoverlapping(bytes.as_ptr(), self.map.add(self.len), bytes.len());
+        }
+        self.len = needed;
+        Ok(())
+    }
+
+    /// Starts writing the mapped pages back to the file without waiting
+    pub fn flush(&mut self) -> Result<()> {
+        // SAFETY: the range lies within the current mapping
+        if unsafe { libc::msync(self.map.cast(), self.len, libc::MS_ASYNC) } != 0 {
+            return Err(eyre!("msync failed: {}", std::io::Error::last_os_error()));
+        }
+        Ok(())
+    }
+
+    /// Unmaps the file and truncates it to the bytes actually written
+    pub fn finish(mut self) -> Result<()> {
+        self.unmap();
+        self.file.set_len(self.len as u64)?;
+        Ok(())
+    }
+
+    fn remap(&mut self, capacity: usize) -> Result<()> {
+        self.unmap();
+        self.file.set_len(capacity as u64)?;
+        // SAFETY: a fresh shared mapping of the file, which is `capacity`
+        // bytes long; no other references into the old mapping remain
+        let map = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                capacity,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                self.file.as_raw_fd(),
+                0,
+            )
+        };
+        if map == libc::MAP_FAILED {
+            return Err(eyre!("mmap failed: {}", std::io::Error::last_os_error()));
+        }
+        self.map = map.cast();
+        self.capacity = capacity;
+        Ok(())
+    }
+
+    fn unmap(&mut self) {
+        if !self.map.is_null() {
+            // SAFETY: `map` and `capacity` describe the current mapping
+            unsafe { libc::munmap(self.map.cast(), self.capacity) };
+            self.map = ptr::null_mut();
+        }
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        self.unmap();
+    }
+}
+
+fn page_size() -> usize {
+    // SAFETY: sysconf has no preconditions
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+/// A file mapped read-only, so its bytes can be referred to in place rather
+/// than copied. The file mustn't change while it is mapped.
+#[derive(Debug)]
+pub struct ReadMap {
+    map: *mut u8,
+    len: usize,
+}
+
+// The mapping is never written through
+unsafe impl Send for ReadMap {}
+unsafe impl Sync for ReadMap {}
+
+impl ReadMap {
+    /// Maps the whole of `file`, or `None` if it is empty, which can't be
+    /// mapped
+    pub fn open(file: &File) -> Result<Option<Self>> {
+        let len = file.metadata()?.len() as usize;
+        if len == 0 {
+            return Ok(None);
+        }
+        // SAFETY: a fresh private, read-only mapping of the file's `len` bytes
+        let map = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if map == libc::MAP_FAILED {
+            return Err(eyre!("mmap failed: {}", std::io::Error::last_os_error()));
+        }
+        Ok(Some(Self {
+            map: map.cast(),
+            len,
+        }))
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        // SAFETY: the mapping covers `len` readable bytes for as long as
+        // `self` lives
+        unsafe { std::slice::from_raw_parts(self.map, self.len) }
+    }
+}
+
+impl Drop for ReadMap {
+    fn drop(&mut self) {
+        // SAFETY: `map` and `len` describe the mapping, which nothing
+        // borrows past `self`
+        unsafe { libc::munmap(self.map.cast(), self.len) };
+    }
+}
@@ -0,0 +1,156 @@
+use std::str::FromStr;
+
+use eyre::{eyre, Report, Result};
+use rand::{
+    distributions::{Distribution as _, Uniform},
+    Rng,
+};
+
+use crate::{
+    distribution::{standard_normal, Distribution},
+    station::Stations,
+    MAX_TEMP, MIN_TEMP,
+};
+
+/// Standard deviation of a station's temperatures around its mean, unless the
+/// station list gives one
+const STATION_STDDEV: f64 = 10.0;
+
+/// How each station's successive measurements relate to each other
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModelSpec {
+    /// Independent draws from the chosen `--distribution`
+    Iid,
+    /// `ar1:<phi>`: an AR(1) process around each station's mean temperature,
+    /// where `phi` in `(-1, 1)` is the correlation between successive values
+    Ar1(f64),
+}
+
+impl FromStr for ModelSpec {
+    type Err = Report;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once(':') {
+            None if s == "iid" => Ok(Self::Iid),
+            Some(("ar1", phi)) => {
+                let phi: f64 = phi
+                    .parse()
+                    .map_err(|_| eyre!("Invalid AR(1) coefficient `{}`", phi))?;
+                if !(-1.0 < phi && phi < 1.0) {
+                    return Err(eyre!("The AR(1) coefficient must be within (-1, 1)"));
+                }
+                Ok(Self::Ar1(phi))
+            }
+            _ => Err(eyre!("Expected `iid` or `ar1:<phi>`")),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Model {
+    kind: ModelKind,
+    /// Cached sampler for the default uniform distribution
+    uniform: Uniform<i32>,
+    means: Vec<f64>,
+    stddevs: Vec<f64>,
+}
+
+#[derive(Clone)]
+enum ModelKind {
+    Iid(Distribution),
+    Ar1 {
+        phi: f64,
+        /// The previous measurement of each station, if it had one
+        previous: Vec<Option<f64>>,
+    },
+}
+
+impl Model {
+    pub fn new(spec: ModelSpec, distribution: Distribution, stations: &Stations) -> Result<Self> {
+        let kind = match spec {
+            ModelSpec::Iid => ModelKind::Iid(distribution),
+            ModelSpec::Ar1(phi) => ModelKind::Ar1 {
+                phi,
+                previous: vec![None; stations.len()],
+            },
+        };
+        // Only the uniform distribution can do without per-station means
+        let means = stations
+            .iter()
+            .map(|s| match (s.mean, &kind) {
+                (Some(mean), _) => Ok(mean),
+                (None, ModelKind::Iid(Distribution::Uniform)) => Ok(0.0),
+                (None, _) => Err(eyre!(
+                    "Station {} has no mean temperature",
+                    stations.name(s)
+                )),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let stddevs = stations
+            .iter()
+            .map(|s| s.stddev.unwrap_or(STATION_STDDEV))
+            .collect();
+        Ok(Self {
+            kind,
+            uniform: Uniform::new_inclusive(MIN_TEMP, MAX_TEMP),
+            means,
+            stddevs,
+        })
+    }
+
+    /// Samples the next measurement of `station`, in tenths of a degree
+    pub fn sample<R: Rng>(&mut self, station: usize, rng: &mut R) -> i32 {
+        let (mean, stddev) = (self.means[station], self.stddevs[station]);
+        let value = match &mut self.kind {
+            ModelKind::Iid(Distribution::Uniform) => {
+                return self.uniform.sample(rng);
+            }
+            ModelKind::Iid(distribution) => distribution.sample(mean, stddev, rng),
+            ModelKind::Ar1 { phi, previous } => {
+                let value = match previous[station] {
+                    // Start from the stationary distribution
+                    None => mean + stddev * standard_normal(rng),
+                    // Innovations scaled to keep the stationary stddev
+                    Some(last) => {
+                        let noise = stddev * (1.0 - *phi * *phi).sqrt();
+                        mean + *phi * (last - mean) + noise * standard_normal(rng)
+                    }
+                };
+                previous[station] = Some(value);
+                value
+            }
+        };
+        ((value * 10.0).round() as i32).clamp(MIN_TEMP, MAX_TEMP)
+    }
+}
+
+/// Restricts measurements to a fixed number of evenly spaced values
+pub struct DistinctTemps(Vec<i32>);
+
+impl DistinctTemps {
+    pub fn new(count: u32) -> Result<Self> {
+        let range = (MAX_TEMP - MIN_TEMP) as u32 + 1;
+        if count == 0 || count > range {
+            return Err(eyre!("--distinct-temps must be between 1 and {}", range));
+        }
+        if count == 1 {
+            return Ok(Self(vec![0]));
+        }
+        let step = f64::from(MAX_TEMP - MIN_TEMP) / f64::from(count - 1);
+        Ok(Self(
+            (0..count)
+                .map(|i| MIN_TEMP + (f64::from(i) * step).round() as i32)
+                .collect(),
+        ))
+    }
+
+    /// Snaps `measurement` to the nearest allowed value
+    pub fn snap(&self, measurement: i32) -> i32 {
+        let i = self.0.partition_point(|v| *v < measurement);
+        match (i.checked_sub(1).map(|i| self.0[i]), self.0.get(i)) {
+            (Some(below), Some(above)) if measurement - below <= above - measurement => below,
+            (_, Some(above)) => *above,
+            (below, None) => below.expect("At least one distinct value"),
+        }
+    }
+}
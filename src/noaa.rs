@@ -0,0 +1,112 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+};
+
+use eyre::{eyre, Result};
+
+/// Columns holding a station's temperature, in order of preference. `TEMP`
+/// is the GSOD daily mean; the others are from the climate normals products.
+const TEMPERATURE_COLUMNS: [&str; 4] = ["TEMP", "DLY-TAVG-NORMAL", "MLY-TAVG-NORMAL", "TAVG"];
+
+/// Running totals of one station's observations
+#[derive(Default)]
+struct Observations {
+    count: u64,
+    sum: f64,
+    sum_of_squares: f64,
+}
+
+/// Converts NOAA GSOD daily summary or climate normals CSV files into a
+/// station list with `name;mean;stddev;weight` columns, where the weight is
+/// the number of observations the station contributed
+pub fn import_noaa(files: &[String], output: Option<&str>, celsius: bool) -> Result<()> {
+    let mut stations: BTreeMap<String, Observations> = BTreeMap::new();
+    for path in files {
+        let file = File::open(path).map_err(|e| eyre!("Could not open {}: {}", path, e))?;
+        let mut lines = BufReader::new(file).lines();
+        let header = match lines.next() {
+            Some(header) => split_csv(&header?),
+            None => continue,
+        };
+        let column = |name: &str| header.iter().position(|h| h == name);
+        let name_column = column("NAME").ok_or_else(|| eyre!("{} has no NAME column", path))?;
+        let temp_column = TEMPERATURE_COLUMNS
+            .iter()
+            .find_map(|c| column(c))
+            .ok_or_else(|| eyre!("{} has no temperature column", path))?;
+
+        for line in lines {
+            let fields = split_csv(&line?);
+            let (Some(name), Some(temp)) = (fields.get(name_column), fields.get(temp_column))
+            else {
+                continue;
+            };
+            let Some(temp) = parse_temperature(temp) else {
+                continue;
+            };
+            let temp = if celsius {
+                temp
+            } else {
+                (temp - 32.0) * 5.0 / 9.0
+            };
+            // The name becomes the first column of a semicolon separated list
+            let observations = stations.entry(name.trim().replace(';', ",")).or_default();
+            observations.count += 1;
+            observations.sum += temp;
+            observations.sum_of_squares += temp * temp;
+        }
+    }
+
+    let mut writer: Box<dyn Write> = match output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+    writeln!(writer, "# Imported from NOAA data: name;mean;stddev;weight")?;
+    for (name, observations) in stations {
+        let count = observations.count as f64;
+        let mean = observations.sum / count;
+        let variance = (observations.sum_of_squares / count - mean * mean).max(0.0);
+        writeln!(
+            writer,
+            "{};{:.1};{:.1};{}",
+            name,
+            mean,
+            variance.sqrt(),
+            observations.count
+        )?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// NOAA marks missing values with runs of nines or negative sentinels
+fn parse_temperature(field: &str) -> Option<f64> {
+    let value: f64 = field.trim().parse().ok()?;
+    if value >= 9999.0 || value <= -777.0 {
+        return None;
+    }
+    Some(value)
+}
+
+/// Splits one CSV line, honoring double quoted fields
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
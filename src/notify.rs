@@ -0,0 +1,73 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use eyre::{eyre, Result};
+
+use crate::summary::{json_string, Summary};
+
+/// Accepts `http://` and `https://` URLs, so a typo is caught before a long
+/// run rather than after it
+pub fn parse_url(url: &str) -> Result<String> {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        Ok(url.to_string())
+    } else {
+        Err(eyre!("`{}` is not an http:// or https:// URL", url))
+    }
+}
+
+/// POSTs how the run producing `output` ended to `url`: its summary once
+/// complete, or the error it failed with. The run is over by now, so a
+/// notification that can't be delivered is only logged.
+pub fn send(url: &str, output: &str, result: &Result<Summary>) {
+    let json = match result {
+        Ok(summary) => format!(
+            "{{\"event\":\"completed\",\"summary\":{}}}\n",
+            summary.json().trim_end()
+        ),
+        Err(error) => format!(
+            "{{\"event\":\"failed\",\"output\":{},\"error\":{}}}\n",
+            json_string(output),
+            json_string(&format!("{:#}", error))
+        ),
+    };
+    if let Err(e) = post(url, &json) {
+        tracing::warn!("Could not notify {}: {}", url, e);
+    }
+}
+
+/// Hands the request to curl, which brings TLS, proxies, and redirects
+fn post(url: &str, json: &str) -> Result<()> {
+    let _span = tracing::debug_span!("notify").entered();
+    let mut curl = Command::new("curl")
+        .args([
+            "--silent",
+            "--show-error",
+            "--fail",
+            "--max-time",
+            "30",
+            "--header",
+            "Content-Type: application/json",
+            "--data-binary",
+            "@-",
+            url,
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| eyre!("could not run curl: {}", e))?;
+    curl.stdin
+        .take()
+        .expect("curl's stdin is piped")
+        .write_all(json.as_bytes())?;
+    let finished = curl.wait_with_output()?;
+    if !finished.status.success() {
+        return Err(eyre!(
+            "{}",
+            String::from_utf8_lossy(&finished.stderr).trim()
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,281 @@
+use std::{
+    mem,
+    sync::mpsc::{Receiver, Sender, SyncSender},
+};
+
+use eyre::{eyre, Result};
+
+use crate::{
+    extract::{measurement, unquoted},
+    protobuf::{push_varint, Proto},
+};
+
+/// Column ids: the root struct, then its fields in order
+const STATION: u64 = 1;
+const MEASUREMENT: u64 = 2;
+
+/// Type kinds, stream kinds, and column encodings, as orc_proto.proto
+/// numbers them
+const DOUBLE: u64 = 6;
+const STRING: u64 = 7;
+const STRUCT: u64 = 12;
+const DATA: u64 = 1;
+const LENGTH: u64 = 2;
+const DIRECT: u64 = 0;
+
+/// Values in one literal run of RLE version 1
+const MAX_LITERALS: usize = 128;
+
+/// Encodes chunks of `station;measurement` lines as an ORC file between the
+/// generator and the writer, returning each text buffer on `spent` once its
+/// rows are taken and handing the file's bytes on as each stripe is
+/// complete, then its footer once `chunks` ends. Stops at the first row that
+/// isn't a measurement.
+pub fn encode_chunks(
+    stripe_size: usize,
+    chunks: Receiver<Vec<u8>>,
+    spent: Sender<Vec<u8>>,
+    encoded: SyncSender<Vec<u8>>,
+) -> Result<()> {
+    let _span = tracing::debug_span!("orc_encoder").entered();
+    let mut encoder = Encoder::new(stripe_size);
+    let mut output = b"ORC".to_vec();
+    for chunk in chunks {
+        let pushed = encoder.push(&chunk, &mut output);
+        // The generator stops taking buffers once it has run out of rows
+        let _ = spent.send(chunk);
+        pushed?;
+        // Only fails once the writer has failed, which it reports
+        if !output.is_empty() && encoded.send(mem::take(&mut output)).is_err() {
+            return Ok(());
+        }
+    }
+    encoder.finish(&mut output);
+    let _ = encoded.send(output);
+    Ok(())
+}
+
+/// Lays rows out as an uncompressed ORC file of a `station` string column
+/// and a `measurement` double column, starting a stripe once the rows
+/// gathered take `stripe_size` bytes as columns. Streams use the original
+/// `DIRECT` encodings and there is no row index, which every ORC reader
+/// accepts.
+struct Encoder {
+    stripe_size: usize,
+    /// Bytes of the file so far, where the next stripe starts
+    offset: u64,
+    stripes: Vec<Stripe>,
+    /// The station names of the stripe being gathered, back to back
+    names: Vec<u8>,
+    lengths: Vec<u64>,
+    /// Its measurements, as little endian doubles
+    measurements: Vec<u8>,
+    stations: StringStats,
+    temps: DoubleStats,
+}
+
+/// Where a stripe was written, as the file footer records it
+struct Stripe {
+    offset: u64,
+    data_length: u64,
+    footer_length: u64,
+    rows: u64,
+}
+
+#[derive(Default)]
+struct StringStats {
+    min: Option<Vec<u8>>,
+    max: Option<Vec<u8>>,
+    total_length: u64,
+}
+
+struct DoubleStats {
+    count: u64,
+    min: f64,
+    max: f64,
+    sum: f64,
+}
+
+impl Encoder {
+    fn new(stripe_size: usize) -> Self {
+        Self {
+            stripe_size,
+            offset: 3,
+            stripes: Vec::new(),
+            names: Vec::new(),
+            lengths: Vec::new(),
+            measurements: Vec::new(),
+            stations: StringStats::default(),
+            temps: DoubleStats {
+                count: 0,
+                min: f64::INFINITY,
+                max: f64::NEG_INFINITY,
+                sum: 0.0,
+            },
+        }
+    }
+
+    /// Adds the `station;measurement` rows of `chunk`, each ending in a
+    /// newline, appending any stripe they complete to `output`
+    fn push(&mut self, chunk: &[u8], output: &mut Vec<u8>) -> Result<()> {
+        let Some(rows) = chunk.strip_suffix(b"\n") else {
+            return Ok(());
+        };
+        for row in rows.split(|&b| b == b'\n') {
+            let split = row
+                .iter()
+                .rposition(|&b| b == b';')
+                .ok_or_else(|| eyre!("{:?} isn't a measurement", String::from_utf8_lossy(row)))?;
+            let name = unquoted(&row[..split]);
+            let temp = measurement(&row[split + 1..])?;
+            self.names.extend_from_slice(&name);
+            self.lengths.push(name.len() as u64);
+            self.measurements.extend_from_slice(&temp.to_le_bytes());
+            self.stations.add(&name);
+            self.temps.add(temp);
+            if self.names.len() + self.measurements.len() >= self.stripe_size {
+                self.write_stripe(output);
+            }
+        }
+        Ok(())
+    }
+
+    /// Appends the last stripe, then the footer and postscript that make
+    /// the file readable, to `output`
+    fn finish(mut self, output: &mut Vec<u8>) {
+        if !self.lengths.is_empty() {
+            self.write_stripe(output);
+        }
+        let rows = self.temps.count;
+
+        let mut footer = Proto::default();
+        footer.uint(1, 3);
+        footer.uint(2, self.offset);
+        for stripe in &self.stripes {
+            let mut info = Proto::default();
+            info.uint(1, stripe.offset);
+            info.uint(2, 0);
+            info.uint(3, stripe.data_length);
+            info.uint(4, stripe.footer_length);
+            info.uint(5, stripe.rows);
+            footer.message(3, &info);
+        }
+        let mut root = Proto::default();
+        root.uint(1, STRUCT);
+        root.packed(2, &[STATION, MEASUREMENT]);
+        root.bytes(3, b"station");
+        root.bytes(3, b"measurement");
+        footer.message(4, &root);
+        for kind in [STRING, DOUBLE] {
+            let mut field = Proto::default();
+            field.uint(1, kind);
+            footer.message(4, &field);
+        }
+        footer.uint(6, rows);
+        footer.message(7, &column_stats(rows, None));
+        let mut strings = Proto::default();
+        if let (Some(min), Some(max)) = (&self.stations.min, &self.stations.max) {
+            strings.bytes(1, min);
+            strings.bytes(2, max);
+        }
+        strings.sint(3, self.stations.total_length as i64);
+        footer.message(7, &column_stats(rows, Some((4, &strings))));
+        let mut doubles = Proto::default();
+        if rows > 0 {
+            doubles.double(1, self.temps.min);
+            doubles.double(2, self.temps.max);
+            doubles.double(3, self.temps.sum);
+        }
+        footer.message(7, &column_stats(rows, Some((3, &doubles))));
+        footer.uint(8, 0);
+
+        let mut postscript = Proto::default();
+        postscript.uint(1, footer.0.len() as u64);
+        postscript.uint(2, 0);
+        postscript.packed(4, &[0, 12]);
+        postscript.uint(5, 0);
+        postscript.bytes(8000, b"ORC");
+        output.extend_from_slice(&footer.0);
+        output.extend_from_slice(&postscript.0);
+        output.push(postscript.0.len() as u8);
+    }
+
+    /// Appends the rows gathered to `output` as a stripe of three streams
+    /// and its footer
+    fn write_stripe(&mut self, output: &mut Vec<u8>) {
+        let mut lengths = Vec::with_capacity(self.lengths.len() * 2);
+        for run in self.lengths.chunks(MAX_LITERALS) {
+            // A negative count introduces a run of literal varints
+            lengths.push((run.len() as i8).wrapping_neg() as u8);
+            for &length in run {
+                push_varint(&mut lengths, length);
+            }
+        }
+        let streams = [
+            (STATION, DATA, &self.names),
+            (STATION, LENGTH, &lengths),
+            (MEASUREMENT, DATA, &self.measurements),
+        ];
+        let mut footer = Proto::default();
+        let mut data_length = 0;
+        for (column, kind, bytes) in streams {
+            output.extend_from_slice(bytes);
+            let mut stream = Proto::default();
+            stream.uint(1, kind);
+            stream.uint(2, column);
+            stream.uint(3, bytes.len() as u64);
+            footer.message(1, &stream);
+            data_length += bytes.len() as u64;
+        }
+        for _ in 0..3 {
+            let mut encoding = Proto::default();
+            encoding.uint(1, DIRECT);
+            footer.message(2, &encoding);
+        }
+        output.extend_from_slice(&footer.0);
+
+        self.stripes.push(Stripe {
+            offset: self.offset,
+            data_length,
+            footer_length: footer.0.len() as u64,
+            rows: self.lengths.len() as u64,
+        });
+        self.offset += data_length + footer.0.len() as u64;
+        self.names.clear();
+        self.lengths.clear();
+        self.measurements.clear();
+    }
+}
+
+impl StringStats {
+    fn add(&mut self, name: &[u8]) {
+        if self.min.as_deref().is_none_or(|min| name < min) {
+            self.min = Some(name.to_vec());
+        }
+        if self.max.as_deref().is_none_or(|max| name > max) {
+            self.max = Some(name.to_vec());
+        }
+        self.total_length += name.len() as u64;
+    }
+}
+
+impl DoubleStats {
+    fn add(&mut self, value: f64) {
+        self.count += 1;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.sum += value;
+    }
+}
+
+/// A column's statistics: its value count, the kind-specific ones under
+/// their field number, and that it holds no nulls
+fn column_stats(rows: u64, kind: Option<(u64, &Proto)>) -> Proto {
+    let mut stats = Proto::default();
+    stats.uint(1, rows);
+    if let Some((field, kind)) = kind {
+        stats.message(field, kind);
+    }
+    stats.uint(10, 0);
+    stats
+}
@@ -0,0 +1,97 @@
+#[cfg(feature = "cli")]
+use clap::ValueEnum;
+use eyre::{eyre, Result};
+use rand::{
+    distributions::{Uniform, WeightedIndex},
+    prelude::Distribution,
+    Rng,
+};
+
+use crate::station::Stations;
+
+/// How generated rows are arranged within the output file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum Order {
+    /// Every row picks a station at random
+    Random,
+    /// Rows are clustered by station, with stations in sorted order
+    ByStation,
+    /// Timestamps never decrease from one row to the next; requires a
+    /// `--schema` with a timestamp column
+    ByTime,
+    /// A true global shuffle of all rows, using temporary spill files next to
+    /// the output
+    Shuffled,
+}
+
+/// Chooses the station for each successive row
+#[derive(Clone)]
+pub enum StationPicker {
+    /// Stations are picked independently, by weight when any station has one
+    Random(StationSampler),
+    /// Station indices in name order, each with the number of rows it has
+    /// left to emit
+    Clustered(std::vec::IntoIter<(usize, u64)>, Option<(usize, u64)>),
+}
+
+/// Samplers are built once up front, since constructing one per row is a
+/// measurable cost over a billion rows
+#[derive(Clone)]
+pub enum StationSampler {
+    Uniform(Uniform<usize>),
+    Weighted(WeightedIndex<f64>),
+}
+
+impl StationPicker {
+    pub fn new<R: Rng>(order: Order, stations: &Stations, rows: u64, rng: &mut R) -> Result<Self> {
+        let sampler = if stations.iter().any(|s| s.weight.is_some()) {
+            let weights = stations.iter().map(|s| s.weight.unwrap_or(1.0));
+            StationSampler::Weighted(
+                WeightedIndex::new(weights).map_err(|e| eyre!("Invalid station weights: {}", e))?,
+            )
+        } else {
+            StationSampler::Uniform(Uniform::new(0, stations.len()))
+        };
+        let mut random = Self::Random(sampler);
+        Ok(match order {
+            Order::Random | Order::ByTime | Order::Shuffled => random,
+            Order::ByStation => {
+                // Draw the same per-station row counts random picking would
+                // produce, then emit each station's rows as one block
+                let mut counts = vec![0u64; stations.len()];
+                for _ in 0..rows {
+                    counts[random.next(rng)] += 1;
+                }
+                let mut blocks: Vec<(usize, u64)> = counts
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(_, count)| *count > 0)
+                    .collect();
+                blocks.sort_by_key(|(station, _)| stations.name(&stations[*station]));
+                Self::Clustered(blocks.into_iter(), None)
+            }
+        })
+    }
+
+    /// Whether rows pick their stations independently of each other
+    pub fn is_random(&self) -> bool {
+        matches!(self, Self::Random(_))
+    }
+
+    pub fn next<R: Rng>(&mut self, rng: &mut R) -> usize {
+        match self {
+            Self::Random(StationSampler::Uniform(uniform)) => uniform.sample(rng),
+            Self::Random(StationSampler::Weighted(weights)) => weights.sample(rng),
+            Self::Clustered(blocks, current) => {
+                while current.is_none_or(|(_, remaining)| remaining == 0) {
+                    // Only asked for as many rows as were counted up front
+                    *current = Some(blocks.next().expect("Ran out of station blocks"));
+                }
+                let (index, remaining) = current.as_mut().expect("Current block was just set");
+                *remaining -= 1;
+                *index
+            }
+        }
+    }
+}
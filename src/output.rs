@@ -0,0 +1,706 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, IoSlice, Seek, SeekFrom, Write},
+    path::Path,
+    sync::{
+        mpsc::{Receiver, RecvTimeoutError, Sender},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use clap::ValueEnum;
+use eyre::{eyre, Result};
+
+#[cfg(any(target_os = "linux", target_os = "macos", windows))]
+use crate::direct::DirectFile;
+#[cfg(unix)]
+use crate::mmap::MappedFile;
+#[cfg(target_os = "linux")]
+use crate::uring::UringFile;
+use crate::{
+    broker::Publisher, checkpoint::Checkpointer, checksum::Checksum, interrupt, progress::Progress,
+    rolling::RollingFile,
+};
+
+/// How chunks reach the output file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum IoBackend {
+    /// Buffered writes
+    Write,
+    /// Copies into a memory map of the file, sized up front from the
+    /// estimated output size; Unix only
+    Mmap,
+    /// Asynchronous writes through io_uring, overlapping several writes
+    /// with generation; Linux only
+    Uring,
+    /// Aligned writes that bypass the page cache, staged in a buffer of
+    /// `--write-buffer` bytes; O_DIRECT on Linux, F_NOCACHE on macOS, and
+    /// FILE_FLAG_NO_BUFFERING on Windows
+    Direct,
+}
+
+/// What happens to the partly written output when generation fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OnError {
+    /// Move it to the output path anyway, as if it were complete
+    Keep,
+    /// Remove it, along with any checkpoint and intermediate files
+    Delete,
+    /// Leave it as `<output>.partial`, so it can't be mistaken for complete
+    RenamePartial,
+}
+
+impl OnError {
+    /// Deals with the output a failed run left at `written`, the first of
+    /// the files it was writing, which belong at `output` once complete
+    pub fn apply(self, output: &str, written: &[&str]) -> Result<()> {
+        let ignore_missing = |result: io::Result<()>| match result {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        };
+        match self {
+            Self::Keep => ignore_missing(std::fs::rename(written[0], output))?,
+            Self::Delete => {
+                let checkpoint = crate::checkpoint::Checkpoint::path(output);
+                for path in written.iter().copied().chain([checkpoint.as_str()]) {
+                    ignore_missing(std::fs::remove_file(path))?;
+                }
+            }
+            Self::RenamePartial => {}
+        }
+        Ok(())
+    }
+}
+
+/// Where the output is written until it is complete, when it is renamed to
+/// `path`
+pub fn partial_path(path: &str) -> String {
+    format!("{}.partial", path)
+}
+
+/// `path` with `.n` put before its extension, as `name.n.ext`
+pub fn suffixed_path(path: &str, n: u32) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}.{}.{}", stem, n, extension.to_string_lossy()),
+        None => format!("{}.{}", stem, n),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+/// One buffer per io_uring write in flight; the backend is only available
+/// on Linux
+#[cfg(target_os = "linux")]
+const URING_BUFFERS: usize = UringFile::DEPTH;
+#[cfg(not(target_os = "linux"))]
+const URING_BUFFERS: usize = 2;
+
+impl IoBackend {
+    /// How many chunk buffers should circulate between the generator and
+    /// the writer
+    pub fn buffer_count(self) -> usize {
+        match self {
+            Self::Write => MAX_BATCH,
+            Self::Uring => URING_BUFFERS,
+            Self::Mmap | Self::Direct => 2,
+        }
+    }
+
+    /// The fewest buffers the writer can run with: io_uring holds one per
+    /// write in flight, and waits for more before handing any back
+    pub fn min_buffer_count(self) -> usize {
+        match self {
+            Self::Uring => URING_BUFFERS,
+            _ => 2,
+        }
+    }
+
+    /// Whether the backend holds a `--write-buffer` sized buffer of its own
+    pub fn uses_write_buffer(self) -> bool {
+        matches!(self, Self::Write | Self::Direct)
+    }
+}
+
+/// Where generated bytes end up
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Sink {
+    /// The output file
+    File,
+    /// Nowhere; bytes are only counted
+    Null,
+    /// The message broker a `nats://` or `mqtt://` `--output` names
+    #[value(skip)]
+    Broker,
+}
+
+impl Sink {
+    /// The option choosing the sink, as errors about conflicts name it
+    pub fn option(self) -> &'static str {
+        match self {
+            Self::File => "--sink file",
+            Self::Null => "--sink null",
+            Self::Broker => "--output naming a broker",
+        }
+    }
+}
+
+/// How the rows are laid out in the output file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// `station;measurement` lines
+    Text,
+    /// An ORC file of a `station` string and a `measurement` double column
+    Orc,
+    /// `Measurement` protobuf messages, each preceded by its length
+    Protobuf,
+}
+
+impl Format {
+    /// The option choosing the format, as errors about conflicts name it
+    pub fn option(self) -> &'static str {
+        match self {
+            Self::Text => "--format text",
+            Self::Orc => "--format orc",
+            Self::Protobuf => "--format protobuf",
+        }
+    }
+}
+
+/// What generation does when the output hasn't taken a chunk for
+/// `--stall-timeout`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OnStall {
+    /// Log a warning and keep waiting
+    Warn,
+    /// Stop with an error
+    Fail,
+}
+
+/// The output file, opened for one of the [`IoBackend`]s, a broker, or the
+/// null sink
+pub enum Output {
+    Buffered(BufWriter<File>),
+    #[cfg(unix)]
+    Mapped(MappedFile),
+    #[cfg(target_os = "linux")]
+    Uring(UringFile),
+    #[cfg(any(target_os = "linux", target_os = "macos", windows))]
+    Direct(DirectFile),
+    Rolling(RollingFile),
+    Publish(Publisher),
+    Null,
+    Rows(RowCallback),
+}
+
+/// Takes each row, without its newline, in place of an output file;
+/// returning an error stops generation
+#[derive(Clone)]
+pub struct RowCallback(pub std::sync::Arc<Mutex<TakeRow>>);
+
+pub type TakeRow = dyn FnMut(&[u8]) -> io::Result<()> + Send;
+
+impl std::fmt::Debug for RowCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("RowCallback")
+    }
+}
+
+impl Output {
+    /// `estimate` is the expected size of the whole output, in bytes, and
+    /// `write_through` has `--io direct` wait for the device on each write
+    pub fn create(
+        path: &str,
+        backend: IoBackend,
+        write_buffer: usize,
+        estimate: usize,
+        write_through: bool,
+    ) -> Result<Self> {
+        match backend {
+            IoBackend::Write => Ok(Self::Buffered(BufWriter::with_capacity(
+                write_buffer,
+                File::create(path)?,
+            ))),
+            #[cfg(unix)]
+            IoBackend::Mmap => {
+                // Shared writable mappings need the file open for reading too
+                let file = std::fs::OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .open(path)?;
+                Ok(Self::Mapped(MappedFile::create(file, estimate)?))
+            }
+            #[cfg(target_os = "linux")]
+            IoBackend::Uring => Ok(Self::Uring(UringFile::create(File::create(path)?)?)),
+            #[cfg(any(target_os = "linux", target_os = "macos", windows))]
+            IoBackend::Direct => Ok(Self::Direct(DirectFile::create(
+                path,
+                write_buffer,
+                write_through,
+            )?)),
+            #[cfg(not(target_os = "linux"))]
+            IoBackend::Uring => Err(eyre!("--io uring is only supported on Linux")),
+            #[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+            IoBackend::Direct => {
+                let _ = write_through;
+                Err(eyre!(
+                    "--io direct is only supported on Linux, macOS, and Windows"
+                ))
+            }
+            #[cfg(not(unix))]
+            IoBackend::Mmap => {
+                let _ = estimate;
+                Err(eyre!("--io mmap is only supported on Unix"))
+            }
+        }
+    }
+
+    /// Reopens the file an interrupted run wrote, for buffered writes after
+    /// its first `len` bytes; anything written after its last checkpoint is
+    /// discarded
+    pub fn resume(path: &str, write_buffer: usize, len: u64) -> Result<Self> {
+        let mut file = std::fs::OpenOptions::new().write(true).open(path)?;
+        if file.metadata()?.len() < len {
+            return Err(eyre!("{} is shorter than its checkpoint records", path));
+        }
+        file.set_len(len)?;
+        file.seek(SeekFrom::End(0))?;
+        Ok(Self::Buffered(BufWriter::with_capacity(write_buffer, file)))
+    }
+
+    /// Reserves storage for the `len` bytes expected after `offset`
+    pub fn preallocate(&self, offset: u64, len: u64) -> Result<()> {
+        let file = match self {
+            Self::Buffered(writer) => writer.get_ref(),
+            #[cfg(target_os = "linux")]
+            Self::Uring(uring) => uring.file(),
+            #[cfg(any(target_os = "linux", target_os = "macos", windows))]
+            Self::Direct(direct) => direct.file(),
+            _ => return Ok(()),
+        };
+        preallocate(file, offset, len)
+    }
+
+    /// The file behind the page cache, except for backends bypassing it
+    fn cached_file(&self) -> Option<&File> {
+        match self {
+            Self::Buffered(writer) => Some(writer.get_ref()),
+            #[cfg(target_os = "linux")]
+            Self::Uring(uring) => Some(uring.file()),
+            Self::Rolling(rolling) => Some(rolling.file()),
+            _ => None,
+        }
+    }
+
+    /// Writes a batch of chunks in order, returning the buffers that are
+    /// free to be refilled
+    fn write(&mut self, batch: Vec<Vec<u8>>) -> Result<Vec<Vec<u8>>> {
+        match self {
+            Self::Buffered(writer) => write_all_vectored(writer, &batch)?,
+            #[cfg(unix)]
+            Self::Mapped(mapped) => {
+                for chunk in &batch {
+                    mapped.write_all(chunk)?;
+                }
+            }
+            #[cfg(target_os = "linux")]
+            Self::Uring(uring) => {
+                let mut free = Vec::new();
+                for chunk in batch {
+                    free.extend(uring.write(chunk)?);
+                }
+                return Ok(free);
+            }
+            #[cfg(any(target_os = "linux", target_os = "macos", windows))]
+            Self::Direct(direct) => {
+                for chunk in &batch {
+                    direct.write_all(chunk)?;
+                }
+            }
+            Self::Rolling(rolling) => {
+                for chunk in &batch {
+                    rolling.write_all(chunk)?;
+                }
+            }
+            Self::Publish(publisher) => {
+                for chunk in &batch {
+                    publisher.write(chunk)?;
+                }
+            }
+            Self::Null => {}
+            Self::Rows(callback) => {
+                let mut callback = callback.0.lock().expect("A row callback panicked");
+                // The remainder chunk is empty when the rows divide evenly
+                for chunk in batch.iter().filter(|chunk| !chunk.is_empty()) {
+                    for row in chunk
+                        .strip_suffix(b"\n")
+                        .unwrap_or(chunk)
+                        .split(|&b| b == b'\n')
+                    {
+                        callback(row)?;
+                    }
+                }
+            }
+        }
+        Ok(batch)
+    }
+
+    fn flush(&mut self) -> Result<Vec<Vec<u8>>> {
+        match self {
+            Self::Buffered(writer) => writer.flush()?,
+            #[cfg(unix)]
+            Self::Mapped(mapped) => mapped.flush()?,
+            #[cfg(target_os = "linux")]
+            Self::Uring(uring) => return uring.flush(),
+            #[cfg(any(target_os = "linux", target_os = "macos", windows))]
+            Self::Direct(direct) => direct.flush()?,
+            Self::Rolling(rolling) => rolling.flush()?,
+            Self::Publish(publisher) => publisher.flush()?,
+            Self::Null => {}
+            Self::Rows(_) => {}
+        }
+        Ok(Vec::new())
+    }
+
+    /// Flushes the output and waits for the file to reach storage
+    fn sync(&mut self) -> Result<Vec<Vec<u8>>> {
+        let free = self.flush()?;
+        if let Some(file) = self.cached_file() {
+            file.sync_data()?;
+        }
+        Ok(free)
+    }
+
+    /// Completes the file, reporting any error that dropping it would
+    /// discard
+    fn finish(self) -> Result<()> {
+        match self {
+            Self::Buffered(mut writer) => Ok(writer.flush()?),
+            #[cfg(unix)]
+            Self::Mapped(mapped) => mapped.finish(),
+            #[cfg(target_os = "linux")]
+            Self::Uring(mut uring) => uring.flush().map(drop),
+            #[cfg(any(target_os = "linux", target_os = "macos", windows))]
+            Self::Direct(direct) => direct.finish(),
+            Self::Rolling(mut rolling) => Ok(rolling.flush()?),
+            Self::Publish(publisher) => publisher.finish(),
+            Self::Null => Ok(()),
+            Self::Rows(_) => Ok(()),
+        }
+    }
+}
+
+/// Bytes written between each time `--drop-cache` evicts them from the page
+/// cache
+const DROP_CACHE_INTERVAL: u64 = 64 << 20;
+
+/// The most chunks written by one call
+const MAX_BATCH: usize = 8;
+
+/// When buffered output is handed to the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FlushPolicy {
+    /// Only once the write buffer fills, for the fewest and largest writes
+    Buffer,
+    /// After every chunk, so readers such as pipes see rows promptly
+    Chunk,
+}
+
+/// Owns the output for the writer thread: writes each chunk received from
+/// the generator, then hands its buffer back to be filled again. Returns
+/// the bytes written, which are also fed to `checksum` if given. Whatever is
+/// buffered is flushed while generation is paused, and `checkpointer` saves
+/// its checkpoints once what they record is on storage.
+#[allow(clippy::too_many_arguments)]
+pub fn write_chunks(
+    mut output: Output,
+    flush: FlushPolicy,
+    drop_cache: bool,
+    checksum: Option<&Mutex<Checksum>>,
+    mut checkpointer: Option<Checkpointer>,
+    chunks: Receiver<Vec<u8>>,
+    spent: Sender<Vec<u8>>,
+    progress: Progress,
+) -> Result<u64> {
+    let _span = tracing::debug_span!("writer").entered();
+    let (mut written, mut dropped) = (0, 0);
+    let mut chunk_count = 0;
+    let mut flushed = true;
+    loop {
+        let chunk = match chunks.recv_timeout(Duration::from_millis(100)) {
+            Ok(chunk) => chunk,
+            Err(RecvTimeoutError::Timeout) => {
+                if interrupt::paused() && !flushed {
+                    for buffer in output.flush()? {
+                        let _ = spent.send(buffer);
+                    }
+                    flushed = true;
+                }
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        flushed = false;
+        let began = Instant::now();
+        // Take every chunk that is already waiting, to write them together
+        let mut batch = vec![chunk];
+        while batch.len() < MAX_BATCH {
+            match chunks.try_recv() {
+                Ok(chunk) => batch.push(chunk),
+                Err(_) => break,
+            }
+        }
+        let count = batch.len() as u64;
+        let bytes = batch.iter().map(|chunk| chunk.len() as u64).sum::<u64>();
+        written += bytes;
+        chunk_count += count;
+        if let Some(checksum) = checksum {
+            let mut checksum = checksum.lock().expect("A writer panicked");
+            for chunk in &batch {
+                checksum.update(chunk);
+            }
+        }
+        let mut free = output.write(batch)?;
+        if flush == FlushPolicy::Chunk {
+            free.extend(output.flush()?);
+        }
+        if drop_cache && written - dropped >= DROP_CACHE_INTERVAL {
+            free.extend(output.flush()?);
+            if let Some(file) = output.cached_file() {
+                drop_written_pages(file, dropped, written - dropped)?;
+            }
+            dropped = written;
+        }
+        if let Some(checkpointer) = checkpointer.as_mut().filter(|c| c.due()) {
+            free.extend(output.sync()?);
+            checkpointer.save(chunk_count, written)?;
+        }
+        // The writer is the dashboard's second thread, after the generator
+        progress.busy(1, began.elapsed());
+        progress.inc(count, bytes);
+        for buffer in free {
+            // The generator stops taking buffers once it has run out of rows
+            let _ = spent.send(buffer);
+        }
+    }
+    if drop_cache {
+        output.flush()?;
+        if let Some(file) = output.cached_file() {
+            drop_written_pages(file, dropped, written - dropped)?;
+        }
+    }
+    if let Some(checkpointer) = &mut checkpointer {
+        output.sync()?;
+        checkpointer.save(chunk_count, written)?;
+    }
+    output.finish()?;
+    Ok(written)
+}
+
+/// Writes `len` bytes at `offset` back to storage and evicts them from the
+/// page cache, which only drops pages that are clean
+#[cfg(target_os = "linux")]
+pub fn drop_written_pages(file: &File, offset: u64, len: u64) -> Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let fd = file.as_raw_fd();
+    let flags = libc::SYNC_FILE_RANGE_WAIT_BEFORE
+        | libc::SYNC_FILE_RANGE_WRITE
+        | libc::SYNC_FILE_RANGE_WAIT_AFTER;
+    // SAFETY: plain system calls on an open file descriptor
+    unsafe {
+        if libc::sync_file_range(fd, offset as i64, len as i64, flags) != 0 {
+            return Err(eyre!(
+                "sync_file_range failed: {}",
+                io::Error::last_os_error()
+            ));
+        }
+        let error = libc::posix_fadvise(fd, offset as i64, len as i64, libc::POSIX_FADV_DONTNEED);
+        if error != 0 {
+            return Err(eyre!(
+                "posix_fadvise failed: {}",
+                io::Error::from_raw_os_error(error)
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn drop_written_pages(_file: &File, _offset: u64, _len: u64) -> Result<()> {
+    Err(eyre!("--drop-cache is only supported on Linux"))
+}
+
+/// Reserves `len` bytes of storage after `offset` in `file` without changing
+/// its size, so the file is laid out in few extents and a full disk is
+/// reported before generating rather than partway through. Special files
+/// and filesystems without fallocate are left alone.
+#[cfg(target_os = "linux")]
+pub fn preallocate(file: &File, offset: u64, len: u64) -> Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    if len == 0 || !file.metadata()?.is_file() {
+        return Ok(());
+    }
+    let fd = file.as_raw_fd();
+    // SAFETY: a plain system call on an open file descriptor
+    let result =
+        unsafe { libc::fallocate(fd, libc::FALLOC_FL_KEEP_SIZE, offset as i64, len as i64) };
+    if result != 0 {
+        let error = io::Error::last_os_error();
+        if error.raw_os_error() == Some(libc::EOPNOTSUPP) {
+            return Ok(());
+        }
+        return Err(eyre!(
+            "Could not reserve {} for the output: {}; --no-preallocate skips this",
+            crate::cli::human_readable(len),
+            error
+        ));
+    }
+    Ok(())
+}
+
+/// macOS reserves from the end of the storage the file already holds, which
+/// while generating is about `offset`, preferring a single extent
+#[cfg(target_os = "macos")]
+pub fn preallocate(file: &File, _offset: u64, len: u64) -> Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    if len == 0 || !file.metadata()?.is_file() {
+        return Ok(());
+    }
+    let fd = file.as_raw_fd();
+    let mut store = libc::fstore_t {
+        fst_flags: libc::F_ALLOCATECONTIG | libc::F_ALLOCATEALL,
+        fst_posmode: libc::F_PEOFPOSMODE,
+        fst_offset: 0,
+        fst_length: len as libc::off_t,
+        fst_bytesalloc: 0,
+    };
+    // SAFETY: a plain system call on an open file descriptor, given a
+    // store that outlives it
+    let mut result = unsafe { libc::fcntl(fd, libc::F_PREALLOCATE, &mut store) };
+    if result == -1 {
+        // Settle for scattered extents when no contiguous run is free
+        store.fst_flags = libc::F_ALLOCATEALL;
+        // SAFETY: as above
+        result = unsafe { libc::fcntl(fd, libc::F_PREALLOCATE, &mut store) };
+    }
+    if result == -1 {
+        let error = io::Error::last_os_error();
+        if error.raw_os_error() == Some(libc::ENOTSUP) {
+            return Ok(());
+        }
+        return Err(eyre!(
+            "Could not reserve {} for the output: {}; --no-preallocate skips this",
+            crate::cli::human_readable(len),
+            error
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn preallocate(_file: &File, _offset: u64, _len: u64) -> Result<()> {
+    Ok(())
+}
+
+/// The bytes free to unprivileged users on the filesystem that holds
+/// `path`, if the platform reports them
+#[cfg(unix)]
+pub fn free_space(path: &str) -> Result<Option<u64>> {
+    use std::{ffi::CString, mem};
+
+    let dir = match Path::new(path).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let dir = CString::new(dir.as_os_str().as_encoded_bytes())?;
+    // SAFETY: `dir` is NUL terminated and `stats` is only read once filled
+    unsafe {
+        let mut stats: libc::statvfs = mem::zeroed();
+        if libc::statvfs(dir.as_ptr(), &mut stats) != 0 {
+            return Err(eyre!(
+                "Could not read the free space for {}: {}",
+                path,
+                io::Error::last_os_error()
+            ));
+        }
+        Ok(Some(stats.f_bavail as u64 * stats.f_frsize as u64))
+    }
+}
+
+#[cfg(not(unix))]
+pub fn free_space(_path: &str) -> Result<Option<u64>> {
+    Ok(None)
+}
+
+/// Whether the file at `path` is the one stdout writes to, as
+/// `/dev/stdout` is
+#[cfg(unix)]
+pub fn is_stdout(path: &str) -> bool {
+    use std::{fs, os::unix::fs::MetadataExt};
+
+    match (fs::metadata(path), fs::metadata("/dev/stdout")) {
+        (Ok(output), Ok(stdout)) => output.dev() == stdout.dev() && output.ino() == stdout.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+pub fn is_stdout(_path: &str) -> bool {
+    false
+}
+
+/// Waits for the file at `path`, and the directory entry naming it, to
+/// reach storage. Special files have nothing to sync.
+pub fn sync_to_storage(path: &str) -> Result<()> {
+    let file = File::open(path)?;
+    if !file.metadata()?.is_file() {
+        return Ok(());
+    }
+    file.sync_all()?;
+    // Directories can only be synced on Unix, where a rename isn't durable
+    // until they are
+    #[cfg(unix)]
+    {
+        let dir = match std::path::Path::new(path).parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => std::path::Path::new("."),
+        };
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+/// Frees whatever storage reserved for the output went unused, past the
+/// end of what was written
+pub fn release_preallocated(path: &str) -> Result<()> {
+    let file = std::fs::OpenOptions::new().write(true).open(path)?;
+    let metadata = file.metadata()?;
+    if metadata.is_file() {
+        file.set_len(metadata.len())?;
+    }
+    Ok(())
+}
+
+/// `write_all` over several buffers, handing them to the writer together so
+/// that large batches become a single vectored write
+fn write_all_vectored<W: Write>(writer: &mut W, batch: &[Vec<u8>]) -> io::Result<()> {
+    let mut slices: Vec<IoSlice> = batch.iter().map(|chunk| IoSlice::new(chunk)).collect();
+    let mut slices = &mut slices[..];
+    // Skips any empty buffers up front
+    IoSlice::advance_slices(&mut slices, 0);
+    while !slices.is_empty() {
+        match writer.write_vectored(slices) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(written) => IoSlice::advance_slices(&mut slices, written),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
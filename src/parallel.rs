@@ -0,0 +1,186 @@
+use std::{
+    fs::File,
+    ops::Range,
+    os::unix::fs::FileExt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Condvar, Mutex,
+    },
+    thread,
+    time::Instant,
+};
+
+use eyre::Result;
+
+use crate::{
+    affinity,
+    checksum::Checksum,
+    cli::Written,
+    huge_pages, interrupt, output,
+    progress::Progress,
+    rng::{BatchRng, GenRng},
+};
+
+/// Hands out file offsets to chunks in row order. Chunks are generated in
+/// parallel, but a chunk's offset is only known once every chunk before it
+/// has been measured, so each waits its turn to claim one; the writes
+/// themselves then proceed independently.
+struct Placement {
+    /// The next chunk to be placed, and the offset it will be written at
+    next: Mutex<(u64, u64)>,
+    turn: Condvar,
+}
+
+impl Placement {
+    /// Claims room for `chunk`'s `bytes`, or `None` if another worker
+    /// failed, leaving earlier chunks unplaced. Chunks are claimed in order,
+    /// so this is also where they're fed to `checksum`.
+    fn claim(&self, chunk: u64, bytes: &[u8], checksum: Option<&Mutex<Checksum>>) -> Option<u64> {
+        let mut next = self.next.lock().expect("A worker panicked");
+        while next.0 != chunk {
+            if next.0 == u64::MAX {
+                return None;
+            }
+            next = self.turn.wait(next).expect("A worker panicked");
+        }
+        if let Some(checksum) = checksum {
+            checksum.lock().expect("A worker panicked").update(bytes);
+        }
+        let offset = next.1;
+        *next = (chunk + 1, offset + bytes.len() as u64);
+        self.turn.notify_all();
+        Some(offset)
+    }
+
+    /// Wakes every waiting worker so they stop rather than wait forever
+    fn abandon(&self) {
+        self.next.lock().expect("A worker panicked").0 = u64::MAX;
+        self.turn.notify_all();
+    }
+}
+
+/// How the parallel workers run
+pub struct Workers<'a> {
+    pub threads: usize,
+    /// Pin each worker to its own CPU, allocating its buffer after pinning
+    /// so that it is local to the worker's NUMA node
+    pub pin: bool,
+    /// Back each worker's buffer with transparent huge pages
+    pub huge_pages: bool,
+    /// Evict each chunk from the page cache once written
+    pub drop_cache: bool,
+    /// Sums the output, chunk by chunk in order
+    pub checksum: Option<&'a Mutex<Checksum>>,
+    /// When the run's `--duration` runs out
+    pub deadline: Option<Instant>,
+}
+
+/// Generates the `rows` of the stream with parallel workers, each taking the next
+/// unclaimed chunk and writing it straight into `file` at its offset, or
+/// discarding it without a file. Every worker runs its own clone of
+/// `generate_line` and `rng`, so rows must only depend on their index and
+/// counter based randomness. Workers wait between chunks while paused, and
+/// stop taking chunks on Ctrl-C or once `workers.deadline` has passed,
+/// finishing those already taken, so the chunks written are still
+/// contiguous. Returns what was written.
+#[allow(clippy::too_many_arguments)]
+pub fn generate<F>(
+    file: Option<&File>,
+    rows: Range<u64>,
+    chunk_rows: u64,
+    out_buf_len: usize,
+    workers: &Workers<'_>,
+    rng: &GenRng,
+    progress: &Progress,
+    generate_line: F,
+) -> Result<Written>
+where
+    F: FnMut(u64, &mut BatchRng, &mut Vec<u8>) -> Result<()> + Clone + Send,
+{
+    let row_count = rows.end - rows.start;
+    let chunk_count = row_count.div_ceil(chunk_rows);
+    progress.set_chunks(chunk_count);
+    let next_chunk = AtomicU64::new(0);
+    let placement = Placement {
+        next: Mutex::new((0, 0)),
+        turn: Condvar::new(),
+    };
+    let cpus = if workers.pin {
+        affinity::allowed_cpus()?
+    } else {
+        Vec::new()
+    };
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers.threads)
+            .map(|worker| {
+                let cpu = (!cpus.is_empty()).then(|| cpus[worker % cpus.len()]);
+                let mut generate_line = generate_line.clone();
+                let mut rng = BatchRng::new(rng.clone());
+                let (next_chunk, placement) = (&next_chunk, &placement);
+                scope.spawn(move || -> Result<()> {
+                    let _span = tracing::debug_span!("worker", worker).entered();
+                    let result = (|| {
+                        if let Some(cpu) = cpu {
+                            affinity::pin_current_thread(cpu)?;
+                        }
+                        // Allocated once pinned, so the buffer is local
+                        let mut out_buf = huge_pages::buffer(out_buf_len, workers.huge_pages)?;
+                        loop {
+                            if interrupt::paused() {
+                                progress.pause();
+                                interrupt::wait_while_paused();
+                                progress.resume();
+                            }
+                            if interrupt::stopping(workers.deadline) {
+                                return Ok(());
+                            }
+                            let chunk = next_chunk.fetch_add(1, Ordering::Relaxed);
+                            if chunk >= chunk_count {
+                                return Ok(());
+                            }
+                            let began = Instant::now();
+                            out_buf.clear();
+                            let first = rows.start + chunk * chunk_rows;
+                            for row in first..(first + chunk_rows).min(rows.end) {
+                                rng.set_row(row);
+                                generate_line(row, &mut rng, &mut out_buf)?;
+                            }
+                            let Some(offset) = placement.claim(chunk, &out_buf, workers.checksum)
+                            else {
+                                return Ok(());
+                            };
+                            if let Some(file) = file {
+                                file.write_all_at(&out_buf, offset)?;
+                                if workers.drop_cache {
+                                    let len = out_buf.len() as u64;
+                                    output::drop_written_pages(file, offset, len)?;
+                                }
+                            }
+                            progress.busy(worker, began.elapsed());
+                            progress.inc(1, out_buf.len() as u64);
+                        }
+                    })();
+                    if result.is_err() {
+                        placement.abandon();
+                    }
+                    result
+                })
+            })
+            .collect();
+        // Join every worker, reporting the first failure
+        let mut result = Ok(());
+        for worker in handles {
+            let worker_result = worker.join().expect("A worker thread panicked");
+            if result.is_ok() {
+                result = worker_result;
+            }
+        }
+        result
+    })?;
+    let (chunks, bytes) = placement.next.into_inner().expect("A worker panicked");
+    Ok(Written {
+        rows: (chunks * chunk_rows).min(row_count),
+        bytes,
+    })
+}
@@ -0,0 +1,136 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader},
+};
+
+use eyre::{eyre, Result, WrapErr};
+
+use crate::{
+    extract::unquoted,
+    normalize::Form,
+    station::{is_line_list, sniff_delimiter, ListOptions, Stations},
+};
+
+/// Columns of a station list, found by the names its header gives them,
+/// carried into every row of their station between its name and its
+/// measurement
+pub struct Passthrough {
+    /// Where the first of them is in the list, counting the name as 0
+    first_column: usize,
+    /// How many there are
+    count: usize,
+    /// Each station's values, each after a `;`
+    values: HashMap<String, String>,
+}
+
+impl Passthrough {
+    /// Reads the `columns` of the station list at `path` for every station,
+    /// its name put in `form` as the list's are
+    pub fn load(path: &str, columns: &[String], options: ListOptions, form: Form) -> Result<Self> {
+        if !is_line_list(path) {
+            return Err(eyre!(
+                "--passthrough-columns needs a delimited station list with a header, not {}",
+                path
+            ));
+        }
+        let delimiter = match options.delimiter {
+            Some(delimiter) => delimiter,
+            None => sniff_delimiter(path)?,
+        };
+        let file = File::open(path).wrap_err_with(|| format!("Could not open file {}", path))?;
+        let mut lines = BufReader::new(file)
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| {
+                line.as_ref().map_or(true, |line| {
+                    !line.trim().is_empty() && !line.starts_with('#')
+                })
+            });
+        let header = match lines.next() {
+            Some((_, line)) => line?,
+            None => return Err(eyre!("{} has no header naming its columns", path)),
+        };
+        let names: Vec<&str> = header
+            .trim_end_matches('\r')
+            .split(delimiter)
+            .map(str::trim)
+            .collect();
+        let indexes = columns
+            .iter()
+            .map(|column| {
+                names
+                    .iter()
+                    .position(|name| name.eq_ignore_ascii_case(column))
+                    .ok_or_else(|| {
+                        eyre!(
+                            "{} has no {} column; its header names {}",
+                            path,
+                            column,
+                            names.join(", ")
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut values = HashMap::new();
+        for (number, line) in lines {
+            let line = line?;
+            let fields: Vec<&str> = line.trim_end_matches('\r').split(delimiter).collect();
+            let name = form.apply(fields[0]);
+            if name.is_empty() {
+                continue;
+            }
+            let mut carried = String::new();
+            for (&index, column) in indexes.iter().zip(columns) {
+                let value = fields.get(index).map_or("", |value| value.trim());
+                if value.contains(|c: char| c == ';' || c.is_control()) {
+                    return Err(eyre!(
+                        "{} line {}: the {} of {} holds `;` or a control character, which would \
+                         corrupt the output",
+                        path,
+                        number + 1,
+                        column,
+                        name
+                    ));
+                }
+                carried.push(';');
+                carried.push_str(value);
+            }
+            values.insert(name.into_owned(), carried);
+        }
+        Ok(Self {
+            first_column: indexes.iter().copied().min().unwrap_or(0),
+            count: columns.len(),
+            values,
+        })
+    }
+
+    /// How many columns after the name still hold the mean, standard
+    /// deviation, and weight: those before the first carried column
+    pub fn param_columns(&self) -> usize {
+        self.first_column.saturating_sub(1).min(3)
+    }
+
+    /// `stations` with each name followed by the station's carried values,
+    /// left empty for a station the list has none for
+    pub fn extend(&self, stations: &Stations) -> Stations {
+        let missing = ";".repeat(self.count);
+        let mut extended = Stations::default();
+        for station in stations.iter() {
+            let name = stations.name(station);
+            // A name may have been quoted since it was read
+            let carried = self.values.get(name).or_else(|| {
+                let unquoted = unquoted(name.as_bytes());
+                self.values.get(String::from_utf8_lossy(&unquoted).as_ref())
+            });
+            extended.push(
+                &format!("{}{}", name, carried.unwrap_or(&missing)),
+                station.mean,
+                station.stddev,
+                station.weight,
+            );
+        }
+        extended
+    }
+}
@@ -0,0 +1,37 @@
+use clap::ValueEnum;
+
+use crate::{MAX_TEMP, MIN_TEMP};
+
+/// Number of representable temperatures, -99.9 through 99.9
+const TEMP_COUNT: u64 = (MAX_TEMP - MIN_TEMP) as u64 + 1;
+
+/// Where stations and measurements come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Pattern {
+    /// Random stations and measurements, shaped by the other options
+    Random,
+    /// No randomness at all: rows cycle through the stations in file order,
+    /// and every station's n-th row measures -99.9 + n/10, wrapping after
+    /// 99.9, so expected aggregates can be worked out by hand
+    Cyclic,
+    /// Random stations, with measurements sweeping every representable value
+    /// from -99.9 to 99.9 in order, repeatedly
+    Ramp,
+}
+
+impl Pattern {
+    /// The station index and measurement of `row` in the cyclic pattern
+    pub fn cyclic(row: u64, station_count: usize) -> (usize, i32) {
+        let station_count = station_count as u64;
+        let cycle = row / station_count;
+        (
+            (row % station_count) as usize,
+            MIN_TEMP + (cycle % TEMP_COUNT) as i32,
+        )
+    }
+
+    /// The measurement of `row` in the ramp pattern
+    pub fn ramp(row: u64) -> i32 {
+        MIN_TEMP + (row % TEMP_COUNT) as i32
+    }
+}
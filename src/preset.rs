@@ -0,0 +1,307 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+use clap::ValueEnum;
+use eyre::{eyre, Result};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::station::MAX_NAME_BYTES;
+
+/// A synthetic station list built to stress one part of a solver
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Preset {
+    /// Names packed with emoji, skin-tone modifiers, ZWJ sequences, flags,
+    /// and keycaps, many of them several code points to a glyph
+    Emoji,
+    /// Arabic and Hebrew names with their vowel marks, Latin names under
+    /// stacked combining diacritics, and bidi controls embedding,
+    /// overriding, and isolating runs of one direction in the other
+    Rtl,
+}
+
+impl Preset {
+    fn name<R: Rng>(self, rng: &mut R) -> String {
+        match self {
+            Self::Emoji => emoji_name(rng),
+            Self::Rtl => rtl_name(rng),
+        }
+    }
+}
+
+/// Writes `count` distinct `name;mean` stations of `preset`, drawn from
+/// `seed`, to `output` or stdout. Every name fits the challenge's 100 bytes.
+pub fn generate(preset: Preset, count: usize, seed: u64, output: Option<&str>) -> Result<()> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut names = HashSet::with_capacity(count);
+    let mut stations = Vec::with_capacity(count);
+    // Each preset has far more names than any list needs; this only stops
+    // a request for more than it has from looping forever
+    let mut attempts = 0;
+    while stations.len() < count {
+        attempts += 1;
+        if attempts > count.saturating_mul(100).max(1_000_000) {
+            return Err(eyre!(
+                "Could only find {} distinct names for the {:?} preset",
+                stations.len(),
+                preset
+            ));
+        }
+        let name = preset.name(&mut rng);
+        if name.len() <= MAX_NAME_BYTES && names.insert(name.clone()) {
+            stations.push((name, rng.gen_range(-100..=350) as f64 / 10.0));
+        }
+    }
+
+    let mut writer: Box<dyn Write> = match output {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+    writeln!(
+        writer,
+        "# Generated by the {} preset with seed {}: name;mean",
+        crate::manifest::value_name(preset),
+        seed
+    )?;
+    for (name, mean) in stations {
+        writeln!(writer, "{};{:.1}", name, mean)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+const ZWJ: char = '\u{200D}';
+const VARIATION_SELECTOR: char = '\u{FE0F}';
+
+/// Single code point emoji, some wide and some with a text presentation too
+const EMOJI: &[&str] = &[
+    "🌋", "🗻", "🏔", "⛰", "🌊", "🏝", "🏜", "🌵", "🌲", "🌴", "🔥", "❄", "☀", "🌧", "⛈", "🌪", "🌈",
+    "☔", "⚡", "🌙", "⭐", "🌍", "🌎", "🌏", "🏙", "🏰", "🗼", "🗽", "⛪", "🕌", "🛕", "🏯", "🐧",
+    "🐻", "🦩", "🐪", "🦘", "🐉", "🦕", "🍕", "🍣", "🥐", "🌮", "☕", "🍵", "🧊", "🌡", "💧",
+];
+
+/// People and gestures taking a skin-tone modifier
+const PEOPLE: &[&str] = &[
+    "👋", "👍", "✌", "🙏", "💪", "👶", "🧒", "👦", "👧", "🧑", "👨", "👩", "🧓", "👴", "👵", "👮",
+];
+
+/// People joined to an occupation with a ZWJ
+const WORKERS: &[&str] = &["🧑", "👨", "👩"];
+
+/// What a worker can be joined to with a ZWJ, as in 👩‍🚀
+const OCCUPATIONS: &[&str] = &[
+    "🚀", "🌾", "🍳", "🎓", "🏫", "🏭", "💻", "🔬", "🎨", "✈", "🚒", "⚕", "🔧", "🎤",
+];
+
+/// Whole ZWJ sequences rendered as one glyph
+const SEQUENCES: &[&str] = &["👨‍👩‍👧‍👦", "👩‍👩‍👦", "🏳️‍🌈", "🏴‍☠️", "🐻‍❄️", "😶‍🌫️", "❤️‍🔥", "🧑‍🤝‍🧑", "👁️‍🗨️"];
+
+/// Countries whose regional indicator pairs render as flags
+const FLAGS: &[&str] = &[
+    "JP", "NO", "BR", "IN", "NZ", "ZA", "IS", "CA", "MX", "KE", "FI", "CL", "EG", "TH",
+];
+
+/// Plain words some names mix in, so emoji sit mid-name as well as alone
+const WORDS: &[&str] = &[
+    "Mount", "Lake", "Port", "New", "Old", "North", "South", "Bay", "Springs", "Valley",
+];
+
+/// One to four emoji of any kind, sometimes among words
+fn emoji_name<R: Rng>(rng: &mut R) -> String {
+    let mut parts = Vec::new();
+    for _ in 0..rng.gen_range(1..=4) {
+        let mut part = String::new();
+        match rng.gen_range(0..7) {
+            0 => part.push_str(choose(rng, EMOJI)),
+            1 => {
+                part.push_str(choose(rng, EMOJI));
+                part.push(VARIATION_SELECTOR);
+            }
+            2 => {
+                part.push_str(choose(rng, PEOPLE));
+                part.push(skin_tone(rng));
+            }
+            3 => {
+                part.push_str(choose(rng, WORKERS));
+                if rng.gen_bool(0.5) {
+                    part.push(skin_tone(rng));
+                }
+                part.push(ZWJ);
+                part.push_str(choose(rng, OCCUPATIONS));
+            }
+            4 => part.push_str(choose(rng, SEQUENCES)),
+            5 => {
+                for letter in choose(rng, FLAGS).chars() {
+                    let indicator = 0x1F1E6 + (letter as u32 - 'A' as u32);
+                    part.push(char::from_u32(indicator).expect("Regional indicators are chars"));
+                }
+            }
+            _ => {
+                part.push(char::from(b'0' + rng.gen_range(0..10)));
+                part.push(VARIATION_SELECTOR);
+                part.push('\u{20E3}');
+            }
+        }
+        parts.push(part);
+    }
+    if rng.gen_bool(0.3) {
+        let at = rng.gen_range(0..=parts.len());
+        parts.insert(at, choose(rng, WORDS).to_string());
+    }
+    let separator = if rng.gen_bool(0.5) { " " } else { "" };
+    parts.join(separator)
+}
+
+const ARABIC: &[&str] = &[
+    "القاهرة",
+    "الرياض",
+    "دبي",
+    "بغداد",
+    "دمشق",
+    "عمان",
+    "الدوحة",
+    "مسقط",
+    "بيروت",
+    "تونس",
+    "الجزائر",
+    "الرباط",
+    "صنعاء",
+    "الكويت",
+    "المنامة",
+    "طرابلس",
+    "الخرطوم",
+    "نواكشوط",
+];
+
+/// Harakat, the Arabic vowel marks
+const HARAKAT: std::ops::RangeInclusive<u32> = 0x064B..=0x0652;
+
+const HEBREW: &[&str] = &[
+    "ירושלים",
+    "תל אביב",
+    "חיפה",
+    "באר שבע",
+    "אילת",
+    "נצרת",
+    "טבריה",
+    "צפת",
+    "עכו",
+    "אשדוד",
+];
+
+/// Niqqud, the Hebrew vowel points
+const NIQQUD: std::ops::RangeInclusive<u32> = 0x05B0..=0x05BC;
+
+const LATIN: &[&str] = &[
+    "Reykjavik",
+    "Sao Paulo",
+    "Malmo",
+    "Cordoba",
+    "Nimes",
+    "Tromso",
+    "Plzen",
+    "Lodz",
+    "Izmir",
+];
+
+/// Combining diacritical marks, stacked onto Latin letters
+const DIACRITICS: std::ops::RangeInclusive<u32> = 0x0300..=0x036F;
+
+/// Pairs of bidi controls and what closes them: right-to-left and
+/// left-to-right embeddings and overrides, closed by PDF, and isolates,
+/// closed by PDI
+const BIDI_RUNS: &[(char, char)] = &[
+    ('\u{202B}', '\u{202C}'),
+    ('\u{202A}', '\u{202C}'),
+    ('\u{202E}', '\u{202C}'),
+    ('\u{202D}', '\u{202C}'),
+    ('\u{2067}', '\u{2069}'),
+    ('\u{2066}', '\u{2069}'),
+    ('\u{2068}', '\u{2069}'),
+];
+
+/// Lone direction marks: LRM, RLM, and the Arabic letter mark
+const BIDI_MARKS: &[char] = &['\u{200E}', '\u{200F}', '\u{061C}'];
+
+/// A right-to-left or heavily accented name, sometimes mixing directions,
+/// with bidi controls around or inside it
+fn rtl_name<R: Rng>(rng: &mut R) -> String {
+    let mut name = match rng.gen_range(0..4) {
+        0 => {
+            let base = choose(rng, ARABIC);
+            marked(rng, base, HARAKAT, 1)
+        }
+        1 => {
+            let base = choose(rng, HEBREW);
+            marked(rng, base, NIQQUD, 1)
+        }
+        2 => {
+            let base = choose(rng, LATIN);
+            marked(rng, base, DIACRITICS, 3)
+        }
+        // Mixed direction, as a transliterated name beside the original
+        _ => {
+            let original = if rng.gen_bool(0.5) { ARABIC } else { HEBREW };
+            format!("{} {}", choose(rng, LATIN), choose(rng, original))
+        }
+    };
+    if rng.gen_bool(0.5) {
+        // Arabic-Indic or ASCII digits, which bidi reordering moves about
+        let digits: String = match rng.gen_bool(0.5) {
+            true => (0..rng.gen_range(1..=3))
+                .map(|_| char::from_u32(0x0660 + rng.gen_range(0..10)).expect("Digits are chars"))
+                .collect(),
+            false => rng.gen_range(1..1000).to_string(),
+        };
+        name = format!("{} {}", name, digits);
+    }
+    match rng.gen_range(0..3) {
+        0 => {
+            let (open, close) = *BIDI_RUNS.choose(rng).expect("Bidi runs aren't empty");
+            format!("{}{}{}", open, name, close)
+        }
+        1 => {
+            let mark = *BIDI_MARKS.choose(rng).expect("Bidi marks aren't empty");
+            let boundaries: Vec<usize> = name
+                .char_indices()
+                .map(|(at, _)| at)
+                .chain([name.len()])
+                .collect();
+            name.insert(*boundaries.choose(rng).expect("Names have an end"), mark);
+            name
+        }
+        _ => name,
+    }
+}
+
+/// `name` with up to `most` marks from `marks` after each letter, half the
+/// letters left bare
+fn marked<R: Rng>(
+    rng: &mut R,
+    name: &str,
+    marks: std::ops::RangeInclusive<u32>,
+    most: usize,
+) -> String {
+    let mut marked = String::new();
+    for c in name.chars() {
+        marked.push(c);
+        if c.is_alphabetic() && rng.gen_bool(0.5) {
+            for _ in 0..rng.gen_range(1..=most) {
+                let mark = rng.gen_range(marks.clone());
+                marked.push(char::from_u32(mark).expect("Combining marks are chars"));
+            }
+        }
+    }
+    marked
+}
+
+/// One of the five Fitzpatrick modifiers
+fn skin_tone<R: Rng>(rng: &mut R) -> char {
+    char::from_u32(rng.gen_range(0x1F3FB..=0x1F3FF)).expect("Skin tones are chars")
+}
+
+fn choose<'a, R: Rng>(rng: &mut R, items: &[&'a str]) -> &'a str {
+    items.choose(rng).expect("Preset tables aren't empty")
+}
@@ -0,0 +1,593 @@
+use std::{
+    fmt::{self, Write},
+    fs::{File, OpenOptions},
+    io::{self, IsTerminal, Write as _},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use clap::ValueEnum;
+use indicatif::{FormattedDuration, MultiProgress, ProgressBar, ProgressState, ProgressStyle};
+
+use eyre::{Result, WrapErr};
+
+use crate::{
+    cli::{human_readable, Written},
+    dashboard::{Dashboard, Snapshot, View},
+    schema::civil_from_days,
+    summary::json_string,
+};
+
+/// How progress is shown while generating
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ProgressMode {
+    /// A live progress bar
+    Bar,
+    /// A single line status printed every few seconds, for logs
+    Plain,
+    /// Newline delimited JSON events on stderr, for tools tracking the run
+    Json,
+    /// A dashboard redrawn in place, graphing throughput and showing how
+    /// busy each thread is alongside the configuration
+    Tui,
+}
+
+impl ProgressMode {
+    /// A bar on a terminal, and plain lines when stderr is redirected
+    pub fn detect() -> Self {
+        if io::stderr().is_terminal() {
+            Self::Bar
+        } else {
+            Self::Plain
+        }
+    }
+}
+
+/// Progress through generating the output, counted in chunks written. The
+/// bytes written are tracked alongside for the throughput shown.
+#[derive(Clone)]
+pub struct Progress {
+    bar: ProgressBar,
+    counts: Counts,
+    /// Status lines printed instead of drawing the bar
+    report: Option<Report>,
+    paused: Arc<AtomicBool>,
+    tui: bool,
+    /// Nanoseconds each thread has spent working, once the dashboard shows
+    /// them
+    busy: Arc<OnceLock<Vec<AtomicU64>>>,
+    dashboard: Arc<Mutex<Option<Dashboard>>>,
+    log: Arc<Mutex<Option<ProgressLog>>>,
+}
+
+/// Progress through a batch of datasets generated one after another: a bar
+/// counting them, below the bars of those generated so far, or a status
+/// line as each starts
+#[derive(Debug, Clone)]
+pub struct Batch {
+    multi: MultiProgress,
+    overall: ProgressBar,
+    /// Whether each dataset starting is printed as a status line, and as
+    /// JSON if so
+    report: Option<bool>,
+}
+
+impl Batch {
+    /// Progress through `datasets` datasets, shown as `mode` unless `quiet`
+    pub fn new(datasets: u64, quiet: bool, mode: ProgressMode) -> Self {
+        let multi = MultiProgress::new();
+        let (overall, report) = match (quiet, mode) {
+            (false, ProgressMode::Bar) => {
+                let style = ProgressStyle::with_template(
+                    "[{elapsed_precise} elapsed] {pos}/{len} datasets done {wide_msg}",
+                )
+                .expect("Could not create progress bar style");
+                (
+                    multi.add(ProgressBar::new(datasets).with_style(style)),
+                    None,
+                )
+            }
+            (false, ProgressMode::Plain | ProgressMode::Json) => {
+                (ProgressBar::hidden(), Some(mode == ProgressMode::Json))
+            }
+            // The dashboard shows one dataset at a time
+            _ => (ProgressBar::hidden(), None),
+        };
+        overall.set_length(datasets);
+        Self {
+            multi,
+            overall,
+            report,
+        }
+    }
+
+    /// Shows that the next dataset, written to `output`, is starting
+    pub fn start(&self, output: &str) {
+        let (number, datasets) = (self.overall.position() + 1, self.overall.length());
+        let datasets = datasets.unwrap_or_default();
+        match self.report {
+            Some(true) => eprintln!(
+                "{{\"event\":\"dataset\",\"number\":{},\"datasets\":{},\"output\":{}}}",
+                number,
+                datasets,
+                json_string(output)
+            ),
+            Some(false) => eprintln!("Dataset {}/{}: {}", number, datasets, output),
+            None => self.overall.set_message(format!("Generating {}", output)),
+        }
+    }
+
+    /// Counts the dataset started last as done
+    pub fn done(&self) {
+        self.overall.inc(1);
+    }
+
+    pub fn finish(&self) {
+        self.overall.finish_with_message("");
+    }
+}
+
+/// Prints a status line whenever `interval` has passed since the last one
+#[derive(Clone)]
+struct Report {
+    json: bool,
+    interval: Duration,
+    last: Arc<Mutex<Instant>>,
+}
+
+/// What the rows and bytes written are worked out from. Chunks are written
+/// in order and all but the last hold `chunk_rows` rows, so the rows
+/// written follow from the chunks.
+#[derive(Clone)]
+struct Counts {
+    rows: u64,
+    chunk_rows: u64,
+    /// What a resumed run's output already held
+    start: Written,
+    bytes: Arc<AtomicU64>,
+}
+
+impl Progress {
+    /// Progress through `chunks` chunks of `chunk_rows` rows, continuing
+    /// from `start` towards `rows` in all, shown as `mode` unless `quiet`.
+    /// Plain and JSON status lines are printed every `interval`.
+    pub fn new(
+        chunks: u64,
+        rows: u64,
+        chunk_rows: u64,
+        start: Written,
+        quiet: bool,
+        mode: ProgressMode,
+        interval: Duration,
+    ) -> Self {
+        let counts = Counts {
+            rows,
+            chunk_rows,
+            start,
+            bytes: Arc::new(AtomicU64::new(start.bytes)),
+        };
+        let (bar, report) = match (quiet, mode) {
+            (true, _) => (ProgressBar::hidden(), None),
+            // The dashboard draws itself from the hidden bar's position
+            (false, ProgressMode::Tui) => (ProgressBar::hidden(), None),
+            (false, ProgressMode::Bar) => {
+                let bar = ProgressBar::new(chunks).with_style(generation_style(&counts));
+                bar.enable_steady_tick(Duration::from_millis(1000));
+                (bar, None)
+            }
+            (false, ProgressMode::Plain | ProgressMode::Json) => {
+                let report = Report {
+                    json: mode == ProgressMode::Json,
+                    interval,
+                    last: Arc::new(Mutex::new(Instant::now())),
+                };
+                (ProgressBar::hidden(), Some(report))
+            }
+        };
+        bar.set_length(chunks);
+        Self {
+            bar,
+            counts,
+            report,
+            paused: Arc::new(AtomicBool::new(false)),
+            tui: !quiet && mode == ProgressMode::Tui,
+            busy: Arc::new(OnceLock::new()),
+            dashboard: Arc::new(Mutex::new(None)),
+            log: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Starts appending a timestamped line with the rows and bytes written,
+    /// and the rate since the line before, to the file at `path` every
+    /// `interval`, however progress is shown
+    pub fn log_to(&self, path: &str, interval: Duration) -> Result<()> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .wrap_err_with(|| format!("Could not open file {}", path))?;
+        let (bar, counts) = (self.bar.clone(), self.counts.clone());
+        let log = ProgressLog::start(file, interval, move || {
+            counts.status(bar.position(), bar.elapsed())
+        });
+        *self.log.lock().expect("The progress log panicked") = Some(log);
+        Ok(())
+    }
+
+    /// Draws the bar above the bar of the `batch` this dataset is one of,
+    /// rather than on its own
+    pub fn join(&self, batch: &Batch) {
+        if !self.bar.is_hidden() && !batch.overall.is_hidden() {
+            batch.multi.insert_before(&batch.overall, self.bar.clone());
+        }
+    }
+
+    /// Starts drawing the dashboard, if that is how progress is shown,
+    /// titled after `output` and listing `config`. `threads` generate the
+    /// rows, which a single generator hands to a writer thread.
+    pub fn show_dashboard(
+        &self,
+        output: &str,
+        config: Vec<(&'static str, String)>,
+        threads: usize,
+    ) {
+        if !self.tui {
+            return;
+        }
+        let names: Vec<String> = match threads {
+            1 => vec!["generator".to_string(), "writer".to_string()],
+            threads => (0..threads)
+                .map(|worker| format!("worker {}", worker))
+                .collect(),
+        };
+        let _ = self
+            .busy
+            .set(names.iter().map(|_| AtomicU64::new(0)).collect());
+        let mut view = View::new(format!("billion-row-gen -> {}", output), config, names);
+        // Not a clone of the progress, which would keep the dashboard alive
+        let (bar, counts) = (self.bar.clone(), self.counts.clone());
+        let (busy, paused) = (self.busy.clone(), self.paused.clone());
+        let dashboard =
+            Dashboard::start(move || view.frame(snapshot(&bar, &counts, &busy, &paused)));
+        *self.dashboard.lock().expect("The dashboard panicked") = Some(dashboard);
+    }
+
+    /// Counts `time` spent working by the `thread`th thread the dashboard
+    /// shows, where a single generator is 0 and its writer 1
+    pub fn busy(&self, thread: usize, time: Duration) {
+        if let Some(busy) = self.busy.get().and_then(|busy| busy.get(thread)) {
+            busy.fetch_add(time.as_nanos() as u64, Ordering::Relaxed);
+        }
+    }
+
+    /// Shows generation as paused, freezing the bar; only the first of
+    /// several workers pausing together reports it
+    pub fn pause(&self) {
+        if self.paused.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        self.bar.disable_steady_tick();
+        self.bar.set_message("Paused, send SIGUSR1 again to resume");
+        self.event("paused", "Paused");
+    }
+
+    pub fn resume(&self) {
+        if !self.paused.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        self.bar.set_message("");
+        if !self.bar.is_hidden() {
+            self.bar.enable_steady_tick(Duration::from_millis(1000));
+        }
+        self.event("resumed", "Resumed");
+    }
+
+    /// Prints that the run was paused or resumed, as a status line
+    fn event(&self, event: &str, message: &str) {
+        let elapsed = self.bar.elapsed();
+        match &self.report {
+            Some(Report { json: true, .. }) => eprintln!(
+                "{{\"event\":\"{}\",\"elapsed_secs\":{:.3}}}",
+                event,
+                elapsed.as_secs_f64()
+            ),
+            Some(_) => eprintln!("[{} elapsed] {}", FormattedDuration(elapsed), message),
+            None => {}
+        }
+    }
+
+    pub fn set_chunks(&self, chunks: u64) {
+        self.bar.set_length(chunks);
+    }
+
+    /// Counts `chunks` more chunks written, holding `bytes` between them
+    pub fn inc(&self, chunks: u64, bytes: u64) {
+        self.counts.bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.bar.inc(chunks);
+        let Some(report) = &self.report else {
+            return;
+        };
+        let mut last = report.last.lock().expect("A progress report panicked");
+        if last.elapsed() < report.interval {
+            return;
+        }
+        if self.paused.load(Ordering::Relaxed) {
+            return;
+        }
+        *last = Instant::now();
+        let elapsed = self.bar.elapsed();
+        let status = self.counts.status(self.bar.position(), elapsed);
+        if report.json {
+            eprintln!(
+                "{{\"event\":\"progress\",\"rows\":{},\"bytes\":{},\"projected_bytes\":{},\
+                 \"rows_per_sec\":{:.0},\"bytes_per_sec\":{:.0},\"elapsed_secs\":{:.3},\
+                 \"eta_secs\":{:.3}}}",
+                status.rows,
+                status.bytes,
+                status.projected,
+                status.rows_per_sec,
+                status.bytes_per_sec,
+                elapsed.as_secs_f64(),
+                self.bar.eta().as_secs_f64()
+            );
+        } else {
+            let len = self.bar.length().unwrap_or(0).max(1);
+            eprintln!(
+                "[{} elapsed] [{:.2}%] {}",
+                FormattedDuration(elapsed),
+                self.bar.position() as f64 / len as f64 * 100.0,
+                status
+            );
+        }
+    }
+
+    /// Reports the run as completed, or interrupted if `written` is short of
+    /// the rows asked for without it being `out_of_time`
+    pub fn finish(&self, written: Written, out_of_time: bool) {
+        let short = written.rows < self.counts.rows;
+        let interrupted = short && !out_of_time;
+        let (event, message) = if interrupted {
+            ("interrupted", "Interrupted".to_string())
+        } else if short {
+            (
+                "completed",
+                format!("Out of time after {} rows", written.rows),
+            )
+        } else {
+            ("completed", "Completed".to_string())
+        };
+        let message = format!(
+            "{}, final file size: {}",
+            message,
+            human_readable(written.bytes)
+        );
+        // The last frame is drawn before the message below it
+        if let Some(dashboard) = self
+            .dashboard
+            .lock()
+            .expect("The dashboard panicked")
+            .take()
+        {
+            drop(dashboard);
+            eprintln!("\n{}", message);
+        }
+        if let Some(log) = self.log.lock().expect("The progress log panicked").take() {
+            log.finish(event, written);
+        }
+        let elapsed = self.bar.elapsed();
+        match &self.report {
+            Some(Report { json: true, .. }) => eprintln!(
+                "{{\"event\":\"{}\",\"rows\":{},\"bytes\":{},\"elapsed_secs\":{:.3}}}",
+                event,
+                written.rows,
+                written.bytes,
+                elapsed.as_secs_f64()
+            ),
+            Some(_) => eprintln!("[{} elapsed] {}", FormattedDuration(elapsed), message),
+            None => {}
+        }
+        if interrupted {
+            self.bar.abandon_with_message(message);
+        } else {
+            self.bar.finish_with_message(message);
+        }
+    }
+}
+
+/// The thread writing `--progress-log`, stopped when dropped
+struct ProgressLog {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<File>>,
+}
+
+impl ProgressLog {
+    fn start<F>(mut file: File, interval: Duration, status: F) -> Self
+    where
+        F: Fn() -> Status + Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stopping = stop.clone();
+        let handle = thread::spawn(move || {
+            let (mut last, mut last_rows, mut last_bytes) = (Instant::now(), None, None);
+            loop {
+                thread::park_timeout(interval.saturating_sub(last.elapsed()));
+                if stopping.load(Ordering::Relaxed) {
+                    return file;
+                }
+                if last.elapsed() < interval {
+                    continue;
+                }
+                let status = status();
+                let seconds = last.elapsed().as_secs_f64();
+                // The first line's rate is the run's so far
+                let rows_per_sec = last_rows.map_or(status.rows_per_sec, |rows| {
+                    (status.rows - rows) as f64 / seconds
+                });
+                let bytes_per_sec = last_bytes.map_or(status.bytes_per_sec, |bytes| {
+                    (status.bytes - bytes) as f64 / seconds
+                });
+                // A log that can't be written mustn't stop the run
+                let _ = writeln!(
+                    file,
+                    "{} progress rows={} bytes={} rows_per_sec={:.0} bytes_per_sec={:.0}",
+                    utc_now(),
+                    status.rows,
+                    status.bytes,
+                    rows_per_sec,
+                    bytes_per_sec
+                );
+                (last, last_rows, last_bytes) =
+                    (Instant::now(), Some(status.rows), Some(status.bytes));
+            }
+        });
+        Self {
+            stop,
+            handle: Some(handle),
+        }
+    }
+
+    /// Stops the thread and logs how the run ended
+    fn finish(mut self, event: &str, written: Written) {
+        if let Some(mut file) = self.stop() {
+            let _ = writeln!(
+                file,
+                "{} {} rows={} bytes={}",
+                utc_now(),
+                event,
+                written.rows,
+                written.bytes
+            );
+        }
+    }
+
+    fn stop(&mut self) -> Option<File> {
+        self.stop.store(true, Ordering::Relaxed);
+        let handle = self.handle.take()?;
+        handle.thread().unpark();
+        handle.join().ok()
+    }
+}
+
+impl Drop for ProgressLog {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// The time now in UTC, as `2024-01-31T12:00:00Z`
+fn utc_now() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs()) as i64;
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86_400));
+    let secs = seconds.rem_euclid(86_400);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3_600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// What the dashboard draws its next frame from
+fn snapshot(
+    bar: &ProgressBar,
+    counts: &Counts,
+    busy: &OnceLock<Vec<AtomicU64>>,
+    paused: &AtomicBool,
+) -> Snapshot {
+    let status = counts.status(bar.position(), bar.elapsed());
+    Snapshot {
+        rows: status.rows,
+        total_rows: counts.rows,
+        bytes: status.bytes,
+        projected: status.projected,
+        elapsed: bar.elapsed(),
+        busy: busy
+            .get()
+            .map(|busy| busy.iter().map(|b| b.load(Ordering::Relaxed)).collect())
+            .unwrap_or_default(),
+        paused: paused.load(Ordering::Relaxed),
+    }
+}
+
+impl Counts {
+    fn status(&self, chunks: u64, elapsed: Duration) -> Status {
+        let rows = (self.start.rows + chunks * self.chunk_rows).min(self.rows);
+        let bytes = self.bytes.load(Ordering::Relaxed);
+        let seconds = elapsed.as_secs_f64().max(f64::EPSILON);
+        Status {
+            rows,
+            bytes,
+            // Scaled up by the average line so far, to the rows still to come
+            projected: match rows {
+                0 => 0,
+                rows => (bytes as f64 / rows as f64 * self.rows as f64) as u64,
+            },
+            rows_per_sec: (rows - self.start.rows) as f64 / seconds,
+            bytes_per_sec: (bytes - self.start.bytes) as f64 / seconds,
+        }
+    }
+}
+
+/// The throughput so far, with the final size it projects
+struct Status {
+    rows: u64,
+    bytes: u64,
+    projected: u64,
+    rows_per_sec: f64,
+    bytes_per_sec: f64,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} of ~{}, {} rows/s, {}/s",
+            human_readable(self.bytes),
+            human_readable(self.projected),
+            human_count(self.rows_per_sec),
+            human_readable(self.bytes_per_sec as u64)
+        )
+    }
+}
+
+/// The plain style, for progress measured in bytes
+pub fn style() -> ProgressStyle {
+    ProgressStyle::with_template(
+        "[{elapsed_precise} elapsed] [{eta_precise} remaining] [{percent:.2}%] {msg}\n{bar:80.cyan/blue} ",
+    )
+    .expect("Could not create progress bar style")
+}
+
+/// The style of a bar counting chunks, adding the bytes written so far, the
+/// final size they project, and rows and bytes per second
+fn generation_style(counts: &Counts) -> ProgressStyle {
+    let counts = counts.clone();
+    ProgressStyle::with_template(
+        "[{elapsed_precise} elapsed] [{eta_precise} remaining] [{percent:.2}%] {msg}\n\
+         {bar:80.cyan/blue} {status}",
+    )
+    .expect("Could not create progress bar style")
+    .with_key("status", move |state: &ProgressState, w: &mut dyn Write| {
+        let _ = write!(w, "{}", counts.status(state.pos(), state.elapsed()));
+    })
+}
+
+/// `value` with a K, M, or G suffix
+pub fn human_count(value: f64) -> String {
+    let (value, suffix) = match value {
+        value if value >= 1e9 => (value / 1e9, "G"),
+        value if value >= 1e6 => (value / 1e6, "M"),
+        value if value >= 1e3 => (value / 1e3, "K"),
+        value => (value, ""),
+    };
+    format!("{:.2}{}", value, suffix)
+}
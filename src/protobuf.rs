@@ -0,0 +1,105 @@
+use std::{
+    mem,
+    sync::mpsc::{Receiver, Sender, SyncSender},
+};
+
+use eyre::{eyre, Result};
+
+use crate::extract::{measurement, unquoted};
+
+/// Encodes chunks of `station;measurement` lines as the `Measurement`
+/// messages of `proto/measurement.proto` between the generator and the
+/// writer, each preceded by its length as a varint, returning each text
+/// buffer on `spent` once its rows are taken. Stops at the first row that
+/// isn't a measurement.
+pub fn encode_chunks(
+    chunks: Receiver<Vec<u8>>,
+    spent: Sender<Vec<u8>>,
+    encoded: SyncSender<Vec<u8>>,
+) -> Result<()> {
+    let _span = tracing::debug_span!("protobuf_encoder").entered();
+    let (mut output, mut message) = (Vec::new(), Proto::default());
+    for chunk in chunks {
+        let pushed = push_messages(&chunk, &mut message, &mut output);
+        // The generator stops taking buffers once it has run out of rows
+        let _ = spent.send(chunk);
+        pushed?;
+        // Only fails once the writer has failed, which it reports
+        if encoded.send(mem::take(&mut output)).is_err() {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+/// Appends the `station;measurement` rows of `chunk`, each ending in a
+/// newline, to `output` as delimited messages, reusing `message`
+fn push_messages(chunk: &[u8], message: &mut Proto, output: &mut Vec<u8>) -> Result<()> {
+    let Some(rows) = chunk.strip_suffix(b"\n") else {
+        return Ok(());
+    };
+    output.reserve(rows.len() + rows.len() / 2);
+    for row in rows.split(|&b| b == b'\n') {
+        let split = row
+            .iter()
+            .rposition(|&b| b == b';')
+            .ok_or_else(|| eyre!("{:?} isn't a measurement", String::from_utf8_lossy(row)))?;
+        let temp = measurement(&row[split + 1..])?;
+        message.0.clear();
+        message.bytes(1, &unquoted(&row[..split]));
+        // proto3 leaves fields at their default out, and 0.0 is the default
+        // while -0.0 isn't
+        if temp.to_bits() != 0 {
+            message.double(2, temp);
+        }
+        push_varint(output, message.0.len() as u64);
+        output.extend_from_slice(&message.0);
+    }
+    Ok(())
+}
+
+/// A protobuf message, encoded field by field
+#[derive(Default)]
+pub struct Proto(pub Vec<u8>);
+
+impl Proto {
+    pub fn uint(&mut self, field: u64, value: u64) {
+        push_varint(&mut self.0, field << 3);
+        push_varint(&mut self.0, value);
+    }
+
+    pub fn sint(&mut self, field: u64, value: i64) {
+        self.uint(field, ((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    pub fn double(&mut self, field: u64, value: f64) {
+        push_varint(&mut self.0, field << 3 | 1);
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn bytes(&mut self, field: u64, bytes: &[u8]) {
+        push_varint(&mut self.0, field << 3 | 2);
+        push_varint(&mut self.0, bytes.len() as u64);
+        self.0.extend_from_slice(bytes);
+    }
+
+    pub fn message(&mut self, field: u64, message: &Proto) {
+        self.bytes(field, &message.0);
+    }
+
+    pub fn packed(&mut self, field: u64, values: &[u64]) {
+        let mut packed = Vec::new();
+        for &value in values {
+            push_varint(&mut packed, value);
+        }
+        self.bytes(field, &packed);
+    }
+}
+
+pub fn push_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
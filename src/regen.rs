@@ -0,0 +1,155 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use clap::Parser;
+use eyre::{eyre, Result, WrapErr};
+
+use crate::{
+    cli::{generate_dataset, with_stations, Args},
+    errors, json,
+    output::{self, Format, RowCallback, Sink},
+};
+
+/// Where a part ends, as the rolling writer splits the output
+struct Parts {
+    max: u64,
+    /// The part being generated, and the bytes it holds so far
+    part: u32,
+    len: u64,
+}
+
+impl Parts {
+    /// The part a line of `len` bytes goes in: the current one while it
+    /// fits, or the next. A line longer than `max` gets a part to itself.
+    fn place(&mut self, len: u64) -> u32 {
+        if self.len > 0 && self.len + len > self.max {
+            self.part += 1;
+            self.len = 0;
+        }
+        self.len += len;
+        self.part
+    }
+}
+
+/// Regenerates part `shard` of the split output the manifest at `path`
+/// records, from the command in it, into the part's file. The rows before
+/// the part are generated without being written, as their lengths decide
+/// where it starts, and generation stops once the part is complete.
+pub fn regen_shard(path: &str, shard: u32, force: bool, quiet: bool) -> Result<()> {
+    let text =
+        fs::read_to_string(path).wrap_err_with(|| format!("Could not open file {}", path))?;
+    let manifest = json::parse(&text).map_err(|e| eyre!("{}: {}", path, e))?;
+    if manifest.get("complete") != Some(&json::Value::Bool(true)) {
+        return Err(eyre!(
+            "{} records an interrupted run, whose parts were never final",
+            path
+        ));
+    }
+    let strings = |value: Option<&json::Value>| -> Option<Vec<String>> {
+        let json::Value::Array(items) = value? else {
+            return None;
+        };
+        items
+            .iter()
+            .map(|item| match item {
+                json::Value::String(item) => Some(item.clone()),
+                _ => None,
+            })
+            .collect()
+    };
+    let command = strings(manifest.get("command"))
+        .ok_or_else(|| eyre!("{} holds no command to regenerate from", path))?;
+    let parts = manifest
+        .get("output")
+        .and_then(|output| output.get("parts"));
+    let parts =
+        strings(parts).ok_or_else(|| eyre!("{} lists no parts, as split output does", path))?;
+    let part = match shard.checked_sub(1).and_then(|i| parts.get(i as usize)) {
+        Some(part) => part.clone(),
+        None => {
+            return Err(eyre!(
+                "{} lists {} parts, numbered from 1",
+                path,
+                parts.len()
+            ))
+        }
+    };
+    if !force && Path::new(&part).exists() {
+        return Err(eyre!("{} already exists, pass --force to replace it", part));
+    }
+
+    let mut args = Args::try_parse_from(&command).map_err(|e| eyre!("{}", errors::problem(&e)))?;
+    let max = args
+        .max_file_size
+        .ok_or_else(|| eyre!("{} records no --max-file-size", path))?;
+    if args.format != Format::Text {
+        return Err(eyre!("Only text output is split on line boundaries"));
+    }
+    // A run cut short by --duration holds fewer rows than its command asks for
+    if let Some(json::Value::Number(rows)) = manifest.get("rows") {
+        if (*rows as u64) < args.row_count() {
+            args.end_row = Some(args.row_range().start + *rows as u64);
+        }
+    }
+
+    let partial = output::partial_path(&part);
+    let file = File::create(&partial).wrap_err_with(|| format!("Could not create {}", partial))?;
+    let writer = Arc::new(Mutex::new((BufWriter::new(file), false)));
+    let taken = Arc::clone(&writer);
+    let mut parts = Parts {
+        max: max as u64,
+        part: 1,
+        len: 0,
+    };
+    let take = move |row: &[u8]| -> io::Result<()> {
+        let (writer, passed) = &mut *taken.lock().expect("The row callback panicked");
+        match parts.place(row.len() as u64 + 1) {
+            part if part < shard => Ok(()),
+            part if part == shard => {
+                writer.write_all(row)?;
+                writer.write_all(b"\n")
+            }
+            _ => {
+                *passed = true;
+                Err(io::Error::other("The part is complete"))
+            }
+        }
+    };
+    let args = Args {
+        command: None,
+        output: part.clone(),
+        max_file_size: None,
+        threads: Some(1),
+        sink: Sink::Null,
+        quiet: true,
+        no_manifest: true,
+        row_callback: Some(RowCallback(Arc::new(Mutex::new(take)))),
+        ..with_stations(&args)?
+    };
+    let generated = generate_dataset(args);
+    let (writer, passed) = &mut *writer.lock().expect("The row callback panicked");
+    let result = (|| -> Result<()> {
+        // Reaching the next part is how regenerating one ends early
+        if !*passed {
+            generated?;
+        }
+        writer.flush()?;
+        if writer.get_ref().metadata()?.len() == 0 {
+            return Err(eyre!("The command in {} made no part {}", path, shard));
+        }
+        Ok(())
+    })();
+    if let Err(error) = result {
+        let _ = fs::remove_file(&partial);
+        return Err(error);
+    }
+    fs::rename(&partial, &part)?;
+    if !quiet {
+        eprintln!("Regenerated {}", part);
+    }
+    Ok(())
+}
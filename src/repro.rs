@@ -0,0 +1,73 @@
+use std::sync::Mutex;
+
+use eyre::{eyre, Result};
+
+use crate::{
+    checksum::{Algorithm, Checksum},
+    cli::{check_thread_options, Args},
+    output::Sink,
+};
+
+/// Generates `rows` of the rows the other options describe from `seed`
+/// twice, the second time on `threads` threads if given, without writing
+/// them, and checks that both runs hash the same. `generate` feeds the rows
+/// `args` describe, from the seed given, to the checksum.
+pub fn repro_check<G>(
+    args: &Args,
+    rows: u64,
+    seed: u64,
+    threads: Option<usize>,
+    generate: G,
+) -> Result<()>
+where
+    G: Fn(&Args, u64, &Mutex<Checksum>) -> Result<u64>,
+{
+    let first = Args {
+        command: None,
+        rows,
+        start_row: 0,
+        end_row: None,
+        sink: Sink::Null,
+        quiet: true,
+        checkpoint_interval: None,
+        max_file_size: None,
+        ..args.clone()
+    };
+    let second = Args {
+        threads: threads.or(first.threads),
+        ..first.clone()
+    };
+    for run in [&first, &second]
+        .into_iter()
+        .filter(|run| run.threads() > 1)
+    {
+        check_thread_options(run)?;
+    }
+
+    let mut hashes = Vec::new();
+    for (number, run) in [(1, &first), (2, &second)] {
+        let checksum = Mutex::new(Checksum::new(Algorithm::Sha256));
+        let bytes = generate(run, seed, &checksum)?;
+        let hash = checksum.into_inner().expect("A writer panicked").finish();
+        println!(
+            "Run {} on {} thread{}: {} bytes, sha256 {}",
+            number,
+            run.threads(),
+            if run.threads() == 1 { "" } else { "s" },
+            bytes,
+            hash
+        );
+        hashes.push(hash);
+    }
+    if hashes[0] != hashes[1] {
+        return Err(eyre!(
+            "The runs differ, so this build doesn't generate deterministically from --seed {}",
+            seed
+        ));
+    }
+    println!(
+        "Both runs match, so output from --seed {} is reproducible",
+        seed
+    );
+    Ok(())
+}
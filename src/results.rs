@@ -0,0 +1,425 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::{self, Read, Write},
+};
+
+use eyre::{eyre, Result, WrapErr};
+
+use crate::{
+    answer::{self, Rounding},
+    census,
+};
+
+/// Mismatches reported in full; past this only the count is kept
+const MAX_REPORTED: usize = 10;
+
+/// Slack for values that are decimal tenths but compared as floats, where
+/// `18.1 - 18.0` is a hair over `0.1`
+const EPSILON: f64 = 1e-9;
+
+/// The statistics of each station, in the order they are printed
+const STATISTICS: [&str; 3] = ["min", "mean", "max"];
+
+/// One station's results as a solver printed them
+struct Entry<'a> {
+    station: &'a str,
+    /// Min, mean, and max, each with at least one decimal
+    values: [String; 3],
+}
+
+/// A station whose results don't match: missing from the solution, not in
+/// the reference, or with some statistics out of tolerance
+struct Difference<'a> {
+    station: &'a str,
+    expected: Option<&'a [String; 3]>,
+    solved: Option<[String; 3]>,
+    /// Which of min, mean, and max diverged
+    diverged: [bool; 3],
+}
+
+/// How far a solver's results may stray from the reference's
+#[derive(Debug, Clone, Copy)]
+pub struct Tolerance {
+    /// Absolute difference allowed
+    pub absolute: f64,
+    /// Difference allowed as a fraction of the larger value
+    pub relative: f64,
+    /// Whether min and max get the tolerance too, rather than only the mean
+    pub min_max: bool,
+}
+
+impl Tolerance {
+    fn allows(self, value: f64, expected: f64) -> bool {
+        let allowed = self
+            .absolute
+            .max(self.relative * value.abs().max(expected.abs()));
+        (value - expected).abs() <= allowed + EPSILON
+    }
+}
+
+/// A solver's results in the official format, `{Abha=-23.0/18.0/59.2, ...}`
+/// sorted by name, however the solver ordered, spaced, or broke them into
+/// lines. Values keep their own decimals, so rounding differently still
+/// shows, but `1` and `+1.0` both become `1.0`.
+pub fn canonical(output: &str) -> Result<String> {
+    let mut entries = parse(output)?;
+    entries.sort_by(|a, b| a.station.as_bytes().cmp(b.station.as_bytes()));
+    let entries: Vec<_> = entries
+        .iter()
+        .map(|entry| format!("{}={}", entry.station, entry.values.join("/")))
+        .collect();
+    Ok(format!("{{{}}}\n", entries.join(", ")))
+}
+
+/// Prints the canonical form of the results in `path`, or on stdin when it
+/// is `-`
+pub fn normalize(path: &str) -> Result<()> {
+    let canonical = canonical(&read(path)?)?;
+    match io::stdout().lock().write_all(canonical.as_bytes()) {
+        // A reader such as `head` closes the pipe once it has seen enough
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        written => Ok(written?),
+    }
+}
+
+/// What a solution is checked against
+#[derive(Debug, Clone, Copy)]
+pub enum Reference<'a> {
+    /// The measurements file the solver was run on, whose results are
+    /// worked out here
+    Measurements(&'a str),
+    /// Results already worked out for it, such as an answer key, in the
+    /// official format or anything `normalize` reads
+    Results(&'a str),
+}
+
+impl Reference<'_> {
+    fn path(&self) -> &str {
+        match self {
+            Self::Measurements(path) | Self::Results(path) => path,
+        }
+    }
+
+    /// Each station's expected min, mean, and max
+    fn expected(&self) -> Result<Vec<(String, [String; 3])>> {
+        match *self {
+            Self::Measurements(path) => Ok(census::tally(&[path.to_string()])?
+                .iter()
+                .map(|(station, stats)| {
+                    let values = [stats.min, answer::mean(stats, Rounding::HalfUp), stats.max]
+                        .map(|tenths| format!("{:.1}", tenths as f64 / 10.0));
+                    (String::from_utf8_lossy(station).into_owned(), values)
+                })
+                .collect()),
+            Self::Results(path) => {
+                let results = read(path)?;
+                let (mut expected, mut seen) = (Vec::new(), HashSet::new());
+                for entry in parse(&results)? {
+                    if !seen.insert(entry.station) {
+                        return Err(eyre!("{} lists {} twice", path, entry.station));
+                    }
+                    expected.push((entry.station.to_string(), entry.values));
+                }
+                Ok(expected)
+            }
+        }
+    }
+}
+
+/// Checks the results in `solution`, or on stdin when it is `-`, against
+/// those expected from `reference`: the same stations, each with its min,
+/// mean, and max within `tolerance` of the expected ones. Either may be read
+/// from stdin, so a solver's output can be piped straight in. Any
+/// mismatches are also written to `report` as an HTML page, if given.
+pub fn verify(
+    reference: Reference,
+    solution: &str,
+    tolerance: Tolerance,
+    report: Option<&str>,
+) -> Result<()> {
+    if tolerance.absolute < 0.0 || tolerance.relative < 0.0 {
+        return Err(eyre!("Tolerances can't be negative"));
+    }
+    if reference.path() == "-" && solution == "-" {
+        return Err(eyre!(
+            "Only one of the solution and the expected results can be read from stdin"
+        ));
+    }
+    let output = read(solution)?;
+    let mut solved: HashMap<&str, [String; 3]> = HashMap::new();
+    for entry in parse(&output)? {
+        if solved.insert(entry.station, entry.values).is_some() {
+            return Err(eyre!("{} lists {} twice", solution, entry.station));
+        }
+    }
+    let expected = reference.expected()?;
+
+    let mut differences = Vec::new();
+    for (station, expected) in &expected {
+        let Some(values) = solved.remove(station.as_str()) else {
+            differences.push(Difference {
+                station,
+                expected: Some(expected),
+                solved: None,
+                diverged: [true; 3],
+            });
+            continue;
+        };
+        let diverged: [bool; 3] = std::array::from_fn(|i| {
+            let value: f64 = values[i].parse().expect("Values are numbers");
+            let wanted: f64 = expected[i].parse().expect("Values are numbers");
+            let tolerated = i == 1 || tolerance.min_max;
+            let matches = match tolerated {
+                true => tolerance.allows(value, wanted),
+                false => value == wanted,
+            };
+            !matches
+        });
+        if diverged.contains(&true) {
+            differences.push(Difference {
+                station,
+                expected: Some(expected),
+                solved: Some(values),
+                diverged,
+            });
+        }
+    }
+    let mut extra: Vec<_> = solved.into_iter().collect();
+    extra.sort_unstable_by(|a, b| a.0.cmp(b.0));
+    differences.extend(extra.into_iter().map(|(station, values)| Difference {
+        station,
+        expected: None,
+        solved: Some(values),
+        diverged: [true; 3],
+    }));
+
+    let mut mismatches = Vec::new();
+    for difference in &differences {
+        let station = difference.station;
+        match (difference.expected, &difference.solved) {
+            (Some(_), None) => mismatches.push(format!("{} is missing", station)),
+            (None, _) => mismatches.push(format!("{} isn't in {}", station, reference.path())),
+            (Some(expected), Some(values)) => {
+                for (i, what) in STATISTICS.into_iter().enumerate() {
+                    if difference.diverged[i] {
+                        mismatches.push(format!(
+                            "{} has a {} of {}, expected {}",
+                            station, what, values[i], expected[i]
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    if !mismatches.is_empty() {
+        if let Some(report) = report {
+            let html = html_report(&differences, &reference, solution, tolerance);
+            fs::write(report, html).wrap_err_with(|| format!("Could not write {}", report))?;
+            eprintln!("Wrote the mismatches to {}", report);
+        }
+        let mut report = format!(
+            "The solution has {} mismatch{}:",
+            mismatches.len(),
+            if mismatches.len() == 1 { "" } else { "es" }
+        );
+        for mismatch in mismatches.iter().take(MAX_REPORTED) {
+            report.push_str(&format!("\n  {}", mismatch));
+        }
+        if mismatches.len() > MAX_REPORTED {
+            report.push_str(&format!("\n  and {} more", mismatches.len() - MAX_REPORTED));
+        }
+        return Err(eyre!(report));
+    }
+    println!("The solution matches all {} stations", expected.len());
+    Ok(())
+}
+
+/// A page listing each station in `differences` with its expected and
+/// solved min, mean, and max side by side, the statistics that diverged
+/// highlighted, and stations only one side has shaded by side
+fn html_report(
+    differences: &[Difference],
+    reference: &Reference,
+    solution: &str,
+    tolerance: Tolerance,
+) -> String {
+    let name = |path: &str| match path {
+        "-" => "stdin".to_string(),
+        path => html_escape(path),
+    };
+    let mut rows = String::new();
+    for difference in differences {
+        let (class, note) = match (difference.expected, &difference.solved) {
+            (Some(_), None) => (" class=\"missing\"", " (missing)"),
+            (None, _) => (" class=\"extra\"", " (extra)"),
+            _ => ("", ""),
+        };
+        rows.push_str(&format!(
+            "<tr{}><th>{}{}</th>",
+            class,
+            html_escape(difference.station),
+            note
+        ));
+        for i in 0..STATISTICS.len() {
+            let cell = |values: Option<&[String; 3]>| {
+                values.map_or("&ndash;".to_string(), |values| html_escape(&values[i]))
+            };
+            let diverged = match difference.diverged[i] && class.is_empty() {
+                true => " class=\"diverged\"",
+                false => "",
+            };
+            rows.push_str(&format!(
+                "<td{0}>{1}</td><td{0}>{2}</td>",
+                diverged,
+                cell(difference.expected),
+                cell(difference.solved.as_ref())
+            ));
+        }
+        rows.push_str("</tr>\n");
+    }
+    let mins_and_maxes = match tolerance.min_max {
+        true => "held to the same tolerances",
+        false => "exact",
+    };
+    format!(
+        "<!DOCTYPE html>
+<html lang=\"en\">
+<head>
+<meta charset=\"utf-8\">
+<title>{mismatched}</title>
+<style>
+body {{ font-family: sans-serif; margin: 2em; }}
+table {{ border-collapse: collapse; }}
+th, td {{ border: 1px solid #ccc; padding: 0.3em 0.6em; }}
+td {{ font-family: monospace; text-align: right; }}
+tbody th {{ text-align: left; font-weight: normal; }}
+td.diverged {{ background: #f8c4c4; font-weight: bold; }}
+tr.missing {{ background: #fde8b8; }}
+tr.extra {{ background: #d4e4fc; }}
+</style>
+</head>
+<body>
+<h1>{mismatched}</h1>
+<p>Solution: {solution}<br>
+Expected: {reference}<br>
+Means may differ by {absolute} or {relative} of the value; mins and maxes are {mins_and_maxes}</p>
+<table>
+<thead>
+<tr><th rowspan=\"2\">Station</th><th colspan=\"2\">Min</th><th colspan=\"2\">Mean</th><th colspan=\"2\">Max</th></tr>
+<tr><th>Expected</th><th>Solved</th><th>Expected</th><th>Solved</th><th>Expected</th><th>Solved</th></tr>
+</thead>
+<tbody>
+{rows}</tbody>
+</table>
+</body>
+</html>
+",
+        mismatched = match differences.len() {
+            1 => "1 station doesn't match".to_string(),
+            count => format!("{} stations don't match", count),
+        },
+        solution = name(solution),
+        reference = name(reference.path()),
+        absolute = tolerance.absolute,
+        relative = tolerance.relative,
+    )
+}
+
+/// `text` with the characters HTML gives meaning to escaped
+fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The text of `path`, or of stdin when it is `-`
+fn read(path: &str) -> Result<String> {
+    match path {
+        "-" => {
+            let mut output = String::new();
+            io::stdin().read_to_string(&mut output)?;
+            Ok(output)
+        }
+        path => fs::read_to_string(path).wrap_err_with(|| format!("Could not open file {}", path)),
+    }
+}
+
+/// Every entry in `output`, in the order printed
+fn parse(output: &str) -> Result<Vec<Entry<'_>>> {
+    let body = output.trim();
+    let body = body.strip_prefix('{').unwrap_or(body);
+    let mut rest = body.strip_suffix('}').unwrap_or(body);
+    let mut entries = Vec::new();
+    loop {
+        rest = rest.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+        if rest.is_empty() {
+            return Ok(entries);
+        }
+        let (entry, after) = entry(rest).ok_or_else(|| {
+            let preview: String = rest.chars().take(40).collect();
+            eyre!(
+                "Expected `<station>=<min>/<mean>/<max>`, found `{}`",
+                preview
+            )
+        })?;
+        entries.push(entry);
+        rest = after;
+    }
+}
+
+/// The first entry in `text` and what follows it. A name may hold `=`, so
+/// it runs to the first `=` followed by three values and the end of the
+/// entry.
+fn entry(text: &str) -> Option<(Entry<'_>, &str)> {
+    text.match_indices('=').find_map(|(at, _)| {
+        let (values, after) = values(&text[at + 1..])?;
+        let ended = after.is_empty() || after.starts_with(|c: char| c == ',' || c.is_whitespace());
+        let entry = Entry {
+            station: text[..at].trim(),
+            values,
+        };
+        ended.then_some((entry, after))
+    })
+}
+
+/// `min/mean/max`, with any spaces around the slashes, and what follows
+fn values(text: &str) -> Option<([String; 3], &str)> {
+    let mut rest = text;
+    let mut values: [String; 3] = Default::default();
+    for (i, value) in values.iter_mut().enumerate() {
+        if i > 0 {
+            rest = rest.trim_start().strip_prefix('/')?;
+        }
+        rest = rest.trim_start();
+        let end = rest
+            .find(|c: char| !(c.is_ascii_digit() || matches!(c, '-' | '+' | '.')))
+            .unwrap_or(rest.len());
+        *value = number(&rest[..end])?;
+        rest = &rest[end..];
+    }
+    Some((values, rest))
+}
+
+/// `text` as a number with at least one decimal and no `+`
+fn number(text: &str) -> Option<String> {
+    text.parse::<f64>().ok().filter(|n| n.is_finite())?;
+    let text = text.strip_prefix('+').unwrap_or(text);
+    let (sign, digits) = match text.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", text),
+    };
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    let whole = if whole.is_empty() { "0" } else { whole };
+    let fraction = if fraction.is_empty() { "0" } else { fraction };
+    Some(format!("{}{}.{}", sign, whole, fraction))
+}
@@ -0,0 +1,338 @@
+#[cfg(feature = "cli")]
+use clap::ValueEnum;
+use rand::{
+    rngs::{SmallRng, StdRng},
+    RngCore, SeedableRng,
+};
+
+/// The random number generator backing generation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum RngKind {
+    /// ChaCha12, cryptographically strong
+    Std,
+    /// rand's fast, platform dependent small generator
+    Small,
+    /// Xoshiro256++, fast and portable
+    #[cfg_attr(feature = "cli", value(name = "xoshiro256++"))]
+    Xoshiro256PlusPlus,
+    /// PCG64 (XSL RR 128/64), fast and portable
+    Pcg64,
+    /// Philox4x32-10, counter based: every row's values depend only on the
+    /// seed and the row index, so any row can be regenerated on its own
+    Philox,
+}
+
+/// One of the generators of [`RngKind`], dispatched with a match rather than
+/// dynamically so the hot loop stays monomorphic
+#[derive(Clone)]
+pub enum GenRng {
+    Std(Box<StdRng>),
+    Small(SmallRng),
+    Xoshiro256PlusPlus(Xoshiro256PlusPlus),
+    Pcg64(Pcg64),
+    Philox(Philox),
+}
+
+impl GenRng {
+    pub fn seed_from_u64(kind: RngKind, seed: u64) -> Self {
+        match kind {
+            RngKind::Std => Self::Std(Box::new(StdRng::seed_from_u64(seed))),
+            RngKind::Small => Self::Small(SmallRng::seed_from_u64(seed)),
+            RngKind::Xoshiro256PlusPlus => {
+                Self::Xoshiro256PlusPlus(Xoshiro256PlusPlus::seed_from_u64(seed))
+            }
+            RngKind::Pcg64 => Self::Pcg64(Pcg64::seed_from_u64(seed)),
+            RngKind::Philox => Self::Philox(Philox::new(seed)),
+        }
+    }
+
+    /// Positions a counter based generator at the start of `row`'s values;
+    /// other generators simply continue their stream
+    pub fn set_row(&mut self, row: u64) {
+        if let Self::Philox(philox) = self {
+            philox.set_row(row);
+        }
+    }
+}
+
+macro_rules! dispatch {
+    ($self:expr, $rng:ident => $body:expr) => {
+        match $self {
+            GenRng::Std($rng) => $body,
+            GenRng::Small($rng) => $body,
+            GenRng::Xoshiro256PlusPlus($rng) => $body,
+            GenRng::Pcg64($rng) => $body,
+            GenRng::Philox($rng) => $body,
+        }
+    };
+}
+
+impl RngCore for GenRng {
+    fn next_u32(&mut self) -> u32 {
+        dispatch!(self, rng => rng.next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        dispatch!(self, rng => rng.next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        dispatch!(self, rng => rng.fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        dispatch!(self, rng => rng.try_fill_bytes(dest))
+    }
+}
+
+/// Serves a generator's output from a buffer refilled in large blocks, so
+/// the hot loop decodes stations and measurements from ready words instead
+/// of stepping the generator between every use. Counter based generators
+/// are passed through, since their values must line up with their rows.
+pub struct BatchRng {
+    source: GenRng,
+    words: Vec<u64>,
+    /// Next unused word of `words`
+    next: usize,
+}
+
+impl BatchRng {
+    /// Number of words generated at once
+    const BATCH_SIZE: usize = 4096;
+
+    pub fn new(source: GenRng) -> Self {
+        let size = match source {
+            GenRng::Philox(_) => 0,
+            _ => Self::BATCH_SIZE,
+        };
+        Self {
+            source,
+            words: vec![0; size],
+            next: size,
+        }
+    }
+
+    pub fn set_row(&mut self, row: u64) {
+        self.source.set_row(row);
+    }
+
+    fn refill(&mut self) {
+        let words = &mut self.words;
+        // Dispatch once per batch, leaving a tight loop for each generator
+        dispatch!(&mut self.source, rng => words.iter_mut().for_each(|w| *w = rng.next_u64()));
+        self.next = 0;
+    }
+}
+
+impl RngCore for BatchRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        if self.next == self.words.len() {
+            if self.words.is_empty() {
+                return self.source.next_u64();
+            }
+            self.refill();
+        }
+        self.next += 1;
+        self.words[self.next - 1]
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        fill_bytes_via_next_u64(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// SplitMix64, used to expand a 64 bit seed into a generator's full state
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+fn fill_bytes_via_next_u64<R: RngCore>(rng: &mut R, dest: &mut [u8]) {
+    for chunk in dest.chunks_mut(8) {
+        let bytes = rng.next_u64().to_le_bytes();
+        chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+}
+
+#[derive(Clone)]
+pub struct Xoshiro256PlusPlus([u64; 4]);
+
+impl Xoshiro256PlusPlus {
+    pub fn seed_from_u64(mut seed: u64) -> Self {
+        Self(std::array::from_fn(|_| splitmix64(&mut seed)))
+    }
+}
+
+impl RngCore for Xoshiro256PlusPlus {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let s = &mut self.0;
+        let result = s[0].wrapping_add(s[3]).rotate_left(23).wrapping_add(s[0]);
+        let t = s[1] << 17;
+        s[2] ^= s[0];
+        s[3] ^= s[1];
+        s[1] ^= s[2];
+        s[0] ^= s[3];
+        s[2] ^= t;
+        s[3] = s[3].rotate_left(45);
+        result
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        fill_bytes_via_next_u64(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct Pcg64 {
+    state: u128,
+    increment: u128,
+}
+
+impl Pcg64 {
+    const MULTIPLIER: u128 = 0x2360_ed05_1fc6_5da4_4385_df64_9fcc_f645;
+
+    pub fn seed_from_u64(mut seed: u64) -> Self {
+        let mut word = || u128::from(splitmix64(&mut seed));
+        let state = word() << 64 | word();
+        // The increment must be odd
+        let increment = (word() << 64 | word()) | 1;
+        let mut pcg = Self { state, increment };
+        pcg.state = pcg.state.wrapping_add(pcg.increment);
+        pcg.step();
+        pcg
+    }
+
+    fn step(&mut self) {
+        self.state = self
+            .state
+            .wrapping_mul(Self::MULTIPLIER)
+            .wrapping_add(self.increment);
+    }
+}
+
+impl RngCore for Pcg64 {
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.step();
+        let rotation = (self.state >> 122) as u32;
+        (((self.state >> 64) as u64) ^ (self.state as u64)).rotate_right(rotation)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        fill_bytes_via_next_u64(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// Philox4x32-10, keyed by the seed. The 128 bit counter is the row index
+/// followed by a block index within the row, so rows never share values.
+/// Until the first row is set, the counter draws from a separate domain
+/// reserved for setup, such as picking station pools.
+#[derive(Clone)]
+pub struct Philox {
+    key: [u32; 2],
+    row: u64,
+    block: u64,
+    buffer: [u32; 4],
+    /// Next unused word of `buffer`
+    index: usize,
+}
+
+impl Philox {
+    const SETUP_DOMAIN: u64 = 1 << 63;
+
+    pub fn new(seed: u64) -> Self {
+        Self {
+            key: [seed as u32, (seed >> 32) as u32],
+            row: 0,
+            block: Self::SETUP_DOMAIN,
+            buffer: [0; 4],
+            index: 4,
+        }
+    }
+
+    pub fn set_row(&mut self, row: u64) {
+        self.row = row;
+        self.block = 0;
+        self.index = 4;
+    }
+
+    fn refill(&mut self) {
+        const M0: u64 = 0xd251_1f53;
+        const M1: u64 = 0xcd9e_8d57;
+        let mut c = [
+            self.row as u32,
+            (self.row >> 32) as u32,
+            self.block as u32,
+            (self.block >> 32) as u32,
+        ];
+        let mut k = self.key;
+        for _ in 0..10 {
+            let p0 = M0 * u64::from(c[0]);
+            let p1 = M1 * u64::from(c[2]);
+            c = [
+                (p1 >> 32) as u32 ^ c[1] ^ k[0],
+                p1 as u32,
+                (p0 >> 32) as u32 ^ c[3] ^ k[1],
+                p0 as u32,
+            ];
+            k[0] = k[0].wrapping_add(0x9e37_79b9);
+            k[1] = k[1].wrapping_add(0xbb67_ae85);
+        }
+        self.buffer = c;
+        self.block += 1;
+        self.index = 0;
+    }
+}
+
+impl RngCore for Philox {
+    fn next_u32(&mut self) -> u32 {
+        if self.index == 4 {
+            self.refill();
+        }
+        self.index += 1;
+        self.buffer[self.index - 1]
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        u64::from(self.next_u32()) | u64::from(self.next_u32()) << 32
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        fill_bytes_via_next_u64(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use eyre::Result;
+
+use crate::{
+    checksum::{Algorithm, Checksum},
+    output, template,
+};
+
+/// Output split across numbered parts of at most `max` bytes each, rolling
+/// to the next part on a line boundary. Parts are written under their
+/// temporary names, and renamed by [`complete`] once the run is.
+pub struct RollingFile {
+    output: String,
+    max: u64,
+    write_buffer: usize,
+    part: u32,
+    len: u64,
+    writer: BufWriter<File>,
+}
+
+impl RollingFile {
+    pub fn create(output: &str, max: u64, write_buffer: usize) -> Result<Self> {
+        Ok(Self {
+            output: output.to_string(),
+            max,
+            write_buffer,
+            part: 1,
+            len: 0,
+            writer: open(output, 1, write_buffer)?,
+        })
+    }
+
+    /// Writes whole lines to the current part while they fit, then rolls to
+    /// the next. A single line longer than `max` gets a part to itself.
+    pub fn write_all(&mut self, mut bytes: &[u8]) -> Result<()> {
+        while !bytes.is_empty() {
+            let room = self.max.saturating_sub(self.len);
+            if bytes.len() as u64 <= room {
+                self.writer.write_all(bytes)?;
+                self.len += bytes.len() as u64;
+                return Ok(());
+            }
+            let split = match bytes[..room as usize].iter().rposition(|&b| b == b'\n') {
+                Some(end) => end + 1,
+                None if self.len > 0 => 0,
+                None => bytes
+                    .iter()
+                    .position(|&b| b == b'\n')
+                    .map_or(bytes.len(), |end| end + 1),
+            };
+            self.writer.write_all(&bytes[..split])?;
+            self.len += split as u64;
+            bytes = &bytes[split..];
+            if !bytes.is_empty() {
+                self.roll()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn roll(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        self.part += 1;
+        self.len = 0;
+        self.writer = open(&self.output, self.part, self.write_buffer)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn file(&self) -> &File {
+        self.writer.get_ref()
+    }
+}
+
+fn open(output: &str, part: u32, write_buffer: usize) -> Result<BufWriter<File>> {
+    let path = output::partial_path(&part_path(output, part));
+    Ok(BufWriter::with_capacity(write_buffer, File::create(path)?))
+}
+
+/// The name of the `part`th part of `output`: its `{shard}` placeholder
+/// filled in, or the number before its extension otherwise, so
+/// `measurements.txt` rolls to `measurements.0001.txt`
+pub fn part_path(output: &str, part: u32) -> String {
+    if let Some(path) = template::shard(output, part) {
+        return path;
+    }
+    let path = Path::new(output);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}.{:04}.{}", stem, part, extension.to_string_lossy()),
+        None => format!("{}.{:04}", stem, part),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+/// The parts of `output` a run has written so far, under their temporary
+/// names
+pub fn partial_parts(output: &str) -> Vec<String> {
+    (1..)
+        .map(|part| output::partial_path(&part_path(output, part)))
+        .take_while(|path| Path::new(path).exists())
+        .collect()
+}
+
+/// Gives every part of a complete run its final name, returning the names.
+/// Parts past the last, left by an earlier run that had more of them, are
+/// removed so they can't be mistaken for this run's.
+pub fn complete(output: &str) -> Result<Vec<String>> {
+    let mut parts = Vec::new();
+    for (part, partial) in (1..).zip(partial_parts(output)) {
+        let path = part_path(output, part);
+        fs::rename(partial, &path)?;
+        parts.push(path);
+    }
+    let stale = (parts.len() as u32 + 1..)
+        .map(|part| part_path(output, part))
+        .take_while(|path| Path::new(path).exists());
+    for path in stale {
+        fs::remove_file(path)?;
+    }
+    Ok(parts)
+}
+
+/// Sums each part of a complete run into `<output>.SHA256SUMS`, or named
+/// after `algorithm`, returning its path and contents. Parts are listed by
+/// file name, as `sha256sum --check` reads them from their directory.
+pub fn write_sums(
+    output: &str,
+    parts: &[String],
+    algorithm: Algorithm,
+) -> Result<(String, String)> {
+    let mut sums = String::new();
+    for part in parts {
+        let mut reader = BufReader::new(File::open(part)?);
+        let mut checksum = Checksum::new(algorithm);
+        let mut buf = vec![0; 64 << 10];
+        loop {
+            let read = reader.read(&mut buf)?;
+            if read == 0 {
+                break;
+            }
+            checksum.update(&buf[..read]);
+        }
+        let name = Path::new(part).file_name().unwrap_or_default();
+        sums.push_str(&format!(
+            "{}  {}\n",
+            checksum.finish(),
+            name.to_string_lossy()
+        ));
+    }
+    let path = format!(
+        "{}.{}SUMS",
+        template::without_shard(output),
+        algorithm.name().to_uppercase()
+    );
+    fs::write(&path, &sums)?;
+    Ok((path, sums))
+}
@@ -0,0 +1,99 @@
+//! The generator's rows as an iterator, for library users that don't build
+//! the command line and its dependencies
+
+use eyre::{eyre, Result};
+
+use crate::{
+    distribution::Distribution,
+    encode::LineEncoder,
+    model::{Model, ModelSpec},
+    order::{Order, StationPicker},
+    rng::{BatchRng, GenRng, RngKind},
+    station::{load_weather_stations, ListOptions, NamePolicy, Quote},
+};
+
+/// The rows to generate, as the options the command line takes for them
+#[derive(Debug, Clone)]
+pub struct RowsConfig {
+    /// The station list, in any format `--weather-stations` reads
+    pub weather_stations: String,
+    pub rows: u64,
+    pub seed: u64,
+}
+
+impl Default for RowsConfig {
+    /// The command line's defaults, with `seed` 0 where it would pick one
+    fn default() -> Self {
+        Self {
+            weather_stations: String::from("./data/weather_stations.csv"),
+            rows: 1_000_000_000,
+            seed: 0,
+        }
+    }
+}
+
+/// The rows the command line writes for a station list with `--rows` and
+/// `--seed` and its other options left as they are, each a
+/// `station;measurement` line without its newline
+pub struct Rows {
+    encoder: LineEncoder,
+    picker: StationPicker,
+    model: Model,
+    rng: BatchRng,
+    /// The next row, and how many there are
+    row: u64,
+    rows: u64,
+    line: Vec<u8>,
+}
+
+impl Rows {
+    /// The rows `config` describes
+    pub fn new(config: &RowsConfig) -> Result<Self> {
+        let RowsConfig {
+            weather_stations,
+            rows,
+            seed,
+        } = config.clone();
+        let mut rng = GenRng::seed_from_u64(RngKind::Std, seed);
+        let mut stations = load_weather_stations(weather_stations, ListOptions::default())?;
+        stations.apply_name_policy(Quote::Never, NamePolicy::Reject)?;
+        if stations.is_empty() {
+            return Err(eyre!("No stations"));
+        }
+        let picker = StationPicker::new(Order::Random, &stations, rows, &mut rng)?;
+        let model = Model::new(ModelSpec::Iid, Distribution::Uniform, &stations)?;
+        Ok(Self {
+            encoder: LineEncoder::new(&stations),
+            picker,
+            model,
+            rng: BatchRng::new(rng),
+            row: 0,
+            rows,
+            line: Vec::new(),
+        })
+    }
+}
+
+impl Iterator for Rows {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        if self.row == self.rows {
+            return None;
+        }
+        self.rng.set_row(self.row);
+        self.row += 1;
+        let index = self.picker.next(&mut self.rng);
+        let measurement = self.model.sample(index, &mut self.rng);
+        self.line.clear();
+        self.encoder.push_line(&mut self.line, index, measurement);
+        self.line.pop();
+        // Station names are read as UTF-8
+        Some(String::from_utf8_lossy(&self.line).into_owned())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = usize::try_from(self.rows - self.row).ok();
+        (left.unwrap_or(usize::MAX), left)
+    }
+}
@@ -0,0 +1,471 @@
+use std::{fs, io::Write};
+
+use eyre::{eyre, Result};
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    distribution::standard_normal,
+    station::{load_weather_stations, ListOptions},
+    toml::{self, Value},
+};
+
+/// A user supplied description of the columns of each generated row.
+///
+/// Schemas are written in a small subset of TOML:
+///
+/// ```toml
+/// delimiter = ";"
+///
+/// [[column]]
+/// name = "station"
+/// kind = "categorical"
+/// path = "./data/weather_stations.csv"
+///
+/// [[column]]
+/// name = "temperature"
+/// kind = "float"
+/// distribution = "normal"
+/// mean = 12.0
+/// stddev = 15.0
+/// min = -99.9
+/// max = 99.9
+/// precision = 1
+/// ```
+///
+/// Supported column kinds are `categorical` (first field of each line in
+/// `path`), `int` (`min`/`max`), `float` (`uniform` or `normal`, with
+/// `precision` decimals) and `timestamp` (`start`/`end`, as epoch seconds or
+/// ISO 8601). Column names are optional and only document the schema.
+#[derive(Debug)]
+pub struct Schema {
+    delimiter: String,
+    columns: Vec<Column>,
+    /// Total row count, when timestamps must be non-decreasing across rows
+    time_ordered_rows: Option<u64>,
+}
+
+#[derive(Debug)]
+enum Column {
+    Categorical(Vec<String>),
+    Int { min: i64, max: i64 },
+    Float(FloatColumn),
+    Timestamp { start: i64, end: i64 },
+}
+
+#[derive(Debug)]
+struct FloatColumn {
+    distribution: FloatDistribution,
+    /// Bounds, in units of `10^-precision`
+    min: i64,
+    max: i64,
+    precision: usize,
+}
+
+#[derive(Debug)]
+enum FloatDistribution {
+    Uniform,
+    Normal { mean: f64, stddev: f64 },
+}
+
+/// The `key = value` pairs of a single table in the schema file
+struct Table<'a> {
+    line: usize,
+    pairs: &'a [(String, Value)],
+}
+
+impl<'a> Table<'a> {
+    fn new(table: &'a toml::Table) -> Self {
+        Self {
+            line: table.line,
+            pairs: &table.pairs,
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&'a Value> {
+        self.pairs.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    fn str(&self, key: &str) -> Result<Option<&'a str>> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Value::String(s)) => Ok(Some(s)),
+            Some(_) => Err(eyre!("Line {}: `{}` must be a string", self.line, key)),
+        }
+    }
+
+    fn int(&self, key: &str) -> Result<Option<i64>> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Value::Number(n)) if !n.contains(['.', 'e', 'E']) => n
+                .parse()
+                .map(Some)
+                .map_err(|_| eyre!("Line {}: `{}` is out of range", self.line, key)),
+            Some(_) => Err(eyre!("Line {}: `{}` must be an integer", self.line, key)),
+        }
+    }
+
+    fn float(&self, key: &str) -> Result<Option<f64>> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Value::Number(n)) => Ok(n.parse().ok()),
+            Some(_) => Err(eyre!("Line {}: `{}` must be a number", self.line, key)),
+        }
+    }
+
+    fn required<T>(&self, key: &str, value: Option<T>) -> Result<T> {
+        value.ok_or_else(|| eyre!("Line {}: missing `{}`", self.line, key))
+    }
+}
+
+impl Schema {
+    pub fn load(path: &str) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| eyre!("Could not read schema file {}: {}", path, e))?;
+        Self::parse(&text)
+    }
+
+    fn parse(text: &str) -> Result<Self> {
+        let tables = toml::parse(text)?;
+        let (root, tables) = tables
+            .split_first()
+            .expect("The root table is always there");
+        if let Some(table) = tables.iter().find(|t| !(t.array && t.name == "column")) {
+            let (open, close) = if table.array {
+                ("[[", "]]")
+            } else {
+                ("[", "]")
+            };
+            return Err(eyre!(
+                "Line {}: unsupported table `{}{}{}`",
+                table.line,
+                open,
+                table.name,
+                close
+            ));
+        }
+
+        let delimiter = Table::new(root)
+            .str("delimiter")?
+            .unwrap_or(";")
+            .to_string();
+        let columns = tables
+            .iter()
+            .map(|table| Column::from_table(&Table::new(table)))
+            .collect::<Result<Vec<_>>>()?;
+        if columns.is_empty() {
+            return Err(eyre!("Schema defines no columns"));
+        }
+        Ok(Self {
+            delimiter,
+            columns,
+            time_ordered_rows: None,
+        })
+    }
+
+    /// A rough estimate of the length of one generated line, in bytes
+    pub fn line_length_estimate(&self) -> usize {
+        let delimiters = self.delimiter.len() * (self.columns.len() - 1);
+        self.columns
+            .iter()
+            .map(Column::length_estimate)
+            .sum::<usize>()
+            + delimiters
+            + 1
+    }
+
+    /// Makes every timestamp column non-decreasing over the `rows` generated
+    pub fn order_by_time(&mut self, rows: u64) -> Result<()> {
+        if !self
+            .columns
+            .iter()
+            .any(|c| matches!(c, Column::Timestamp { .. }))
+        {
+            return Err(eyre!("--order by-time requires a timestamp column"));
+        }
+        self.time_ordered_rows = Some(rows);
+        Ok(())
+    }
+
+    pub fn generate_line<R: Rng>(
+        &self,
+        row: u64,
+        rng: &mut R,
+        out_buf: &mut Vec<u8>,
+    ) -> Result<()> {
+        for (i, column) in self.columns.iter().enumerate() {
+            if i > 0 {
+                out_buf.extend_from_slice(self.delimiter.as_bytes());
+            }
+            match (column, self.time_ordered_rows) {
+                (Column::Timestamp { start, end }, Some(rows)) => {
+                    // Row r lands in the r-th of `rows` equal slices of the
+                    // range plus jitter within its slice, so timestamps only
+                    // depend on the row index and never decrease
+                    let span = (end - start) as u128 + 1;
+                    let offset = (row as u128 * span + rng.gen_range(0..span)) / rows as u128;
+                    push_timestamp(out_buf, start + offset as i64);
+                }
+                _ => column.generate(rng, out_buf)?,
+            }
+        }
+        out_buf.push(b'\n');
+        Ok(())
+    }
+
+    /// Checks that `line`, without its newline, is one the schema generates:
+    /// a field per column, each within the column's bounds
+    pub fn check_line(&self, line: &[u8]) -> Result<()> {
+        let line = std::str::from_utf8(line).map_err(|_| eyre!("not UTF-8"))?;
+        let fields: Vec<&str> = line.split(self.delimiter.as_str()).collect();
+        if fields.len() != self.columns.len() {
+            return Err(eyre!(
+                "{} fields where the schema has {} columns",
+                fields.len(),
+                self.columns.len()
+            ));
+        }
+        for (i, (column, field)) in self.columns.iter().zip(fields).enumerate() {
+            column
+                .check(field)
+                .map_err(|e| eyre!("column {}: {}", i + 1, e))?;
+        }
+        Ok(())
+    }
+}
+
+impl Column {
+    fn from_table(table: &Table) -> Result<Self> {
+        let kind = table.required("kind", table.str("kind")?)?;
+        match kind {
+            "categorical" => {
+                let path = table.required("path", table.str("path")?)?;
+                let values: Vec<String> =
+                    load_weather_stations(path.to_string(), ListOptions::default())?
+                        .names()
+                        .map(str::to_string)
+                        .collect();
+                if values.is_empty() {
+                    return Err(eyre!("Line {}: {} contains no values", table.line, path));
+                }
+                Ok(Self::Categorical(values))
+            }
+            "int" => {
+                let min = table.required("min", table.int("min")?)?;
+                let max = table.required("max", table.int("max")?)?;
+                check_bounds(table, min, max)?;
+                Ok(Self::Int { min, max })
+            }
+            "float" => {
+                let precision = table.int("precision")?.unwrap_or(1);
+                if !(0..=9).contains(&precision) {
+                    return Err(eyre!("Line {}: precision must be 0-9", table.line));
+                }
+                let scale = 10f64.powi(precision as i32);
+                let distribution = match table.str("distribution")?.unwrap_or("uniform") {
+                    "uniform" => FloatDistribution::Uniform,
+                    "normal" => FloatDistribution::Normal {
+                        mean: table.required("mean", table.float("mean")?)?,
+                        stddev: table.required("stddev", table.float("stddev")?)?,
+                    },
+                    other => {
+                        return Err(eyre!(
+                            "Line {}: unknown distribution `{}`",
+                            table.line,
+                            other
+                        ))
+                    }
+                };
+                let (default_min, default_max) = match distribution {
+                    FloatDistribution::Uniform => (None, None),
+                    FloatDistribution::Normal { .. } => (Some(f64::MIN), Some(f64::MAX)),
+                };
+                let min = table.required("min", table.float("min")?.or(default_min))?;
+                let max = table.required("max", table.float("max")?.or(default_max))?;
+                let min = (min * scale).round().max(i64::MIN as f64) as i64;
+                let max = (max * scale).round().min(i64::MAX as f64) as i64;
+                check_bounds(table, min, max)?;
+                Ok(Self::Float(FloatColumn {
+                    distribution,
+                    min,
+                    max,
+                    precision: precision as usize,
+                }))
+            }
+            "timestamp" => {
+                let start = timestamp(table, "start")?;
+                let end = timestamp(table, "end")?;
+                check_bounds(table, start, end)?;
+                Ok(Self::Timestamp { start, end })
+            }
+            other => Err(eyre!(
+                "Line {}: unknown column kind `{}`",
+                table.line,
+                other
+            )),
+        }
+    }
+
+    fn length_estimate(&self) -> usize {
+        match self {
+            Self::Categorical(values) => {
+                values.iter().map(String::len).sum::<usize>() / values.len()
+            }
+            Self::Int { min, max } => min.to_string().len().max(max.to_string().len()),
+            Self::Float(f) => f.min.to_string().len().max(f.max.to_string().len()) + 1,
+            Self::Timestamp { .. } => "1970-01-01T00:00:00Z".len(),
+        }
+    }
+
+    fn generate<R: Rng>(&self, rng: &mut R, out_buf: &mut Vec<u8>) -> Result<()> {
+        match self {
+            Self::Categorical(values) => {
+                let value = values
+                    .choose(rng)
+                    .ok_or_else(|| eyre!("No categorical values"))?;
+                out_buf.extend_from_slice(value.as_bytes());
+            }
+            Self::Int { min, max } => {
+                write!(out_buf, "{}", rng.gen_range(*min..=*max))
+                    .expect("Writing to a Vec cannot fail");
+            }
+            Self::Float(f) => {
+                let scaled = match f.distribution {
+                    FloatDistribution::Uniform => rng.gen_range(f.min..=f.max),
+                    FloatDistribution::Normal { mean, stddev } => {
+                        let scale = 10f64.powi(f.precision as i32);
+                        let value = (mean + stddev * standard_normal(rng)) * scale;
+                        (value.round() as i64).clamp(f.min, f.max)
+                    }
+                };
+                push_fixed(out_buf, scaled, f.precision);
+            }
+            Self::Timestamp { start, end } => {
+                push_timestamp(out_buf, rng.gen_range(*start..=*end));
+            }
+        }
+        Ok(())
+    }
+
+    fn check(&self, field: &str) -> Result<()> {
+        let in_bounds = match self {
+            Self::Categorical(_) => !field.is_empty(),
+            Self::Int { min, max } => field
+                .parse::<i64>()
+                .is_ok_and(|value| (*min..=*max).contains(&value)),
+            Self::Float(f) => {
+                let decimals = field.split_once('.').map_or(0, |(_, d)| d.len());
+                decimals == f.precision
+                    && field
+                        .replacen('.', "", 1)
+                        .parse::<i64>()
+                        .is_ok_and(|scaled| (f.min..=f.max).contains(&scaled))
+            }
+            Self::Timestamp { start, end } => {
+                parse_timestamp(field).is_some_and(|time| (*start..=*end).contains(&time))
+            }
+        };
+        match in_bounds {
+            true => Ok(()),
+            false => Err(eyre!("`{}` isn't a value the column generates", field)),
+        }
+    }
+}
+
+fn check_bounds(table: &Table, min: i64, max: i64) -> Result<()> {
+    if min > max {
+        return Err(eyre!(
+            "Line {}: lower bound exceeds upper bound",
+            table.line
+        ));
+    }
+    Ok(())
+}
+
+/// Reads a timestamp given either as seconds since the epoch or as an
+/// ISO 8601 `YYYY-MM-DD[THH:MM:SS[Z]]` string
+fn timestamp(table: &Table, key: &str) -> Result<i64> {
+    match table.required(key, table.get(key))? {
+        Value::Number(_) => table.required(key, table.int(key)?),
+        Value::String(s) => parse_timestamp(s)
+            .ok_or_else(|| eyre!("Line {}: invalid timestamp `{}`", table.line, s)),
+        _ => Err(eyre!("Line {}: `{}` must be a timestamp", table.line, key)),
+    }
+}
+
+fn parse_timestamp(s: &str) -> Option<i64> {
+    let s = s.strip_suffix('Z').unwrap_or(s);
+    let (date, time) = s.split_once('T').unwrap_or((s, "00:00:00"));
+    let mut date = date.splitn(3, '-').map(str::parse::<i64>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+    let mut time = time.splitn(3, ':').map(str::parse::<i64>);
+    let (hour, minute, second) = (time.next()?.ok()?, time.next()?.ok()?, time.next()?.ok()?);
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || !(0..24).contains(&hour)
+        || !(0..60).contains(&minute)
+        || !(0..60).contains(&second)
+    {
+        return None;
+    }
+    Some(days_from_civil(year, month, day) * 86_400 + hour * 3_600 + minute * 60 + second)
+}
+
+// Howard Hinnant's days_from_civil / civil_from_days algorithms
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+pub fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn push_timestamp(out_buf: &mut Vec<u8>, seconds: i64) {
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86_400));
+    let secs = seconds.rem_euclid(86_400);
+    write!(
+        out_buf,
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3_600,
+        secs % 3_600 / 60,
+        secs % 60
+    )
+    .expect("Writing to a Vec cannot fail");
+}
+
+/// Writes `scaled * 10^-precision` with exactly `precision` decimals
+fn push_fixed(out_buf: &mut Vec<u8>, scaled: i64, precision: usize) {
+    if precision == 0 {
+        write!(out_buf, "{}", scaled).expect("Writing to a Vec cannot fail");
+        return;
+    }
+    let divisor = 10u64.pow(precision as u32);
+    let magnitude = scaled.unsigned_abs();
+    if scaled < 0 {
+        out_buf.push(b'-');
+    }
+    write!(
+        out_buf,
+        "{}.{:0width$}",
+        magnitude / divisor,
+        magnitude % divisor,
+        width = precision
+    )
+    .expect("Writing to a Vec cannot fail");
+}
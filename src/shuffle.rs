@@ -0,0 +1,108 @@
+use std::{
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Write},
+    sync::Mutex,
+};
+
+use eyre::Result;
+use indicatif::ProgressBar;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{checksum::Checksum, extract::check_replaceable, output::partial_path, progress};
+
+/// Target size of each spill file, which bounds the memory needed to shuffle
+/// it in the second pass
+const BUCKET_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Shuffles the lines of the existing file `input` into `output`, by way of a
+/// partial file renamed once complete, as [`shuffle_file`] does. An existing
+/// `output` is only replaced when `force` is set.
+pub fn shuffle_existing<R: Rng>(
+    input: &str,
+    output: &str,
+    memory_limit: Option<usize>,
+    quiet: bool,
+    force: bool,
+    rng: &mut R,
+) -> Result<()> {
+    let _span = tracing::debug_span!("shuffle_existing").entered();
+    check_replaceable(input, output, force)?;
+    let partial = partial_path(output);
+    match shuffle_file(input, &partial, memory_limit, quiet, None, rng) {
+        Ok(()) => Ok(fs::rename(&partial, output)?),
+        Err(e) => {
+            let _ = fs::remove_file(&partial);
+            Err(e)
+        }
+    }
+}
+
+/// Shuffles the lines of `input` into `output` with a two pass external
+/// shuffle: lines are first scattered to random spill files, then each spill
+/// file is shuffled in memory and appended to the output. With a
+/// `memory_limit`, spill files are kept small enough that one and the index
+/// of its lines fit within it. The output is fed to `checksum` if given.
+pub fn shuffle_file<R: Rng>(
+    input: &str,
+    output: &str,
+    memory_limit: Option<usize>,
+    quiet: bool,
+    checksum: Option<&Mutex<Checksum>>,
+    rng: &mut R,
+) -> Result<()> {
+    let size = fs::metadata(input)?.len();
+    // A slice per line takes about as much again as the lines themselves
+    let bucket_size = memory_limit.map_or(BUCKET_SIZE, |limit| (limit as u64 / 3).min(BUCKET_SIZE));
+    let bucket_count = (size / bucket_size + 1) as usize;
+    let bar = if quiet {
+        ProgressBar::hidden()
+    } else {
+        ProgressBar::new(size * 2).with_style(progress::style())
+    };
+    bar.set_message("Shuffling");
+
+    let paths: Vec<String> = (0..bucket_count)
+        .map(|i| format!("{}.shuffle.{}", output, i))
+        .collect();
+    let scatter = tracing::debug_span!("shuffle_scatter", buckets = bucket_count).entered();
+    let mut buckets = paths
+        .iter()
+        .map(|path| File::create(path).map(BufWriter::new))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut reader = BufReader::new(File::open(input)?);
+    let mut line = Vec::new();
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        bar.inc(line.len() as u64);
+        if !line.ends_with(b"\n") {
+            line.push(b'\n');
+        }
+        buckets[rng.gen_range(0..bucket_count)].write_all(&line)?;
+        line.clear();
+    }
+    for mut bucket in buckets {
+        bucket.flush()?;
+    }
+
+    drop(scatter);
+
+    let _gather = tracing::debug_span!("shuffle_gather").entered();
+    let mut writer = BufWriter::new(File::create(output)?);
+    for path in &paths {
+        let data = fs::read(path)?;
+        let mut lines: Vec<&[u8]> = data.split_inclusive(|b| *b == b'\n').collect();
+        lines.shuffle(rng);
+        let mut checksum = checksum.map(|checksum| checksum.lock().expect("A writer panicked"));
+        for line in lines {
+            writer.write_all(line)?;
+            if let Some(checksum) = &mut checksum {
+                checksum.update(line);
+            }
+        }
+        fs::remove_file(path)?;
+        bar.inc(data.len() as u64);
+    }
+    writer.flush()?;
+
+    bar.finish_with_message("Shuffled");
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use crate::protobuf::push_varint;
+
+/// The chunk that opens a framed stream, naming the format
+const STREAM_IDENTIFIER: &[u8] = b"\xff\x06\x00\x00sNaPpY";
+
+/// Chunk types of the framing format
+const COMPRESSED: u8 = 0x00;
+const UNCOMPRESSED: u8 = 0x01;
+
+/// Uncompressed bytes a chunk of the framing format may hold
+const MAX_CHUNK: usize = 1 << 16;
+
+/// Bits of the hash that finds earlier copies of 4 bytes
+const HASH_BITS: u32 = 14;
+
+/// `data` as a complete snappy framed stream, which concatenated with
+/// others still reads back as one stream since the identifier may repeat
+pub fn framed(data: &[u8]) -> Vec<u8> {
+    let mut stream = Vec::with_capacity(data.len() / 2 + STREAM_IDENTIFIER.len());
+    stream.extend_from_slice(STREAM_IDENTIFIER);
+    let mut block = Vec::with_capacity(MAX_CHUNK + MAX_CHUNK / 6 + 32);
+    let mut table = vec![0u16; 1 << HASH_BITS];
+    for input in data.chunks(MAX_CHUNK) {
+        block.clear();
+        compress_block(input, &mut table, &mut block);
+        // Data that doesn't shrink is stored as it is
+        let (kind, body) = match block.len() < input.len() {
+            true => (COMPRESSED, &block[..]),
+            false => (UNCOMPRESSED, input),
+        };
+        let length = (body.len() + 4) as u32;
+        stream.push(kind);
+        stream.extend_from_slice(&length.to_le_bytes()[..3]);
+        stream.extend_from_slice(&masked_crc32c(input).to_le_bytes());
+        stream.extend_from_slice(body);
+    }
+    stream
+}
+
+/// Appends `input`, of at most [`MAX_CHUNK`] bytes, to `output` as a snappy
+/// block: its length, then literals and copies of 4 or more bytes found
+/// through a table of where each hash of 4 bytes was last seen
+fn compress_block(input: &[u8], table: &mut [u16], output: &mut Vec<u8>) {
+    push_varint(output, input.len() as u64);
+    table.fill(0);
+    let (mut literal, mut i) = (0, 1);
+    while i + 4 <= input.len() {
+        let key = u32::from_le_bytes(input[i..i + 4].try_into().expect("4 bytes"));
+        let slot = &mut table[(key.wrapping_mul(0x1e35_a7bd) >> (32 - HASH_BITS)) as usize];
+        let candidate = usize::from(*slot);
+        *slot = i as u16;
+        if input[candidate..candidate + 4] != input[i..i + 4] {
+            i += 1;
+            continue;
+        }
+        push_literal(output, &input[literal..i]);
+        let length = 4 + input[i + 4..]
+            .iter()
+            .zip(&input[candidate + 4..])
+            .take_while(|(a, b)| a == b)
+            .count();
+        push_copy(output, i - candidate, length);
+        i += length;
+        literal = i;
+    }
+    push_literal(output, &input[literal..]);
+}
+
+fn push_literal(output: &mut Vec<u8>, literal: &[u8]) {
+    if literal.is_empty() {
+        return;
+    }
+    let n = literal.len() - 1;
+    if n < 60 {
+        output.push((n as u8) << 2);
+    } else {
+        // The length follows the tag in as few little endian bytes as it takes
+        let bytes = (usize::BITS - n.leading_zeros()).div_ceil(8) as usize;
+        output.push((59 + bytes as u8) << 2);
+        output.extend_from_slice(&n.to_le_bytes()[..bytes]);
+    }
+    output.extend_from_slice(literal);
+}
+
+/// Appends copies of up to 64 bytes `offset` back, as many as `length`
+/// takes, using the 2 byte form only where the offset doesn't fit the 1
+/// byte one
+fn push_copy(output: &mut Vec<u8>, offset: usize, mut length: usize) {
+    while length > 0 {
+        // Never leave fewer than 4 bytes, the shortest copy with a 1 byte offset
+        let n = match length {
+            65..=67 => 60,
+            _ => length.min(64),
+        };
+        if (4..12).contains(&n) && offset < 2048 {
+            output.push(((offset >> 8) as u8) << 5 | ((n - 4) as u8) << 2 | 1);
+            output.push(offset as u8);
+        } else {
+            output.push(((n - 1) as u8) << 2 | 2);
+            output.extend_from_slice(&(offset as u16).to_le_bytes());
+        }
+        length -= n;
+    }
+}
+
+/// The CRC-32C of `data` as the framing format stores it, rotated and
+/// offset so that CRCs of data holding CRCs stay useful
+fn masked_crc32c(data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut crc = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                crc = if crc & 1 == 1 {
+                    0x82f6_3b78 ^ (crc >> 1)
+                } else {
+                    crc >> 1
+                };
+                bit += 1;
+            }
+            table[i] = crc;
+            i += 1;
+        }
+        table
+    };
+    let crc = !data.iter().fold(!0u32, |crc, &byte| {
+        TABLE[((crc ^ u32::from(byte)) & 0xff) as usize] ^ (crc >> 8)
+    });
+    crc.rotate_right(15).wrapping_add(0xa282_ead8)
+}
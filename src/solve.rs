@@ -0,0 +1,56 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+};
+
+use eyre::{eyre, Result};
+
+use crate::{
+    answer::{self, Format, Rounding},
+    census::{self, Stats},
+};
+
+/// Works out each station's min, mean, and max over the measurements in
+/// `path`, reading it on every core, or line by line on one if `naive`, and
+/// prints them to stdout as `format`
+pub fn solve(path: &str, naive: bool, format: Format, rounding: Rounding) -> Result<()> {
+    let _span = tracing::debug_span!("solve", naive).entered();
+    let stations = match naive {
+        true => solve_naively(path)?,
+        false => census::tally(&[path.to_string()])?,
+    };
+    let mut writer = BufWriter::new(io::stdout().lock());
+    answer::render(&mut writer, &stations, format, rounding)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// The simplest solver that is still right: one line at a time into a
+/// `HashMap`, then sorted by name. Every measurement has exactly one
+/// decimal, so dropping the `.` gives it in tenths, which add up exactly.
+fn solve_naively(path: &str) -> Result<Vec<(Vec<u8>, Stats)>> {
+    let mut stations: HashMap<String, Stats> = HashMap::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        let not_a_measurement =
+            || eyre!("{} holds a line that isn't a measurement: {}", path, line);
+        let (station, measurement) = line.rsplit_once(';').ok_or_else(not_a_measurement)?;
+        let tenths: i64 = measurement
+            .replace('.', "")
+            .parse()
+            .map_err(|_| not_a_measurement())?;
+        match stations.get_mut(station) {
+            Some(stats) => stats.add(tenths),
+            None => {
+                stations.insert(station.to_string(), Stats::new(tenths));
+            }
+        }
+    }
+    let mut stations: Vec<_> = stations
+        .into_iter()
+        .map(|(station, stats)| (station.into_bytes(), stats))
+        .collect();
+    stations.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(stations)
+}
@@ -0,0 +1,219 @@
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Write},
+};
+
+use clap::ValueEnum;
+use eyre::{eyre, Result};
+use indicatif::ProgressBar;
+
+use crate::{
+    extract::{check_replaceable, measurement},
+    output::partial_path,
+    progress,
+};
+
+/// Target size of each sorted run, which bounds the memory the first pass
+/// needs
+const RUN_SIZE: u64 = 256 * 1024 * 1024;
+
+/// What `sort` orders rows by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortKey {
+    /// Station name, byte by byte, clustering each station's rows
+    Station,
+    /// Temperature, lowest first
+    Temp,
+}
+
+/// A row, split before its measurement, with the measurement parsed when
+/// sorting by it
+struct Row {
+    split: usize,
+    temp: f64,
+}
+
+impl Row {
+    fn parse(line: &[u8], by: SortKey) -> Result<Self> {
+        let text = line.strip_suffix(b"\n").unwrap_or(line);
+        let split = text
+            .iter()
+            .rposition(|&b| b == b';')
+            .ok_or_else(|| eyre!("{:?} isn't a measurement", String::from_utf8_lossy(text)))?;
+        let temp = match by {
+            SortKey::Station => 0.0,
+            SortKey::Temp => measurement(&text[split + 1..])?,
+        };
+        Ok(Self { split, temp })
+    }
+}
+
+fn compare(by: SortKey, a: (&[u8], &Row), b: (&[u8], &Row)) -> Ordering {
+    match by {
+        SortKey::Station => a.0[..a.1.split].cmp(&b.0[..b.1.split]),
+        SortKey::Temp => a.1.temp.total_cmp(&b.1.temp),
+    }
+}
+
+/// The next row of one sorted run during the merge
+struct Head {
+    by: SortKey,
+    line: Vec<u8>,
+    row: Row,
+    run: usize,
+}
+
+impl Ord for Head {
+    /// Reversed, so the heap yields the smallest row first, and the earlier
+    /// run on ties, keeping rows with equal keys in file order
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare(self.by, (&other.line, &other.row), (&self.line, &self.row))
+            .then(other.run.cmp(&self.run))
+    }
+}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Head {}
+
+/// Sorts the rows of `input` by `by` into `output` with an external merge
+/// sort: runs small enough to sort in memory are sorted and spilled to files
+/// beside the output, then merged. With a `memory_limit`, runs are kept
+/// small enough that one and the index of its rows fit within it. Rows with
+/// equal keys keep their order. An existing `output` is only replaced when
+/// `force` is set.
+pub fn sort_file(
+    input: &str,
+    output: &str,
+    by: SortKey,
+    memory_limit: Option<usize>,
+    quiet: bool,
+    force: bool,
+) -> Result<()> {
+    check_replaceable(input, output, force)?;
+    let size = fs::metadata(input)?.len();
+    // The index of a run's rows takes about as much again as the rows
+    let run_size = memory_limit.map_or(RUN_SIZE, |limit| (limit as u64 / 3).min(RUN_SIZE));
+    let bar = if quiet {
+        ProgressBar::hidden()
+    } else {
+        ProgressBar::new(size * 2).with_style(progress::style())
+    };
+    bar.set_message("Sorting");
+
+    let mut runs = Vec::new();
+    let partial = partial_path(output);
+    let sorted = write_runs(input, output, by, run_size, &bar, &mut runs)
+        .and_then(|()| merge(&runs, &partial, by, &bar));
+    for run in &runs {
+        let _ = fs::remove_file(run);
+    }
+    match sorted {
+        Ok(()) => fs::rename(&partial, output)?,
+        Err(e) => {
+            let _ = fs::remove_file(&partial);
+            return Err(e);
+        }
+    }
+    bar.finish_with_message("Sorted");
+    Ok(())
+}
+
+/// Sorts `input` a run of about `run_size` bytes at a time, writing each to
+/// a spill file beside `output` and its path to `runs`
+fn write_runs(
+    input: &str,
+    output: &str,
+    by: SortKey,
+    run_size: u64,
+    bar: &ProgressBar,
+    runs: &mut Vec<String>,
+) -> Result<()> {
+    let _span = tracing::debug_span!("sort_runs").entered();
+    let mut reader = BufReader::with_capacity(1 << 20, File::open(input)?);
+    let (mut data, mut rows) = (Vec::new(), Vec::new());
+    loop {
+        let start = data.len();
+        let read = reader.read_until(b'\n', &mut data)?;
+        if read > 0 {
+            if !data.ends_with(b"\n") {
+                data.push(b'\n');
+            }
+            rows.push((start..data.len(), Row::parse(&data[start..], by)?));
+            bar.inc(read as u64);
+        }
+        if (read == 0 && !rows.is_empty()) || data.len() as u64 >= run_size {
+            rows.sort_by(|a, b| {
+                compare(by, (&data[a.0.clone()], &a.1), (&data[b.0.clone()], &b.1))
+            });
+            let path = format!("{}.sort.{}", output, runs.len());
+            runs.push(path.clone());
+            let mut run = BufWriter::with_capacity(1 << 20, File::create(&path)?);
+            for (range, _) in rows.drain(..) {
+                run.write_all(&data[range])?;
+            }
+            run.flush()?;
+            data.clear();
+        }
+        if read == 0 {
+            tracing::debug!(runs = runs.len(), "Wrote sorted runs");
+            return Ok(());
+        }
+    }
+}
+
+/// Merges the sorted `runs` into `output`
+fn merge(runs: &[String], output: &str, by: SortKey, bar: &ProgressBar) -> Result<()> {
+    let _span = tracing::debug_span!("sort_merge", runs = runs.len()).entered();
+    let mut readers = runs
+        .iter()
+        .map(|path| File::open(path).map(|file| BufReader::with_capacity(1 << 16, file)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut heads = BinaryHeap::with_capacity(readers.len());
+    for (run, reader) in readers.iter_mut().enumerate() {
+        if let Some(head) = next(reader, by, run, Vec::new())? {
+            heads.push(head);
+        }
+    }
+    let mut writer = BufWriter::with_capacity(1 << 20, File::create(output)?);
+    while let Some(head) = heads.pop() {
+        writer.write_all(&head.line)?;
+        bar.inc(head.line.len() as u64);
+        let run = head.run;
+        if let Some(head) = next(&mut readers[run], by, run, head.line)? {
+            heads.push(head);
+        }
+    }
+    writer
+        .into_inner()
+        .map_err(|e| e.into_error())?
+        .sync_all()?;
+    Ok(())
+}
+
+/// The next row of `run`, read into `line`, or `None` once it is exhausted
+fn next(
+    reader: &mut impl BufRead,
+    by: SortKey,
+    run: usize,
+    mut line: Vec<u8>,
+) -> Result<Option<Head>> {
+    line.clear();
+    if reader.read_until(b'\n', &mut line)? == 0 {
+        return Ok(None);
+    }
+    let row = Row::parse(&line, by)?;
+    Ok(Some(Head { by, line, row, run }))
+}
@@ -0,0 +1,800 @@
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{BufRead, BufReader},
+    ops::{Deref, DerefMut},
+    path::Path,
+};
+
+#[cfg(feature = "cli")]
+use clap::ValueEnum;
+use eyre::{eyre, Result, WrapErr};
+use rand::Rng;
+
+#[cfg(unix)]
+use std::sync::Arc;
+#[cfg(feature = "cli")]
+use std::{
+    fs::OpenOptions,
+    io,
+    process::{self, Command, Stdio},
+};
+
+#[cfg(unix)]
+use crate::mmap::ReadMap;
+#[cfg(feature = "cli")]
+use crate::{checksum::Xxh64, errors, manifest, notify};
+use crate::{json, normalize::Form};
+
+/// Longest station name the challenge allows, in bytes
+pub const MAX_NAME_BYTES: usize = 100;
+
+/// Most distinct stations the challenge allows
+pub const MAX_STATIONS: usize = 10_000;
+
+/// A station from a `name[;mean[;stddev[;weight]]]` line
+#[derive(Debug, Clone)]
+pub struct WeatherStation {
+    /// Offset and length of the name in its list's arena
+    pub name: (usize, usize),
+    /// Mean temperature
+    pub mean: Option<f64>,
+    /// Standard deviation of the temperature
+    pub stddev: Option<f64>,
+    /// Relative frequency of the station in the output
+    pub weight: Option<f64>,
+}
+
+/// What is done with a station name holding the `;` delimiter, a line
+/// break, or another control character, any of which would corrupt the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum NamePolicy {
+    /// Refuse to run, naming the station
+    Reject,
+    /// Leave the station out, with a warning
+    Skip,
+    /// Replace each offending character with a `\n`, `\r`, `\t` or `\xNN`
+    /// escape
+    Escape,
+    /// Wrap the name in double quotes, doubling any inside it, as RFC 4180
+    /// quotes a field
+    Quote,
+}
+
+/// Which station names are written as RFC 4180 quoted fields
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ValueEnum))]
+pub enum Quote {
+    /// Every name
+    Always,
+    /// Names holding `;`, `"`, or a line break, which a CSV reader needs
+    /// quoted
+    Needed,
+    /// None, leaving such names to `--bad-name-policy`
+    Never,
+}
+
+/// Station lists at least this long are mapped into memory and their names
+/// referred to in place, rather than read and copied
+const MAP_THRESHOLD: u64 = 16 << 20;
+
+/// A station list, with every name stored back to back in one arena rather
+/// than a heap allocation each, so millions of stations stay compact.
+/// Dereferences to the stations, which can be reordered or dropped freely.
+#[derive(Debug, Default, Clone)]
+pub struct Stations {
+    names: Arena,
+    entries: Vec<WeatherStation>,
+}
+
+/// Where station names are kept: copied in one at a time, or in a station
+/// list mapped whole, which they point into and copies of the list share
+#[derive(Debug, Clone)]
+enum Arena {
+    Owned(String),
+    /// Checked to be UTF-8 when mapped
+    #[cfg(unix)]
+    Mapped(Arc<ReadMap>),
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Self::Owned(String::new())
+    }
+}
+
+impl Arena {
+    fn as_str(&self) -> &str {
+        match self {
+            Self::Owned(names) => names,
+            // SAFETY: mapped lists are only kept once checked to be UTF-8
+            #[cfg(unix)]
+            Self::Mapped(map) => unsafe { std::str::from_utf8_unchecked(map.bytes()) },
+        }
+    }
+
+    /// The owned names, copying a mapped list's bytes in first
+    fn owned(&mut self) -> &mut String {
+        #[cfg(unix)]
+        if let Self::Mapped(_) = self {
+            *self = Self::Owned(self.as_str().to_string());
+        }
+        match self {
+            Self::Owned(names) => names,
+            #[cfg(unix)]
+            Self::Mapped(_) => unreachable!("Mapped names were just copied"),
+        }
+    }
+}
+
+impl Stations {
+    pub fn push(
+        &mut self,
+        name: &str,
+        mean: Option<f64>,
+        stddev: Option<f64>,
+        weight: Option<f64>,
+    ) {
+        let names = self.names.owned();
+        self.entries.push(WeatherStation {
+            name: (names.len(), name.len()),
+            mean,
+            stddev,
+            weight,
+        });
+        names.push_str(name);
+    }
+
+    /// Keeps a uniform random sample of at most `max` of the stations,
+    /// drawn as streaming them through a reservoir would
+    pub fn sample<R: Rng>(&mut self, max: usize, rng: &mut R) {
+        for seen in max..self.entries.len() {
+            let slot = rng.gen_range(0..=seen);
+            if slot < max {
+                self.entries[slot] = self.entries[seen].clone();
+            }
+        }
+        self.entries.truncate(max);
+        self.compact();
+    }
+
+    /// Puts every name in `form`, returning how many it changed
+    pub fn normalize(&mut self, form: Form) -> usize {
+        let mut normalized = Self::default();
+        let mut changed = 0;
+        for station in &self.entries {
+            let name = form.apply(self.name(station));
+            changed += usize::from(matches!(name, Cow::Owned(_)));
+            normalized.push(&name, station.mean, station.stddev, station.weight);
+        }
+        *self = normalized;
+        changed
+    }
+
+    /// Quotes the names `quote` picks, then applies `policy` to every other
+    /// name that would corrupt the output, returning how many it changed or
+    /// left out
+    pub fn apply_name_policy(&mut self, quote: Quote, policy: NamePolicy) -> Result<usize> {
+        // Nothing to change is the usual case, which leaves a mapped list's
+        // names where they are
+        if quote == Quote::Never && !self.names().any(|name| name.contains(corrupts)) {
+            return Ok(0);
+        }
+        let mut kept = Self::default();
+        let mut affected = 0;
+        for station in &self.entries {
+            let name = self.name(station);
+            let quoted = match quote {
+                Quote::Always => true,
+                Quote::Needed => name.contains([';', '"', '\n', '\r']),
+                Quote::Never => false,
+            };
+            if quoted {
+                kept.push(
+                    &quoted_field(name),
+                    station.mean,
+                    station.stddev,
+                    station.weight,
+                );
+                continue;
+            }
+            if !name.contains(corrupts) {
+                kept.push(name, station.mean, station.stddev, station.weight);
+                continue;
+            }
+            affected += 1;
+            let name = match policy {
+                NamePolicy::Reject => {
+                    return Err(eyre!(
+                        "Station {:?} holds `;`, a line break, or a control character, which would \
+                         corrupt the output; --bad-name-policy can skip, escape, or quote it",
+                        name
+                    ))
+                }
+                NamePolicy::Skip => continue,
+                NamePolicy::Escape => name
+                    .chars()
+                    .map(|c| match c {
+                        '\n' => "\\n".to_string(),
+                        '\r' => "\\r".to_string(),
+                        '\t' => "\\t".to_string(),
+                        c if corrupts(c) => format!("\\x{:02x}", c as u32),
+                        c => c.to_string(),
+                    })
+                    .collect(),
+                NamePolicy::Quote => quoted_field(name),
+            };
+            kept.push(&name, station.mean, station.stddev, station.weight);
+        }
+        *self = kept;
+        Ok(affected)
+    }
+
+    /// Drops the names no station refers to any more from the arena
+    fn compact(&mut self) {
+        let mut compacted = Self::default();
+        for station in &self.entries {
+            let name = self.name(station);
+            compacted.push(name, station.mean, station.stddev, station.weight);
+        }
+        *self = compacted;
+    }
+
+    /// The name of `station`, one of these stations
+    pub fn name(&self, station: &WeatherStation) -> &str {
+        let (offset, len) = station.name;
+        &self.names.as_str()[offset..offset + len]
+    }
+
+    /// Every station's name, in order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|station| self.name(station))
+    }
+}
+
+/// Whether `c` in a name would corrupt the output
+fn corrupts(c: char) -> bool {
+    c == ';' || c.is_control()
+}
+
+/// `field` in double quotes, with any inside it doubled, as RFC 4180 quotes
+fn quoted_field(field: &str) -> String {
+    format!("\"{}\"", field.replace('"', "\"\""))
+}
+
+/// The name, and the mean, standard deviation and weight, on a
+/// `name[;mean[;stddev[;weight]]]` line with its columns split by
+/// `delimiter`, reading no more than `param_columns` after the name
+fn parse_line(
+    line: &str,
+    delimiter: char,
+    param_columns: usize,
+) -> Result<(&str, [Option<f64>; 3])> {
+    let mut split = line.split(delimiter).take(1 + param_columns);
+    let id = split
+        .next()
+        .filter(|id| !id.is_empty())
+        .ok_or_else(|| eyre!("No station name"))?;
+    let mut column = |name: &str| {
+        split
+            .next()
+            .map(|value| {
+                value
+                    .trim()
+                    .parse::<f64>()
+                    .map_err(|_| eyre!("Invalid {} for {}", name, id))
+            })
+            .transpose()
+    };
+    let mean = column("mean temperature")?;
+    let stddev = column("standard deviation")?;
+    let weight = column("weight")?;
+    Ok((id, [mean, stddev, weight]))
+}
+
+impl Deref for Stations {
+    type Target = Vec<WeatherStation>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.entries
+    }
+}
+
+impl DerefMut for Stations {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.entries
+    }
+}
+
+/// Accepts 64 hex digits, in either case
+#[cfg(feature = "cli")]
+pub fn parse_sha256(sha256: &str) -> Result<String> {
+    match sha256.len() == 64 && sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+        true => Ok(sha256.to_ascii_lowercase()),
+        false => Err(eyre!("`{}` is not a SHA-256 in hex", sha256)),
+    }
+}
+
+/// The local path of the station list at `location`, downloading it first
+/// if it's a URL, after checking it has the SHA-256 `pinned` to it
+#[cfg(feature = "cli")]
+pub fn resolve(location: &str, pinned: Option<&str>) -> Result<String> {
+    let path = match notify::parse_url(location) {
+        Ok(url) => fetch(&url)?,
+        Err(_) => location.to_string(),
+    };
+    if let Some(pinned) = pinned {
+        let sha256 = manifest::file_sha256(&path)
+            .wrap_err_with(|| format!("Could not open file {}", path))?;
+        if sha256 != pinned {
+            return Err(eyre!(
+                "The station list {} has SHA-256 {} where {} was pinned",
+                location,
+                sha256,
+                pinned
+            ));
+        }
+    }
+    Ok(path)
+}
+
+/// Downloads `url` into the temporary directory, named after it so its
+/// extension still picks the format, and returns where it was saved. The
+/// download goes to a new file of its own, which concurrent runs and links
+/// planted in the shared directory can't touch, renamed once complete.
+#[cfg(feature = "cli")]
+fn fetch(url: &str) -> Result<String> {
+    let _span = tracing::debug_span!("fetch_stations", url).entered();
+    let mut hash = Xxh64::default();
+    hash.update(url.as_bytes());
+    let name = url
+        .split(['?', '#'])
+        .next()
+        .and_then(|url| url.rsplit('/').next())
+        .and_then(|name| Path::new(name).extension())
+        .and_then(|extension| extension.to_str())
+        .map_or("csv", |extension| extension);
+    let path = std::env::temp_dir().join(format!(
+        "billion-row-gen-stations-{:016x}.{}",
+        hash.finish(),
+        name
+    ));
+    let mut attempt = 0;
+    let (partial, file) = loop {
+        let partial = path.with_extension(format!("{}.{}.partial", process::id(), attempt));
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&partial)
+        {
+            Ok(file) => break (partial, file),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => attempt += 1,
+            Err(e) => return Err(eyre!("Could not create {}: {}", partial.display(), e)),
+        }
+    };
+    let finished = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--location"])
+        .args(["--max-time", "300"])
+        .arg(url)
+        .stdout(file)
+        .stderr(Stdio::piped())
+        .output();
+    let finished = match finished {
+        Ok(finished) => finished,
+        Err(e) => {
+            let _ = fs::remove_file(&partial);
+            return Err(eyre!("Could not run curl to download {}: {}", url, e));
+        }
+    };
+    if !finished.status.success() {
+        let _ = fs::remove_file(&partial);
+        return Err(errors::coded(
+            "download_failed",
+            true,
+            format!(
+                "Could not download {}: {}",
+                url,
+                String::from_utf8_lossy(&finished.stderr).trim()
+            ),
+        ));
+    }
+    fs::rename(&partial, &path)?;
+    tracing::info!(url, path = %path.display(), "Downloaded the station list");
+    Ok(path.to_string_lossy().into_owned())
+}
+
+/// How a station list is read
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ListOptions {
+    /// Skip malformed entries with a warning instead of failing
+    pub lenient: bool,
+    /// Never take the first line for a header
+    pub no_header: bool,
+    /// What separates the columns, sniffed from the first lines if `None`
+    pub delimiter: Option<char>,
+    /// How many columns after the name hold the mean, standard deviation,
+    /// and weight, when fewer than all three do
+    pub param_columns: Option<usize>,
+}
+
+impl ListOptions {
+    /// Differs between options that read the same list differently, for
+    /// telling caches of it apart
+    pub fn fingerprint(self) -> u64 {
+        let delimiter = self
+            .delimiter
+            .map_or(0, |delimiter| u64::from(delimiter) + 1);
+        let param_columns = self.param_columns.map_or(0, |columns| columns as u64 + 1);
+        u64::from(self.lenient)
+            | u64::from(self.no_header) << 1
+            | delimiter << 2
+            | param_columns << 24
+    }
+}
+
+/// Accepts a single character, or `tab` for a tab
+pub fn parse_delimiter(delimiter: &str) -> Result<char> {
+    let mut chars = delimiter.chars();
+    match (chars.next(), chars.next()) {
+        _ if delimiter.eq_ignore_ascii_case("tab") || delimiter == "\\t" => Ok('\t'),
+        (Some(c), None) => Ok(c),
+        _ => Err(eyre!("`{}` is not a single character", delimiter)),
+    }
+}
+
+/// Malformed entries warned about one by one when lenient; past this only
+/// the count is kept
+const MAX_WARNED: usize = 10;
+
+/// Loads the stations from `path`, read as JSON if it ends in `.json` and as
+/// `name[;mean[;stddev[;weight]]]` lines otherwise
+pub fn load_weather_stations(path: String, options: ListOptions) -> Result<Stations> {
+    match Path::new(&path).extension().and_then(|e| e.to_str()) {
+        Some(e) if e.eq_ignore_ascii_case("json") => return load_json_stations(&path, options),
+        Some(e) if e.eq_ignore_ascii_case("parquet") => {
+            return Err(eyre!(
+                "Parquet station lists aren't supported; convert {} to CSV or JSON",
+                path
+            ))
+        }
+        _ => {}
+    }
+    #[cfg(unix)]
+    if let Some(stations) = map_station_lines(&path, options)? {
+        return Ok(stations);
+    }
+    let mut stations = Stations::default();
+    read_station_lines(path, options, |id, [mean, stddev, weight]| {
+        stations.push(id, mean, stddev, weight)
+    })?;
+    Ok(stations)
+}
+
+/// Loads a list of at least `MAP_THRESHOLD` bytes by mapping it into memory,
+/// its stations' names pointing into the map rather than copied out of it,
+/// or `None` for a shorter list, or one that isn't all UTF-8 and so is left
+/// to be read line by line
+#[cfg(unix)]
+fn map_station_lines(path: &str, options: ListOptions) -> Result<Option<Stations>> {
+    let file = load_weather_stations_file(path.to_string())?;
+    if file.metadata()?.len() < MAP_THRESHOLD {
+        return Ok(None);
+    }
+    let Some(map) = ReadMap::open(&file)? else {
+        return Ok(None);
+    };
+    if std::str::from_utf8(map.bytes()).is_err() {
+        return Ok(None);
+    }
+    let _span = tracing::debug_span!("map_station_list").entered();
+    let base = map.bytes().as_ptr() as usize;
+    let mut entries = Vec::new();
+    let mut lines = StationLines::new(path, options)?;
+    for line in map.bytes().split_inclusive(|&b| b == b'\n') {
+        lines.visit(line, |id, [mean, stddev, weight]| {
+            entries.push(WeatherStation {
+                name: (id.as_ptr() as usize - base, id.len()),
+                mean,
+                stddev,
+                weight,
+            })
+        })?;
+    }
+    lines.finish();
+    Ok(Some(Stations {
+        names: Arena::Mapped(Arc::new(map)),
+        entries,
+    }))
+}
+
+/// Loads a uniform random sample of at most `max` of the stations from
+/// `path`. A `name;...` list is streamed through a reservoir, so only the
+/// sample is ever held however long the list is.
+pub fn sample_weather_stations<R: Rng>(
+    path: String,
+    options: ListOptions,
+    max: usize,
+    rng: &mut R,
+) -> Result<Stations> {
+    if !is_line_list(&path) {
+        let mut stations = load_weather_stations(path, options)?;
+        stations.sample(max, rng);
+        return Ok(stations);
+    }
+    let mut stations = Stations::default();
+    let mut seen = 0;
+    read_station_lines(path, options, |id, [mean, stddev, weight]| {
+        if seen < max {
+            stations.push(id, mean, stddev, weight);
+        } else {
+            let slot = rng.gen_range(0..=seen);
+            if slot < max {
+                stations.push(id, mean, stddev, weight);
+                stations.entries.swap_remove(slot);
+            }
+        }
+        seen += 1;
+    })?;
+    stations.compact();
+    Ok(stations)
+}
+
+/// Whether `path` is read as `name;...` lines, rather than by its extension
+pub fn is_line_list(path: &str) -> bool {
+    let extension = Path::new(path).extension().and_then(|e| e.to_str());
+    !extension.is_some_and(|e| e.eq_ignore_ascii_case("json") || e.eq_ignore_ascii_case("parquet"))
+}
+
+/// Hands `visit` the name and parameters of each station in the list at
+/// `path`, skipping blank lines and `#` comments. A malformed line fails the
+/// list, or when lenient is skipped.
+fn read_station_lines(
+    path: String,
+    options: ListOptions,
+    mut visit: impl FnMut(&str, [Option<f64>; 3]),
+) -> Result<()> {
+    let mut lines = StationLines::new(&path, options)?;
+    let file: File = load_weather_stations_file(path.clone())?;
+    let mut reader: BufReader<File> = BufReader::new(file);
+    let mut line = Vec::new();
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        lines.visit(&line, &mut visit)?;
+        line.clear();
+    }
+    lines.finish();
+    Ok(())
+}
+
+/// Where reading a `name;...` list has got to, line by line
+struct StationLines<'a> {
+    path: &'a str,
+    delimiter: char,
+    param_columns: usize,
+    malformed: Malformed<'a>,
+    number: usize,
+    /// Whether the next entry is the first, which may be a header
+    first: bool,
+}
+
+impl<'a> StationLines<'a> {
+    fn new(path: &'a str, options: ListOptions) -> Result<Self> {
+        let delimiter = match options.delimiter {
+            Some(delimiter) => delimiter,
+            None => sniff_delimiter(path)?,
+        };
+        Ok(Self {
+            path,
+            delimiter,
+            param_columns: options.param_columns.unwrap_or(3),
+            malformed: Malformed::new(path, options),
+            number: 0,
+            first: !options.no_header,
+        })
+    }
+
+    /// Hands `visit` the station on `line`, with or without its line break,
+    /// if it holds one
+    fn visit(&mut self, line: &[u8], mut visit: impl FnMut(&str, [Option<f64>; 3])) -> Result<()> {
+        self.number += 1;
+        let entry = line.strip_suffix(b"\n").unwrap_or(line);
+        let entry = entry.strip_suffix(b"\r").unwrap_or(entry);
+        let result = match std::str::from_utf8(entry) {
+            Ok(entry) if entry.trim().is_empty() || entry.starts_with('#') => Ok(()),
+            Ok(entry) if std::mem::take(&mut self.first) && is_header(entry, self.delimiter) => {
+                tracing::info!("Skipping the header {:?} of {}", entry, self.path);
+                Ok(())
+            }
+            Ok(entry) => parse_line(entry, self.delimiter, self.param_columns)
+                .map(|(id, params)| visit(id, params)),
+            Err(_) => Err(eyre!("Not UTF-8")),
+        };
+        if let Err(e) = result {
+            self.malformed.skip(&format!("line {}", self.number), e)?;
+        }
+        Ok(())
+    }
+
+    fn finish(self) {
+        self.malformed.finish();
+    }
+}
+
+/// Delimiters a station list's columns may be split by, in order of
+/// preference
+const DELIMITERS: [char; 3] = [';', '\t', ','];
+
+/// The delimiter of the list at `path`: the first of `DELIMITERS` splitting
+/// one of its first two entries into a name and numbers, so that a header
+/// line leaves it to the line after. Lists where none does, such as those of
+/// names alone, keep `;`.
+pub fn sniff_delimiter(path: &str) -> Result<char> {
+    let file = File::open(path).wrap_err_with(|| format!("Could not open file {}", path))?;
+    let entries = BufReader::new(file)
+        .split(b'\n')
+        .map_while(|line| line.ok())
+        .filter_map(|line| String::from_utf8(line).ok())
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .take(2);
+    for entry in entries {
+        let entry = entry.trim_end_matches('\r');
+        let delimiter = DELIMITERS.into_iter().find(|&delimiter| {
+            let mut columns = entry.split(delimiter).skip(1).peekable();
+            columns.peek().is_some() && columns.all(|column| column.trim().parse::<f64>().is_ok())
+        });
+        if let Some(delimiter) = delimiter {
+            if delimiter != ';' {
+                tracing::debug!(?delimiter, "Sniffed the station list's delimiter");
+            }
+            return Ok(delimiter);
+        }
+    }
+    Ok(';')
+}
+
+/// Whether the first entry of a list is a header such as `station;mean_temp`
+/// rather than a station: its other columns aren't numbers, or it has none
+/// and names the column
+fn is_header(line: &str, delimiter: char) -> bool {
+    let mut columns = line.split(delimiter);
+    let first = columns.next().unwrap_or_default().trim();
+    let others: Vec<&str> = columns.map(str::trim).collect();
+    match others.is_empty() {
+        true => ["station", "name", "city"]
+            .iter()
+            .any(|word| first.eq_ignore_ascii_case(word)),
+        false => others
+            .iter()
+            .all(|column| !column.is_empty() && column.parse::<f64>().is_err()),
+    }
+}
+
+/// The malformed entries of a station list, failing it unless lenient
+struct Malformed<'a> {
+    path: &'a str,
+    lenient: bool,
+    count: usize,
+}
+
+impl<'a> Malformed<'a> {
+    fn new(path: &'a str, options: ListOptions) -> Self {
+        Self {
+            path,
+            lenient: options.lenient,
+            count: 0,
+        }
+    }
+
+    /// Fails with `error` at `entry`, or when lenient warns of it and carries on
+    fn skip(&mut self, entry: &str, error: eyre::ErrReport) -> Result<()> {
+        if !self.lenient {
+            return Err(eyre!("{} {}: {}", self.path, entry, error));
+        }
+        self.count += 1;
+        if self.count <= MAX_WARNED {
+            tracing::warn!("Skipping {} {}: {}", self.path, entry, error);
+        }
+        Ok(())
+    }
+
+    fn finish(self) {
+        if self.count > 0 {
+            tracing::warn!(
+                "Skipped {} malformed entr{} of {}",
+                self.count,
+                if self.count == 1 { "y" } else { "ies" },
+                self.path
+            );
+        }
+    }
+}
+
+/// Loads an array of `{"name": ..., "mean": ..., "stddev": ..., "weight": ...}`
+/// objects, all but the name optional or null
+fn load_json_stations(path: &str, options: ListOptions) -> Result<Stations> {
+    let text =
+        fs::read_to_string(path).wrap_err_with(|| format!("Could not open file {}", path))?;
+    let json::Value::Array(items) = json::parse(&text).map_err(|e| eyre!("{}: {}", path, e))?
+    else {
+        return Err(eyre!("{} must hold an array of stations", path));
+    };
+    let mut stations = Stations::default();
+    let mut malformed = Malformed::new(path, options);
+    for (i, item) in items.iter().enumerate() {
+        let station = json_station(item);
+        match station {
+            Ok((id, [mean, stddev, weight])) => stations.push(id, mean, stddev, weight),
+            Err(e) => malformed.skip(&format!("station {}", i + 1), e)?,
+        }
+    }
+    malformed.finish();
+    Ok(stations)
+}
+
+/// The name, and the mean, standard deviation and weight, of a JSON station
+fn json_station(item: &json::Value) -> Result<(&str, [Option<f64>; 3])> {
+    let id = match item.get("name") {
+        Some(json::Value::String(id)) if !id.is_empty() => id,
+        _ => return Err(eyre!("No station name")),
+    };
+    let number = |key: &str| match item.get(key) {
+        None | Some(json::Value::Null) => Ok(None),
+        Some(json::Value::Number(value)) => Ok(Some(*value)),
+        Some(_) => Err(eyre!("Invalid {} for {}", key, id)),
+    };
+    Ok((id, [number("mean")?, number("stddev")?, number("weight")?]))
+}
+
+fn load_weather_stations_file(path: String) -> Result<File> {
+    File::open(path).wrap_err("Could not open file")
+}
+
+/// Overrides the mean, standard deviation and weight of the stations named in
+/// a `name;mean;stddev;weight` file, its names put in `form` as the list's
+/// were. Empty fields keep the station's values.
+pub fn apply_station_params(stations: &mut Stations, path: &str, form: Form) -> Result<()> {
+    let file = File::open(path).wrap_err_with(|| format!("Could not open file {}", path))?;
+    let mut params: HashMap<String, [Option<f64>; 3]> = HashMap::new();
+    for line_result in BufReader::new(file).lines() {
+        let line = line_result?;
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        let mut split = line.split(';');
+        let id = form.apply(split.next().unwrap_or_default()).into_owned();
+        let mut values = [None; 3];
+        for (value, field) in values.iter_mut().zip(split) {
+            let field = field.trim();
+            if !field.is_empty() {
+                *value = Some(
+                    field
+                        .parse()
+                        .map_err(|_| eyre!("Invalid station parameter for {}", id))?,
+                );
+            }
+        }
+        params.insert(id, values);
+    }
+
+    let mut unmatched: HashSet<&String> = params.keys().collect();
+    let Stations { names, entries } = stations;
+    for station in entries.iter_mut() {
+        let (offset, len) = station.name;
+        let id = &names.as_str()[offset..offset + len];
+        if let Some((id, [mean, stddev, weight])) = params.get_key_value(id) {
+            unmatched.remove(id);
+            station.mean = mean.or(station.mean);
+            station.stddev = stddev.or(station.stddev);
+            station.weight = weight.or(station.weight);
+        }
+    }
+    if let Some(id) = unmatched.into_iter().next() {
+        return Err(eyre!(
+            "Station {} from {} is not in the station list",
+            id,
+            path
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use std::{fs, time::Duration};
+
+use eyre::Result;
+
+use crate::{cli::Args, manifest};
+
+/// What a finished run produced, written as JSON for automation
+pub struct Summary {
+    pub output: String,
+    pub rows: u64,
+    pub bytes: u64,
+    pub elapsed: Duration,
+    pub seed: u64,
+    pub config_hash: u64,
+    /// Named after its algorithm, as `sha256:<hex>`, if the output was summed
+    pub checksum: Option<String>,
+}
+
+impl Summary {
+    /// Writes the summary to `path`, or to stdout for `-`
+    pub fn write(&self, path: &str) -> Result<()> {
+        match path {
+            "-" => print!("{}", self.json()),
+            path => fs::write(path, self.json())?,
+        }
+        Ok(())
+    }
+
+    pub fn json(&self) -> String {
+        let seconds = self.elapsed.as_secs_f64();
+        format!(
+            "{{\n  \"output\": {},\n  \"rows\": {},\n  \"bytes\": {},\n  \
+             \"duration_secs\": {:.3},\n  \"rows_per_sec\": {:.0},\n  \
+             \"bytes_per_sec\": {:.0},\n  \"seed\": {},\n  \
+             \"config_hash\": \"{:016x}\",\n  \"checksum\": {}\n}}\n",
+            json_string(&self.output),
+            self.rows,
+            self.bytes,
+            seconds,
+            self.rows as f64 / seconds,
+            self.bytes as f64 / seconds,
+            self.seed,
+            self.config_hash,
+            match &self.checksum {
+                Some(checksum) => json_string(checksum),
+                None => "null".to_string(),
+            }
+        )
+    }
+}
+
+/// A hash of every option that shapes the rows generated, but not of how
+/// they are written, so runs producing the same rows share a hash. FNV-1a,
+/// which is stable across builds, unlike the standard library's hasher.
+pub fn config_hash(args: &Args, seed: u64) -> u64 {
+    let config = format!("{:?}", manifest::generation_args(args, seed));
+    config.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100_0000_01b3)
+    })
+}
+
+/// `s` as a quoted JSON string
+pub fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
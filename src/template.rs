@@ -0,0 +1,118 @@
+use std::{
+    fmt::Write,
+    ops::Range,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use eyre::{eyre, Result};
+
+use crate::schema::civil_from_days;
+
+/// A `{name}` or zero padded `{name:04}` placeholder in an `--output` path
+struct Placeholder<'a> {
+    range: Range<usize>,
+    name: &'a str,
+    width: usize,
+}
+
+fn placeholders(template: &str) -> Result<Vec<Placeholder<'_>>> {
+    let mut found = Vec::new();
+    let mut rest = 0;
+    while let Some(open) = template[rest..].find('{').map(|i| rest + i) {
+        let close = template[open..]
+            .find('}')
+            .map(|i| open + i)
+            .ok_or_else(|| eyre!("Unclosed `{{` in --output {}", template))?;
+        let inner = &template[open + 1..close];
+        let (name, spec) = inner.split_once(':').unwrap_or((inner, ""));
+        if !matches!(name, "shard" | "seed" | "date") {
+            return Err(eyre!(
+                "Unknown placeholder `{{{}}}` in --output; `shard`, `seed`, and `date` are known",
+                name
+            ));
+        }
+        let width = match spec {
+            "" => 0,
+            spec if spec.starts_with('0') => spec
+                .parse()
+                .map_err(|_| eyre!("Invalid width `{}` in --output", spec))?,
+            spec => return Err(eyre!("Invalid width `{}` in --output, such as `04`", spec)),
+        };
+        found.push(Placeholder {
+            range: open..close + 1,
+            name,
+            width,
+        });
+        rest = close + 1;
+    }
+    Ok(found)
+}
+
+/// `template` with the placeholders `value` gives a value for filled in,
+/// and the rest left as they were
+fn fill(template: &str, value: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let mut filled = String::new();
+    let mut last = 0;
+    for placeholder in placeholders(template)? {
+        filled.push_str(&template[last..placeholder.range.start]);
+        match value(placeholder.name) {
+            Some(value) => write!(filled, "{:0>1$}", value, placeholder.width)
+                .expect("Writing to a String cannot fail"),
+            None => filled.push_str(&template[placeholder.range.clone()]),
+        }
+        last = placeholder.range.end;
+    }
+    filled.push_str(&template[last..]);
+    Ok(filled)
+}
+
+/// Whether `template` has a `{name}` placeholder
+pub fn uses(template: &str, name: &str) -> bool {
+    placeholders(template).is_ok_and(|found| found.iter().any(|p| p.name == name))
+}
+
+/// Fills in the `{seed}` and `{date}` placeholders of an `--output`
+/// template, leaving `{shard}`, which only split output numbers
+pub fn expand(template: &str, seed: u64, split: bool) -> Result<String> {
+    if uses(template, "shard") && !split {
+        return Err(eyre!("`{{shard}}` in --output requires --max-file-size"));
+    }
+    fill(template, |name| match name {
+        "seed" => Some(seed.to_string()),
+        "date" => Some(today()),
+        _ => None,
+    })
+}
+
+/// `output` named for its `part`th part, if it has a `{shard}` placeholder
+pub fn shard(output: &str, part: u32) -> Option<String> {
+    uses(output, "shard")
+        .then(|| fill(output, |name| (name == "shard").then(|| part.to_string())).ok())
+        .flatten()
+}
+
+/// `output` without its `{shard}` placeholders, or the separator before
+/// each, naming what all its parts share, such as the manifest
+pub fn without_shard(output: &str) -> String {
+    let Ok(found) = placeholders(output) else {
+        return output.to_string();
+    };
+    let mut stripped = String::new();
+    let mut last = 0;
+    for placeholder in found.iter().filter(|p| p.name == "shard") {
+        let before = &output[last..placeholder.range.start];
+        stripped.push_str(before.strip_suffix(['-', '_', '.']).unwrap_or(before));
+        last = placeholder.range.end;
+    }
+    stripped.push_str(&output[last..]);
+    stripped
+}
+
+/// Today's date in UTC, as `2024-01-31`
+fn today() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
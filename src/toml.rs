@@ -0,0 +1,304 @@
+use eyre::{eyre, Result};
+
+/// A parsed TOML value, of the kinds a config needs
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    /// A number as written, without its `_` separators
+    Number(String),
+    String(String),
+    Array(Vec<Value>),
+}
+
+/// The `key = value` pairs under one `[name]` or `[[name]]` header, in the
+/// order written
+#[derive(Debug, Clone)]
+pub struct Table {
+    /// Empty for the pairs before the first header
+    pub name: String,
+    /// Whether it's one of an array of tables, under a `[[name]]` header
+    pub array: bool,
+    /// The line its header is on, or 1 for the pairs before the first
+    pub line: usize,
+    pub pairs: Vec<(String, Value)>,
+}
+
+/// Parses `text` as a TOML document of `[name]` tables and `[[name]]`
+/// arrays of tables holding booleans, numbers, strings, and arrays of them,
+/// returning the pairs before the first header, then each table. Dates,
+/// inline tables, dotted keys, and multi-line strings aren't supported.
+pub fn parse(text: &str) -> Result<Vec<Table>> {
+    let mut parser = Parser {
+        bytes: text.as_bytes(),
+        at: 0,
+    };
+    let mut tables = vec![Table {
+        name: String::new(),
+        array: false,
+        line: 1,
+        pairs: Vec::new(),
+    }];
+    loop {
+        parser.blank();
+        if parser.at == parser.bytes.len() {
+            return Ok(tables);
+        }
+        if parser.eat(b'[') {
+            let array = parser.eat(b'[');
+            parser.spaces();
+            let name = parser.key()?;
+            parser.spaces();
+            if !parser.eat(b']') || (array && !parser.eat(b']')) {
+                return Err(parser.error(if array {
+                    "expected `]]`"
+                } else {
+                    "expected `]`"
+                }));
+            }
+            // Only an array of tables is given again, each time as one
+            if tables
+                .iter()
+                .any(|table| table.name == name && !(array && table.array))
+            {
+                return Err(parser.error(&format!("the table `{}` is given twice", name)));
+            }
+            tables.push(Table {
+                name,
+                array,
+                line: parser.line(),
+                pairs: Vec::new(),
+            });
+        } else {
+            let key = parser.key()?;
+            parser.spaces();
+            if !parser.eat(b'=') {
+                return Err(parser.error("expected `=`"));
+            }
+            let value = parser.value()?;
+            let table = tables.last_mut().expect("The root table is always there");
+            if table.pairs.iter().any(|(seen, _)| *seen == key) {
+                return Err(parser.error(&format!("the key `{}` is given twice", key)));
+            }
+            table.pairs.push((key, value));
+        }
+        parser.end_of_line()?;
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl Parser<'_> {
+    fn key(&mut self) -> Result<String> {
+        if self.bytes.get(self.at) == Some(&b'"') {
+            return self.string();
+        }
+        let start = self.at;
+        while let Some(b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'_' | b'-') =
+            self.bytes.get(self.at)
+        {
+            self.at += 1;
+        }
+        match self.at > start {
+            true => Ok(String::from_utf8_lossy(&self.bytes[start..self.at]).into_owned()),
+            false => Err(self.error("expected a key")),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        self.spaces();
+        match self.bytes.get(self.at) {
+            Some(b'[') => self.array(),
+            Some(b'"') => Ok(Value::String(self.string()?)),
+            Some(b'\'') => self.literal_string(),
+            Some(b't') => self.word("true", Value::Bool(true)),
+            Some(b'f') => self.word("false", Value::Bool(false)),
+            Some(b'-' | b'+' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("expected a value")),
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+    /// An array, which may span lines and hold comments
+    fn array(&mut self) -> Result<Value> {
+        self.at += 1;
+        let mut items = Vec::new();
+        loop {
+            self.blank();
+            if self.eat(b']') {
+                return Ok(Value::Array(items));
+            }
+            items.push(self.value()?);
+            self.blank();
+            if self.eat(b']') {
+                return Ok(Value::Array(items));
+            }
+            if !self.eat(b',') {
+                return Err(self.error("expected `,` or `]`"));
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        self.at += 1;
+        let mut string = Vec::new();
+        loop {
+            match self.bytes.get(self.at) {
+                Some(b'"') => {
+                    self.at += 1;
+                    return String::from_utf8(string).map_err(|_| self.error("invalid UTF-8"));
+                }
+                Some(b'\\') => {
+                    self.at += 1;
+                    let escaped = match self.bytes.get(self.at) {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => self.unicode_escape(4)?,
+                        Some(b'U') => self.unicode_escape(8)?,
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    self.at += 1;
+                    let mut buf = [0; 4];
+                    string.extend_from_slice(escaped.encode_utf8(&mut buf).as_bytes());
+                }
+                Some(b'\n') | None => return Err(self.error("unterminated string")),
+                Some(&b) => {
+                    string.push(b);
+                    self.at += 1;
+                }
+            }
+        }
+    }
+
+    /// The character of a `\uXXXX` or `\UXXXXXXXX` escape, leaving `at` on
+    /// its last digit
+    fn unicode_escape(&mut self, digits: usize) -> Result<char> {
+        let code = self
+            .bytes
+            .get(self.at + 1..self.at + 1 + digits)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.error("invalid escape"))?;
+        self.at += digits;
+        Ok(code)
+    }
+
+    /// A `'...'` string, taken as written
+    fn literal_string(&mut self) -> Result<Value> {
+        self.at += 1;
+        let start = self.at;
+        while let Some(&b) = self.bytes.get(self.at) {
+            match b {
+                b'\'' => {
+                    let string = std::str::from_utf8(&self.bytes[start..self.at])
+                        .map_err(|_| self.error("invalid UTF-8"))?;
+                    self.at += 1;
+                    return Ok(Value::String(string.to_string()));
+                }
+                b'\n' => break,
+                _ => self.at += 1,
+            }
+        }
+        Err(self.error("unterminated string"))
+    }
+
+    fn number(&mut self) -> Result<Value> {
+        let start = self.at;
+        while let Some(b'-' | b'+' | b'.' | b'_' | b'e' | b'E' | b'0'..=b'9') =
+            self.bytes.get(self.at)
+        {
+            self.at += 1;
+        }
+        let written = String::from_utf8_lossy(&self.bytes[start..self.at]);
+        let number = written.replace('_', "");
+        match number.parse::<f64>() {
+            Ok(_) if !written.starts_with('_') && !written.ends_with('_') => {
+                Ok(Value::Number(number))
+            }
+            _ => Err(self.error("invalid number")),
+        }
+    }
+
+    fn word(&mut self, word: &str, value: Value) -> Result<Value> {
+        match self.bytes[self.at..].starts_with(word.as_bytes()) {
+            true => {
+                self.at += word.len();
+                Ok(value)
+            }
+            false => Err(self.error("expected a value")),
+        }
+    }
+
+    /// Nothing but spaces and a comment up to the end of the line
+    fn end_of_line(&mut self) -> Result<()> {
+        self.spaces();
+        self.comment();
+        match self.bytes.get(self.at) {
+            None => Ok(()),
+            Some(b'\n') => {
+                self.at += 1;
+                Ok(())
+            }
+            Some(b'\r') if self.bytes.get(self.at + 1) == Some(&b'\n') => {
+                self.at += 2;
+                Ok(())
+            }
+            Some(_) => Err(self.error("expected the end of the line")),
+        }
+    }
+
+    fn comment(&mut self) {
+        if self.bytes.get(self.at) == Some(&b'#') {
+            while !matches!(self.bytes.get(self.at), None | Some(b'\n')) {
+                self.at += 1;
+            }
+        }
+    }
+
+    /// Spaces, line breaks, and comments
+    fn blank(&mut self) {
+        loop {
+            self.spaces();
+            self.comment();
+            match self.bytes.get(self.at) {
+                Some(b'\n' | b'\r') => self.at += 1,
+                _ => return,
+            }
+        }
+    }
+
+    fn spaces(&mut self) {
+        while let Some(b' ' | b'\t') = self.bytes.get(self.at) {
+            self.at += 1;
+        }
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        let matched = self.bytes.get(self.at) == Some(&byte);
+        if matched {
+            self.at += 1;
+        }
+        matched
+    }
+
+    /// The line `at` is on, counting from 1
+    fn line(&self) -> usize {
+        self.bytes[..self.at.min(self.bytes.len())]
+            .iter()
+            .filter(|&&b| b == b'\n')
+            .count()
+            + 1
+    }
+
+    fn error(&self, problem: &str) -> eyre::ErrReport {
+        eyre!("Invalid TOML on line {}: {}", self.line(), problem)
+    }
+}
@@ -0,0 +1,128 @@
+use std::io::Write;
+
+use clap::ValueEnum;
+use eyre::{eyre, Result};
+
+use crate::extract::{for_each_row, measurement, unquoted, write_rows};
+
+/// A temperature scale measurements are written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Unit {
+    #[value(name = "c", alias = "celsius")]
+    Celsius,
+    #[value(name = "f", alias = "fahrenheit")]
+    Fahrenheit,
+    #[value(name = "k", alias = "kelvin")]
+    Kelvin,
+}
+
+impl Unit {
+    /// `value`, in this unit, in Celsius
+    fn celsius(self, value: f64) -> f64 {
+        match self {
+            Self::Celsius => value,
+            Self::Fahrenheit => (value - 32.0) * 5.0 / 9.0,
+            Self::Kelvin => value - 273.15,
+        }
+    }
+
+    /// `celsius` in this unit
+    fn converted(self, celsius: f64) -> f64 {
+        match self {
+            Self::Celsius => celsius,
+            Self::Fahrenheit => celsius * 9.0 / 5.0 + 32.0,
+            Self::Kelvin => celsius + 273.15,
+        }
+    }
+}
+
+/// How `transform` rewrites each row
+#[derive(Debug, Clone, Copy)]
+pub struct Rewrite {
+    pub from_unit: Unit,
+    pub to_unit: Unit,
+    pub delimiter: char,
+    /// Decimals each measurement is rounded to, or `None` for as many as
+    /// it was written with
+    pub decimals: Option<usize>,
+}
+
+/// Rewrites the measurements file `path` to `output` in one streaming pass,
+/// converting each measurement from `rewrite.from_unit` to
+/// `rewrite.to_unit`, rounding it to `rewrite.decimals`, and separating it
+/// from the station with `rewrite.delimiter`. A station name holding the
+/// new delimiter is quoted. An existing `output` is only replaced when
+/// `force` is set.
+pub fn transform(path: &str, output: &str, rewrite: Rewrite, force: bool) -> Result<()> {
+    let _span = tracing::debug_span!("transform").entered();
+    if rewrite.delimiter == '"' || rewrite.delimiter == '\n' || rewrite.delimiter == '\r' {
+        return Err(eyre!(
+            "{:?} can't separate the station from the measurement",
+            rewrite.delimiter
+        ));
+    }
+    let mut delimiter = [0; 4];
+    let delimiter = rewrite.delimiter.encode_utf8(&mut delimiter).as_bytes();
+    let (mut quoted, mut text) = (Vec::new(), String::new());
+    let rows = write_rows(path, output, force, |writer| {
+        let mut rows = 0;
+        for_each_row(path, |station, temp| {
+            let name = unquoted(station);
+            let needs_quotes = station.first() != Some(&b'"')
+                && delimiter != b";"
+                && name
+                    .windows(delimiter.len())
+                    .any(|window| window == delimiter);
+            if needs_quotes {
+                quoted.clear();
+                quoted.push(b'"');
+                for &b in name.iter() {
+                    quoted.push(b);
+                    if b == b'"' {
+                        quoted.push(b'"');
+                    }
+                }
+                quoted.push(b'"');
+                writer.write_all(&quoted)?;
+            } else {
+                writer.write_all(station)?;
+            }
+            writer.write_all(delimiter)?;
+
+            let temp = temp.strip_suffix(b"\r").unwrap_or(temp);
+            if rewrite.from_unit == rewrite.to_unit && rewrite.decimals.is_none() {
+                writer.write_all(temp)?;
+            } else {
+                let written = temp
+                    .iter()
+                    .position(|&b| b == b'.')
+                    .map_or(0, |point| temp.len() - point - 1);
+                let value = rewrite
+                    .to_unit
+                    .converted(rewrite.from_unit.celsius(measurement(temp)?));
+                text.clear();
+                round(&mut text, value, rewrite.decimals.unwrap_or(written));
+                writer.write_all(text.as_bytes())?;
+            }
+            writer.write_all(b"\n")?;
+            rows += 1;
+            Ok(())
+        })?;
+        Ok(rows)
+    })?;
+    eprintln!("Rewrote {} rows to {}", rows, output);
+    Ok(())
+}
+
+/// Writes `value` to `text` rounded half away from zero to `decimals`,
+/// without a sign on a value that rounds to zero
+fn round(text: &mut String, value: f64, decimals: usize) {
+    use std::fmt::Write;
+
+    let scale = 10f64.powi(decimals as i32);
+    // Settles values such as 0.15 * 10 = 1.4999999999999998 on the decimal
+    // they were written as before rounding
+    let scaled = (value * scale * 1e6).round() / 1e6;
+    let rounded = scaled.round() / scale + 0.0;
+    write!(text, "{:.*}", decimals, rounded).expect("Writing to a String can't fail");
+}
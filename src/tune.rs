@@ -0,0 +1,278 @@
+use std::{
+    fs,
+    io::{self, Write},
+    thread,
+    time::{Duration, Instant},
+};
+
+use clap::ValueEnum;
+use eyre::{eyre, Result};
+use indicatif::HumanDuration;
+
+use crate::{
+    cli::{check_thread_options, human_readable, Args},
+    interrupt, memory,
+    output::{self, IoBackend, Sink},
+    rng::RngKind,
+};
+
+/// Rows generated by each trial, enough to take a fraction of a second
+const TRIAL_ROWS: u64 = 2_000_000;
+
+/// Rows `estimate` generates to measure the line length and speed
+const SAMPLE_ROWS: u64 = 5_000_000;
+
+const CHUNK_ROWS: [u64; 4] = [1_000, 10_000, 100_000, 1_000_000];
+
+const WRITE_BUFFERS: [usize; 4] = [256 << 10, 1 << 20, 8 << 20, 64 << 20];
+
+/// Benchmarks chunk sizes, write buffer sizes, and thread counts in turn by
+/// generating trial files next to the output, keeping whichever value of
+/// each gives the highest throughput, and returns `args` with the best
+/// configuration. `generate` writes the rows `args` describe to the path
+/// given.
+pub fn auto_tune<G>(args: &Args, generate: G) -> Result<Args>
+where
+    G: Fn(&Args, &str) -> Result<u64>,
+{
+    let _span = tracing::debug_span!("auto_tune").entered();
+    let path = format!("{}.tune", args.output);
+    let mut best = args.clone();
+    best.rows = args.row_count().min(TRIAL_ROWS);
+    (best.start_row, best.end_row) = (0, None);
+    best.quiet = true;
+    best.checkpoint_interval = None;
+    best.max_file_size = None;
+    // Parts of a split output are written by a single writer
+    let parallel = args.max_file_size.is_none();
+    let result = run_trials(&mut best, &path, parallel, &generate);
+    let _ = fs::remove_file(&path);
+    result?;
+
+    tracing::info!(
+        "Auto-tuned: --chunk-rows {} --write-buffer {} --threads {}",
+        best.chunk_rows,
+        size_arg(best.write_buffer),
+        best.threads()
+    );
+    Ok(Args {
+        chunk_rows: best.chunk_rows,
+        write_buffer: best.write_buffer,
+        // Tuned, so not left to the default
+        threads: Some(best.threads()),
+        ..args.clone()
+    })
+}
+
+fn run_trials<G>(best: &mut Args, path: &str, parallel: bool, generate: &G) -> Result<()>
+where
+    G: Fn(&Args, &str) -> Result<u64>,
+{
+    let measure = |trial: &Args| -> Result<f64> {
+        let (bytes, elapsed) = run_trial(trial, path, generate)?;
+        let rate = bytes as f64 / elapsed.as_secs_f64();
+        tracing::debug!(
+            chunk_rows = trial.chunk_rows,
+            write_buffer = trial.write_buffer,
+            threads = trial.threads(),
+            bytes_per_sec = rate,
+            "Trial"
+        );
+        Ok(rate)
+    };
+    let mut best_rate = measure(best)?;
+    let mut keep_fastest = |best: &mut Args, trials: Vec<Args>| -> Result<()> {
+        for trial in trials {
+            let rate = measure(&trial)?;
+            if rate > best_rate {
+                (*best, best_rate) = (trial, rate);
+            }
+        }
+        Ok(())
+    };
+
+    let trials = CHUNK_ROWS
+        .map(|chunk_rows| Args {
+            chunk_rows,
+            ..best.clone()
+        })
+        .into();
+    keep_fastest(best, trials)?;
+    let trials = WRITE_BUFFERS
+        .map(|write_buffer| Args {
+            write_buffer,
+            ..best.clone()
+        })
+        .into();
+    keep_fastest(best, trials)?;
+    // Only where the options allow parallel generation at all
+    let cpus = thread::available_parallelism().map_or(1, |n| n.get());
+    if cpus > 1
+        && parallel
+        && check_thread_options(&Args {
+            threads: Some(2),
+            ..best.clone()
+        })
+        .is_ok()
+    {
+        let trials = thread_counts(cpus)
+            .map(|threads| Args {
+                threads: Some(threads),
+                ..best.clone()
+            })
+            .collect();
+        keep_fastest(best, trials)?;
+    }
+    Ok(())
+}
+
+/// Generates `trial` into `path`, returning the bytes written and how long
+/// it took, or an error if Ctrl-C cut it short
+fn run_trial<G>(trial: &Args, path: &str, generate: &G) -> Result<(u64, Duration)>
+where
+    G: Fn(&Args, &str) -> Result<u64>,
+{
+    let start = Instant::now();
+    let bytes = generate(trial, path)?;
+    if interrupt::requested() {
+        return Err(eyre!("Interrupted"));
+    }
+    Ok((bytes, start.elapsed()))
+}
+
+/// Prints the throughput of generating `rows` rows with every thread count
+/// and I/O backend, in a scratch file next to the output. Configurations
+/// the platform or the other options don't allow are listed with why.
+pub fn self_bench<G>(args: &Args, rows: u64, generate: G) -> Result<()>
+where
+    G: Fn(&Args, &str) -> Result<u64>,
+{
+    let path = format!("{}.bench", args.output);
+    let base = Args {
+        command: None,
+        rows,
+        start_row: 0,
+        end_row: None,
+        rng: RngKind::Philox,
+        quiet: true,
+        checkpoint_interval: None,
+        max_file_size: None,
+        ..args.clone()
+    };
+    let cpus = thread::available_parallelism().map_or(1, |n| n.get());
+    let configurations = IoBackend::value_variants()
+        .iter()
+        .map(|&io| Args { io, ..base.clone() })
+        .chain(thread_counts(cpus).map(|threads| Args {
+            threads: Some(threads),
+            ..base.clone()
+        }));
+
+    println!(
+        "{:>8}  {:<8}  {:>14}  {:>10}",
+        "threads", "io", "rows/s", "MB/s"
+    );
+    let result = (|| {
+        for trial in configurations {
+            let io = trial
+                .io
+                .to_possible_value()
+                .expect("Backends have no skipped values");
+            print!("{:>8}  {:<8}  ", trial.threads(), io.get_name());
+            io::stdout().flush()?;
+            let allowed = if trial.threads() > 1 {
+                check_thread_options(&trial)
+            } else {
+                Ok(())
+            };
+            match allowed.and_then(|()| run_trial(&trial, &path, &generate)) {
+                Ok((bytes, elapsed)) => {
+                    let seconds = elapsed.as_secs_f64();
+                    println!(
+                        "{:>14.0}  {:>10.1}",
+                        rows as f64 / seconds,
+                        bytes as f64 / seconds / 1e6
+                    );
+                }
+                Err(e) if interrupt::requested() => {
+                    println!();
+                    return Err(e);
+                }
+                Err(e) => println!("unavailable: {}", e),
+            }
+        }
+        Ok(())
+    })();
+    let _ = fs::remove_file(&path);
+    result
+}
+
+/// Powers of two below `cpus`, then `cpus` itself, skipping one thread
+fn thread_counts(cpus: usize) -> impl Iterator<Item = usize> {
+    (1..)
+        .map(|power| 1 << power)
+        .take_while(move |&threads| threads < cpus)
+        .chain((cpus > 1).then_some(cpus))
+}
+
+/// `size` as `--write-buffer` would be given it
+fn size_arg(size: usize) -> String {
+    match size {
+        size if size % (1 << 20) == 0 => format!("{}MiB", size >> 20),
+        size if size % (1 << 10) == 0 => format!("{}KiB", size >> 10),
+        size => size.to_string(),
+    }
+}
+
+/// Prints the expected size of the output, how long generating it takes,
+/// and the memory held in buffers, scaled up from generating a sample of
+/// the rows into the null sink
+pub fn estimate<G>(args: &Args, generate: G) -> Result<()>
+where
+    G: Fn(&Args, &str) -> Result<u64>,
+{
+    if args.threads() > 1 {
+        check_thread_options(args)?;
+    }
+    let rows = args.row_count();
+    let sample = Args {
+        command: None,
+        rows: rows.min(SAMPLE_ROWS),
+        start_row: 0,
+        end_row: None,
+        sink: Sink::Null,
+        quiet: true,
+        checkpoint_interval: None,
+        ..args.clone()
+    };
+    let (bytes, elapsed) = run_trial(&sample, &args.output, &generate)?;
+    let bytes_per_row = bytes as f64 / sample.rows.max(1) as f64;
+    let rows_per_sec = sample.rows as f64 / elapsed.as_secs_f64();
+    let size = (bytes_per_row * rows as f64) as u64;
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let plan = memory::Plan::new(args, bytes_per_row.ceil() as usize, threads);
+    let plan = match args.memory_limit {
+        Some(limit) => plan.fit(limit)?,
+        None => plan,
+    };
+
+    println!("rows:        {}", rows);
+    println!(
+        "size:        ~{} ({:.1} bytes per row)",
+        human_readable(size),
+        bytes_per_row
+    );
+    println!(
+        "duration:    ~{} generating at {:.0} rows/s, before the disk is counted",
+        HumanDuration(Duration::from_secs_f64(rows as f64 / rows_per_sec)),
+        rows_per_sec
+    );
+    println!(
+        "memory:      ~{} in buffers",
+        human_readable(plan.usage() as u64)
+    );
+    if let Some(free) = output::free_space(&args.output)? {
+        println!("free space:  {} for {}", human_readable(free), args.output);
+    }
+    Ok(())
+}
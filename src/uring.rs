@@ -0,0 +1,418 @@
+use std::{
+    fs::File,
+    io,
+    os::unix::io::{AsRawFd, RawFd},
+    ptr,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use eyre::{eyre, Result};
+
+// From linux/io_uring.h
+const IORING_OFF_SQ_RING: i64 = 0;
+const IORING_OFF_CQ_RING: i64 = 0x800_0000;
+const IORING_OFF_SQES: i64 = 0x1000_0000;
+const IORING_ENTER_GETEVENTS: u32 = 1;
+const IORING_OP_WRITE: u8 = 23;
+
+#[repr(C)]
+#[derive(Default)]
+struct SqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct CqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct Params {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: SqringOffsets,
+    cq_off: CqringOffsets,
+}
+
+#[repr(C)]
+struct Sqe {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    rw_flags: u32,
+    user_data: u64,
+    buf_index: u16,
+    personality: u16,
+    splice_fd_in: i32,
+    addr3: u64,
+    pad: u64,
+}
+
+#[repr(C)]
+struct Cqe {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+/// A shared memory region of the ring, unmapped on drop
+struct Region {
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl Region {
+    fn map(fd: RawFd, len: usize, offset: i64) -> Result<Self> {
+        // SAFETY: maps a region the kernel set up for this ring
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_POPULATE,
+                fd,
+                offset,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(eyre!("mmap failed: {}", io::Error::last_os_error()));
+        }
+        Ok(Self {
+            ptr: ptr.cast(),
+            len,
+        })
+    }
+
+    /// # Safety
+    /// `offset` must be within the region and aligned for `T`
+    unsafe fn at<T>(&self, offset: u32) -> *mut T {
+        self.ptr.add(offset as usize).cast()
+    }
+}
+
+impl Drop for Region {
+    fn drop(&mut self) {
+        // SAFETY: `ptr` and `len` describe a live mapping
+        unsafe { libc::munmap(self.ptr.cast(), self.len) };
+    }
+}
+
+/// A minimal io_uring submitting positioned writes, written against the raw
+/// system calls
+struct Ring {
+    fd: RawFd,
+    sq_head: *const AtomicU32,
+    sq_tail: *const AtomicU32,
+    sq_mask: u32,
+    sq_array: *mut u32,
+    sqes: *mut Sqe,
+    cq_head: *const AtomicU32,
+    cq_tail: *const AtomicU32,
+    cq_mask: u32,
+    cqes: *const Cqe,
+    // Held for their mappings, and dropped before the ring is closed
+    _regions: [Region; 3],
+}
+
+impl Ring {
+    fn new(entries: u32) -> Result<Self> {
+        let mut params = Params::default();
+        // SAFETY: `params` is a valid io_uring_params for the kernel to fill
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_io_uring_setup,
+                entries,
+                &mut params as *mut Params,
+            )
+        };
+        if fd < 0 {
+            return Err(eyre!(
+                "io_uring_setup failed: {}",
+                io::Error::last_os_error()
+            ));
+        }
+        let fd = fd as RawFd;
+        let sq_len = params.sq_off.array as usize + params.sq_entries as usize * 4;
+        let cq_len =
+            params.cq_off.cqes as usize + params.cq_entries as usize * std::mem::size_of::<Cqe>();
+        let sqes_len = params.sq_entries as usize * std::mem::size_of::<Sqe>();
+        let regions = (|| {
+            Ok::<_, eyre::Report>([
+                Region::map(fd, sq_len, IORING_OFF_SQ_RING)?,
+                Region::map(fd, cq_len, IORING_OFF_CQ_RING)?,
+                Region::map(fd, sqes_len, IORING_OFF_SQES)?,
+            ])
+        })();
+        let regions = match regions {
+            Ok(regions) => regions,
+            Err(e) => {
+                // SAFETY: `fd` is the ring just created
+                unsafe { libc::close(fd) };
+                return Err(e);
+            }
+        };
+        let [sq, cq, sqes] = &regions;
+        let (sq_off, cq_off) = (&params.sq_off, &params.cq_off);
+        // SAFETY: the kernel reported these offsets within the regions
+        unsafe {
+            Ok(Self {
+                fd,
+                sq_head: sq.at(sq_off.head),
+                sq_tail: sq.at(sq_off.tail),
+                sq_mask: *sq.at::<u32>(sq_off.ring_mask),
+                sq_array: sq.at(sq_off.array),
+                sqes: sqes.at(0),
+                cq_head: cq.at(cq_off.head),
+                cq_tail: cq.at(cq_off.tail),
+                cq_mask: *cq.at::<u32>(cq_off.ring_mask),
+                cqes: cq.at(cq_off.cqes),
+                _regions: regions,
+            })
+        }
+    }
+
+    /// Queues a write of `len` bytes at `addr` to `offset` in `file`. The
+    /// buffer must stay alive until its completion is reaped.
+    fn push_write(
+        &mut self,
+        file: RawFd,
+        addr: *const u8,
+        len: usize,
+        offset: u64,
+        user_data: u64,
+    ) {
+        // SAFETY: only this thread produces submissions, the kernel only
+        // advances the head, and callers never exceed the ring's size
+        unsafe {
+            let tail = (*self.sq_tail).load(Ordering::Relaxed);
+            let index = tail & self.sq_mask;
+            self.sqes.add(index as usize).write(Sqe {
+                opcode: IORING_OP_WRITE,
+                flags: 0,
+                ioprio: 0,
+                fd: file,
+                off: offset,
+                addr: addr as u64,
+                len: len as u32,
+                rw_flags: 0,
+                user_data,
+                buf_index: 0,
+                personality: 0,
+                splice_fd_in: 0,
+                addr3: 0,
+                pad: 0,
+            });
+            *self.sq_array.add(index as usize) = index;
+            (*self.sq_tail).store(tail.wrapping_add(1), Ordering::Release);
+        }
+    }
+
+    /// Submits queued writes, waiting until at least `min_complete` have
+    /// completed
+    fn enter(&mut self, min_complete: u32) -> Result<()> {
+        // SAFETY: both atomics live in the mapped submission ring
+        let to_submit = unsafe {
+            (*self.sq_tail)
+                .load(Ordering::Relaxed)
+                .wrapping_sub((*self.sq_head).load(Ordering::Acquire))
+        };
+        loop {
+            // SAFETY: a plain io_uring_enter on this ring, without a signal
+            // mask
+            let result = unsafe {
+                libc::syscall(
+                    libc::SYS_io_uring_enter,
+                    self.fd,
+                    to_submit,
+                    min_complete,
+                    IORING_ENTER_GETEVENTS,
+                    ptr::null::<libc::sigset_t>(),
+                    0,
+                )
+            };
+            if result >= 0 {
+                return Ok(());
+            }
+            let error = io::Error::last_os_error();
+            if error.kind() != io::ErrorKind::Interrupted {
+                return Err(eyre!("io_uring_enter failed: {}", error));
+            }
+        }
+    }
+
+    /// Takes one completion, as its user data and result, if any is ready
+    fn pop_completion(&mut self) -> Option<(u64, i32)> {
+        // SAFETY: the kernel publishes completions up to the tail, and only
+        // this thread consumes them
+        unsafe {
+            let head = (*self.cq_head).load(Ordering::Relaxed);
+            if head == (*self.cq_tail).load(Ordering::Acquire) {
+                return None;
+            }
+            let cqe = &*self.cqes.add((head & self.cq_mask) as usize);
+            let completion = (cqe.user_data, cqe.res);
+            (*self.cq_head).store(head.wrapping_add(1), Ordering::Release);
+            Some(completion)
+        }
+    }
+}
+
+impl Drop for Ring {
+    fn drop(&mut self) {
+        // SAFETY: `fd` is this ring, and nothing uses it afterwards
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+// The ring is only ever used through `&mut self`
+unsafe impl Send for Ring {}
+
+/// A chunk being written, with how much of it has been written so far
+struct InFlight {
+    chunk: Vec<u8>,
+    offset: u64,
+    written: usize,
+}
+
+/// Writes chunks to a file asynchronously through io_uring, holding each
+/// chunk's buffer until the kernel has written it
+pub struct UringFile {
+    file: File,
+    ring: Ring,
+    slots: Vec<Option<InFlight>>,
+    offset: u64,
+}
+
+impl UringFile {
+    /// How many chunks may be written at once
+    pub const DEPTH: usize = 4;
+
+    pub fn create(file: File) -> Result<Self> {
+        Ok(Self {
+            file,
+            ring: Ring::new(Self::DEPTH as u32)?,
+            slots: (0..Self::DEPTH).map(|_| None).collect(),
+            offset: 0,
+        })
+    }
+
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// Queues `chunk` after everything written so far, returning the
+    /// buffers of chunks that have finished writing. Waits for a write to
+    /// finish when the queue is full.
+    pub fn write(&mut self, chunk: Vec<u8>) -> Result<Vec<Vec<u8>>> {
+        // The kernel would report an empty write as writing nothing
+        if chunk.is_empty() {
+            return Ok(vec![chunk]);
+        }
+        let slot = match self.slots.iter().position(Option::is_none) {
+            Some(slot) => slot,
+            None => {
+                let mut done = Vec::new();
+                while done.is_empty() {
+                    self.ring.enter(1)?;
+                    self.reap(&mut done)?;
+                }
+                return self.write(chunk).map(|more| {
+                    done.extend(more);
+                    done
+                });
+            }
+        };
+        let in_flight = InFlight {
+            chunk,
+            offset: self.offset,
+            written: 0,
+        };
+        self.offset += in_flight.chunk.len() as u64;
+        self.submit(slot, in_flight);
+        self.ring.enter(0)?;
+        let mut done = Vec::new();
+        self.reap(&mut done)?;
+        Ok(done)
+    }
+
+    /// Waits for every queued write to finish
+    pub fn flush(&mut self) -> Result<Vec<Vec<u8>>> {
+        let mut done = Vec::new();
+        while self.slots.iter().any(Option::is_some) {
+            self.ring.enter(1)?;
+            self.reap(&mut done)?;
+        }
+        Ok(done)
+    }
+
+    fn submit(&mut self, slot: usize, in_flight: InFlight) {
+        let rest = &in_flight.chunk[in_flight.written..];
+        self.ring.push_write(
+            self.file.as_raw_fd(),
+            rest.as_ptr(),
+            rest.len(),
+            in_flight.offset + in_flight.written as u64,
+            slot as u64,
+        );
+        self.slots[slot] = Some(in_flight);
+    }
+
+    /// Collects finished chunks into `done`, resubmitting short writes
+    fn reap(&mut self, done: &mut Vec<Vec<u8>>) -> Result<()> {
+        let mut resubmitted = false;
+        while let Some((slot, result)) = self.ring.pop_completion() {
+            let slot = slot as usize;
+            let mut in_flight = self.slots[slot]
+                .take()
+                .expect("Completions only arrive for submitted writes");
+            if result < 0 {
+                return Err(eyre!(
+                    "Write failed: {}",
+                    io::Error::from_raw_os_error(-result)
+                ));
+            }
+            if result == 0 {
+                return Err(eyre!("Write failed: no bytes were written"));
+            }
+            in_flight.written += result as usize;
+            if in_flight.written < in_flight.chunk.len() {
+                self.submit(slot, in_flight);
+                resubmitted = true;
+            } else {
+                done.push(in_flight.chunk);
+            }
+        }
+        if resubmitted {
+            self.ring.enter(0)?;
+        }
+        Ok(())
+    }
+}
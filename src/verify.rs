@@ -0,0 +1,332 @@
+use std::{
+    fs::{self, File},
+    io::{BufRead, BufReader, Read, Seek, SeekFrom},
+    ops::Range,
+    thread,
+};
+
+use eyre::{eyre, Result};
+use indicatif::ProgressBar;
+use rand::Rng;
+
+use crate::{progress, schema::Schema};
+
+/// Problems reported in full; past this only the count is kept
+const MAX_REPORTED: usize = 10;
+
+/// What a line of the output must look like
+pub enum Format {
+    /// `<station>;<measurement>`, with one decimal within ±99.9
+    Measurements,
+    Schema(Schema),
+}
+
+impl Format {
+    /// Checks `line`, without its newline
+    pub fn check_line(&self, line: &[u8]) -> Result<()> {
+        match self {
+            Self::Measurements => check_measurement(line),
+            Self::Schema(schema) => schema.check_line(line),
+        }
+    }
+}
+
+/// A line that failed its check
+struct Problem {
+    offset: u64,
+    /// Counted from the start of the range it was found in, until reported
+    line: u64,
+    message: String,
+}
+
+/// The lines in one range of a file, and the first problems among them
+#[derive(Default)]
+struct Tally {
+    lines: u64,
+    problems: Vec<Problem>,
+    /// Problems found, including those past `MAX_REPORTED`
+    problem_count: u64,
+}
+
+/// Re-reads the complete output from `paths`, its parts in order, checking
+/// that every line is one `format` describes and that `rows` lines were
+/// written in all. Each file is split into ranges read on every core.
+pub fn verify(paths: &[String], format: &Format, rows: u64, quiet: bool) -> Result<()> {
+    let _span = tracing::debug_span!("verify").entered();
+    let sizes = paths
+        .iter()
+        .map(|path| Ok(fs::metadata(path)?.len()))
+        .collect::<Result<Vec<u64>>>()?;
+    let bar = if quiet {
+        ProgressBar::hidden()
+    } else {
+        ProgressBar::new(sizes.iter().sum()).with_style(progress::style())
+    };
+    bar.set_message("Verifying");
+    let (mut lines, mut problems, mut problem_count) = (0, Vec::new(), 0);
+    for (path, &size) in paths.iter().zip(&sizes) {
+        let tallies = thread::scope(|scope| {
+            let handles: Vec<_> = ranges(size)
+                .into_iter()
+                .map(|range| {
+                    let bar = &bar;
+                    scope.spawn(move || check_range(path, range, format, bar))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("A verifier panicked"))
+                .collect::<Result<Vec<_>>>()
+        })?;
+        // Line numbers within the file follow from the lines before each range
+        let mut file_lines = 0;
+        for tally in tallies {
+            for mut problem in tally.problems {
+                problem.line += file_lines + 1;
+                if problems.len() < MAX_REPORTED {
+                    problems.push((path, problem));
+                }
+            }
+            file_lines += tally.lines;
+            problem_count += tally.problem_count;
+        }
+        lines += file_lines;
+    }
+    bar.finish_and_clear();
+
+    if problem_count > 0 {
+        let mut report = format!(
+            "Verification found {} malformed line{}:",
+            problem_count,
+            if problem_count == 1 { "" } else { "s" }
+        );
+        for (path, problem) in &problems {
+            report.push_str(&format!(
+                "\n  {} line {} (byte {}): {}",
+                path, problem.line, problem.offset, problem.message
+            ));
+        }
+        if problem_count > problems.len() as u64 {
+            report.push_str(&format!(
+                "\n  and {} more",
+                problem_count - problems.len() as u64
+            ));
+        }
+        return Err(eyre!(report));
+    }
+    if lines != rows {
+        return Err(eyre!(
+            "Verification counted {} rows where {} were written",
+            lines,
+            rows
+        ));
+    }
+    tracing::info!(rows, "Verified the output");
+    Ok(())
+}
+
+/// Bytes read either side of a spot check's offset to find the line there
+const WINDOW: u64 = 4096;
+
+/// Checks the lines found at `samples` random offsets into the output from
+/// `paths`, each file picked in proportion to its size, and that each file
+/// ends on a newline, for a check far cheaper than reading it all back
+pub fn spot_check(paths: &[String], format: &Format, samples: u64) -> Result<()> {
+    let _span = tracing::debug_span!("spot_check", samples).entered();
+    let sizes = paths
+        .iter()
+        .map(|path| Ok(fs::metadata(path)?.len()))
+        .collect::<Result<Vec<u64>>>()?;
+    let total: u64 = sizes.iter().sum();
+    if total == 0 {
+        return Ok(());
+    }
+    let mut files = paths
+        .iter()
+        .map(File::open)
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut rng = rand::thread_rng();
+    let mut problems = Vec::new();
+    for (path, (file, &size)) in paths.iter().zip(files.iter_mut().zip(&sizes)) {
+        if size > 0 && read_at(file, size - 1, 1)? != b"\n" {
+            problems.push(format!("{}: the last line has no newline", path));
+        }
+    }
+    for _ in 0..samples {
+        let mut offset = rng.gen_range(0..total);
+        let index = sizes
+            .iter()
+            .position(|&size| match offset < size {
+                true => true,
+                false => {
+                    offset -= size;
+                    false
+                }
+            })
+            .expect("The offset is within the total size");
+        let (path, file) = (&paths[index], &mut files[index]);
+        let Some((start, line)) = line_at(file, offset, sizes[index])? else {
+            problems.push(format!(
+                "{} byte {}: no line break within {} bytes",
+                path, offset, WINDOW
+            ));
+            continue;
+        };
+        if let Err(e) = format.check_line(&line) {
+            problems.push(format!(
+                "{} line at byte {}: {}: `{}`",
+                path,
+                start,
+                e,
+                excerpt(&line)
+            ));
+        }
+    }
+    if !problems.is_empty() {
+        let mut report = format!(
+            "Spot check found {} malformed line{} in {} samples:",
+            problems.len(),
+            if problems.len() == 1 { "" } else { "s" },
+            samples
+        );
+        for problem in problems.iter().take(MAX_REPORTED) {
+            report.push_str(&format!("\n  {}", problem));
+        }
+        if problems.len() > MAX_REPORTED {
+            report.push_str(&format!("\n  and {} more", problems.len() - MAX_REPORTED));
+        }
+        return Err(eyre!(report));
+    }
+    tracing::info!(samples, "Spot checked the output");
+    Ok(())
+}
+
+/// The line of a file `size` bytes long holding the byte at `offset`,
+/// without its newline, and where it starts; `None` if it runs past the
+/// bytes read around the offset
+fn line_at(file: &mut File, offset: u64, size: u64) -> Result<Option<(u64, Vec<u8>)>> {
+    let from = offset.saturating_sub(WINDOW);
+    let window = read_at(file, from, (offset + WINDOW).min(size) - from)?;
+    let at = (offset - from) as usize;
+    let start = match window[..at].iter().rposition(|&b| b == b'\n') {
+        Some(end) => end + 1,
+        None if from == 0 => 0,
+        None => return Ok(None),
+    };
+    let end = match window[at..].iter().position(|&b| b == b'\n') {
+        Some(end) => at + end,
+        None if from + window.len() as u64 == size => window.len(),
+        None => return Ok(None),
+    };
+    Ok(Some((from + start as u64, window[start..end].to_vec())))
+}
+
+fn read_at(file: &mut File, offset: u64, len: u64) -> Result<Vec<u8>> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut buf = vec![0; len as usize];
+    file.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Checks the lines of `path` starting within `range`
+fn check_range(path: &str, range: Range<u64>, format: &Format, bar: &ProgressBar) -> Result<Tally> {
+    let mut tally = Tally::default();
+    let mut unreported = range.start;
+    let end = read_lines(path, range, |offset, line| {
+        let result = match line.strip_suffix(b"\n") {
+            Some(line) => format.check_line(line),
+            None => Err(eyre!("the last line has no newline")),
+        };
+        if let Err(e) = result {
+            tally.problem_count += 1;
+            if tally.problems.len() < MAX_REPORTED {
+                tally.problems.push(Problem {
+                    offset,
+                    line: tally.lines,
+                    message: format!("{}: `{}`", e, excerpt(line)),
+                });
+            }
+        }
+        tally.lines += 1;
+        if offset - unreported >= 1 << 20 {
+            bar.inc(offset - unreported);
+            unreported = offset;
+        }
+    })?;
+    bar.inc(end.saturating_sub(unreported));
+    Ok(tally)
+}
+
+/// `size` bytes split into a range per core, for reading in parallel
+pub fn ranges(size: u64) -> Vec<Range<u64>> {
+    let threads = thread::available_parallelism().map_or(1, |n| n.get()) as u64;
+    let span = size.div_ceil(threads).max(1);
+    (0..size)
+        .step_by(span as usize)
+        .map(|start| start..(start + span).min(size))
+        .collect()
+}
+
+/// Hands `visit` each line of `path` starting within `range`, with its
+/// newline if it has one, and the offset it starts at; the line running into
+/// the range from before belongs to the range it starts in. Returns the
+/// offset reading stopped at.
+pub fn read_lines(path: &str, range: Range<u64>, mut visit: impl FnMut(u64, &[u8])) -> Result<u64> {
+    let mut file = File::open(path)?;
+    let mut offset = range.start.saturating_sub(1);
+    file.seek(SeekFrom::Start(offset))?;
+    let mut reader = BufReader::with_capacity(1 << 20, file);
+    let mut line = Vec::new();
+    if range.start > 0 {
+        offset += reader.read_until(b'\n', &mut line)? as u64;
+    }
+    while offset < range.end {
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)? as u64;
+        if read == 0 {
+            break;
+        }
+        visit(offset, &line);
+        offset += read;
+    }
+    Ok(offset)
+}
+
+/// `<station>;<measurement>`, where the measurement is `-?\d{1,2}\.\d`
+fn check_measurement(line: &[u8]) -> Result<()> {
+    // A quoted station name may hold `;` itself, but the measurement can't
+    let Some(split) = line.iter().rposition(|&b| b == b';') else {
+        return Err(eyre!("no `;` between station and measurement"));
+    };
+    let (station, measurement) = (&line[..split], &line[split + 1..]);
+    if station.is_empty() {
+        return Err(eyre!("empty station name"));
+    }
+    if std::str::from_utf8(station).is_err() {
+        return Err(eyre!("station name isn't UTF-8"));
+    }
+    let digits = measurement.strip_prefix(b"-").unwrap_or(measurement);
+    let well_formed = match digits {
+        [whole @ .., b'.', tenth] => {
+            matches!(whole.len(), 1 | 2)
+                && whole.iter().all(u8::is_ascii_digit)
+                && tenth.is_ascii_digit()
+        }
+        _ => false,
+    };
+    if !well_formed {
+        return Err(eyre!(
+            "measurement isn't a number with one decimal in ±99.9"
+        ));
+    }
+    Ok(())
+}
+
+/// The start of `line`, readable in a message
+fn excerpt(line: &[u8]) -> String {
+    let line = String::from_utf8_lossy(line.strip_suffix(b"\n").unwrap_or(line));
+    match line.char_indices().nth(80) {
+        Some((end, _)) => format!("{}...", &line[..end]),
+        None => line.into_owned(),
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    fs, thread,
+    time::{Duration, SystemTime},
+};
+
+use eyre::Result;
+
+use crate::{cli::Args, interrupt, summary::Summary};
+
+/// Time between looking at the inputs for changes
+const POLL: Duration = Duration::from_millis(500);
+
+/// Time an edited input must stay unchanged for before regenerating, so an
+/// editor's save is seen whole
+const SETTLE: Duration = Duration::from_millis(200);
+
+/// Generates the dataset `args` describe, then again whenever one of the
+/// files it is generated from changes, until Ctrl-C. A run that fails is
+/// reported and the inputs watched for a fix.
+pub fn run<F>(mut args: Args, mut generate: F) -> Result<()>
+where
+    F: FnMut(Args) -> Result<Summary>,
+{
+    interrupt::install()?;
+    // The same seed each time, so only the edits change the rows
+    args.seed.get_or_insert_with(rand::random);
+    let inputs = inputs(&args);
+    loop {
+        // Taken before generating, so edits made during a run are seen
+        let before = stamps(&inputs);
+        let result = generate(args.clone());
+        if interrupt::requested() {
+            return result.map(drop);
+        }
+        match result {
+            // From now on the output is the one this watch wrote
+            Ok(_) => args.force = true,
+            Err(error) => eprintln!("Error: {:?}", error),
+        }
+        eprintln!("Watching {} for changes, Ctrl-C to stop", inputs.join(", "));
+        let Some(changed) = wait_for_change(&inputs, &before) else {
+            return Ok(());
+        };
+        eprintln!("{} changed, regenerating", changed);
+    }
+}
+
+/// The files the rows are generated from
+fn inputs(args: &Args) -> Vec<String> {
+    let mut inputs = vec![args.weather_stations.clone()];
+    inputs.extend(args.station_params.clone());
+    inputs.extend(args.schema.clone());
+    inputs.extend(args.script.clone());
+    inputs
+}
+
+/// When each input was last modified and how long it was, or `None` for
+/// one that doesn't exist, as an editor replacing it may briefly leave it
+type Stamp = Option<(SystemTime, u64)>;
+
+fn stamps(inputs: &[String]) -> Vec<Stamp> {
+    inputs
+        .iter()
+        .map(|path| {
+            let metadata = fs::metadata(path).ok()?;
+            Some((metadata.modified().ok()?, metadata.len()))
+        })
+        .collect()
+}
+
+/// Blocks until an input differs from `before` and has settled, returning
+/// the first that changed, or `None` on Ctrl-C
+fn wait_for_change(inputs: &[String], before: &[Stamp]) -> Option<String> {
+    loop {
+        if interrupt::requested() {
+            return None;
+        }
+        thread::sleep(POLL);
+        let mut now = stamps(inputs);
+        if now == before {
+            continue;
+        }
+        loop {
+            thread::sleep(SETTLE);
+            let settled = stamps(inputs);
+            if settled == now {
+                break;
+            }
+            now = settled;
+        }
+        // An edit undone before it settled leaves nothing to regenerate
+        let changed = inputs
+            .iter()
+            .zip(before.iter().zip(&now))
+            .find(|(_, (before, now))| before != now);
+        if let Some((path, _)) = changed {
+            return Some(path.clone());
+        }
+    }
+}